@@ -1,19 +1,21 @@
+#![allow(dead_code)]
+
 use std::hash::{Hash, Hasher};
 use std::ops::Range;
 
 use crc32fast::Hasher as CRCHasher;
-use criterion::{black_box, Criterion, criterion_group, criterion_main};
+use criterion::{BatchSize, black_box, Criterion, criterion_group, criterion_main};
 use fastmurmur3::murmur3_x64_128;
-use fxhash::{FxHasher64, hash64};
+use fxhash::FxHasher64;
 use getrandom::getrandom;
 use rand::Rng;
 use rand::rngs::ThreadRng;
 use siphasher::sip::SipHasher13;
 use twox_hash::{Xxh3Hash64, XxHash64};
 use xxhash_rust::const_xxh3::xxh3_64 as const_xxh3;
-use xxhash_rust::xxh3::{xxh3_64, xxh3_64_with_seed};
+use xxhash_rust::xxh3::xxh3_64_with_seed;
 
-use fastbloom_rs::{BloomFilter, CountingBloomFilter, Deletable, FilterBuilder, Hashes, Membership};
+use fastbloom_rs::{BloomFilter, FilterBuilder, HashAlgorithm, Membership};
 
 #[inline]
 fn sip_new(key: &[u8; 16]) -> SipHasher13 {
@@ -25,7 +27,7 @@ fn bloom_hash<T>(hashes: &mut [u64; 2], item: &T, k_i: u32, sips: &mut [SipHashe
         T: Hash,
 {
     if k_i < 2 {
-        let mut sip = &mut sips[k_i as usize].clone();
+        let sip = &mut sips[k_i as usize].clone();
         item.hash(sip);
         let hash = sip.finish();
         hashes[k_i as usize] = hash;
@@ -47,17 +49,17 @@ fn bloom_add_test(filter: &mut BloomFilter, data: &[u8]) {
     filter.add(data);
 }
 
-fn random_test(random: &mut ThreadRng, range: &Range<i32>) {
+fn random_test(random: &mut ThreadRng, _range: &Range<i32>) {
     let value: usize = random.gen_range(0..4096 * 1024 - 1);
     black_box(&u64::to_le_bytes(value as u64));
 }
 
-fn bloom_add_random_test(filter: &mut BloomFilter, random: &mut ThreadRng, range: &Range<i32>) {
+fn bloom_add_random_test(filter: &mut BloomFilter, random: &mut ThreadRng, _range: &Range<i32>) {
     let value = random.gen_range(0..10_000_000);
-    filter.add(&i64::to_le_bytes(value));
+    filter.add(i64::to_le_bytes(value));
 }
 
-fn bound_check_test(vec: &mut Vec<usize>, random: &mut ThreadRng) {
+fn bound_check_test(vec: &mut [usize], random: &mut ThreadRng) {
     let value = random.gen_range(0..20 * 1024 * 1024 - 1);
     vec[value] = value;
 }
@@ -85,7 +87,7 @@ fn hash_bench(c: &mut Criterion) {
     let mut crc = CRCHasher::new();
     c.bench_function("crc32fast", |b| b.iter(|| {
         crc.update(black_box(hello.as_bytes()));
-        crc.finish();
+        black_box(crc.finish());
     }));
 
     let mut seed = [0u8; 32];
@@ -103,19 +105,19 @@ fn hash_bench(c: &mut Criterion) {
     let mut fxhash = FxHasher64::default();
     c.bench_function("fxhash", |b| b.iter(|| {
         hello.hash(&mut fxhash);
-        fxhash.finish();
+        black_box(fxhash.finish());
     }));
 
     c.bench_function("xxh3", |b| b.iter(|| {
         let mut xxh3 = Xxh3Hash64::default();
         hello.hash(&mut xxh3);
-        xxh3.finish();
+        black_box(xxh3.finish());
     }));
 
     c.bench_function("xxh", |b| b.iter(|| {
         let mut xxh = XxHash64::default();
         hello.hash(&mut xxh);
-        xxh.finish();
+        black_box(xxh.finish());
     }));
 
     c.bench_function("xxh3_64", |b| b.iter(|| {
@@ -150,7 +152,92 @@ fn bloom_add_bench(c: &mut Criterion) {
     c.bench_function("bloom_add_all_test", |b| b.iter(|| bloom_add_all_test(&mut filter, &inputs[..])));
 
     c.bench_function("bloom_contains_test", |b| b.iter(|| filter.contains(black_box(hello.as_bytes()))));
+    c.bench_function("bloom_contains_and_add_test", |b| b.iter(|| {
+        let contains = filter.contains(black_box(hello.as_bytes()));
+        filter.add(black_box(hello.as_bytes()));
+        contains
+    }));
+    c.bench_function("bloom_contains_then_add_test", |b| b.iter(|| filter.contains_then_add(black_box(hello.as_bytes()))));
     c.bench_function("bloom_not_contains_test", |b| b.iter(|| filter.contains(black_box(b"hellohellohello"))));
+
+    let duplicates: Vec<String> = (0..1_000_000).map(|n| { (n % 1000).to_string() }).collect();
+    let mut builder = FilterBuilder::new(items_count as u64, 0.001);
+    let mut repeat = builder.build_bloom_filter();
+    builder.enable_repeat_insert(false);
+    let mut skip = builder.build_bloom_filter();
+    c.bench_function("bloom_add_duplicates_repeat_test", |b| b.iter(|| bloom_add_all_test(&mut repeat, &duplicates[..])));
+    c.bench_function("bloom_add_duplicates_skip_test", |b| b.iter(|| bloom_add_all_test(&mut skip, &duplicates[..])));
+
+    let frozen = filter.clone().freeze();
+    c.bench_function("frozen_bloom_contains_test", |b| b.iter(|| frozen.contains(black_box(hello.as_bytes()))));
+    c.bench_function("frozen_bloom_not_contains_test", |b| b.iter(|| frozen.contains(black_box(b"hellohellohello"))));
+}
+
+fn bloom_power_of_two_bench(c: &mut Criterion) {
+    let hello = "hellohellohellohello".to_string();
+    let mut builder = FilterBuilder::new(10_000_000, 0.001);
+    let mut modulo = builder.build_bloom_filter();
+    let mut builder = FilterBuilder::new(10_000_000, 0.001);
+    builder.force_power_of_two_size(true);
+    let mut masked = builder.build_bloom_filter();
+
+    c.bench_function("bloom_modulo_add_test", |b| b.iter(|| modulo.add(black_box(hello.as_bytes()))));
+    c.bench_function("bloom_power_of_two_add_test", |b| b.iter(|| masked.add(black_box(hello.as_bytes()))));
+    c.bench_function("bloom_modulo_contains_test", |b| b.iter(|| modulo.contains(black_box(hello.as_bytes()))));
+    c.bench_function("bloom_power_of_two_contains_test", |b| b.iter(|| masked.contains(black_box(hello.as_bytes()))));
+}
+
+fn bloom_hash_algorithm_bench(c: &mut Criterion) {
+    for len in [8usize, 64, 1024] {
+        let key: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        let other: Vec<u8> = (0..len).map(|i| !i as u8).collect();
+        let algorithms = [("xxh128", HashAlgorithm::Xxh128), ("xxh3", HashAlgorithm::Xxh3),
+            ("murmur3", HashAlgorithm::Murmur3)];
+        for (name, algorithm) in algorithms {
+            let mut filter = FilterBuilder::new(10_000_000, 0.001).with_hash_algorithm(algorithm)
+                .build_bloom_filter();
+            filter.add(&key);
+            c.bench_function(&format!("bloom_{}_add_{}_test", name, len), |b| b.iter(|| filter.add(black_box(&key))));
+            c.bench_function(&format!("bloom_{}_contains_{}_test", name, len), |b| b.iter(|| filter.contains(black_box(&key))));
+            c.bench_function(&format!("bloom_{}_not_contains_{}_test", name, len), |b| b.iter(|| filter.contains(black_box(&other))));
+        }
+    }
+}
+
+fn bloom_precomputed_fan_out_bench(c: &mut Criterion) {
+    let key = "hellohellohellohellohellohellohellohellohellohellohellohellohello".to_string();
+    let mut shards: Vec<BloomFilter> = (0..32).map(|_| FilterBuilder::new(1_000_000, 0.001).build_bloom_filter()).collect();
+    shards[31].add(key.as_bytes());
+
+    c.bench_function("bloom_fan_out_32_contains_test", |b| b.iter(|| shards.iter().filter(|shard| shard.contains(black_box(key.as_bytes()))).count()));
+    c.bench_function("bloom_fan_out_32_contains_precomputed_test", |b| b.iter(|| {
+        let hashes = shards[0].hash_key(black_box(key.as_bytes()));
+        shards.iter().filter(|shard| shard.contains_precomputed(&hashes)).count()
+    }));
+}
+
+fn bloom_from_bytes_bench(c: &mut Criterion) {
+    let mut bloom = FilterBuilder::from_size_and_hashes(800_000_000, 7).build_bloom_filter();
+    bloom.add(b"hello");
+    let bytes = bloom.to_bytes();
+
+    let mut group = c.benchmark_group("bloom_from_bytes_100mb");
+    group.sample_size(20);
+    group.bench_function("bloom_from_bytes_test", |b| b.iter(|| BloomFilter::from_bytes(black_box(&bytes)).unwrap()));
+    group.bench_function("bloom_from_bytes_unverified_test", |b| b.iter(|| BloomFilter::from_bytes_unverified(black_box(&bytes)).unwrap()));
+    group.finish();
+}
+
+fn bloom_from_u64_vec_bench(c: &mut Criterion) {
+    let mut bloom = FilterBuilder::from_size_and_hashes(800_000_000, 7).build_bloom_filter();
+    bloom.add(b"hello");
+    let words = bloom.to_u64_vec();
+
+    let mut group = c.benchmark_group("bloom_from_u64_100mb");
+    group.sample_size(20);
+    group.bench_function("bloom_from_u64_array_test", |b| b.iter(|| BloomFilter::from_u64_array(black_box(&words), 7)));
+    group.bench_function("bloom_from_u64_vec_test", |b| b.iter_batched(|| words.clone(), |words| BloomFilter::from_u64_vec(words, 7), BatchSize::LargeInput));
+    group.finish();
 }
 
 fn counting_bloom_add_bench(c: &mut Criterion) {
@@ -167,5 +254,7 @@ fn counting_bloom_add_bench(c: &mut Criterion) {
     }));
 }
 
-criterion_group!(benches, bloom_add_bench, counting_bloom_add_bench);
+criterion_group!(benches, bloom_add_bench, bloom_power_of_two_bench, bloom_hash_algorithm_bench,
+                 bloom_precomputed_fan_out_bench, bloom_from_bytes_bench, bloom_from_u64_vec_bench,
+                 counting_bloom_add_bench);
 criterion_main!(benches);
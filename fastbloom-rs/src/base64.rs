@@ -0,0 +1,79 @@
+//! Standard base64 (RFC 4648) with padding, for [crate::BloomFilter::to_base64].
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Encodes `bytes` with padding to a multiple of 4 characters.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                res.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                res.push('=');
+            }
+        }
+    }
+    res
+}
+
+fn value(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+        b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// Decodes `text`, with or without the padding. Fails with a description of the first invalid
+/// character, or if the length or the bits after the last byte are not those of an encoding.
+pub(crate) fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let data = text.as_bytes();
+    let unpadded = data.strip_suffix(b"==").or_else(|| data.strip_suffix(b"=")).unwrap_or(data);
+    if unpadded.len() != data.len() && !data.len().is_multiple_of(4) {
+        return Err(format!("padded length {} is not a multiple of 4", data.len()));
+    }
+    if unpadded.len() % 4 == 1 {
+        return Err(format!("length {} is not the length of any encoding", unpadded.len()));
+    }
+    let mut res = Vec::with_capacity(unpadded.len() / 4 * 3 + 2);
+    for (i, chunk) in unpadded.chunks(4).enumerate() {
+        let mut n = 0;
+        for (j, &c) in chunk.iter().enumerate() {
+            let v = value(c).ok_or_else(|| format!("invalid character {:?} at {}", c as char,
+                                                   i * 4 + j))?;
+            n |= v << (18 - 6 * j);
+        }
+        let len = chunk.len() - 1;
+        if n & (0xffffff >> (8 * len)) != 0 {
+            return Err("trailing bits after the last byte".to_string());
+        }
+        res.extend_from_slice(&n.to_be_bytes()[1..1 + len]);
+    }
+    Ok(res)
+}
+
+#[test]
+fn base64_test() {
+    // RFC 4648 test vectors
+    for (bytes, text) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+                          ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")] {
+        assert_eq!(encode(bytes.as_bytes()), text);
+        assert_eq!(decode(text).unwrap(), bytes.as_bytes());
+        assert_eq!(decode(text.trim_end_matches('=')).unwrap(), bytes.as_bytes());
+    }
+    let bytes: Vec<u8> = (0..=255).collect();
+    assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+    assert_eq!(encode(&[0xfb, 0xff]), "+/8=");
+
+    assert_eq!(decode("Zm9v!A"), Err("invalid character '!' at 4".to_string()));
+    assert_eq!(decode("Zm9vY"), Err("length 5 is not the length of any encoding".to_string()));
+    assert_eq!(decode("Zm8=="), Err("padded length 5 is not a multiple of 4".to_string()));
+    assert_eq!(decode("Zm9="), Err("trailing bits after the last byte".to_string()));
+    assert_eq!(decode("Zm=v").is_err(), true);
+}
@@ -1,4 +1,4 @@
-use fastbloom_rs::{FilterBuilder, Membership};
+use fastbloom_rs::FilterBuilder;
 
 fn main() {
     let false_positive_probability = 0.01;
@@ -9,13 +9,13 @@ fn main() {
         let mut bloom = builder.build_bloom_filter();
 
         for x in 1..except {
-            bloom.add(&u64::to_le_bytes(x));
+            bloom.add(u64::to_le_bytes(x));
         }
 
         let mut error = 0;
         for x in 1..except {
             let check = x + except;
-            if bloom.contains(&u64::to_le_bytes(check)) {
+            if bloom.contains(u64::to_le_bytes(check)) {
                 error += 1;
             }
         }
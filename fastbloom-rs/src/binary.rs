@@ -0,0 +1,1723 @@
+//! The binary format of [BloomFilter::to_bytes] and [BloomFilter::to_writer].
+
+use std::cmp::min;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use xxhash_rust::xxh3::{xxh3_64, Xxh3Default};
+
+#[cfg(feature = "compression")]
+use crate::compress;
+use crate::{base64, hex, varint};
+use crate::error::FastBloomError;
+use crate::hasher::{HashAlgorithm, ProbeScheme};
+use crate::vec::BloomBitVec;
+use crate::{BloomFilter, FilterBuilder, Hashes};
+
+/// The first bytes of every serialized filter.
+const MAGIC: [u8; 4] = *b"FBLM";
+
+/// The length of the fields of the header every version has.
+const FIXED_LEN: usize = 36;
+
+/// The length of the fixed part of the header, with the encoding of the bits of version 3.
+const HEADER_LEN: usize = FIXED_LEN + 1;
+
+/// The length of the checksum after the bits, from version 2 on.
+const CHECKSUM_LEN: usize = 8;
+
+/// The hasher id of hashers stored by name.
+const NAMED_HASHER: u8 = 0xff;
+
+/// The encoding of the bits as `u64` words.
+const DENSE: u8 = 0;
+
+/// The encoding of the bits compressed by [BloomFilter::to_bytes_compressed].
+const COMPRESSED: u8 = 1;
+
+/// The encoding of the bits as the positions of the set bits, see
+/// [BloomFilter::to_bytes_with_sparse_threshold].
+const SPARSE: u8 = 2;
+
+/// The largest size in bits of a filter read with sparse or compressed bits, 128 GiB of bits on
+/// 64-bit platforms. Unlike dense bits, a few bytes of them can claim any size, so the words are
+/// only allocated up to this size.
+const MAX_DECODED_SIZE: u64 = if usize::MAX as u64 > 1 << 40 { 1 << 40 } else { usize::MAX as u64 };
+
+/// The number of words [BloomFilter::to_writer] and [BloomFilter::from_reader] buffer.
+const CHUNK_WORDS: usize = 512;
+
+fn hasher_id(hasher: &HashAlgorithm) -> u8 {
+    match hasher {
+        HashAlgorithm::Xxh128 => 0,
+        HashAlgorithm::Xxh3 => 1,
+        HashAlgorithm::Murmur3 => 2,
+        HashAlgorithm::Murmur64A => 3,
+        HashAlgorithm::PyBloomMd5 => 4,
+        _ => NAMED_HASHER,
+    }
+}
+
+fn probe_scheme_id(scheme: ProbeScheme) -> u8 {
+    match scheme {
+        ProbeScheme::Double => 0,
+        ProbeScheme::EnhancedDouble => 1,
+        ProbeScheme::Triple => 2,
+        ProbeScheme::Guava => 3,
+        ProbeScheme::RedisBloom => 4,
+        ProbeScheme::PyBloom => 5,
+    }
+}
+
+/// The length of the name of a hasher stored by name.
+fn name_len(name: &str) -> u16 {
+    u16::try_from(name.len())
+        .unwrap_or_else(|_| panic!("hasher name with len {} is too long!", name.len()))
+}
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization { reason }
+}
+
+/// Fills `buf`, failing with a [FastBloomError::Deserialization] naming `what` if the data ends
+/// before.
+fn read_exact<R: Read>(reader: &mut R, buf: &mut [u8], what: &str) -> Result<(), FastBloomError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => invalid(format!("the data ends within the {}", what)),
+        _ => e.into(),
+    })
+}
+
+/// The fields of the header.
+struct Header {
+    version: u8,
+    config: FilterBuilder,
+    len: u64,
+    encoding: u8,
+    /// The bytes of the header, for the checksum.
+    raw: Vec<u8>,
+}
+
+impl Header {
+    /// Reads the header, from the magic bytes to the name of the hasher.
+    fn read<R: Read>(reader: &mut R) -> Result<Header, FastBloomError> {
+        let mut fixed = [0u8; HEADER_LEN];
+        read_exact(reader, &mut fixed[..MAGIC.len() + 1], "header")?;
+        if fixed[..MAGIC.len()] != MAGIC {
+            return Err(FastBloomError::NotABloomFilter);
+        }
+        let version = fixed[MAGIC.len()];
+        if version == 0 || version > BloomFilter::FORMAT_VERSION {
+            return Err(FastBloomError::UnsupportedVersion {
+                version,
+                supported: BloomFilter::FORMAT_VERSION,
+            });
+        }
+        let fixed = if version >= 3 { &mut fixed[..] } else { &mut fixed[..FIXED_LEN] };
+        read_exact(reader, &mut fixed[MAGIC.len() + 1..], "header")?;
+        let mut raw = fixed.to_vec();
+        let encoding = fixed.get(FIXED_LEN).copied().unwrap_or(DENSE);
+
+        let hash_version = fixed[6];
+        let probe_scheme = match fixed[7] {
+            0 => ProbeScheme::Double,
+            1 => ProbeScheme::EnhancedDouble,
+            2 => ProbeScheme::Triple,
+            3 => ProbeScheme::Guava,
+            4 => ProbeScheme::RedisBloom,
+            5 => ProbeScheme::PyBloom,
+            id => return Err(invalid(format!("unknown probe scheme {}", id))),
+        };
+        let seed = u64::from_le_bytes(fixed[8..16].try_into().unwrap());
+        let hashes = u32::from_le_bytes(fixed[16..20].try_into().unwrap());
+        let size = u64::from_le_bytes(fixed[20..28].try_into().unwrap());
+        let len = u64::from_le_bytes(fixed[28..36].try_into().unwrap());
+        let hasher = match fixed[5] {
+            0 => HashAlgorithm::Xxh128,
+            1 => HashAlgorithm::Xxh3,
+            2 => HashAlgorithm::Murmur3,
+            3 => HashAlgorithm::Murmur64A,
+            4 => HashAlgorithm::PyBloomMd5,
+            NAMED_HASHER => {
+                let mut name_len = [0u8; 2];
+                read_exact(reader, &mut name_len, "hasher name")?;
+                let mut name = vec![0u8; u16::from_le_bytes(name_len) as usize];
+                read_exact(reader, &mut name, "hasher name")?;
+                raw.extend_from_slice(&name_len);
+                raw.extend_from_slice(&name);
+                let name = String::from_utf8(name)
+                    .map_err(|_| invalid("hasher name is not UTF-8".to_string()))?;
+                HashAlgorithm::from_name(name)
+            }
+            id => return Err(invalid(format!("unknown hasher {}", id))),
+        };
+        if !(1..=FilterBuilder::HASH_VERSION).contains(&hash_version) {
+            return Err(invalid(format!("unknown hash version {}", hash_version)));
+        }
+        if size == 0 || hashes == 0 {
+            return Err(invalid(format!("invalid size {} with {} hashes", size, hashes)));
+        }
+        if !matches!(encoding, DENSE | COMPRESSED | SPARSE) {
+            return Err(invalid(format!("unknown encoding of the bits {}", encoding)));
+        }
+
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashes)
+            .with_seed(seed)
+            .with_hash_version(hash_version)
+            .with_probe_scheme(probe_scheme);
+        config.hasher = hasher;
+        Ok(Header { version, config, len, encoding, raw })
+    }
+
+    /// The number of `u64` words of bits following the header.
+    fn words(&self) -> u64 {
+        self.config.size.div_ceil(64)
+    }
+
+    /// Reads the bits following the header, adding them to the checksum as `u64` words.
+    fn read_bits<R: Read>(&self, reader: &mut R, hasher: &mut Xxh3Default)
+                          -> Result<Vec<u64>, FastBloomError> {
+        if self.encoding != DENSE {
+            let words = if self.encoding == SPARSE {
+                self.read_sparse(reader)?
+            } else {
+                self.read_compressed(reader)?
+            };
+            for word in &words {
+                hasher.update(&word.to_le_bytes());
+            }
+            return Ok(words);
+        }
+        let total = self.words();
+        // grows with the data read instead of trusting the size of the header
+        let mut words = Vec::with_capacity(min(total, 1 << 20) as usize);
+        let mut buf = [0u8; 8 * CHUNK_WORDS];
+        let mut read = 0;
+        while read < total {
+            let n = min(total - read, CHUNK_WORDS as u64) as usize;
+            read_exact(reader, &mut buf[..n * 8], "bits")?;
+            hasher.update(&buf[..n * 8]);
+            words.extend(buf[..n * 8].chunks_exact(8)
+                .map(|word| u64::from_le_bytes(word.try_into().unwrap())));
+            read += n as u64;
+        }
+        Ok(words)
+    }
+
+    fn read_sparse<R: Read>(&self, reader: &mut R) -> Result<Vec<u64>, FastBloomError> {
+        let mut len = [0u8; 8];
+        read_exact(reader, &mut len, "sparse bits")?;
+        let len = u64::from_le_bytes(len);
+        self.check_decoded_size("sparse")?;
+        // grows with the data read instead of trusting the length
+        let mut data = Vec::with_capacity(min(len, 1 << 20) as usize);
+        reader.take(len).read_to_end(&mut data)?;
+        if data.len() as u64 != len {
+            return Err(invalid("the data ends within the sparse bits".to_string()));
+        }
+        varint::decode(&data, self.config.size).map_err(invalid)
+    }
+
+    /// Fails if the filter is larger than [MAX_DECODED_SIZE], naming the `encoding` of the bits.
+    fn check_decoded_size(&self, encoding: &str) -> Result<(), FastBloomError> {
+        if self.config.size > MAX_DECODED_SIZE {
+            return Err(invalid(format!("a size of {} bits is too large for {} bits, at most {}",
+                                       self.config.size, encoding, MAX_DECODED_SIZE)));
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "compression")]
+    fn read_compressed<R: Read>(&self, reader: &mut R) -> Result<Vec<u64>, FastBloomError> {
+        let mut len = [0u8; 8];
+        read_exact(reader, &mut len, "compressed bits")?;
+        let len = u64::from_le_bytes(len);
+        self.check_decoded_size("compressed")?;
+        if len > self.words() * 8 {
+            return Err(FastBloomError::CorruptCompressedData {
+                reason: format!("{} bytes of compressed bits for {} bytes of bits", len,
+                                self.words() * 8),
+            });
+        }
+        let mut data = vec![0u8; len as usize];
+        read_exact(reader, &mut data, "compressed bits")?;
+        compress::decode(&data, self.config.size)
+            .map_err(|reason| FastBloomError::CorruptCompressedData { reason })
+    }
+
+    #[cfg(not(feature = "compression"))]
+    fn read_compressed<R: Read>(&self, _: &mut R) -> Result<Vec<u64>, FastBloomError> {
+        Err(FastBloomError::CompressionDisabled)
+    }
+
+    fn into_filter(self, words: Vec<u64>) -> BloomFilter {
+        let size = self.config.size;
+        let bit_vec = BloomBitVec::from_u64_vec(words, size);
+        let mut bloom = BloomFilter::from_parts(self.config, bit_vec);
+        bloom.set_len(self.len);
+        bloom
+    }
+}
+
+impl BloomFilter {
+    /// The version of the binary format written by [BloomFilter::to_bytes].
+    ///
+    /// 1. The first version, still written by [BloomFilter::to_compact_bytes].
+    /// 2. A checksum of the header and the bits follows the bits.
+    /// 3. A byte for the encoding of the bits follows the fixed fields of the header, see
+    ///    [BloomFilter::to_bytes_compressed].
+    pub const FORMAT_VERSION: u8 = 3;
+
+    /// The share of set bits below which [BloomFilter::to_bytes] writes the bits sparse, as the
+    /// positions of the set bits. Below 5% nearly all the gaps between the set bits take one byte,
+    /// so the sparse bits are at most about 40% of the `u64` words, and 1.6% at 0.1%.
+    pub const SPARSE_THRESHOLD: f64 = 0.05;
+
+    /// Serializes the filter with everything needed to query it again: unlike
+    /// [BloomFilter::get_u8_array] the size, hashes, seed, hasher, hash version, probe scheme and
+    /// [BloomFilter::len] are written along with the bits. Read it back with
+    /// [BloomFilter::from_bytes].
+    ///
+    /// The format is the same on every platform, all integers are little endian:
+    ///
+    /// | bytes | field |
+    /// |-------|-------|
+    /// | 4 | magic bytes `FBLM` |
+    /// | 1 | format version, [BloomFilter::FORMAT_VERSION] |
+    /// | 1 | hasher, see below |
+    /// | 1 | hash version, see [FilterBuilder::with_hash_version] |
+    /// | 1 | probe scheme, see below |
+    /// | 8 | seed |
+    /// | 4 | hashes |
+    /// | 8 | size in bits |
+    /// | 8 | [BloomFilter::len] |
+    /// | 1 | encoding of the bits: 0 `u64` words, 1 compressed, 2 sparse, from version 3 on |
+    /// | 2 + n | for hasher 255, the length and the UTF-8 bytes of [HashAlgorithm::name] |
+    /// | 8 * ceil(size / 64) | the bits as `u64` words, see [BloomFilter::to_u64_vec] |
+    /// | 8 | xxh3 64 of all the bytes before it, from version 2 on |
+    ///
+    /// The hasher is 0 xxh128, 1 xxh3, 2 murmur3_x64_128, 3 murmur64a, 4 pybloom_md5 or 255 for
+    /// a hasher stored by name, the probe scheme 0 double, 1 enhanced double, 2 triple, 3 guava,
+    /// 4 redisbloom or 5 pybloom.
+    ///
+    /// Filters with less than [BloomFilter::SPARSE_THRESHOLD] of their bits set are written
+    /// sparse, see [BloomFilter::to_bytes_with_sparse_threshold]. Sparse and compressed bits are
+    /// the length of the data as 8 bytes followed by the data, and the checksum is still the one
+    /// of the header and the bits as `u64` words, so it detects a damaged filter however it is
+    /// encoded.
+    ///
+    /// Like serde, only the name of a hasher which is not built in is written, and the filter
+    /// read back gets a [HashAlgorithm::Unresolved] hasher. The other fields of the
+    /// configuration are not written, see [BloomFilter::from_bytes].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name of the hasher is longer than 65535 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).with_seed(7).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let bytes = bloom.to_bytes();
+    ///
+    /// let copy = BloomFilter::from_bytes(&bytes).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// assert_eq!(copy, bloom);
+    /// ```
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.to_bytes_with_sparse_threshold(BloomFilter::SPARSE_THRESHOLD)
+    }
+
+    /// Same as [BloomFilter::to_bytes] with the bits written sparse if less than `threshold` of
+    /// them are set instead of [BloomFilter::SPARSE_THRESHOLD], e.g. 0 to always write them as
+    /// `u64` words.
+    ///
+    /// Sparse bits are the gaps between the set bits as varints, cheap to decode and about one
+    /// or two bytes per set bit: a filter of a billion bits holding 100k elements with 7 hashes
+    /// takes about 1.4 MB instead of 125 MB. From about 12% set bits on they are larger than the
+    /// `u64` words.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(1_000_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let dense = bloom.to_bytes_with_sparse_threshold(0.0);
+    /// let sparse = bloom.to_bytes();
+    /// assert!(sparse.len() < dense.len() / 100);
+    ///
+    /// assert_eq!(BloomFilter::from_bytes(&dense).unwrap(), bloom);
+    /// assert_eq!(BloomFilter::from_bytes(&sparse).unwrap(), bloom);
+    /// ```
+    pub fn to_bytes_with_sparse_threshold(&self, threshold: f64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        if !self.is_sparse(threshold) {
+            bytes.reserve_exact(HEADER_LEN + 2 + self.config().hasher.name().len()
+                + self.config().size.div_ceil(64) as usize * 8 + CHECKSUM_LEN);
+        }
+        self.to_writer_with_sparse_threshold(&mut bytes, threshold)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    fn is_sparse(&self, threshold: f64) -> bool {
+        (self.count_ones() as f64) < threshold * self.config().size as f64
+    }
+
+    /// Writes the filter in the format of [BloomFilter::to_bytes] to `writer`, 4 KiB of bits at
+    /// a time, without copying the whole filter into memory first. Only sparse bits, which are
+    /// small, are encoded before they are written. Read it back with [BloomFilter::from_reader].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name of the hasher is longer than 65535 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let mut buf = Vec::new();
+    /// bloom.to_writer(&mut buf).unwrap();
+    ///
+    /// let copy = BloomFilter::from_reader(&buf[..]).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn to_writer<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.to_writer_with_sparse_threshold(writer, BloomFilter::SPARSE_THRESHOLD)
+    }
+
+    /// Same as [BloomFilter::to_writer] with the sparse threshold of
+    /// [BloomFilter::to_bytes_with_sparse_threshold].
+    pub fn to_writer_with_sparse_threshold<W: Write>(&self, writer: W, threshold: f64)
+                                                     -> io::Result<()> {
+        if self.is_sparse(threshold) {
+            let sparse = varint::encode(self.bit_vec(), self.config().size);
+            return self.write_encoded(writer, Some((SPARSE, &sparse)));
+        }
+        self.write_encoded(writer, None)
+    }
+
+    /// Writes the filter with the bits as `u64` words, or as the `encoded` data of an encoding
+    /// if given.
+    fn write_encoded<W: Write>(&self, mut writer: W, encoded: Option<(u8, &[u8])>)
+                               -> io::Result<()> {
+        let config = self.config();
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&[0; FIXED_LEN]);
+        let id = self.write_fixed_header(&mut header, BloomFilter::FORMAT_VERSION);
+        header.push(encoded.map_or(DENSE, |(encoding, _)| encoding));
+        if id == NAMED_HASHER {
+            let name = config.hasher.name();
+            header.extend_from_slice(&name_len(&name).to_le_bytes());
+            header.extend_from_slice(name.as_bytes());
+        }
+        let mut hasher = Xxh3Default::new();
+        hasher.update(&header);
+        writer.write_all(&header)?;
+
+        let bit_vec = self.bit_vec();
+        let words = config.size.div_ceil(64) as usize;
+        let mut buf = [0u8; 8 * CHUNK_WORDS];
+        for start in (0..words).step_by(CHUNK_WORDS) {
+            let end = min(start + CHUNK_WORDS, words);
+            for (i, chunk) in (start..end).zip(buf.chunks_exact_mut(8)) {
+                chunk.copy_from_slice(&bit_vec.u64_word(i).to_le_bytes());
+            }
+            let chunk = &buf[..(end - start) * 8];
+            hasher.update(chunk);
+            if encoded.is_none() {
+                writer.write_all(chunk)?;
+            }
+        }
+        if let Some((_, data)) = encoded {
+            writer.write_all(&(data.len() as u64).to_le_bytes())?;
+            writer.write_all(data)?;
+        }
+        writer.write_all(&hasher.digest().to_le_bytes())
+    }
+
+    /// Writes the fields of the header every version has to the first [FIXED_LEN] bytes of
+    /// `out`, returning the hasher id.
+    fn write_fixed_header(&self, out: &mut [u8], version: u8) -> u8 {
+        let config = self.config_ref();
+        let id = hasher_id(&config.hasher);
+        out[..4].copy_from_slice(&MAGIC);
+        out[4] = version;
+        out[5] = id;
+        out[6] = config.hash_version;
+        out[7] = probe_scheme_id(config.probe_scheme);
+        out[8..16].copy_from_slice(&config.seed.to_le_bytes());
+        out[16..20].copy_from_slice(&self.hashes().to_le_bytes());
+        out[20..28].copy_from_slice(&config.size.to_le_bytes());
+        out[28..36].copy_from_slice(&self.len().to_le_bytes());
+        id
+    }
+
+    /// Writes the length and the name of the hasher to the start of `out` if it is stored by
+    /// name, returning the number of bytes written.
+    fn write_hasher_name(&self, out: &mut [u8], id: u8) -> usize {
+        if id != NAMED_HASHER {
+            return 0;
+        }
+        let name = self.config_ref().hasher.name();
+        out[..2].copy_from_slice(&name_len(&name).to_le_bytes());
+        out[2..2 + name.len()].copy_from_slice(name.as_bytes());
+        2 + name.len()
+    }
+
+    /// The bytes of [BloomFilter::write_into] before the bits: the header with the encoding of
+    /// `u64` words and the name of the hasher if it is stored by name.
+    pub(crate) fn dense_header(&self) -> Vec<u8> {
+        let mut header = vec![0u8; self.compact_serialized_size() + 1
+            - self.config_ref().size.div_ceil(64) as usize * 8];
+        let id = self.write_fixed_header(&mut header, BloomFilter::FORMAT_VERSION);
+        header[FIXED_LEN] = DENSE;
+        self.write_hasher_name(&mut header[HEADER_LEN..], id);
+        header
+    }
+
+    /// The length of [BloomFilter::write_into], to allocate the buffer. It is the length of
+    /// [BloomFilter::to_bytes] for filters whose bits are not written sparse.
+    pub fn serialized_size(&self) -> usize {
+        self.compact_serialized_size() + 1 + CHECKSUM_LEN
+    }
+
+    /// Writes the filter to the start of `buf` in the format of [BloomFilter::to_bytes] and
+    /// returns the number of bytes written, [BloomFilter::serialized_size]. Nothing is
+    /// allocated, except the name of a [HashAlgorithm::SipHash13] hasher is formatted, so one
+    /// buffer allocated up front can take filter after filter. The bits are always written as
+    /// `u64` words, as encoding them sparse would allocate. Read them back with
+    /// [BloomFilter::from_bytes] from the bytes written, not the whole buffer.
+    ///
+    /// Returns [FastBloomError::BufferTooSmall], stating the bytes needed, without writing
+    /// anything if `buf` is shorter than the filter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name of the hasher is longer than 65535 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let mut frame = [0u8; 2048];
+    /// let len = bloom.write_into(&mut frame).unwrap();
+    /// assert_eq!(len, bloom.serialized_size());
+    ///
+    /// let copy = BloomFilter::from_bytes(&frame[..len]).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn write_into(&self, buf: &mut [u8]) -> Result<usize, FastBloomError> {
+        let len = self.serialized_size();
+        if buf.len() < len {
+            return Err(FastBloomError::BufferTooSmall { needed: len, len: buf.len() });
+        }
+        let id = self.write_fixed_header(buf, BloomFilter::FORMAT_VERSION);
+        buf[FIXED_LEN] = DENSE;
+        let pos = HEADER_LEN + self.write_hasher_name(&mut buf[HEADER_LEN..], id);
+        let end = len - CHECKSUM_LEN;
+        let bit_vec = self.bit_vec();
+        for (i, word) in buf[pos..end].chunks_exact_mut(8).enumerate() {
+            word.copy_from_slice(&bit_vec.u64_word(i).to_le_bytes());
+        }
+        let mut hasher = Xxh3Default::new();
+        hasher.update(&buf[..end]);
+        buf[end..len].copy_from_slice(&hasher.digest().to_le_bytes());
+        Ok(len)
+    }
+
+    /// The length of [BloomFilter::to_compact_bytes], to allocate the buffer.
+    pub fn compact_serialized_size(&self) -> usize {
+        let config = self.config_ref();
+        let name = match hasher_id(&config.hasher) {
+            NAMED_HASHER => 2 + config.hasher.name().len(),
+            _ => 0,
+        };
+        FIXED_LEN + name + config.size.div_ceil(64) as usize * 8
+    }
+
+    /// Writes the filter to the start of `out` in a compact profile of the binary format, for
+    /// small filters kept where allocations and format overhead matter, and returns the number
+    /// of bytes written, [BloomFilter::compact_serialized_size]. Nothing is allocated, except
+    /// the name of a [HashAlgorithm::SipHash13] hasher is formatted. To embed the filter into a
+    /// postcard or other serde message, store the bytes as a byte slice.
+    ///
+    /// The compact bytes are version 1 of the format of [BloomFilter::to_bytes]: the fixed
+    /// width little endian header (36 bytes for the built-in hashers) followed by the bits as
+    /// `u64` words, without the encoding byte and the checksum of later versions. Since nothing
+    /// detects modified bits, keep them where the storage or transport checks their integrity.
+    /// Read them back with [BloomFilter::from_compact_bytes].
+    ///
+    /// Returns [FastBloomError::BufferTooSmall] without writing anything if `out` is shorter
+    /// than the filter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name of the hasher is longer than 65535 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(256, 3).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let mut buf = [0u8; 128];
+    /// assert_eq!(bloom.compact_serialized_size(), 36 + 32);
+    /// let len = bloom.to_compact_bytes(&mut buf).unwrap();
+    ///
+    /// let copy = BloomFilter::from_compact_bytes(&buf[..len]).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn to_compact_bytes(&self, out: &mut [u8]) -> Result<usize, FastBloomError> {
+        let len = self.compact_serialized_size();
+        if out.len() < len {
+            return Err(FastBloomError::BufferTooSmall { needed: len, len: out.len() });
+        }
+        let id = self.write_fixed_header(out, 1);
+        let pos = FIXED_LEN + self.write_hasher_name(&mut out[FIXED_LEN..], id);
+        let bit_vec = self.bit_vec();
+        for (i, word) in out[pos..len].chunks_exact_mut(8).enumerate() {
+            word.copy_from_slice(&bit_vec.u64_word(i).to_le_bytes());
+        }
+        Ok(len)
+    }
+
+    /// Reads a filter written by [BloomFilter::to_compact_bytes]. The bytes must hold exactly
+    /// the filter, [BloomFilter::compact_serialized_size] bytes. As they are a version of the
+    /// binary format, it reads the bytes of [BloomFilter::to_bytes] too, and
+    /// [BloomFilter::from_bytes] and [BloomFilter::from_reader] read compact bytes.
+    pub fn from_compact_bytes(bytes: &[u8]) -> Result<BloomFilter, FastBloomError> {
+        BloomFilter::from_bytes(bytes)
+    }
+
+    /// Same as [BloomFilter::to_bytes] with the bits compressed, for filters which are shipped
+    /// or stored while they are far from full. [BloomFilter::from_bytes] and the other readers
+    /// detect and decompress them.
+    ///
+    /// The gaps between the set bits are Rice coded, which is close to the entropy of the bits
+    /// of a filter: a 5% full filter compresses to about a third of its size, a 1% full one to
+    /// about a tenth. If that is not smaller than the bits written by [BloomFilter::to_bytes],
+    /// sparse or `u64` words, e.g. for filters which are about a third full or more, those are
+    /// written. There is no level to trade speed for size, the code is as small as it gets.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// for i in 0..5_000u32 {
+    ///     bloom.add(&i.to_le_bytes());
+    /// }
+    /// let bytes = bloom.to_bytes_compressed();
+    /// assert!(bytes.len() < bloom.to_bytes_with_sparse_threshold(0.0).len() / 2);
+    /// assert!(bytes.len() < bloom.to_bytes().len());
+    ///
+    /// let copy = BloomFilter::from_bytes(&bytes).unwrap();
+    /// assert_eq!(copy, bloom);
+    /// ```
+    #[cfg(feature = "compression")]
+    pub fn to_bytes_compressed(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.to_writer_compressed(&mut bytes).expect("writing to a Vec can't fail");
+        bytes
+    }
+
+    /// Same as [BloomFilter::to_writer] with the bits compressed like
+    /// [BloomFilter::to_bytes_compressed] does. Unlike [BloomFilter::to_writer] the compressed
+    /// bits are held in memory before they are written. [BloomFilter::from_reader] reads them.
+    #[cfg(feature = "compression")]
+    pub fn to_writer_compressed<W: Write>(&self, writer: W) -> io::Result<()> {
+        let compressed = compress::encode(self.bit_vec(), self.config().size);
+        if self.is_sparse(BloomFilter::SPARSE_THRESHOLD) {
+            let sparse = varint::encode(self.bit_vec(), self.config().size);
+            if sparse.len() <= compressed.len() {
+                return self.write_encoded(writer, Some((SPARSE, &sparse)));
+            }
+        } else if compressed.len() as u64 + 8 >= self.config().size.div_ceil(64) * 8 {
+            return self.write_encoded(writer, None);
+        }
+        self.write_encoded(writer, Some((COMPRESSED, &compressed)))
+    }
+
+    /// Reads a filter written by [BloomFilter::to_bytes].
+    ///
+    /// The configuration is rebuilt from the size and hashes like
+    /// [FilterBuilder::from_size_and_hashes] does with the seed, hasher, hash version and probe
+    /// scheme read back, so the filter is [BloomFilter::compatible] with the one written.
+    ///
+    /// The checksum is verified before anything else is read, so a truncated or modified filter
+    /// returns [FastBloomError::CorruptData] instead of wrong answers. It is computed with xxh3 at
+    /// memory speed: it adds about 10 ms to loading a 100 MB filter, 62 ms instead of 52 ms in
+    /// the `bloom_from_bytes_bench` benchmark.
+    /// Version 1 data, which has no checksum, is read unverified.
+    ///
+    /// Sparse and compressed bits, see [BloomFilter::to_bytes_with_sparse_threshold] and
+    /// [BloomFilter::to_bytes_compressed], are decoded before the checksum is verified. Sparse
+    /// and compressed bits of filters larger than 2^40 bits fail with
+    /// [FastBloomError::Deserialization], as their size is not bounded by the length of the data.
+    /// Compressed bits fail with [FastBloomError::CorruptCompressedData] if they
+    /// can't be decompressed and with [FastBloomError::CompressionDisabled] if the `compression`
+    /// feature is disabled.
+    ///
+    /// Returns [FastBloomError::NotABloomFilter] if the bytes don't start with the magic bytes,
+    /// [FastBloomError::UnsupportedVersion] if they were written in a newer version of the
+    /// format, and [FastBloomError::Deserialization] if they are invalid otherwise, e.g. have
+    /// trailing bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BloomFilter, FastBloomError> {
+        BloomFilter::read_bytes(bytes, true)
+    }
+
+    /// Same as [BloomFilter::from_bytes] without verifying the checksum, for callers which
+    /// check the integrity of the data themselves, e.g. with the checksums of their storage.
+    pub fn from_bytes_unverified(bytes: &[u8]) -> Result<BloomFilter, FastBloomError> {
+        BloomFilter::read_bytes(bytes, false)
+    }
+
+    fn read_bytes(bytes: &[u8], verify: bool) -> Result<BloomFilter, FastBloomError> {
+        if !bytes.starts_with(&MAGIC) {
+            return Err(FastBloomError::NotABloomFilter);
+        }
+        if bytes.len() < FIXED_LEN {
+            return Err(invalid(format!("{} bytes are too short for the header", bytes.len())));
+        }
+        let version = bytes[MAGIC.len()];
+        let mut data = bytes;
+        if version >= 3 && matches!(bytes.get(FIXED_LEN), Some(&(COMPRESSED | SPARSE))) {
+            let bloom = BloomFilter::read_from(&mut data, verify)?;
+            if !data.is_empty() {
+                return Err(invalid(format!("{} bytes after the filter", data.len())));
+            }
+            return Ok(bloom);
+        }
+        if (2..=BloomFilter::FORMAT_VERSION).contains(&version) {
+            if bytes.len() < FIXED_LEN + CHECKSUM_LEN {
+                return Err(invalid(format!("{} bytes are too short for the header",
+                                           bytes.len())));
+            }
+            let (rest, checksum) = bytes.split_at(bytes.len() - CHECKSUM_LEN);
+            if verify {
+                let expected = u64::from_le_bytes(checksum.try_into().unwrap());
+                let found = xxh3_64(rest);
+                if found != expected {
+                    return Err(FastBloomError::CorruptData { expected, found });
+                }
+            }
+            data = rest;
+        }
+        let header = Header::read(&mut data)?;
+        if header.encoding != DENSE {
+            return Err(invalid(format!("unknown encoding of the bits {}", header.encoding)));
+        }
+
+        let words = header.words();
+        if data.len() as u64 != words * 8 {
+            return Err(invalid(format!("{} bytes of bits for a size of {} bits, expected {}",
+                                       data.len(), header.config.size, words * 8)));
+        }
+        let words: Vec<u64> = data.chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        Ok(header.into_filter(words))
+    }
+
+    /// Reads a filter written by [BloomFilter::to_writer] or [BloomFilter::to_bytes] from
+    /// `reader`, 4 KiB of bits at a time, e.g. straight from a file or a socket. Reads which
+    /// return fewer bytes are continued until the filter is complete, and the reader is left
+    /// right after the filter.
+    ///
+    /// The header can't be verified before the bits are read, so while a modified filter fails
+    /// with [FastBloomError::CorruptData] like with [BloomFilter::from_bytes], a modified size
+    /// can also fail with [FastBloomError::Deserialization] when the data ends early. Errors of
+    /// the reader are returned as [FastBloomError::Io] and data which is not a filter at all fails
+    /// with [FastBloomError::NotABloomFilter].
+    pub fn from_reader<R: Read>(reader: R) -> Result<BloomFilter, FastBloomError> {
+        BloomFilter::read_from(reader, true)
+    }
+
+    fn read_from<R: Read>(mut reader: R, verify: bool) -> Result<BloomFilter, FastBloomError> {
+        let header = Header::read(&mut reader)?;
+        let mut hasher = Xxh3Default::new();
+        hasher.update(&header.raw);
+        let words = header.read_bits(&mut reader, &mut hasher)?;
+        if header.version >= 2 {
+            let found = hasher.digest();
+            let mut checksum = [0u8; CHECKSUM_LEN];
+            read_exact(&mut reader, &mut checksum, "checksum")?;
+            let expected = u64::from_le_bytes(checksum);
+            if verify && found != expected {
+                return Err(FastBloomError::CorruptData { expected, found });
+            }
+        }
+        Ok(header.into_filter(words))
+    }
+
+    /// Writes the filter to the file at `path` with [BloomFilter::to_writer], replacing it
+    /// atomically: the filter is written to a temporary file in the same directory, synced to
+    /// disk and then renamed to `path`, so a crash leaves either the old file or the complete new
+    /// one, never a partially written filter. Read it back with [BloomFilter::load_from_file].
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(1_000_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"https://example.com");
+    /// bloom.save_to_file("urls.bloom")?;
+    ///
+    /// let bloom = BloomFilter::load_from_file("urls.bloom")?;
+    /// assert!(bloom.contains(b"https://example.com"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.save_to_file_with(path.as_ref(), |_| Ok(()))
+    }
+
+    /// [BloomFilter::save_to_file] calling `before_rename` with the synced temporary file.
+    fn save_to_file_with<F>(&self, path: &Path, before_rename: F) -> io::Result<()>
+        where F: FnOnce(&Path) -> io::Result<()> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let name = path.file_name().ok_or_else(|| io::Error::new(
+            ErrorKind::InvalidInput, format!("{} is not a file name", path.display())))?;
+        let mut tmp_name = std::ffi::OsString::from(".");
+        tmp_name.push(name);
+        tmp_name.push(format!(".{}.{}.tmp", std::process::id(),
+                              COUNTER.fetch_add(1, Ordering::Relaxed)));
+        let tmp = path.with_file_name(tmp_name);
+
+        let file = OpenOptions::new().write(true).create_new(true).open(&tmp)?;
+        let res = (|| {
+            let mut writer = BufWriter::new(file);
+            self.to_writer(&mut writer)?;
+            writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+            before_rename(&tmp)?;
+            fs::rename(&tmp, path)
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+            return res;
+        }
+        // persist the rename itself
+        #[cfg(unix)]
+        if let Some(dir) = path.parent() {
+            let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Reads a filter written by [BloomFilter::save_to_file] from the file at `path` with
+    /// [BloomFilter::from_reader].
+    ///
+    /// The error tells what went wrong: [FastBloomError::Io] with [ErrorKind::NotFound] if the
+    /// file is missing, [FastBloomError::NotABloomFilter] if it is not a filter,
+    /// [FastBloomError::UnsupportedVersion] if it was written by a newer version of the crate and
+    /// [FastBloomError::CorruptData] or [FastBloomError::Deserialization] if it is damaged. The
+    /// message of I/O errors names the file.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<BloomFilter, FastBloomError> {
+        let path = path.as_ref();
+        let with_path = |e: io::Error| FastBloomError::Io {
+            kind: e.kind(),
+            reason: format!("{}: {}", path.display(), e),
+        };
+        let file = File::open(path).map_err(with_path)?;
+        BloomFilter::from_reader(BufReader::new(file)).map_err(|e| match e {
+            FastBloomError::Io { kind, reason } => with_path(io::Error::new(kind, reason)),
+            e => e,
+        })
+    }
+
+    /// Encodes [BloomFilter::to_bytes] as standard base64 with padding, e.g. to put a filter
+    /// into a JSON config, an environment variable or an HTTP header. Read it back with
+    /// [BloomFilter::from_base64].
+    ///
+    /// The text is 4/3 of the binary format: at most `4 * ceil((45 + 8 * ceil(size / 64)) / 3)`
+    /// characters for the built-in hashers, about 1.33 characters per byte of bits, and less
+    /// for sparse filters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let text = bloom.to_base64();
+    /// assert!(text.len() <= 4 * (45 + 8 * 15usize).div_ceil(3));
+    ///
+    /// let copy = BloomFilter::from_base64(&text).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn to_base64(&self) -> String {
+        base64::encode(&self.to_bytes())
+    }
+
+    /// Decodes a filter encoded by [BloomFilter::to_base64], with or without the padding, and
+    /// reads it with [BloomFilter::from_bytes]. Whitespace around the text is ignored.
+    ///
+    /// Returns [FastBloomError::Deserialization] if the text is not valid base64 and the errors
+    /// of [BloomFilter::from_bytes] if the decoded bytes are not a valid filter.
+    pub fn from_base64(text: &str) -> Result<BloomFilter, FastBloomError> {
+        let bytes = base64::decode(text.trim())
+            .map_err(|reason| invalid(format!("invalid base64: {}", reason)))?;
+        BloomFilter::from_bytes(&bytes)
+    }
+
+    /// Encodes [BloomFilter::to_bytes] as lower case hex digits, e.g. for SQL or a debugging
+    /// console. The text has two characters per byte of the binary format. Read it back with
+    /// [BloomFilter::from_hex].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let text = bloom.to_hex();
+    /// assert!(text.starts_with("46424c4d"));
+    ///
+    /// let copy = BloomFilter::from_hex(&text).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.to_bytes())
+    }
+
+    /// Decodes a filter encoded by [BloomFilter::to_hex], in upper or lower case, and reads it
+    /// with [BloomFilter::from_bytes]. Whitespace around the text is ignored.
+    ///
+    /// Returns [FastBloomError::Deserialization] if the text is not an even number of hex digits
+    /// and the errors of [BloomFilter::from_bytes] if the decoded bytes are not a valid filter.
+    pub fn from_hex(text: &str) -> Result<BloomFilter, FastBloomError> {
+        let bytes = hex::decode(text.trim())
+            .map_err(|reason| invalid(format!("invalid hex: {}", reason)))?;
+        BloomFilter::from_bytes(&bytes)
+    }
+}
+
+/// Reads a filter written by [BloomFilter::to_bytes] or the other writers of the binary
+/// format, like [BloomFilter::from_bytes].
+///
+/// These are the bytes of the self-describing format starting with the magic bytes `FBLM`,
+/// not the raw bits of [BloomFilter::get_u8_array], which [BloomFilter::from_u8_array] reads
+/// with the number of hashes given separately. Raw bits fail with
+/// [FastBloomError::NotABloomFilter] instead of being read as a filter.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, FastBloomError, FilterBuilder, Membership};
+///
+/// let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+/// bloom.add(b"hello");
+/// let bytes = bloom.to_bytes();
+///
+/// let copy: BloomFilter = bytes.as_slice().try_into()?;
+/// assert!(copy.contains(b"hello"));
+/// assert!(BloomFilter::try_from(bloom.get_u8_array()).is_err());
+/// # Ok::<(), FastBloomError>(())
+/// ```
+impl TryFrom<&[u8]> for BloomFilter {
+    type Error = FastBloomError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        BloomFilter::from_bytes(bytes)
+    }
+}
+
+/// Same as `TryFrom<&[u8]>`, taking the buffer the filter was read into. The bits are copied
+/// all the same: a `Vec<u8>` is allocated with the alignment of bytes, and an allocation must
+/// be freed with the alignment it was made with, so it can't hold the `u64` words of the bits.
+impl TryFrom<Vec<u8>> for BloomFilter {
+    type Error = FastBloomError;
+
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        BloomFilter::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+use crate::BloomHasher;
+
+/// The filter of the golden files `testvectors/bloom_v*.bin`.
+#[cfg(test)]
+pub(crate) fn golden_filter() -> BloomFilter {
+    // the hash version the golden bytes were written with
+    let mut bloom = FilterBuilder::from_size_and_hashes(1000, 5).with_seed(42)
+        .with_probe_scheme(ProbeScheme::EnhancedDouble)
+        .with_hash_version(2)
+        .build_bloom_filter();
+    for key in ["apple", "banana", "cherry"] {
+        bloom.add(key.as_bytes());
+    }
+    bloom
+}
+
+#[test]
+fn binary_golden_test() {
+    let bloom = golden_filter();
+    let golden = include_bytes!("../testvectors/bloom_v3.bin");
+    assert_eq!(bloom.to_bytes_with_sparse_threshold(0.0), golden.to_vec());
+    let sparse = include_bytes!("../testvectors/bloom_v3_sparse.bin");
+    assert_eq!(sparse[FIXED_LEN], SPARSE);
+    assert_eq!(bloom.to_bytes(), sparse.to_vec());
+
+    // older versions stay readable, version 2 only lacks the encoding and version 1 the checksum
+    let v2 = include_bytes!("../testvectors/bloom_v2.bin");
+    assert_eq!(v2[4], 2);
+    assert_eq!(golden[FIXED_LEN], DENSE);
+    assert_eq!(v2[5..FIXED_LEN], golden[5..FIXED_LEN]);
+    assert_eq!(v2[FIXED_LEN..v2.len() - CHECKSUM_LEN],
+               golden[HEADER_LEN..golden.len() - CHECKSUM_LEN]);
+    let v1 = include_bytes!("../testvectors/bloom_v1.bin");
+    assert_eq!(v1[4], 1);
+    assert_eq!(v1[5..], v2[5..v2.len() - CHECKSUM_LEN]);
+    let compressed = include_bytes!("../testvectors/bloom_v3_compressed.bin");
+    assert_eq!(compressed[FIXED_LEN], COMPRESSED);
+    #[cfg(feature = "compression")]
+    assert_eq!(compressed[HEADER_LEN + 8..compressed.len() - CHECKSUM_LEN],
+               compress::encode(bloom.bit_vec(), 1000));
+
+    let readable: &[&[u8]] = if cfg!(feature = "compression") {
+        &[golden, sparse, v2, v1, compressed]
+    } else {
+        &[golden, sparse, v2, v1]
+    };
+    for bytes in readable {
+        let copy = BloomFilter::from_bytes(bytes).unwrap();
+        assert_eq!(copy, bloom);
+        assert_eq!(copy.len(), 3);
+        assert_eq!(copy.config().seed, 42);
+        assert_eq!(copy.config().probe_scheme, ProbeScheme::EnhancedDouble);
+        for key in ["apple", "banana", "cherry"] {
+            assert_eq!(copy.contains(key.as_bytes()), true);
+        }
+        assert_eq!(BloomFilter::from_reader(&bytes[..]).unwrap(), bloom);
+    }
+
+    // the 24 bits after the 1000 bits of the filter are ignored
+    let mut padded = v1.to_vec();
+    let len = padded.len();
+    padded[len - 3..].fill(0xff);
+    let copy = BloomFilter::from_bytes(&padded).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.count_ones(), bloom.count_ones());
+}
+
+#[test]
+fn binary_round_trip_test() {
+    for mut config in [FilterBuilder::new(10_000, 0.01),
+                   FilterBuilder::new(10_000, 0.01).with_hash_algorithm(HashAlgorithm::Xxh3)
+                       .with_hash_version(1),
+                   FilterBuilder::new(10_000, 0.01).with_hash_algorithm(HashAlgorithm::Murmur3)
+                       .with_probe_scheme(ProbeScheme::Triple),
+                   FilterBuilder::from_size_and_hashes(77, 3).with_seed(u64::MAX)] {
+        let mut bloom = config.build_bloom_filter();
+        for i in 0..1000u32 {
+            bloom.add(i.to_le_bytes());
+        }
+        let bytes = bloom.to_bytes();
+        assert_eq!(bytes.len() as u64, (HEADER_LEN + CHECKSUM_LEN) as u64
+            + bloom.config().size.div_ceil(64) * 8);
+        let copy = BloomFilter::from_bytes(&bytes).unwrap();
+        assert_eq!(copy, bloom);
+        assert_eq!(copy.len(), 1000);
+        assert_eq!(copy.to_bytes(), bytes);
+    }
+
+    #[derive(Debug)]
+    struct Fnv;
+
+    impl BloomHasher for Fnv {
+        fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+            let hash = data.iter().fold(0xcbf29ce484222325 ^ seed,
+                                        |h, b| (h ^ *b as u64).wrapping_mul(0x100000001b3));
+            (hash, hash.rotate_left(32))
+        }
+
+        fn name(&self) -> &str {
+            "fnv1a"
+        }
+    }
+
+    let mut bloom = FilterBuilder::new(1000, 0.01).with_hasher(Fnv).build_bloom_filter();
+    bloom.add(b"hello");
+    let mut copy = BloomFilter::from_bytes(&bloom.to_bytes()).unwrap();
+    assert_eq!(copy.config().hasher, HashAlgorithm::Unresolved("fnv1a".to_string()));
+    assert_eq!(copy.set_hasher(Fnv), Ok(()));
+    assert_eq!(copy.contains(b"hello"), true);
+}
+
+#[test]
+fn binary_invalid_test() {
+    let bytes = golden_filter().to_bytes_with_sparse_threshold(0.0);
+    let reason = |bytes: &[u8]| match BloomFilter::from_bytes(bytes) {
+        Err(FastBloomError::Deserialization { reason }) => reason,
+        res => panic!("{:?}", res.map(|_| ())),
+    };
+
+    assert_eq!(BloomFilter::from_bytes(b"PK\x03\x04 not a filter at all").err(),
+               Some(FastBloomError::NotABloomFilter));
+    assert_eq!(reason(&bytes[..20]), "20 bytes are too short for the header");
+    let resealed = |mut data: Vec<u8>| {
+        let checksum = xxh3_64(&data);
+        data.extend_from_slice(&checksum.to_le_bytes());
+        data
+    };
+    let data = &bytes[..bytes.len() - CHECKSUM_LEN];
+    assert_eq!(reason(&resealed(data[..data.len() - 1].to_vec())),
+               "127 bytes of bits for a size of 1000 bits, expected 128");
+    let trailing = resealed([data, &[0]].concat());
+    assert_eq!(reason(&trailing), "129 bytes of bits for a size of 1000 bits, expected 128");
+
+    let mut newer = bytes.clone();
+    newer[4] = BloomFilter::FORMAT_VERSION + 1;
+    assert_eq!(BloomFilter::from_bytes(&newer).err(), Some(FastBloomError::UnsupportedVersion {
+        version: BloomFilter::FORMAT_VERSION + 1,
+        supported: BloomFilter::FORMAT_VERSION,
+    }));
+    let mut unknown = data.to_vec();
+    unknown[7] = 9;
+    assert_eq!(reason(&resealed(unknown)), "unknown probe scheme 9");
+    let mut empty = data.to_vec();
+    empty[16..20].copy_from_slice(&0u32.to_le_bytes());
+    assert_eq!(reason(&resealed(empty)), "invalid size 1000 with 0 hashes");
+}
+
+#[test]
+fn binary_checksum_test() {
+    let bloom = golden_filter();
+    let bytes = bloom.to_bytes_with_sparse_threshold(0.0);
+    let checksum = u64::from_le_bytes(bytes[bytes.len() - CHECKSUM_LEN..].try_into().unwrap());
+
+    // a bit flipped in the bits, in the header and in the checksum
+    for (index, bit) in [(bytes.len() / 2, 0x10), (20, 0x01), (bytes.len() - 1, 0x80)] {
+        let mut corrupt = bytes.clone();
+        corrupt[index] ^= bit;
+        match BloomFilter::from_bytes(&corrupt) {
+            Err(FastBloomError::CorruptData { expected, found }) => {
+                assert_ne!(expected, found);
+                if index < bytes.len() - CHECKSUM_LEN {
+                    assert_eq!(expected, checksum);
+                } else {
+                    assert_eq!(found, checksum);
+                }
+            }
+            res => panic!("{} {:?}", index, res.map(|_| ())),
+        }
+    }
+    // truncated and extended
+    for len in [bytes.len() - 1, bytes.len() - 8, HEADER_LEN + CHECKSUM_LEN] {
+        assert_eq!(matches!(BloomFilter::from_bytes(&bytes[..len]),
+                            Err(FastBloomError::CorruptData { .. })), true, "{}", len);
+    }
+    let extended = [&bytes[..], &[0]].concat();
+    assert_eq!(matches!(BloomFilter::from_bytes(&extended),
+                        Err(FastBloomError::CorruptData { .. })), true);
+
+    // the caller checks the integrity
+    let mut corrupt = bytes.clone();
+    corrupt[bytes.len() / 2] ^= 0x10;
+    let copy = BloomFilter::from_bytes_unverified(&corrupt).unwrap();
+    assert_ne!(copy, bloom);
+    assert_eq!(copy.compatible(&bloom), true);
+    let mut checksum = bytes.clone();
+    let last = checksum.len() - 1;
+    checksum[last] ^= 0x80;
+    assert_eq!(BloomFilter::from_bytes_unverified(&checksum).unwrap(), bloom);
+}
+
+#[cfg(test)]
+/// A reader like a socket, delivering the data in pieces of at most `step` bytes.
+struct Trickle<'a> {
+    bytes: &'a [u8],
+    step: usize,
+}
+
+#[cfg(test)]
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = min(min(self.step, buf.len()), self.bytes.len());
+        buf[..n].copy_from_slice(&self.bytes[..n]);
+        self.bytes = &self.bytes[n..];
+        Ok(n)
+    }
+}
+
+#[test]
+fn binary_stream_test() {
+    let mut bloom = FilterBuilder::new(100_000, 0.01).with_seed(3).build_bloom_filter();
+    for i in 0..10_000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    let bytes = bloom.to_bytes();
+
+    let path = std::env::temp_dir().join(format!("fastbloom-stream-{}.bloom", std::process::id()));
+    bloom.to_writer(std::io::BufWriter::new(std::fs::File::create(&path).unwrap())).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), bytes);
+    let copy = BloomFilter::from_reader(std::fs::File::open(&path).unwrap()).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), 10_000);
+    assert_eq!((0..10_000u32).all(|i| copy.contains(i.to_le_bytes())), true);
+
+    for step in [1, 7, 4096, 5000] {
+        let copy = BloomFilter::from_reader(Trickle { bytes: &bytes, step }).unwrap();
+        assert_eq!(copy, bloom, "{}", step);
+    }
+    // the reader is left after the filter
+    let twice = [&bytes[..], &bytes[..]].concat();
+    let mut reader = &twice[..];
+    assert_eq!(BloomFilter::from_reader(&mut reader).unwrap(), bloom);
+    assert_eq!(BloomFilter::from_reader(&mut reader).unwrap(), bloom);
+    assert_eq!(reader.is_empty(), true);
+    let v1 = include_bytes!("../testvectors/bloom_v1.bin");
+    assert_eq!(BloomFilter::from_reader(&v1[..]).unwrap(), golden_filter());
+}
+
+#[test]
+fn binary_stream_invalid_test() {
+    let bloom = golden_filter();
+    let bytes = bloom.to_bytes_with_sparse_threshold(0.0);
+    let read = |bytes: &[u8]| BloomFilter::from_reader(Trickle { bytes, step: 3 }).err();
+    let reason = |bytes: &[u8]| match read(bytes) {
+        Some(FastBloomError::Deserialization { reason }) => reason,
+        e => panic!("{:?}", e),
+    };
+
+    assert_eq!(reason(b"FB"), "the data ends within the header");
+    assert_eq!(read(b"not a filter"), Some(FastBloomError::NotABloomFilter));
+    assert_eq!(reason(&bytes[..20]), "the data ends within the header");
+    assert_eq!(reason(&bytes[..100]), "the data ends within the bits");
+    assert_eq!(reason(&bytes[..bytes.len() - 1]), "the data ends within the checksum");
+
+    // more bits than the header declares
+    let mut longer = bytes.clone();
+    longer.splice(HEADER_LEN..HEADER_LEN, [0xff; 8]);
+    assert_eq!(matches!(read(&longer), Some(FastBloomError::CorruptData { .. })), true);
+    // fewer bits than the header declares
+    let mut shorter = bytes.clone();
+    shorter.drain(HEADER_LEN..HEADER_LEN + 8);
+    assert_eq!(reason(&shorter), "the data ends within the checksum");
+    let mut corrupt = bytes.clone();
+    corrupt[HEADER_LEN + 3] ^= 1;
+    assert_eq!(matches!(read(&corrupt), Some(FastBloomError::CorruptData { .. })), true);
+    let mut newer = bytes;
+    newer[4] = BloomFilter::FORMAT_VERSION + 1;
+    assert_eq!(matches!(read(&newer), Some(FastBloomError::UnsupportedVersion { .. })), true);
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(ErrorKind::ConnectionReset, "reset"))
+        }
+    }
+
+    assert_eq!(BloomFilter::from_reader(Failing).err(), Some(FastBloomError::Io {
+        kind: ErrorKind::ConnectionReset,
+        reason: "reset".to_string(),
+    }));
+    let mut full = [0u8; 16];
+    assert_eq!(bloom.to_writer(&mut full[..]).unwrap_err().kind(), ErrorKind::WriteZero);
+}
+
+#[cfg(test)]
+/// A directory for the files of one test, removed when dropped.
+struct TestDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TestDir {
+    fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("fastbloom-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TestDir(dir)
+    }
+
+    fn files(&self) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(&self.0).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn binary_file_test() {
+    let dir = TestDir::new("file");
+    let path = dir.0.join("urls.bloom");
+    let mut bloom = FilterBuilder::new(2_000_000, 0.01).build_bloom_filter();
+    for i in 0..100_000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    bloom.save_to_file(&path).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), bloom.to_bytes());
+    assert_eq!(dir.files(), vec!["urls.bloom"]);
+    let copy = BloomFilter::load_from_file(&path).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), 100_000);
+
+    // replaced by the next save
+    let small = golden_filter();
+    small.save_to_file(&path).unwrap();
+    assert_eq!(BloomFilter::load_from_file(&path).unwrap(), small);
+    assert_eq!(dir.files(), vec!["urls.bloom"]);
+}
+
+#[test]
+fn binary_file_atomic_test() {
+    let dir = TestDir::new("atomic");
+    let path = dir.0.join("urls.bloom");
+    let old = golden_filter();
+    old.save_to_file(&path).unwrap();
+
+    let mut new = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    new.add(b"hello");
+    // a crash after the new filter is written, before it is renamed
+    let res = new.save_to_file_with(&path, |tmp| {
+        assert_eq!(BloomFilter::load_from_file(tmp).unwrap(), new);
+        Err(io::Error::other("crash"))
+    });
+    assert_eq!(res.unwrap_err().to_string(), "crash");
+    assert_eq!(BloomFilter::load_from_file(&path).unwrap(), old);
+    assert_eq!(dir.files(), vec!["urls.bloom"]);
+
+    assert_eq!(new.save_to_file(dir.0.join("missing").join("urls.bloom")).unwrap_err().kind(),
+               ErrorKind::NotFound);
+    assert_eq!(dir.files(), vec!["urls.bloom"]);
+}
+
+#[test]
+fn binary_load_from_file_errors_test() {
+    let dir = TestDir::new("errors");
+    let missing = dir.0.join("missing.bloom");
+    match BloomFilter::load_from_file(&missing) {
+        Err(FastBloomError::Io { kind: ErrorKind::NotFound, reason }) =>
+            assert_eq!(reason.starts_with(&missing.display().to_string()), true, "{}", reason),
+        res => panic!("{:?}", res.map(|_| ())),
+    }
+
+    let text = dir.0.join("notes.txt");
+    fs::write(&text, "not a filter").unwrap();
+    assert_eq!(BloomFilter::load_from_file(&text).err(), Some(FastBloomError::NotABloomFilter));
+
+    let newer = dir.0.join("newer.bloom");
+    let mut bytes = golden_filter().to_bytes();
+    bytes[4] = BloomFilter::FORMAT_VERSION + 1;
+    fs::write(&newer, &bytes).unwrap();
+    assert_eq!(BloomFilter::load_from_file(&newer).err(), Some(FastBloomError::UnsupportedVersion {
+        version: BloomFilter::FORMAT_VERSION + 1,
+        supported: BloomFilter::FORMAT_VERSION,
+    }));
+
+    let truncated = dir.0.join("truncated.bloom");
+    let bytes = golden_filter().to_bytes();
+    fs::write(&truncated, &bytes[..bytes.len() / 2]).unwrap();
+    assert_eq!(matches!(BloomFilter::load_from_file(&truncated),
+                        Err(FastBloomError::Deserialization { .. })), true);
+}
+
+#[test]
+fn binary_base64_test() {
+    // a size which is not a multiple of 8
+    let mut bloom = FilterBuilder::from_size_and_hashes(1001, 4).with_seed(5).build_bloom_filter();
+    for key in ["apple", "banana", "cherry"] {
+        bloom.add(key.as_bytes());
+    }
+    let text = bloom.to_base64();
+    assert_eq!(text.len(), 4 * bloom.to_bytes().len().div_ceil(3));
+    assert_eq!(text.len() < 4 * (HEADER_LEN + CHECKSUM_LEN + 8 * 16).div_ceil(3), true);
+    for text in [text.clone(), text.trim_end_matches('=').to_string(), format!(" {}\n", text)] {
+        let copy = BloomFilter::from_base64(&text).unwrap();
+        assert_eq!(copy, bloom);
+        assert_eq!(copy.config().size, 1001);
+        assert_eq!(copy.len(), 3);
+        assert_eq!(copy.contains(b"banana"), true);
+    }
+
+    match BloomFilter::from_base64("not base64!") {
+        Err(FastBloomError::Deserialization { reason }) =>
+            assert_eq!(reason, "invalid base64: invalid character ' ' at 3"),
+        res => panic!("{:?}", res.map(|_| ())),
+    }
+    assert_eq!(BloomFilter::from_base64("aGVsbG8gd29ybGQ=").err(),
+               Some(FastBloomError::NotABloomFilter));
+    let mut corrupt = text.into_bytes();
+    // in the seed
+    corrupt[14] = if corrupt[14] == b'A' { b'B' } else { b'A' };
+    assert_eq!(matches!(BloomFilter::from_base64(std::str::from_utf8(&corrupt).unwrap()),
+                        Err(FastBloomError::CorruptData { .. })), true);
+}
+
+#[test]
+fn binary_hex_test() {
+    let bloom = golden_filter();
+    let text = bloom.to_hex();
+    assert_eq!(text.len(), bloom.to_bytes().len() * 2);
+    assert_eq!(text, text.to_lowercase());
+    for text in [text.clone(), text.to_uppercase(), format!("{}\n", text)] {
+        let copy = BloomFilter::from_hex(&text).unwrap();
+        assert_eq!(copy, bloom);
+        assert_eq!(copy, BloomFilter::from_bytes(&bloom.to_bytes()).unwrap());
+        assert_eq!(copy.len(), 3);
+        assert_eq!(copy.contains(b"cherry"), true);
+    }
+
+    let reason = |text: &str| match BloomFilter::from_hex(text) {
+        Err(FastBloomError::Deserialization { reason }) => reason,
+        res => panic!("{:?}", res.map(|_| ())),
+    };
+    assert_eq!(reason(&text[1..]), format!("invalid hex: odd number of digits {}",
+                                           text.len() - 1));
+    assert_eq!(reason("46424c4dzz"), "invalid hex: invalid character 'z' at 8");
+    assert_eq!(BloomFilter::from_hex("deadbeef").err(), Some(FastBloomError::NotABloomFilter));
+}
+
+#[cfg(not(feature = "compression"))]
+#[test]
+fn binary_compression_disabled_test() {
+    let compressed = include_bytes!("../testvectors/bloom_v3_compressed.bin");
+    assert_eq!(BloomFilter::from_bytes(compressed).err(),
+               Some(FastBloomError::CompressionDisabled));
+    assert_eq!(BloomFilter::from_reader(&compressed[..]).err(),
+               Some(FastBloomError::CompressionDisabled));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn binary_compressed_test() {
+    // 5% of the bits set, and not written sparse
+    let mut bloom = FilterBuilder::from_size_and_hashes(1 << 20, 3).build_bloom_filter();
+    let mut i = 0u32;
+    while bloom.count_ones() <= (1 << 20) / 20 {
+        bloom.add(i.to_le_bytes());
+        i += 1;
+    }
+    let dense = bloom.to_bytes();
+    let bytes = bloom.to_bytes_compressed();
+    let ratio = bytes.len() as f64 / dense.len() as f64;
+    assert_eq!(ratio < 0.35, true, "{}", ratio);
+    assert_eq!(bytes[FIXED_LEN], COMPRESSED);
+    let copy = BloomFilter::from_bytes(&bytes).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), i as u64);
+    assert_eq!((0..i).all(|i| copy.contains(i.to_le_bytes())), true);
+    let mut buf = Vec::new();
+    bloom.to_writer_compressed(&mut buf).unwrap();
+    assert_eq!(buf, bytes);
+    assert_eq!(BloomFilter::from_reader(Trickle { bytes: &buf, step: 5 }).unwrap(), bloom);
+    assert_eq!(BloomFilter::from_base64(&base64::encode(&bytes)).unwrap(), bloom);
+
+    // half full filters are written dense
+    let mut full = FilterBuilder::from_size_and_hashes(1 << 12, 3).build_bloom_filter();
+    for i in 0..1000u32 {
+        full.add(i.to_le_bytes());
+    }
+    assert_eq!(full.to_bytes_compressed(), full.to_bytes());
+    // as are sparse ones which are smaller
+    let empty = FilterBuilder::from_size_and_hashes(1 << 20, 3).build_bloom_filter();
+    assert_eq!(empty.to_bytes_compressed(), empty.to_bytes());
+    assert_eq!(empty.to_bytes()[FIXED_LEN], SPARSE);
+    assert_eq!(golden_filter().to_bytes_compressed(), golden_filter().to_bytes());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn binary_compressed_corrupt_test() {
+    let bloom = golden_filter();
+    let bytes = include_bytes!("../testvectors/bloom_v3_compressed.bin").to_vec();
+    let data = HEADER_LEN + 8;
+    assert_eq!(bytes.len(), data + 9 + 14 + CHECKSUM_LEN);
+
+    // a stream which can't be decompressed
+    let mut count = bytes.clone();
+    count[data + 1] = 100;
+    assert_eq!(BloomFilter::from_bytes(&count).err(), Some(FastBloomError::CorruptCompressedData {
+        reason: "the data ends within a gap".to_string(),
+    }));
+    let mut len = bytes.clone();
+    len[HEADER_LEN] = 200;
+    assert_eq!(matches!(BloomFilter::from_bytes(&len),
+                        Err(FastBloomError::CorruptCompressedData { .. })), true);
+    // a stream which decompresses to other bits
+    let mut gap = bytes.clone();
+    gap[data + 9] ^= 0x02;
+    match BloomFilter::from_bytes(&gap) {
+        Err(FastBloomError::CorruptData { .. }) => {}
+        res => panic!("{:?}", res.map(|_| ())),
+    }
+    assert_eq!(BloomFilter::from_bytes_unverified(&gap).unwrap().compatible(&bloom), true);
+    let trailing = [&bytes[..], &[0]].concat();
+    assert_eq!(matches!(BloomFilter::from_bytes(&trailing),
+                        Err(FastBloomError::Deserialization { .. })), true);
+
+    // no set bits in 9 bytes, with a size only bounded by MAX_DECODED_SIZE
+    for size in [u64::MAX, 1 << 46] {
+        let mut huge = [&bytes[..HEADER_LEN], &9u64.to_le_bytes(), &[0; 9 + CHECKSUM_LEN]].concat();
+        huge[20..28].copy_from_slice(&size.to_le_bytes());
+        let expected = Some(FastBloomError::Deserialization {
+            reason: format!("a size of {} bits is too large for compressed bits, at most {}",
+                            size, MAX_DECODED_SIZE),
+        });
+        assert_eq!(BloomFilter::from_bytes(&huge).err(), expected);
+        assert_eq!(BloomFilter::from_reader(&huge[..]).err(), expected);
+    }
+}
+
+#[test]
+fn binary_sparse_test() {
+    // 0.1% of the bits set
+    let mut bloom = FilterBuilder::from_size_and_hashes(1 << 24, 4).build_bloom_filter();
+    for i in 0..4000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    assert_eq!(bloom.count_ones() < (1 << 24) / 1000, true);
+    let dense = bloom.to_bytes_with_sparse_threshold(0.0);
+    let sparse = bloom.to_bytes();
+    assert_eq!(dense[FIXED_LEN], DENSE);
+    assert_eq!(sparse[FIXED_LEN], SPARSE);
+    assert_eq!(sparse.len() * 40 < dense.len(), true);
+    for bytes in [&dense, &sparse] {
+        let copy = BloomFilter::from_bytes(bytes).unwrap();
+        assert_eq!(copy, bloom);
+        assert_eq!(copy.len(), 4000);
+        assert_eq!((0..4000u32).all(|i| copy.contains(i.to_le_bytes())), true);
+        assert_eq!(BloomFilter::from_reader(Trickle { bytes, step: 3 }).unwrap(), bloom);
+    }
+    let mut buf = Vec::new();
+    bloom.to_writer(&mut buf).unwrap();
+    assert_eq!(buf, sparse);
+
+    // the threshold decides, a full filter can still be written sparse
+    let mut full = FilterBuilder::from_size_and_hashes(1000, 3).build_bloom_filter();
+    for i in 0..1000u32 {
+        full.add(i.to_le_bytes());
+    }
+    assert_eq!(full.to_bytes()[FIXED_LEN], DENSE);
+    let bytes = full.to_bytes_with_sparse_threshold(1.0);
+    assert_eq!(bytes[FIXED_LEN], SPARSE);
+    assert_eq!(BloomFilter::from_bytes(&bytes).unwrap(), full);
+    let empty = FilterBuilder::from_size_and_hashes(1000, 3).build_bloom_filter();
+    assert_eq!(empty.to_bytes().len(), HEADER_LEN + 8 + CHECKSUM_LEN);
+    assert_eq!(empty.to_bytes_with_sparse_threshold(0.0)[FIXED_LEN], DENSE);
+    assert_eq!(BloomFilter::from_bytes(&empty.to_bytes()).unwrap(), empty);
+}
+
+#[test]
+fn binary_sparse_invalid_test() {
+    let bloom = golden_filter();
+    let bytes = bloom.to_bytes();
+    let data = HEADER_LEN + 8;
+    assert_eq!(bytes.len(), data + 16 + CHECKSUM_LEN);
+    let reason = |bytes: &[u8]| match BloomFilter::from_bytes(bytes) {
+        Err(FastBloomError::Deserialization { reason }) => reason,
+        res => panic!("{:?}", res.map(|_| ())),
+    };
+
+    assert_eq!(reason(&[&bytes[..], &[0]].concat()), "1 bytes after the filter");
+    let mut len = bytes.clone();
+    len[HEADER_LEN] = 100;
+    assert_eq!(reason(&len), "the data ends within the sparse bits");
+    let mut index = bytes.clone();
+    index[data + 15] = 0x80;
+    assert_eq!(reason(&index), "the sparse bits end within an index");
+    index[data..data + 2].copy_from_slice(&[0xff, 0x7f]);
+    assert_eq!(reason(&index), "a set bit is out of range for 1000 bits");
+    // a gap which moves a set bit
+    let mut gap = bytes.clone();
+    gap[data] ^= 1;
+    assert_eq!(matches!(BloomFilter::from_bytes(&gap), Err(FastBloomError::CorruptData { .. })),
+               true);
+    assert_eq!(BloomFilter::from_bytes_unverified(&gap).unwrap().compatible(&bloom), true);
+    assert_eq!(matches!(BloomFilter::from_reader(&gap[..]),
+                        Err(FastBloomError::CorruptData { .. })), true);
+    assert_eq!(matches!(BloomFilter::from_reader(&bytes[..bytes.len() - 10]),
+                        Err(FastBloomError::Deserialization { .. })), true);
+
+    // the size of empty sparse bits is only bounded by MAX_DECODED_SIZE
+    let empty = FilterBuilder::new(100, 0.01).build_bloom_filter().to_bytes();
+    assert_eq!(empty.len(), 53);
+    for size in [u64::MAX, 1 << 46] {
+        let mut huge = empty.clone();
+        huge[20..28].copy_from_slice(&size.to_le_bytes());
+        let expected = format!("a size of {} bits is too large for sparse bits, at most {}",
+                               size, MAX_DECODED_SIZE);
+        assert_eq!(reason(&huge), expected);
+        assert_eq!(matches!(BloomFilter::from_reader(&huge[..]),
+                            Err(FastBloomError::Deserialization { reason }) if reason == expected),
+                   true);
+    }
+}
+
+#[test]
+fn binary_compact_test() {
+    let bloom = golden_filter();
+    let size = bloom.compact_serialized_size();
+    assert_eq!(size, FIXED_LEN + 16 * 8);
+    // version 1 of the binary format
+    let v1 = include_bytes!("../testvectors/bloom_v1.bin");
+    let mut exact = vec![0u8; size];
+    assert_eq!(bloom.to_compact_bytes(&mut exact), Ok(size));
+    assert_eq!(exact, v1.to_vec());
+    let copy = BloomFilter::from_compact_bytes(&exact).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), 3);
+    assert_eq!(BloomFilter::from_reader(&exact[..]).unwrap(), bloom);
+    assert_eq!(BloomFilter::from_compact_bytes(&bloom.to_bytes()).unwrap(), bloom);
+
+    // only the start of a larger buffer is written
+    let mut larger = vec![0xaa; size + 10];
+    assert_eq!(bloom.to_compact_bytes(&mut larger), Ok(size));
+    assert_eq!(larger[..size], exact[..]);
+    assert_eq!(larger[size..], [0xaa; 10]);
+    // nothing is written to a buffer which is too small
+    for len in [0, FIXED_LEN, size - 1] {
+        let mut small = vec![0xaa; len];
+        assert_eq!(bloom.to_compact_bytes(&mut small),
+                   Err(FastBloomError::BufferTooSmall { needed: size, len }));
+        assert_eq!(small.iter().all(|b| *b == 0xaa), true);
+    }
+    assert_eq!(matches!(BloomFilter::from_compact_bytes(&exact[..size - 1]),
+                        Err(FastBloomError::Deserialization { .. })), true);
+    assert_eq!(matches!(BloomFilter::from_compact_bytes(&larger),
+                        Err(FastBloomError::Deserialization { .. })), true);
+
+    // a size which is not a multiple of 64 and a hasher stored by name
+    struct Fnv;
+
+    impl crate::BloomHasher for Fnv {
+        fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+            let h = data.iter().fold(0xcbf29ce484222325 ^ seed, |h, b| {
+                (h ^ *b as u64).wrapping_mul(0x100000001b3)
+            });
+            (h, h.rotate_left(32) | 1)
+        }
+        fn name(&self) -> &str {
+            "fnv1a"
+        }
+    }
+
+    let mut named = FilterBuilder::from_size_and_hashes(100, 2).with_seed(9).with_hasher(Fnv)
+        .build_bloom_filter();
+    named.add(b"hello");
+    let size = named.compact_serialized_size();
+    assert_eq!(size, FIXED_LEN + 2 + 5 + 2 * 8);
+    let mut buf = [0u8; 64];
+    let len = named.to_compact_bytes(&mut buf).unwrap();
+    assert_eq!(len, size);
+    let mut copy = BloomFilter::from_compact_bytes(&buf[..len]).unwrap();
+    assert_eq!(copy.to_u64_vec(), named.to_u64_vec());
+    assert_eq!(copy.config().hasher, HashAlgorithm::Unresolved("fnv1a".to_string()));
+    assert_eq!(copy.set_hasher(Fnv), Ok(()));
+    assert_eq!(copy.contains(b"hello"), true);
+}
+
+#[test]
+fn binary_write_into_test() {
+    let bloom = golden_filter();
+    let size = bloom.serialized_size();
+    let dense = bloom.to_bytes_with_sparse_threshold(0.0);
+    assert_eq!(size, dense.len());
+    let mut exact = vec![0u8; size];
+    assert_eq!(bloom.write_into(&mut exact), Ok(size));
+    assert_eq!(exact, dense);
+    assert_eq!(BloomFilter::from_bytes(&exact).unwrap(), bloom);
+
+    // only the start of a larger buffer is written, and the bytes written are the filter
+    let mut larger = vec![0xaa; size + 10];
+    assert_eq!(bloom.write_into(&mut larger), Ok(size));
+    assert_eq!(larger[..size], exact[..]);
+    assert_eq!(larger[size..], [0xaa; 10]);
+    assert_eq!(BloomFilter::from_bytes(&larger[..size]).unwrap(), bloom);
+    // nothing is written to a buffer which is too small
+    for len in [0, HEADER_LEN, size - 1] {
+        let mut small = vec![0xaa; len];
+        let err = bloom.write_into(&mut small).unwrap_err();
+        assert_eq!(err, FastBloomError::BufferTooSmall { needed: size, len });
+        assert_eq!(err.to_string(), format!("can't serialize bloom filter: the buffer of {} bytes \
+                                             is too small for {} bytes", len, size));
+        assert_eq!(small.iter().all(|b| *b == 0xaa), true);
+    }
+
+    // a buffer reused for a filter with a hasher stored by name
+    struct Reversed;
+
+    impl BloomHasher for Reversed {
+        fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+            let (h1, h2) = crate::Xxh3Hasher.hash128(data, seed);
+            (h2, h1)
+        }
+        fn name(&self) -> &str {
+            "reversed"
+        }
+    }
+
+    let mut named = FilterBuilder::from_size_and_hashes(100, 2).with_hasher(Reversed)
+        .build_bloom_filter();
+    named.add(b"hello");
+    assert_eq!(named.serialized_size(), HEADER_LEN + 2 + 8 + 2 * 8 + CHECKSUM_LEN);
+    let len = named.write_into(&mut larger).unwrap();
+    assert_eq!(larger[..len], named.to_bytes_with_sparse_threshold(0.0)[..]);
+    let mut copy = BloomFilter::from_bytes(&larger[..len]).unwrap();
+    assert_eq!(copy.set_hasher(Reversed), Ok(()));
+    assert_eq!(copy, named);
+}
+
+#[test]
+fn binary_try_from_test() {
+    let bloom = golden_filter();
+    let bytes = bloom.to_bytes();
+    assert_eq!(BloomFilter::try_from(bytes.as_slice()), Ok(bloom.clone()));
+    assert_eq!(BloomFilter::try_from(bytes.clone()), Ok(bloom.clone()));
+    let compact: &[u8] = include_bytes!("../testvectors/bloom_v1.bin");
+    assert_eq!(BloomFilter::try_from(compact), Ok(bloom.clone()));
+
+    // truncated, sparse and as u64 words
+    let dense = bloom.to_bytes_with_sparse_threshold(0.0);
+    for bytes in [&bytes, &dense] {
+        for len in [0, 3, FIXED_LEN - 1, HEADER_LEN + 5, bytes.len() - 1] {
+            assert_eq!(BloomFilter::try_from(&bytes[..len]).is_err(), true);
+            assert_eq!(BloomFilter::try_from(bytes[..len].to_vec()).is_err(), true);
+        }
+    }
+    assert_eq!(matches!(BloomFilter::try_from(&dense[..dense.len() - 1]),
+                        Err(FastBloomError::CorruptData { .. })), true);
+    assert_eq!(matches!(BloomFilter::try_from(&dense[..FIXED_LEN - 1]),
+                        Err(FastBloomError::Deserialization { .. })), true);
+
+    // the raw bits of get_u8_array are not read as a filter
+    let mut raw_bits = BloomFilter::new(FilterBuilder::from_size_and_hashes(9600, 7));
+    raw_bits.add(b"hello");
+    assert_eq!(BloomFilter::try_from(raw_bits.get_u8_array()),
+               Err(FastBloomError::NotABloomFilter));
+    assert_eq!(BloomFilter::try_from(raw_bits.get_u8_array().to_vec()),
+               Err(FastBloomError::NotABloomFilter));
+    let raw = BloomFilter::from_u8_array(raw_bits.get_u8_array(), raw_bits.hashes());
+    assert_eq!(raw.contains(b"hello"), true);
+}
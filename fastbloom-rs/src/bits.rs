@@ -0,0 +1,135 @@
+//! Reading and writing single bits and Rice codes, for the compressed bits of a filter and the
+//! codes of a [crate::GolombCodedSet].
+
+/// Writes bits least significant first.
+pub(crate) struct BitWriter {
+    bytes: Vec<u8>,
+    acc: u64,
+    n: u32,
+}
+
+impl BitWriter {
+    /// A writer appending to `bytes`.
+    pub(crate) fn new(bytes: Vec<u8>) -> Self {
+        BitWriter { bytes, acc: 0, n: 0 }
+    }
+
+    /// Writes the `bits` low bits of `value`, at most 32.
+    #[inline]
+    pub(crate) fn write(&mut self, value: u64, bits: u32) {
+        self.acc |= value << self.n;
+        self.n += bits;
+        while self.n >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.n -= 8;
+        }
+    }
+
+    /// Writes `q` in unary, as `q` ones followed by a zero.
+    #[inline]
+    pub(crate) fn write_unary(&mut self, mut q: u64) {
+        while q >= 32 {
+            self.write(u32::MAX as u64, 32);
+            q -= 32;
+        }
+        self.write((1 << q) - 1, q as u32 + 1);
+    }
+
+    /// Writes the Rice code of `value` with parameter `k`, at most 63: the quotient by `2^k` in
+    /// unary, then the `k` low bits.
+    #[inline]
+    pub(crate) fn write_rice(&mut self, value: u64, k: u32) {
+        self.write_unary(value >> k);
+        if k > 32 {
+            self.write(value & u32::MAX as u64, 32);
+            self.write((value >> 32) & ((1 << (k - 32)) - 1), k - 32);
+        } else {
+            self.write(value & ((1 << k) - 1), k);
+        }
+    }
+
+    /// The bytes written, the last one padded with zeros.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.n > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+/// Reads the bits of a [BitWriter].
+pub(crate) struct BitReader<'a> {
+    bytes: &'a [u8],
+    acc: u64,
+    n: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, acc: 0, n: 0 }
+    }
+
+    #[inline]
+    fn refill(&mut self) {
+        while self.n <= 56 && !self.bytes.is_empty() {
+            self.acc |= (self.bytes[0] as u64) << self.n;
+            self.n += 8;
+            self.bytes = &self.bytes[1..];
+        }
+    }
+
+    /// Reads `bits` bits, at most 32.
+    #[inline]
+    pub(crate) fn read(&mut self, bits: u32) -> Result<u64, String> {
+        if self.n < bits {
+            self.refill();
+            if self.n < bits {
+                return Err("the data ends within a gap".to_string());
+            }
+        }
+        let value = self.acc & ((1u64 << bits) - 1);
+        self.acc >>= bits;
+        self.n -= bits;
+        Ok(value)
+    }
+
+    #[inline]
+    pub(crate) fn read_unary(&mut self) -> Result<u64, String> {
+        let mut q = 0;
+        loop {
+            if self.n == 0 {
+                self.refill();
+                if self.n == 0 {
+                    return Err("the data ends within a gap".to_string());
+                }
+            }
+            let ones = self.acc.trailing_ones().min(self.n);
+            q += ones as u64;
+            if ones < self.n {
+                self.acc >>= ones + 1;
+                self.n -= ones + 1;
+                return Ok(q);
+            }
+            self.acc = 0;
+            self.n = 0;
+        }
+    }
+
+    /// Reads a Rice code of [BitWriter::write_rice], `None` if the value doesn't fit a `u64`.
+    #[inline]
+    pub(crate) fn read_rice(&mut self, k: u32) -> Result<Option<u64>, String> {
+        let q = self.read_unary()?;
+        let low = if k > 32 {
+            self.read(32)? | self.read(k - 32)? << 32
+        } else {
+            self.read(k)?
+        };
+        Ok(q.checked_shl(k).filter(|high| high >> k == q).map(|high| high | low))
+    }
+
+    /// Whether only the zero padding of the last byte is left.
+    pub(crate) fn is_at_end(&self) -> bool {
+        self.bytes.is_empty() && self.n < 8 && self.acc == 0
+    }
+}
@@ -1,80 +1,219 @@
-use std::clone;
 use std::cmp::min;
+use std::fmt::{Debug, Formatter};
+use std::hash::Hash;
+use std::iter::Sum;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign};
 use std::ptr::slice_from_raw_parts;
+use std::sync::Arc;
 
-use fastmurmur3::murmur3_x64_128;
+use smallvec::SmallVec;
+#[cfg(test)]
 use xxhash_rust::xxh3::xxh3_64_with_seed;
+use xxhash_rust::xxh3::Xxh3Default;
 
 use crate::{Deletable, Hashes, Membership};
-use crate::builder::FilterBuilder;
+use crate::builder::{CompatibilityReport, FilterBuilder};
+#[cfg(test)]
+use crate::builder::CounterSize;
+use crate::delta::BloomDelta;
+use crate::error::{BuildError, CapacityExceeded, FastBloomError, FoldError, IncompatibleFilterError};
+use crate::frozen::FrozenBloomFilter;
+use crate::hasher::{BloomHasher, HashAlgorithm, ProbeScheme, StableHasher};
+use crate::key::KeyWriter;
+use crate::sparse::SparseBloom;
 use crate::vec::{BloomBitVec, CountingVec};
+use crate::view::BloomFilterRef;
 
-#[inline]
-fn bit_set(bit_set: &mut BloomBitVec, value: &[u8], m: u64, k: u64) {
-    // let len = m >> 5;
-    // let hash1 = (murmur3_x64_128(value, 0) % m) as u64;
-    // let hash2 = (murmur3_x64_128(value, 32) % m) as u64;
-    let hash1 = xxh3_64_with_seed(value, 0) % m;
-    let hash2 = xxh3_64_with_seed(value, 32) % m;
-
-    let m = m as u64;
-    for i in 1..k {
-        let mo = ((hash1 + i * hash2) % m) as usize;
-        bit_set.set(mo);
+/// Reduces a hash to an index below `m`. For a power of two `m` this is a mask instead of the
+/// much slower modulo, with the same result.
+#[inline(always)]
+fn reduce(hash: u64, m: u64) -> u64 {
+    if m.is_power_of_two() { hash & (m - 1) } else { hash % m }
+}
+
+/// The parameters placing the bits of an element besides the size and the number of hashes.
+#[derive(Clone, Copy)]
+pub(crate) struct Probe<'a> {
+    pub(crate) hasher: &'a HashAlgorithm,
+    pub(crate) seed: u64,
+    /// See [FilterBuilder::HASH_VERSION].
+    pub(crate) version: u8,
+    pub(crate) scheme: ProbeScheme,
+}
+
+/// The base hashes of `value` before the reduction, the third one only for
+/// [ProbeScheme::Triple].
+#[inline(always)]
+fn base_hashes(value: &[u8], probe: Probe<'_>) -> [u64; 3] {
+    let (hash1, hash2) = probe.hasher.hash128(value, probe.seed);
+    let hash3 = match probe.scheme {
+        ProbeScheme::Triple => probe.hasher.hash128(value, probe.seed.wrapping_add(1)).0,
+        _ => 0,
     };
-    bit_set.set(hash1 as usize);
+    [hash1, hash2, hash3]
 }
 
-#[inline]
-fn bit_check(bit_set: &BloomBitVec, value: &[u8], m: u64, k: u64) -> bool {
-    // let hash1 = (murmur3_x64_128(value, 0) % m) as u64;
-    // let hash2 = (murmur3_x64_128(value, 32) % m) as u64;
-    let hash1 = xxh3_64_with_seed(value, 0) % m;
-    let hash2 = xxh3_64_with_seed(value, 32) % m;
-    let mut res = bit_set.get(hash1 as usize);
-    if !res { return false; }
-    // let m = m as u64;
-    for i in 1..k {
-        let mo = ((hash1 + i * hash2) % m) as usize;
-        res = res && bit_set.get(mo);
-        if !res { return false; }
+/// The two base hashes for double hashing, reduced below `m`. From hash version 2 on the second
+/// hash is not 0, which would put all hashes of the element on the same bit: version 2 replaces
+/// 0 by 1 after the reduction. Version 3 makes the hash odd before it, which keeps the result
+/// the same modulo every even divisor of `m` for [BloomFilter::fold], and replaces 0 by 1 after
+/// it too, as an odd hash can still be a multiple of an odd `m`.
+#[inline(always)]
+fn reduce_pair(hash1: u64, hash2: u64, m: u64, version: u8) -> (u64, u64) {
+    let hash2 = match version {
+        1 => return (reduce(hash1, m), reduce(hash2, m)),
+        2 => reduce(hash2, m),
+        _ => reduce(hash2 | 1, m),
+    };
+    (reduce(hash1, m), if hash2 == 0 { 1 } else { hash2 })
+}
+
+/// One step of SplitMix64, advancing `state` and returning the next output.
+#[inline(always)]
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// The `k` bit indices of an element in the order they are probed, see [ProbeScheme]. Every
+/// filter gets the indices of an element from here, so adding and checking always agree.
+#[derive(Clone)]
+pub(crate) struct ProbeSequence {
+    /// The next index.
+    x: u64,
+    /// The step to the index after it.
+    y: u64,
+    /// The increment of the step for [ProbeScheme::Triple].
+    z: u64,
+    i: u64,
+    k: u64,
+    m: u64,
+    scheme: ProbeScheme,
+}
+
+impl Iterator for ProbeSequence {
+    type Item = u64;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<u64> {
+        if self.i == self.k { return None; }
+        self.i += 1;
+        if self.scheme == ProbeScheme::PyBloom {
+            // x and y are the 128 bits the chunks are cut from, index i is in slice i
+            let slice = (self.m / self.k).max(1);
+            let width = if slice < 1 << 15 { 16 } else if slice < 1 << 31 { 32 } else { 64 };
+            let i = self.i - 1;
+            let bits = (self.x as u128 | (self.y as u128) << 64) >> (i * width % 128);
+            let chunk = bits as u64 & (u64::MAX >> (64 - width));
+            return Some(reduce(i * slice + chunk % slice, self.m));
+        }
+        if matches!(self.scheme, ProbeScheme::Guava | ProbeScheme::RedisBloom) {
+            // x and y are the unreduced hashes, reduced only after adding them up, and Guava
+            // drops the sign bit first
+            let mask = if self.scheme == ProbeScheme::Guava { i64::MAX as u64 } else { u64::MAX };
+            let index = reduce(self.x & mask, self.m);
+            self.x = self.x.wrapping_add(self.y);
+            return Some(index);
+        }
+        let index = self.x;
+        // index i is hash1 + i * hash2, plus i * (i * i - 1) / 6 for the enhanced scheme and
+        // i * (i - 1) / 2 * hash3 for triple hashing
+        self.x = reduce(self.x + self.y, self.m);
+        match self.scheme {
+            ProbeScheme::Double => {}
+            ProbeScheme::EnhancedDouble => self.y = reduce(self.y + self.i, self.m),
+            ProbeScheme::Triple => self.y = reduce(self.y + self.z, self.m),
+            ProbeScheme::Guava | ProbeScheme::RedisBloom | ProbeScheme::PyBloom =>
+                unreachable!(),
+        }
+        Some(index)
     }
-    res
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = (self.k - self.i) as usize;
+        (len, Some(len))
+    }
+}
+
+impl ProbeSequence {
+    #[inline(always)]
+    fn new([hash1, hash2, hash3]: [u64; 3], m: u64, k: u64, probe: Probe<'_>) -> Self {
+        if matches!(probe.scheme, ProbeScheme::Guava | ProbeScheme::RedisBloom
+                                  | ProbeScheme::PyBloom) {
+            return ProbeSequence { x: hash1, y: hash2, z: 0, i: 0, k, m, scheme: probe.scheme };
+        }
+        let (hash1, hash2) = reduce_pair(hash1, hash2, m, probe.version);
+        ProbeSequence { x: hash1, y: hash2, z: reduce(hash3, m), i: 0, k, m, scheme: probe.scheme }
+    }
+}
+
+/// The `k` bit indices of `value` in the order they are probed by [bit_check].
+#[inline]
+pub(crate) fn bit_indices(value: &[u8], m: u64, k: u64, probe: Probe<'_>) -> ProbeSequence {
+    ProbeSequence::new(base_hashes(value, probe), m, k, probe)
 }
 
+/// The `k` bit indices of a pre-hashed element, see [BloomFilter::add_hash].
 #[inline]
-fn bit_check_and_set(bit_set: &mut BloomBitVec, value: &[u8], m: u64, k: u64) -> bool {
-    // let hash1 = (murmur3_x64_128(value, 0) % m) as u64;
-    // let hash2 = (murmur3_x64_128(value, 32) % m) as u64;
-    let hash1 = xxh3_64_with_seed(value, 0) % m;
-    let hash2 = xxh3_64_with_seed(value, 32) % m;
-    let mut res = bit_set.get(hash1 as usize);
-    bit_set.set(hash1 as usize);
-    // let m = m as u64;
-    for i in 1..k {
-        let mo = ((hash1 + i * hash2) % m) as usize;
-        res = res && bit_set.get(mo);
-        bit_set.set(mo);
+fn prehashed_indices(hash: u64, m: u64, k: u64, probe: Probe<'_>) -> ProbeSequence {
+    let mut state = hash ^ probe.seed;
+    let hashes = [splitmix64(&mut state), splitmix64(&mut state), splitmix64(&mut state)];
+    ProbeSequence::new(hashes, m, k, probe)
+}
+
+#[inline]
+fn bit_set(bit_set: &mut BloomBitVec, value: &[u8], m: u64, k: u64, probe: Probe<'_>) {
+    for index in bit_indices(value, m, k, probe) {
+        bit_set.set(index as usize);
     }
-    res
 }
 
 #[inline]
-fn get_bit_indices(bit_set: &BloomBitVec, value: &[u8], m: u64, k: u64) -> Vec<u64> {
-    let mut res = Vec::<u64>::with_capacity(k as usize);
-    // let hash1 = (murmur3_x64_128(value, 0) % m) as u64;
-    // let hash2 = (murmur3_x64_128(value, 32) % m) as u64;
-    let hash1 = xxh3_64_with_seed(value, 0) % m;
-    let hash2 = xxh3_64_with_seed(value, 32) % m;
-    res.push(hash1);
-    // let m = m as u64;
-    for i in 1..k {
-        let mo = ((hash1 + i * hash2) % m) as usize;
-        res.push(mo as u64);
+fn bit_check(bit_set: &BloomBitVec, value: &[u8], m: u64, k: u64, probe: Probe<'_>) -> bool {
+    bit_check_with(|index| bit_set.get(index), value, m, k, probe)
+}
+
+/// Same as [bit_check] for any bit storage, `get` returns the bit at an index.
+#[inline]
+pub(crate) fn bit_check_with<F: Fn(usize) -> bool>(get: F, value: &[u8], m: u64, k: u64,
+                                                   probe: Probe<'_>) -> bool {
+    bit_indices(value, m, k, probe).all(|index| get(index as usize))
+}
+
+#[inline]
+fn bit_check_and_set(bit_set: &mut BloomBitVec, value: &[u8], m: u64, k: u64,
+                     probe: Probe<'_>) -> bool {
+    let mut res = true;
+    for index in bit_indices(value, m, k, probe) {
+        res = res && bit_set.get(index as usize);
+        bit_set.set(index as usize);
     }
     res
 }
 
+#[inline]
+fn get_bit_indices(value: &[u8], m: u64, k: u64, probe: Probe<'_>) -> Vec<u64> {
+    bit_indices(value, m, k, probe).collect()
+}
+
+/// Estimates the number of elements inserted into a Bloom filter of size `m` with `k` hashes from
+/// its number of set bits.
+#[inline]
+fn estimate_count_from_ones(ones: u64, m: u64, k: u32) -> u64 {
+    estimate_from_ones(ones, m, k).round() as u64
+}
+
+#[inline]
+fn estimate_from_ones(ones: u64, m: u64, k: u32) -> f64 {
+    if ones == 0 { return 0.0; }
+    let x = min(ones, m - 1) as f64;
+    let m = m as f64;
+    -(m / k as f64) * (1.0 - x / m).ln()
+}
+
 /// A Bloom filter is a space-efficient probabilistic data structure, conceived by Burton Howard
 /// Bloom in 1970, that is used to test whether an element is a member of a set. False positive
 /// matches are possible, but false negatives are not.
@@ -82,19 +221,240 @@ fn get_bit_indices(bit_set: &BloomBitVec, value: &[u8], m: u64, k: u64) -> Vec<u
 /// **Reference**: Bloom, B. H. (1970). Space/time trade-offs in hash coding with allowable errors.
 /// Communications of the ACM, 13(7), 422-426.
 /// [Full text article](http://crystal.uta.edu/~mcguigan/cse6350/papers/Bloom.pdf)
+///
+/// With the `serde` feature a filter is serialized as its configuration, the bits as bytes
+/// (little endian `u64` words, written with `serialize_bytes` so binary formats store them
+/// compactly) and [BloomFilter::len]. Deserializing fails if the number of bytes doesn't match
+/// the size of the configuration. Filters serialized by versions which wrote the bits as a
+/// `bit_set` of machine words still load.
 #[derive(Clone)]
-#[derive(Debug)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct BloomFilter {
     config: FilterBuilder,
-    bit_set: BloomBitVec,
+    /// Shared with the [BloomSnapshot]s of the filter and copied on the next write, see
+    /// [BloomFilter::bits_mut].
+    bit_set: Arc<BloomBitVec>,
+    /// Number of insert operations, see [BloomFilter::len].
+    inserted: u64,
+    capacity_check: CapacityCheck,
+    /// Buffer reused by [BloomFilter::key] to assemble keys.
+    key_buf: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for BloomFilter {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        /// The bits as the bytes of little endian words.
+        struct Bits<'a>(&'a BloomBitVec);
+
+        impl serde::Serialize for Bits<'_> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let words = self.0.nbits.div_ceil(64) as usize;
+                let bytes: Vec<u8> = self.0.to_u64_words().iter().take(words)
+                    .flat_map(|word| word.to_le_bytes())
+                    .collect();
+                serializer.serialize_bytes(&bytes)
+            }
+        }
+
+        let mut state = serializer.serialize_struct("BloomFilter", 3)?;
+        state.serialize_field("config", &self.config)?;
+        state.serialize_field("bits", &Bits(&self.bit_set))?;
+        state.serialize_field("inserted", &self.inserted)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BloomFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        /// Bytes from `serialize_bytes`, which formats without a byte type (like JSON) write as
+        /// a sequence.
+        struct Bits(Vec<u8>);
+
+        impl<'de> serde::Deserialize<'de> for Bits {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D)
+                                                        -> Result<Self, D::Error> {
+                deserializer.deserialize_byte_buf(BitsVisitor)
+            }
+        }
+
+        struct BitsVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BitsVisitor {
+            type Value = Bits;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                write!(f, "the bytes of a bloom filter")
+            }
+
+            fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Bits, E> {
+                Ok(Bits(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Bits, E> {
+                Ok(Bits(v))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<Bits, A::Error> {
+                let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(Bits(bytes))
+            }
+        }
+
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            config: FilterBuilder,
+            #[serde(default)]
+            bits: Option<Bits>,
+            /// The bits as written by older versions.
+            #[serde(default)]
+            bit_set: Option<BloomBitVec>,
+            #[serde(default)]
+            inserted: u64,
+        }
+
+        let invalid = |reason: String| D::Error::custom(FastBloomError::Deserialization { reason });
+        let repr = Repr::deserialize(deserializer)?;
+        let size = repr.config.size;
+        if size == 0 {
+            return Err(invalid("the size must be larger than 0".to_string()));
+        }
+        let bit_set = match (repr.bits, repr.bit_set) {
+            (Some(Bits(bytes)), _) => {
+                let expected = size.div_ceil(64) * 8;
+                if bytes.len() as u64 != expected {
+                    return Err(invalid(format!("{} bytes of bits for a size of {} bits, \
+                                                expected {}", bytes.len(), size, expected)));
+                }
+                let words: Vec<u64> = bytes.chunks_exact(8)
+                    .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+                    .collect();
+                BloomBitVec::from_u64_vec(words, size)
+            }
+            (None, Some(mut bit_set)) => {
+                let bits = bit_set.storage.len() as u64 * usize::BITS as u64;
+                if bit_set.nbits != size || bits < size {
+                    return Err(invalid(format!("{} bits for a size of {} bits", bit_set.nbits,
+                                               size)));
+                }
+                bit_set.clear_padding();
+                bit_set
+            }
+            (None, None) => return Err(D::Error::missing_field("bits")),
+        };
+        let mut res = BloomFilter::from_parts(repr.config, bit_set);
+        res.inserted = repr.inserted;
+        Ok(res)
+    }
+}
+
+/// The hashes of a key computed once with [BloomFilter::hash_key], to probe several filters
+/// without hashing the key again, e.g. the shards or generations of a partitioned set.
+///
+/// The hashes are kept before the reduction by the size, so they are valid for every filter
+/// with the same hasher, seed and hash version, whatever its size and number of hashes.
+/// Filters using [ProbeScheme::Triple] need a third hash, so their hashes must be computed by
+/// such a filter too. Probing a filter the hashes are not valid for is an
+/// [IncompatibleFilterError].
+#[derive(Clone, Debug)]
+pub struct PrecomputedHashes {
+    hashes: [u64; 3],
+    hasher: HashAlgorithm,
+    seed: u64,
+    version: u8,
+    scheme: ProbeScheme,
+}
+
+/// A saved state of a [BloomFilter], see [BloomFilter::snapshot].
+#[derive(Clone)]
+pub struct BloomSnapshot {
+    config: FilterBuilder,
+    bit_set: Arc<BloomBitVec>,
+    inserted: u64,
+}
+
+/// Cached result of the capacity check done by [BloomFilter::try_add].
+#[derive(Clone)]
+#[derive(Debug)]
+struct CapacityCheck {
+    /// Number of inserts between two checks.
+    interval: u64,
+    /// Value of `inserted` at which the next check is done.
+    next: u64,
+    exceeded: Option<CapacityExceeded>,
+}
+
+impl Default for CapacityCheck {
+    fn default() -> Self {
+        CapacityCheck { interval: 1024, next: 0, exceeded: None }
+    }
+}
+
+impl CapacityCheck {
+    fn reset(&mut self) {
+        self.next = 0;
+        self.exceeded = None;
+    }
+}
+
+/// Number of `u64` words printed by the alternate (`{:#?}`) [Debug] format of [BloomFilter].
+const DEBUG_WORDS: usize = 8;
+
+/// Prints a summary of the filter instead of its bits, which would be unreadable (and huge) for
+/// large filters. The alternate format `{:#?}` also prints the first 8 `u64` words.
+impl Debug for BloomFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let alternate = f.alternate();
+        let mut res = f.debug_struct("BloomFilter");
+        res.field("size", &self.config.size)
+            .field("hashes", &self.config.hashes)
+            .field("expected_elements", &self.config.expected_elements)
+            .field("false_positive_probability", &self.config.false_positive_probability)
+            .field("len", &self.inserted)
+            .field("set_bits", &self.count_ones())
+            .field("fill_ratio", &self.fill_ratio());
+        if alternate {
+            let words: Vec<u64> = (0..min(DEBUG_WORDS, self.bit_set.u64_len()))
+                .map(|i| self.bit_set.u64_word(i))
+                .collect();
+            res.field("first_words", &words);
+        }
+        res.field("bits", &format_args!("<{} bits elided>", self.config.size))
+            .finish()
+    }
+}
+
+/// Prints a summary like the [Debug] format of [BloomFilter].
+impl Debug for BloomSnapshot {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BloomSnapshot")
+            .field("size", &self.config.size)
+            .field("hashes", &self.config.hashes)
+            .field("len", &self.inserted)
+            .field("set_bits", &self.bit_set.count_ones())
+            .field("bits", &format_args!("<{} bits elided>", self.config.size))
+            .finish()
+    }
 }
 
 impl Membership for BloomFilter {
-    /// Adds the passed value to the filter.
+    /// Adds the passed value to the filter. If `enable_repeat_insert` is disabled, elements which
+    /// are already present are skipped, see [BloomFilter::insert].
     fn add(&mut self, element: &[u8]) {
-        bit_set(&mut self.bit_set, element, self.config.size,
-                self.config.hashes as u64);
+        if !self.config.enable_repeat_insert {
+            self.insert(element);
+            return;
+        }
+        let (m, k) = (self.config.size, self.config.hashes as u64);
+        bit_set(Arc::make_mut(&mut self.bit_set), element, m, k, self.config.probe());
+        self.inserted += 1;
     }
 
     /// Tests whether an element is present in the filter (subject to the specified false
@@ -102,13 +462,13 @@ impl Membership for BloomFilter {
     #[inline]
     fn contains(&self, element: &[u8]) -> bool {
         bit_check(&self.bit_set, element, self.config.size,
-                  self.config.hashes as u64)
+                  self.config.hashes as u64, self.config.probe())
     }
 
     /// Get the hashes indices of the element in the filter.
     fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
-        get_bit_indices(&self.bit_set, element, self.config.size,
-                        self.config.hashes as u64)
+        get_bit_indices(element, self.config.size,
+                        self.config.hashes as u64, self.config.probe())
     }
 
     /// Tests whether a hashes indices is present in the filter
@@ -122,7 +482,170 @@ impl Membership for BloomFilter {
 
     /// Removes all elements from the filter (i.e. resets all bits to zero).
     fn clear(&mut self) {
-        self.bit_set.clear();
+        self.bits_mut().clear();
+        self.inserted = 0;
+        self.capacity_check.reset();
+    }
+
+    /// See [BloomFilter::estimate_current_fpp].
+    fn estimated_fpp(&self) -> f64 {
+        self.estimate_current_fpp()
+    }
+}
+
+impl FromIterator<Vec<u8>> for BloomFilter {
+    /// Build a Bloom filter sized for exactly the number of elements in `iter`, with
+    /// [BloomFilter::DEFAULT_FALSE_POSITIVE_PROBABILITY]. The elements are buffered to count them
+    /// first, use [BloomFilter::from_iter_with] to avoid that.
+    fn from_iter<I: IntoIterator<Item=Vec<u8>>>(iter: I) -> Self {
+        let elements: Vec<Vec<u8>> = iter.into_iter().collect();
+        let expected_elements = (elements.len() as u64).max(1);
+        let builder =
+            FilterBuilder::new(expected_elements, BloomFilter::DEFAULT_FALSE_POSITIVE_PROBABILITY);
+        BloomFilter::from_iter_with(builder, elements)
+    }
+}
+
+impl<'a> Extend<&'a [u8]> for BloomFilter {
+    fn extend<I: IntoIterator<Item=&'a [u8]>>(&mut self, iter: I) {
+        for element in iter {
+            self.add(element);
+        }
+    }
+}
+
+impl Extend<Vec<u8>> for BloomFilter {
+    fn extend<I: IntoIterator<Item=Vec<u8>>>(&mut self, iter: I) {
+        for element in iter {
+            self.add(&element);
+        }
+    }
+}
+
+impl<'a> Extend<&'a str> for BloomFilter {
+    fn extend<I: IntoIterator<Item=&'a str>>(&mut self, iter: I) {
+        for element in iter {
+            self.add(element.as_bytes());
+        }
+    }
+}
+
+/// Two filters are equal if they are compatible (same size and hashes) and have the same bits
+/// set. The [BloomFilter::len] counter and the initial `expected_elements` and
+/// `false_positive_probability` are not compared.
+impl PartialEq for BloomFilter {
+    fn eq(&self, other: &Self) -> bool {
+        self.compatible(other) && self.bit_set.storage == other.bit_set.storage
+    }
+}
+
+impl Eq for BloomFilter {}
+
+/// Union of two compatible filters, see [BloomFilter::union_of].
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible, like slice operations do on a length mismatch.
+impl BitOr for &BloomFilter {
+    type Output = BloomFilter;
+
+    fn bitor(self, rhs: Self) -> BloomFilter {
+        self.union_of(rhs).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Union of two compatible filters reusing the storage of the left one.
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible.
+impl BitOr for BloomFilter {
+    type Output = BloomFilter;
+
+    fn bitor(mut self, rhs: Self) -> BloomFilter {
+        self |= &rhs;
+        self
+    }
+}
+
+/// In place union of two compatible filters, see [BloomFilter::try_union].
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible.
+impl BitOrAssign<&BloomFilter> for BloomFilter {
+    fn bitor_assign(&mut self, rhs: &BloomFilter) {
+        self.try_union(rhs).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Intersection of two compatible filters, see [BloomFilter::intersect_of].
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible.
+impl BitAnd for &BloomFilter {
+    type Output = BloomFilter;
+
+    fn bitand(self, rhs: Self) -> BloomFilter {
+        self.intersect_of(rhs).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Intersection of two compatible filters reusing the storage of the left one.
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible.
+impl BitAnd for BloomFilter {
+    type Output = BloomFilter;
+
+    fn bitand(mut self, rhs: Self) -> BloomFilter {
+        self &= &rhs;
+        self
+    }
+}
+
+/// In place intersection of two compatible filters, see [BloomFilter::try_intersect].
+///
+/// # Panics
+///
+/// Panics if the filters are not compatible.
+impl BitAndAssign<&BloomFilter> for BloomFilter {
+    fn bitand_assign(&mut self, rhs: &BloomFilter) {
+        self.try_intersect(rhs).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
+/// Merges all filters with [BloomFilter::union], using the first one as the accumulator. This
+/// pairs with e.g. rayon's `.map(build_filter).sum()`.
+///
+/// # Panics
+///
+/// Panics if the iterator is empty, as there is no configuration to build an empty filter from,
+/// or if the filters are not compatible.
+impl Sum for BloomFilter {
+    fn sum<I: Iterator<Item=BloomFilter>>(mut iter: I) -> Self {
+        let mut res = iter.next().expect("can't sum an empty iterator of bloom filters");
+        for filter in iter {
+            res |= &filter;
+        }
+        res
+    }
+}
+
+/// Same as the [Sum] of owned filters, cloning the first filter as the accumulator.
+///
+/// # Panics
+///
+/// Panics if the iterator is empty or if the filters are not compatible.
+impl<'a> Sum<&'a BloomFilter> for BloomFilter {
+    fn sum<I: Iterator<Item=&'a BloomFilter>>(mut iter: I) -> Self {
+        let mut res = iter.next().expect("can't sum an empty iterator of bloom filters").clone();
+        for filter in iter {
+            res |= filter;
+        }
+        res
     }
 }
 
@@ -134,6 +657,10 @@ impl Hashes for BloomFilter {
 }
 
 impl BloomFilter {
+    /// The false positive probability used by the [FromIterator] implementation of
+    /// [BloomFilter].
+    pub const DEFAULT_FALSE_POSITIVE_PROBABILITY: f64 = 0.01;
+
     /// Build a Bloom filter form [FilterBuilder].
     ///
     /// # Examples:
@@ -146,729 +673,4534 @@ impl BloomFilter {
     /// ```
     pub fn new(mut config: FilterBuilder) -> Self {
         config.complete();
-        #[cfg(target_pointer_width = "64")]
-            let bit_set = BloomBitVec::new((config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let bit_set = BloomBitVec::new((config.size >> 5) as usize);
-        BloomFilter { config, bit_set }
-    }
-
-    /// Tests whether an element is present in the filter (subject to the specified false
-    /// positive rate). And if it is not in this filter, add it to the filter.
-    #[inline]
-    pub fn add_if_not_contains(&mut self, element: &[u8]) -> bool {
-        bit_check_and_set(&mut self.bit_set, element, self.config.size,
-                          self.config.hashes as u64)
+        let bit_set = BloomBitVec::with_bits(config.size);
+        BloomFilter::from_parts(config, bit_set)
     }
 
-    /// Build a Bloom filter form `&[u8]`.
+    /// Creates an empty filter of `size_bits` bits using `hashes` hash functions, computing the
+    /// configuration with integer arithmetic only, see [FilterBuilder::from_size_and_hashes]. It
+    /// is compatible with every filter of the same size and hashes, however it was built.
     ///
     /// # Examples
     ///
     /// ```rust
-    /// use fastbloom_rs::BloomFilter;
-    /// let mut array = vec![0u8; 4096];
-    /// let bloom = BloomFilter::from_u8_array(array.as_bytes(), 4);
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
+    ///
+    /// let bloom = BloomFilter::with_size_and_hashes(958505856, 7);
+    /// assert!(bloom.compatible(&FilterBuilder::new(100_000_000, 0.01).build_bloom_filter()));
     /// ```
-    pub fn from_u8_array(array: &[u8], hashes: u32) -> Self {
-        let mut config =
-            FilterBuilder::from_size_and_hashes((array.len() * 8) as u64, hashes);
-        config.complete();
-        #[cfg(target_pointer_width = "64")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 5) as usize);
-
-        let ptr = array.as_ptr() as *const usize;
-        #[cfg(target_pointer_width = "64")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 5) as usize);
-
-        bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size_bits` or `hashes` is zero.
+    pub fn with_size_and_hashes(size_bits: u64, hashes: u32) -> Self {
+        BloomFilter::from_bit_vec(BloomBitVec::with_bits(size_bits), hashes)
+    }
 
-        BloomFilter { config, bit_set: bit_vec }
+    pub(crate) fn from_parts(config: FilterBuilder, bit_set: BloomBitVec) -> Self {
+        BloomFilter {
+            config,
+            bit_set: Arc::new(bit_set),
+            inserted: 0,
+            capacity_check: CapacityCheck::default(),
+            key_buf: Vec::new(),
+        }
     }
 
-    /// Build a Bloom filter form `&[u16]`.
-    ///
-    /// # Examples
-    ///
-    /// ```rust
-    /// use fastbloom_rs::BloomFilter;
-    /// let mut array = vec![0u16; 2048];
-    /// let bloom = BloomFilter::from_u16_array(array.as_bytes(), 4);
-    /// ```
-    pub fn from_u16_array(array: &[u16], hashes: u32) -> Self {
-        let mut config =
-            FilterBuilder::from_size_and_hashes((array.len() * 16) as u64, hashes);
-        config.complete();
-        #[cfg(target_pointer_width = "64")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 5) as usize);
+    /// The configuration of the filter without cloning it like [BloomFilter::config] does.
+    pub(crate) fn config_ref(&self) -> &FilterBuilder {
+        &self.config
+    }
 
-        let ptr = array.as_ptr() as *const usize;
-        #[cfg(target_pointer_width = "64")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 5) as usize);
+    /// The bits of the filter.
+    pub(crate) fn bit_vec(&self) -> &BloomBitVec {
+        &self.bit_set
+    }
 
-        bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
+    /// Sets the number of insert operations, e.g. as read back with the filter.
+    pub(crate) fn set_len(&mut self, len: u64) {
+        self.inserted = len;
+    }
 
-        BloomFilter { config, bit_set: bit_vec }
+    /// Returns the bits for writing, copying them first if they are shared with a
+    /// [BloomSnapshot].
+    #[inline]
+    fn bits_mut(&mut self) -> &mut BloomBitVec {
+        Arc::make_mut(&mut self.bit_set)
     }
 
+    /// Moves the bits out, copying them if they are shared with a [BloomSnapshot].
+    fn into_bit_set(self) -> BloomBitVec {
+        Arc::try_unwrap(self.bit_set).unwrap_or_else(|shared| (*shared).clone())
+    }
 
-    /// Build a Bloom filter form `&[u32]`.
+    /// Takes a snapshot of the filter to roll back to with [BloomFilter::restore].
+    ///
+    /// Taking the snapshot is cheap: the snapshot shares the bits with the filter, which copies
+    /// them on its next modification (copy-on-write) as long as the snapshot is alive.
     ///
     /// # Examples
     ///
     /// ```rust
-    /// use fastbloom_rs::BloomFilter;
-    /// let mut array = vec![0u32; 1024];
-    /// let bloom = BloomFilter::from_u32_array(array.as_bytes(), 4);
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let snapshot = bloom.snapshot();
+    /// bloom.add(b"world");
+    /// bloom.restore(&snapshot);
+    /// assert!(bloom.contains(b"hello"));
+    /// assert!(!bloom.contains(b"world"));
     /// ```
-    pub fn from_u32_array(array: &[u32], hashes: u32) -> Self {
-        let mut config =
-            FilterBuilder::from_size_and_hashes((array.len() * 32) as u64, hashes);
-        config.complete();
-        #[cfg(target_pointer_width = "64")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 5) as usize);
-
-        let ptr = array.as_ptr() as *const usize;
-        #[cfg(target_pointer_width = "64")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 5) as usize);
-
-        bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
+    pub fn snapshot(&self) -> BloomSnapshot {
+        BloomSnapshot {
+            config: self.config.clone(),
+            bit_set: Arc::clone(&self.bit_set),
+            inserted: self.inserted,
+        }
+    }
 
-        BloomFilter { config, bit_set: bit_vec }
+    /// Brings the filter back to the state of `snapshot`: its configuration, bits and
+    /// [BloomFilter::len] counter. The bits are shared with the snapshot again until the next
+    /// modification, so restoring is cheap too and the snapshot can be restored several times.
+    pub fn restore(&mut self, snapshot: &BloomSnapshot) {
+        self.config = snapshot.config.clone();
+        self.bit_set = Arc::clone(&snapshot.bit_set);
+        self.inserted = snapshot.inserted;
+        self.capacity_check.reset();
     }
 
-    /// Build a Bloom filter form `&[u64]`.
+    /// Creates a new empty filter with exactly the same configuration as this one, so it stays
+    /// compatible (e.g. for [BloomFilter::union]) with it. The insert counter starts at 0.
     ///
     /// # Examples
     ///
     /// ```rust
-    /// use fastbloom_rs::BloomFilter;
-    /// let mut array = vec![0u64; 512];
-    /// let bloom = BloomFilter::from_u32_array(array.as_bytes(), 4);
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let fresh = bloom.clone_empty();
+    /// assert!(bloom.compatible(&fresh));
+    /// assert!(!fresh.contains(b"hello"));
     /// ```
-    pub fn from_u64_array(array: &[u64], hashes: u32) -> Self {
-        let mut config =
-            FilterBuilder::from_size_and_hashes((array.len() * 64) as u64, hashes);
-        config.complete();
-        #[cfg(target_pointer_width = "64")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let mut bit_vec = BloomBitVec::new((config.size >> 5) as usize);
-
-        let ptr = array.as_ptr() as *const usize;
-        #[cfg(target_pointer_width = "64")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 6) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let usize_array = slice_from_raw_parts(ptr, (config.size >> 5) as usize);
-
-        bit_vec.storage.copy_from_slice(unsafe { &*usize_array });
-
-        BloomFilter { config, bit_set: bit_vec }
+    pub fn clone_empty(&self) -> Self {
+        let bit_set = BloomBitVec::with_bits(self.config.size);
+        let mut res = BloomFilter::from_parts(self.config.clone(), bit_set);
+        res.capacity_check.interval = self.capacity_check.interval;
+        res
     }
 
-    /// Returns the configuration/builder of the Bloom filter.
-    /// # Examples
+    /// Build a Bloom filter form [FilterBuilder] and add all elements of `iter` to it.
+    ///
+    /// # Examples:
     ///
     /// ```rust
-    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
     ///
-    /// let bloom = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
-    /// let builder = bloom.config();
+    /// let builder = FilterBuilder::new(100, 0.01);
+    /// let bloom = BloomFilter::from_iter_with(builder, ["hello", "world"]);
+    /// assert!(bloom.contains(b"hello"));
     /// ```
+    pub fn from_iter_with<I, T>(config: FilterBuilder, iter: I) -> Self
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+        let mut bloom = BloomFilter::new(config);
+        for element in iter {
+            bloom.add(element.as_ref());
+        }
+        bloom
+    }
+
+    /// Adds the passed value to the filter, the same as [Membership::add] for anything which can
+    /// be viewed as bytes (`&[u8]`, `&str`, `String`, `Vec<u8>`, `[u8; N]`, ...). The value is
+    /// only borrowed through [AsRef], nothing is copied.
     ///
-    pub fn config(&self) -> FilterBuilder {
-        self.config.clone()
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add("hello");
+    /// bloom.add(String::from("world"));
+    /// assert!(bloom.contains(b"hello"));
+    /// assert!(bloom.contains(vec![b'w', b'o', b'r', b'l', b'd']));
+    /// ```
+    #[inline]
+    pub fn add<T: AsRef<[u8]>>(&mut self, element: T) {
+        Membership::add(self, element.as_ref())
     }
 
-    /// Return the underlying byte vector of the Bloom filter.
-    pub fn get_u8_array(&self) -> &[u8] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr();
-        let u8_ptr = ptr as *const u8;
-        #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 8);
-        #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 4);
-        unsafe { &*ptr }
+    /// Adds the element to the filter unless it is already present, i.e. all its `k` bits are
+    /// set, and returns `true` if it was new.
+    ///
+    /// Unlike [BloomFilter::contains_then_add] nothing is written for a present element, which
+    /// saves the writes in duplicate-heavy streams and doesn't copy bits shared with a
+    /// [BloomSnapshot]. Present elements are not counted by [BloomFilter::len]. This is what
+    /// [Membership::add] does when `enable_repeat_insert` of the [FilterBuilder] is disabled; the
+    /// bits end up the same in both modes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// assert_eq!(bloom.insert(b"hello"), true);
+    /// assert_eq!(bloom.insert(b"hello"), false);
+    /// assert_eq!(bloom.len(), 1);
+    /// ```
+    pub fn insert<T: AsRef<[u8]>>(&mut self, element: T) -> bool {
+        let (m, k) = (self.config.size, self.config.hashes as u64);
+        let probe = self.config.probe();
+        if bit_check(&self.bit_set, element.as_ref(), m, k, probe) {
+            return false;
+        }
+        bit_set(Arc::make_mut(&mut self.bit_set), element.as_ref(), m, k, probe);
+        self.inserted += 1;
+        true
     }
 
-    /// Return the underlying u16 vector of the Bloom filter.
-    pub fn get_u16_array(&self) -> &[u16] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr() as *const u16;
-        #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() * 4);
-        #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
-        unsafe { &*ptr }
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate), the same as [Membership::contains] for anything which can be viewed as
+    /// bytes.
+    #[inline]
+    pub fn contains<T: AsRef<[u8]>>(&self, element: T) -> bool {
+        Membership::contains(self, element.as_ref())
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate). And if it is not in this filter, add it to the filter.
+    #[inline]
+    pub fn add_if_not_contains<T: AsRef<[u8]>>(&mut self, element: T) -> bool {
+        self.inserted += 1;
+        let (m, k) = (self.config.size, self.config.hashes as u64);
+        bit_check_and_set(Arc::make_mut(&mut self.bit_set), element.as_ref(), m, k,
+                          self.config.probe())
+    }
+
+    /// Adds the element to the filter unless the filter is over capacity, i.e. its estimated
+    /// false positive probability exceeds the configured `false_positive_probability`. This
+    /// allows ingestion pipelines to rotate to a fresh filter instead of silently degrading.
+    ///
+    /// To keep this cheap the estimate is only recomputed every
+    /// [BloomFilter::set_capacity_check_interval] inserts (1024 by default), so a few more
+    /// elements than the exact limit may be accepted. [Membership::add] keeps working on a filter
+    /// which is over capacity.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100, 0.01).build_bloom_filter();
+    /// bloom.set_capacity_check_interval(1);
+    /// let accepted = (0..1000u32).take_while(|i| bloom.try_add(&i.to_le_bytes()).is_ok()).count();
+    /// assert!(accepted < 200);
+    /// ```
+    pub fn try_add<T: AsRef<[u8]>>(&mut self, element: T) -> Result<(), CapacityExceeded> {
+        if self.inserted >= self.capacity_check.next {
+            self.capacity_check.next = self.inserted + self.capacity_check.interval;
+            let estimated_fpp = self.estimate_current_fpp();
+            self.capacity_check.exceeded =
+                if estimated_fpp > self.config.false_positive_probability {
+                    Some(CapacityExceeded {
+                        estimated_fpp,
+                        estimated_elements: self.estimate_element_count(),
+                    })
+                } else { None };
+        }
+        match &self.capacity_check.exceeded {
+            Some(exceeded) => Err(exceeded.clone()),
+            None => {
+                self.add(element);
+                Ok(())
+            }
+        }
+    }
+
+    /// Sets the number of inserts between two capacity checks of [BloomFilter::try_add].
+    pub fn set_capacity_check_interval(&mut self, interval: u64) {
+        assert!(interval > 0, "interval must larger than 0!");
+        self.capacity_check.interval = interval;
+        self.capacity_check.reset();
+    }
+
+    /// Adds all elements of `elements` to the filter, equivalent to calling `add` for each one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add_all(vec!["hello", "world"]);
+    /// assert!(bloom.contains(b"world"));
+    /// ```
+    pub fn add_all<I, T>(&mut self, elements: I)
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+        if !self.config.enable_repeat_insert {
+            for element in elements {
+                self.insert(element);
+            }
+            return;
+        }
+        let m = self.config.size;
+        let k = self.config.hashes as u64;
+        let probe = self.config.probe();
+        let bit_vec = Arc::make_mut(&mut self.bit_set);
+        for element in elements {
+            bit_set(bit_vec, element.as_ref(), m, k, probe);
+            self.inserted += 1;
+        }
+    }
+
+    /// Adds all elements of the slice to the filter, see [BloomFilter::add_all].
+    pub fn add_slice(&mut self, elements: &[&[u8]]) {
+        self.add_all(elements)
+    }
+
+    /// Tests whether each element of the slice is present in the filter. The result is aligned
+    /// with the input, i.e. `res[i]` is the answer for `elements[i]`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.contains_batch(&[b"hello", b"world"]), vec![true, false]);
+    /// ```
+    pub fn contains_batch(&self, elements: &[&[u8]]) -> Vec<bool> {
+        self.contains_batch_iter(elements)
+    }
+
+    /// Same as [BloomFilter::contains_batch] but takes any iterator of elements, so the input
+    /// doesn't need to be collected first.
+    pub fn contains_batch_iter<I, T>(&self, elements: I) -> Vec<bool>
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+        let m = self.config.size;
+        let k = self.config.hashes as u64;
+        let probe = self.config.probe();
+        elements.into_iter()
+            .map(|element| bit_check(&self.bit_set, element.as_ref(), m, k, probe))
+            .collect()
+    }
+
+    /// Returns `true` if every element of the slice is present in the filter, stopping at the
+    /// first missing one. An empty slice gives `true`.
+    pub fn contains_all(&self, elements: &[&[u8]]) -> bool {
+        self.contains_all_iter(elements.iter())
+    }
+
+    /// Returns `true` if at least one element of the slice is present in the filter, stopping at
+    /// the first present one. An empty slice gives `false`.
+    pub fn contains_any(&self, elements: &[&[u8]]) -> bool {
+        self.contains_any_iter(elements.iter())
+    }
+
+    fn contains_all_iter<I, T>(&self, mut elements: I) -> bool
+        where I: Iterator<Item=T>, T: AsRef<[u8]> {
+        elements.all(|element| self.contains(element.as_ref()))
+    }
+
+    fn contains_any_iter<I, T>(&self, mut elements: I) -> bool
+        where I: Iterator<Item=T>, T: AsRef<[u8]> {
+        elements.any(|element| self.contains(element.as_ref()))
+    }
+
+    /// Tests whether an element is present in the filter and adds it, hashing the element only
+    /// once.
+    ///
+    /// Returns `true` if all `k` bits of the element were already set, i.e. the element was
+    /// probably already present. If only some of the bits were set the element is definitely new
+    /// and `false` is returned. In both cases all `k` bits are set afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// assert_eq!(bloom.contains_then_add(b"hello"), false);
+    /// assert_eq!(bloom.contains_then_add(b"hello"), true);
+    /// ```
+    #[inline]
+    pub fn contains_then_add<T: AsRef<[u8]>>(&mut self, element: T) -> bool {
+        self.inserted += 1;
+        let (m, k) = (self.config.size, self.config.hashes as u64);
+        bit_check_and_set(Arc::make_mut(&mut self.bit_set), element.as_ref(), m, k,
+                          self.config.probe())
+    }
+
+    /// Adds any `T: Hash` to the filter.
+    ///
+    /// The item is fed through an internal stable hasher which writes integers little endian and
+    /// `usize` as 64 bits, so the result is the same across runs and architectures. The std
+    /// `DefaultHasher` can't be used for this as its output is not guaranteed to be stable.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add_hashable(&(1u64, "hello"));
+    /// assert!(bloom.contains_hashable(&(1u64, "hello")));
+    /// ```
+    pub fn add_hashable<T: Hash + ?Sized>(&mut self, item: &T) {
+        let mut hasher = StableHasher::new();
+        item.hash(&mut hasher);
+        self.add(hasher.as_bytes());
+    }
+
+    /// Tests whether a `T: Hash` added by [BloomFilter::add_hashable] is present in the filter
+    /// (subject to the specified false positive rate).
+    pub fn contains_hashable<T: Hash + ?Sized>(&self, item: &T) -> bool {
+        let mut hasher = StableHasher::new();
+        item.hash(&mut hasher);
+        self.contains(hasher.as_bytes())
+    }
+
+    /// Adds an element by a 64-bit hash of it which the caller already has, without hashing any
+    /// bytes. Pre-hashed elements are a namespace of their own: `add_hash(h)` is only found by
+    /// [BloomFilter::contains_hash] with the same `h`, and never by [BloomFilter::contains] of
+    /// any bytes, even the bytes `h` was computed from (up to false positives).
+    ///
+    /// The hashes are derived from `hash` without the hasher of the filter, so other systems can
+    /// reproduce the bits: `h1` and `h2` are the first two outputs of SplitMix64 started from
+    /// `hash ^ seed`, and `h3` (for [crate::ProbeScheme::Triple]) is the third one. They are then
+    /// reduced and probed like the hashes of bytes: `h2` made odd with `h2 | 1`, both taken
+    /// modulo the size `m`, `h2` replaced by 1 if it is then 0, which only happens for an odd `m`
+    /// (hash version 3), and index `i` in `0..k` is `(h1 + i * h2) % m` with the default
+    /// [crate::ProbeScheme::Double]. The SplitMix64 step is
+    ///
+    /// ```text
+    /// state = state + 0x9E3779B97F4A7C15
+    /// z = (state ^ (state >> 30)) * 0xBF58476D1CE4E5B9
+    /// z = (z ^ (z >> 27)) * 0x94D049BB133111EB
+    /// output = z ^ (z >> 31)
+    /// ```
+    ///
+    /// with wrapping 64-bit arithmetic. The quality of the filter depends on `hash`: it must be
+    /// well mixed, like a 64-bit xxhash, not e.g. a sequential id.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add_hash(0x8f3a_51c2_77d0_e914);
+    /// assert!(bloom.contains_hash(0x8f3a_51c2_77d0_e914));
+    /// assert!(!bloom.contains(0x8f3a_51c2_77d0_e914u64.to_le_bytes()));
+    /// ```
+    pub fn add_hash(&mut self, hash: u64) {
+        let (m, k) = (self.config.size, self.config.hashes as u64);
+        let indices = prehashed_indices(hash, m, k, self.config.probe());
+        if !self.config.enable_repeat_insert
+            && indices.clone().all(|index| self.bit_set.get(index as usize)) {
+            return;
+        }
+        let bit_set = Arc::make_mut(&mut self.bit_set);
+        for index in indices {
+            bit_set.set(index as usize);
+        }
+        self.inserted += 1;
+    }
+
+    /// Tests whether a hash added by [BloomFilter::add_hash] is present in the filter (subject
+    /// to the specified false positive rate).
+    pub fn contains_hash(&self, hash: u64) -> bool {
+        let (m, k) = (self.config.size, self.config.hashes as u64);
+        prehashed_indices(hash, m, k, self.config.probe())
+            .all(|index| self.bit_set.get(index as usize))
+    }
+
+    /// Hashes a key once for [BloomFilter::contains_precomputed] and
+    /// [BloomFilter::add_precomputed] of this and any other filter with the same hasher, seed and
+    /// hash version, see [PrecomputedHashes].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut shards: Vec<_> = (0..32).map(|i| {
+    ///     FilterBuilder::new(10_000 * (i + 1), 0.01).build_bloom_filter()
+    /// }).collect();
+    /// let hashes = shards[0].hash_key(b"hello");
+    /// shards[7].add_precomputed(&hashes);
+    /// let found: Vec<usize> = (0..32).filter(|&i| shards[i].contains_precomputed(&hashes))
+    ///     .collect();
+    /// assert_eq!(found, vec![7]);
+    /// assert!(shards[7].contains(b"hello"));
+    /// ```
+    pub fn hash_key(&self, key: &[u8]) -> PrecomputedHashes {
+        PrecomputedHashes {
+            hashes: base_hashes(key, self.config.probe()),
+            hasher: self.config.hasher.clone(),
+            seed: self.config.seed,
+            version: self.config.hash_version,
+            scheme: self.config.probe_scheme,
+        }
+    }
+
+    /// Checks that `hashes` can be probed in this filter.
+    fn check_precomputed(&self, hashes: &PrecomputedHashes) -> Result<(), IncompatibleFilterError> {
+        if !self.config.hasher.same(&hashes.hasher) {
+            return Err(IncompatibleFilterError::Hasher {
+                left: self.config.hasher.name().into_owned(),
+                right: hashes.hasher.name().into_owned(),
+            });
+        }
+        if self.config.seed != hashes.seed {
+            let (left, right) = (self.config.seed, hashes.seed);
+            return Err(IncompatibleFilterError::Seed { left, right });
+        }
+        if self.config.hash_version != hashes.version {
+            return Err(IncompatibleFilterError::HashVersion {
+                left: self.config.hash_version,
+                right: hashes.version,
+            });
+        }
+        if self.config.probe_scheme == ProbeScheme::Triple && hashes.scheme != ProbeScheme::Triple {
+            return Err(IncompatibleFilterError::ProbeScheme {
+                left: self.config.probe_scheme,
+                right: hashes.scheme,
+            });
+        }
+        Ok(())
+    }
+
+    /// The bit indices of `hashes` in this filter.
+    #[inline]
+    fn precomputed_indices(&self, hashes: &PrecomputedHashes) -> ProbeSequence {
+        ProbeSequence::new(hashes.hashes, self.config.size, self.config.hashes as u64,
+                           self.config.probe())
+    }
+
+    /// Tests whether the key of `hashes` is present in the filter, the same as
+    /// [BloomFilter::contains] of the key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hashes are not valid for this filter, see
+    /// [BloomFilter::try_contains_precomputed].
+    pub fn contains_precomputed(&self, hashes: &PrecomputedHashes) -> bool {
+        self.try_contains_precomputed(hashes).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as [BloomFilter::contains_precomputed] but returns an error if the hashes were
+    /// computed with another hasher, seed or hash version.
+    pub fn try_contains_precomputed(&self, hashes: &PrecomputedHashes)
+                                    -> Result<bool, IncompatibleFilterError> {
+        self.check_precomputed(hashes)?;
+        Ok(self.precomputed_indices(hashes).all(|index| self.bit_set.get(index as usize)))
+    }
+
+    /// Adds the key of `hashes` to the filter, the same as [BloomFilter::add] of the key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the hashes are not valid for this filter, see
+    /// [BloomFilter::try_add_precomputed].
+    pub fn add_precomputed(&mut self, hashes: &PrecomputedHashes) {
+        self.try_add_precomputed(hashes).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as [BloomFilter::add_precomputed] but returns an error if the hashes were computed
+    /// with another hasher, seed or hash version.
+    pub fn try_add_precomputed(&mut self, hashes: &PrecomputedHashes)
+                               -> Result<(), IncompatibleFilterError> {
+        self.check_precomputed(hashes)?;
+        let indices = self.precomputed_indices(hashes);
+        if !self.config.enable_repeat_insert
+            && indices.clone().all(|index| self.bit_set.get(index as usize)) {
+            return Ok(());
+        }
+        let bit_set = Arc::make_mut(&mut self.bit_set);
+        for index in indices {
+            bit_set.set(index as usize);
+        }
+        self.inserted += 1;
+        Ok(())
+    }
+
+    /// Starts building a composite key from several pieces, which is then added or tested
+    /// with [KeyWriter::add] or [KeyWriter::contains]. The pieces are written into a buffer
+    /// owned by the filter and reused for every key, so no allocation is made per key once the
+    /// buffer has grown to the key size. See [KeyWriter] for how the pieces are framed.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// let mut key = bloom.key();
+    /// key.write(b"tenant");
+    /// key.write_u64(1_700_000_000);
+    /// key.write_str("/a/path");
+    /// key.add();
+    ///
+    /// let mut key = bloom.key();
+    /// key.write(b"tenant");
+    /// key.write_u64(1_700_000_000);
+    /// key.write_str("/a/path");
+    /// assert!(key.contains());
+    /// ```
+    pub fn key(&mut self) -> KeyWriter<'_> {
+        KeyWriter::new(self)
+    }
+
+    /// Takes the buffer used by [BloomFilter::key], to be handed back with
+    /// [BloomFilter::put_key_buf].
+    pub(crate) fn take_key_buf(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.key_buf)
+    }
+
+    pub(crate) fn put_key_buf(&mut self, buf: Vec<u8>) {
+        self.key_buf = buf;
+    }
+
+    /// Adds a string to the filter as its UTF-8 bytes.
+    ///
+    /// The typed helpers use one canonical encoding, which is also the one of the Python
+    /// bindings: strings are their UTF-8 bytes and integers their little endian bytes at the
+    /// width of the type. `add_str(s)` is the same as `add(s.as_bytes())` and `add_str` of the
+    /// Python `BloomFilter`, `add_i64` is the same as `add_int` of the Python `BloomFilter`.
+    /// Integers of different widths give different bytes, so `add_u32(7)` is not found by
+    /// `contains_u64(7)`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add_str("hello");
+    /// bloom.add_i64(-7);
+    /// assert!(bloom.contains(b"hello"));
+    /// assert!(bloom.contains(&(-7i64).to_le_bytes()));
+    /// ```
+    pub fn add_str(&mut self, element: &str) {
+        self.add(element.as_bytes());
+    }
+
+    /// Adds a `u64` to the filter as its 8 little endian bytes, see [BloomFilter::add_str].
+    pub fn add_u64(&mut self, element: u64) {
+        self.add(element.to_le_bytes());
+    }
+
+    /// Adds an `i64` to the filter as its 8 little endian bytes, see [BloomFilter::add_str].
+    pub fn add_i64(&mut self, element: i64) {
+        self.add(element.to_le_bytes());
+    }
+
+    /// Adds a `u32` to the filter as its 4 little endian bytes, see [BloomFilter::add_str].
+    pub fn add_u32(&mut self, element: u32) {
+        self.add(element.to_le_bytes());
+    }
+
+    /// Tests whether a string added by [BloomFilter::add_str] is present in the filter.
+    pub fn contains_str(&self, element: &str) -> bool {
+        self.contains(element.as_bytes())
+    }
+
+    /// Tests whether a `u64` added by [BloomFilter::add_u64] is present in the filter.
+    pub fn contains_u64(&self, element: u64) -> bool {
+        self.contains(element.to_le_bytes())
+    }
+
+    /// Tests whether an `i64` added by [BloomFilter::add_i64] is present in the filter.
+    pub fn contains_i64(&self, element: i64) -> bool {
+        self.contains(element.to_le_bytes())
+    }
+
+    /// Tests whether a `u32` added by [BloomFilter::add_u32] is present in the filter.
+    pub fn contains_u32(&self, element: u32) -> bool {
+        self.contains(element.to_le_bytes())
+    }
+
+    /// Build a Bloom filter form `&[u8]`.
+    ///
+    /// The array is taken as the raw bits of [BloomFilter::get_u8_array], every byte of it, with
+    /// the number of hashes given here. The bytes of [BloomFilter::to_bytes], which describe the
+    /// filter themselves, are read with [BloomFilter::from_bytes] or `BloomFilter::try_from`.
+    ///
+    /// Like every constructor from raw bits but [BloomFilter::from_u8_array_with_config], the
+    /// filter places the bits like the default configuration of [FilterBuilder::new] does. The
+    /// raw bits don't say how they were placed, so the bits of filters built with another
+    /// configuration, e.g. with [HashAlgorithm::Xxh3] and hash version 1 by the versions before
+    /// [HashAlgorithm::Xxh128] was the default, are read with
+    /// [BloomFilter::from_u8_array_with_config].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::BloomFilter;
+    /// let mut array = vec![0u8; 4096];
+    /// let bloom = BloomFilter::from_u8_array(&array, 4);
+    /// ```
+    pub fn from_u8_array(array: &[u8], hashes: u32) -> Self {
+        BloomFilter::from_raw_bytes(array, (array.len() * 8) as u64, hashes)
+    }
+
+    /// Build a Bloom filter of `size` bits form `&[u8]` with the layout of
+    /// [BloomFilter::get_u8_array]. Unlike [BloomFilter::from_u8_array], whose size is always
+    /// `array.len() * 8`, this rebuilds filters whose size is not a multiple of the word size,
+    /// e.g. built with [FilterBuilder::exact_size].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, HashAlgorithm, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(1001, 4).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let copy = BloomFilter::from_u8_array_with_size(bloom.get_u8_array(), 1001, 4);
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn from_u8_array_with_size(array: &[u8], size: u64, hashes: u32) -> Self {
+        assert!(array.len() as u64 * 8 >= size,
+                "u8 array with len {} is too short for {} bits!", array.len(), size);
+        BloomFilter::from_raw_bytes(array, size, hashes)
+    }
+
+    /// Build a Bloom filter with the configuration `config` form `&[u8]` with the layout of
+    /// [BloomFilter::get_u8_array]. The other constructors from raw bits place the bits like the
+    /// default configuration, see [BloomFilter::from_u8_array], this one rebuilds filters built
+    /// with any configuration, e.g. the bits saved by versions before [HashAlgorithm::Xxh128]
+    /// was the default:
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, HashAlgorithm, Membership};
+    ///
+    /// let legacy = FilterBuilder::from_size_and_hashes(1024, 4)
+    ///     .with_hash_algorithm(HashAlgorithm::Xxh3)
+    ///     .with_hash_version(1);
+    /// let mut bloom = legacy.clone().build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let copy = BloomFilter::from_u8_array_with_config(bloom.get_u8_array(), legacy);
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let config = FilterBuilder::from_size_and_hashes(1024, 4);
+    /// let mut bloom = config.clone().build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let copy = BloomFilter::from_u8_array_with_config(bloom.get_u8_array(), config);
+    /// assert_eq!(copy, bloom);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `array` is too short for the size of `config`.
+    pub fn from_u8_array_with_config(array: &[u8], mut config: FilterBuilder) -> Self {
+        config.complete();
+        assert!(array.len() as u64 * 8 >= config.size,
+                "u8 array with len {} is too short for {} bits!", array.len(), config.size);
+        let bit_vec = BloomFilter::bit_vec_from_bytes(array, config.size);
+        BloomFilter::from_parts(config, bit_vec)
+    }
+
+    /// Same as [BloomFilter::from_u8_array_with_size] but returns an error instead of panicking
+    /// if `size` or `hashes` is zero or `array` is too short, e.g. for untrusted input.
+    pub fn try_from_u8_array_with_size(array: &[u8], size: u64, hashes: u32)
+                                       -> Result<Self, FastBloomError> {
+        if size == 0 {
+            return Err(BuildError::ZeroSize.into());
+        }
+        if hashes == 0 {
+            return Err(BuildError::ZeroHashes.into());
+        }
+        if (array.len() as u64) < size.div_ceil(8) {
+            return Err(FastBloomError::Deserialization {
+                reason: format!("u8 array with len {} is too short for {} bits", array.len(), size),
+            });
+        }
+        Ok(BloomFilter::from_raw_bytes(array, size, hashes))
+    }
+
+    /// Build a Bloom filter form `&[u16]`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::BloomFilter;
+    /// let mut array = vec![0u16; 2048];
+    /// let bloom = BloomFilter::from_u16_array(&array, 4);
+    /// ```
+    pub fn from_u16_array(array: &[u16], hashes: u32) -> Self {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(array.as_ptr() as *const u8, std::mem::size_of_val(array))
+        };
+        BloomFilter::from_raw_bytes(bytes, (array.len() * 16) as u64, hashes)
+    }
+
+
+    /// Build a Bloom filter form `&[u32]`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::BloomFilter;
+    /// let mut array = vec![0u32; 1024];
+    /// let bloom = BloomFilter::from_u32_array(&array, 4);
+    /// ```
+    pub fn from_u32_array(array: &[u32], hashes: u32) -> Self {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(array.as_ptr() as *const u8, std::mem::size_of_val(array))
+        };
+        BloomFilter::from_raw_bytes(bytes, (array.len() * 32) as u64, hashes)
+    }
+
+    /// Build a Bloom filter form `&[u64]`, where bit `i` of the filter is bit `i % 64` of
+    /// `array[i / 64]`. This is the layout of [BloomFilter::get_u64_array] and
+    /// [BloomFilter::to_u64_vec] and doesn't depend on the endianness of the platform.
+    ///
+    /// The size of the filter is `array.len() * 64`, use [BloomFilter::from_u64_array_with_size]
+    /// for filters whose size is not a multiple of 64.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::BloomFilter;
+    /// let mut array = vec![0u64; 512];
+    /// let bloom = BloomFilter::from_u64_array(&array, 4);
+    /// ```
+    pub fn from_u64_array(array: &[u64], hashes: u32) -> Self {
+        BloomFilter::from_u64_array_with_size(array, (array.len() * 64) as u64, hashes)
+    }
+
+    /// Build a Bloom filter of `size` bits form `&[u64]`, see [BloomFilter::from_u64_array].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, HashAlgorithm, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(1000, 4).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let copy = BloomFilter::from_u64_array_with_size(&bloom.to_u64_vec(), 1000, 4);
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn from_u64_array_with_size(array: &[u64], size: u64, hashes: u32) -> Self {
+        assert!(array.len() as u64 * 64 >= size,
+                "u64 array with len {} is too short for {} bits!", array.len(), size);
+        BloomFilter::from_bit_vec(BloomBitVec::from_u64_words(array, size), hashes)
+    }
+
+    /// Build a Bloom filter form `Vec<u64>` taking ownership of the words, with the same layout as
+    /// [BloomFilter::from_u64_array].
+    ///
+    /// On 64-bit platforms the allocation of `array` is reused as the storage of the filter, so
+    /// no copy is made. Prefer this over the borrowing constructors for large filters, which
+    /// copy the whole array: a filter of 100 MB takes 4 µs instead of 68 ms in the
+    /// `bloom_from_u64_vec_bench` benchmark.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::BloomFilter;
+    /// let bloom = BloomFilter::from_u64_vec(vec![0u64; 512], 4);
+    /// ```
+    pub fn from_u64_vec(array: Vec<u64>, hashes: u32) -> Self {
+        let size = (array.len() * 64) as u64;
+        BloomFilter::from_bit_vec(BloomBitVec::from_u64_vec(array, size), hashes)
+    }
+
+    /// The same as [BloomFilter::from_u64_vec]: the storage of the filter is not public, its
+    /// bit vector is handed over as the `u64` words of [BloomFilter::into_bit_vec].
+    pub fn from_bit_vec_owned(bit_vec: Vec<u64>, hashes: u32) -> Self {
+        BloomFilter::from_u64_vec(bit_vec, hashes)
+    }
+
+    /// Build a Bloom filter from `Vec<u8>` taking ownership of the bytes, with the layout of
+    /// [BloomFilter::from_u8_array]. The bytes can't become the storage of the filter, which is
+    /// made of aligned words, so they are copied once and dropped: prefer
+    /// [BloomFilter::from_u64_vec] for large filters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::BloomFilter;
+    /// let bloom = BloomFilter::from_vec_owned(vec![0u8; 4096], 4);
+    /// ```
+    pub fn from_vec_owned(bytes: Vec<u8>, hashes: u32) -> Self {
+        BloomFilter::from_u8_array(&bytes, hashes)
+    }
+
+    /// Builds a filter of `size` bits whose storage starts with the native endian `bytes`, the
+    /// layout of [BloomFilter::get_u8_array]. The bytes are copied, so they don't need to be
+    /// aligned like the words of the storage.
+    fn from_raw_bytes(bytes: &[u8], size: u64, hashes: u32) -> Self {
+        BloomFilter::from_bit_vec(BloomFilter::bit_vec_from_bytes(bytes, size), hashes)
+    }
+
+    fn bit_vec_from_bytes(bytes: &[u8], size: u64) -> BloomBitVec {
+        let mut bit_vec = BloomBitVec::with_bits(size);
+        let len = min(bytes.len(), bit_vec.storage.len() * std::mem::size_of::<usize>());
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), bit_vec.storage.as_mut_ptr() as *mut u8,
+                                          len);
+        }
+        bit_vec.clear_padding();
+        bit_vec
+    }
+
+    /// Builds a filter from its bits and number of hashes, which every constructor from raw bits
+    /// goes through, with the configuration of [FilterBuilder::from_size_and_hashes].
+    fn from_bit_vec(bit_vec: BloomBitVec, hashes: u32) -> Self {
+        assert!(bit_vec.nbits > 0, "bloom filter size must be positive!");
+        assert!(hashes > 0, "hashes must larger than 0!");
+        BloomFilter::from_parts(FilterBuilder::from_size_and_hashes(bit_vec.nbits, hashes), bit_vec)
+    }
+
+    /// Same as [Membership::contains_hash_indices] but returns an error instead of panicking if
+    /// an index is out of range, e.g. for indices from untrusted input.
+    pub fn try_contains_hash_indices(&self, indices: &[u64]) -> Result<bool, FastBloomError> {
+        let size = self.config.size;
+        if let Some(&index) = indices.iter().find(|&&index| index >= size) {
+            return Err(FastBloomError::IndexOutOfRange { index, size });
+        }
+        Ok(indices.iter().all(|&index| self.bit_set.get(index as usize)))
+    }
+
+    /// Returns the configuration/builder of the Bloom filter.
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
+    ///
+    /// let bloom = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
+    /// let builder = bloom.config();
+    /// ```
+    ///
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+
+    /// Returns the hash seed of the filter, see [FilterBuilder::with_seed].
+    pub fn seed(&self) -> u64 {
+        self.config.seed
+    }
+
+    /// Supplies the hasher of a filter which only knows its name, see
+    /// [HashAlgorithm::Unresolved], e.g. after deserializing a filter built with
+    /// [FilterBuilder::with_hasher]. Fails if the name of `hasher` is not the name of the
+    /// hasher of the filter.
+    pub fn set_hasher<H: BloomHasher + 'static>(&mut self, hasher: H)
+                                                -> Result<(), IncompatibleFilterError> {
+        self.set_hash_algorithm(HashAlgorithm::custom(hasher))
+    }
+
+    /// Same as [BloomFilter::set_hasher] for a built-in hasher, e.g. to supply the key of a
+    /// deserialized [HashAlgorithm::SipHash13] filter. Fails with the fingerprints of both keys
+    /// if the key is not the one the filter was built with.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, HashAlgorithm, IncompatibleFilterError};
+    ///
+    /// let key = [7u8; 16];
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01)
+    ///     .with_hash_algorithm(HashAlgorithm::SipHash13 { key })
+    ///     .build_bloom_filter();
+    /// bloom.add(b"hello");
+    ///
+    /// let wrong = HashAlgorithm::SipHash13 { key: [8u8; 16] };
+    /// assert!(matches!(bloom.set_hash_algorithm(wrong),
+    ///                  Err(IncompatibleFilterError::Hasher { .. })));
+    /// assert!(bloom.set_hash_algorithm(HashAlgorithm::SipHash13 { key }).is_ok());
+    /// assert!(bloom.contains(b"hello"));
+    /// ```
+    pub fn set_hash_algorithm(&mut self, algorithm: HashAlgorithm)
+                              -> Result<(), IncompatibleFilterError> {
+        if algorithm != self.config.hasher {
+            return Err(IncompatibleFilterError::Hasher {
+                left: self.config.hasher.name().into_owned(),
+                right: algorithm.name().into_owned(),
+            });
+        }
+        self.config.hasher = algorithm;
+        Ok(())
+    }
+
+    /// Returns a read-only [BloomFilterRef] over the bytes of this filter.
+    pub fn as_filter_ref(&self) -> BloomFilterRef<'_> {
+        BloomFilterRef::new(self.get_u8_array(), self.config.size, self.config.hashes)
+            .with_seed(self.config.seed)
+            .with_hasher(&self.config.hasher)
+            .with_hash_version(self.config.hash_version)
+            .with_probe_scheme(self.config.probe_scheme)
+    }
+
+    /// Return the underlying byte vector of the Bloom filter.
+    pub fn get_u8_array(&self) -> &[u8] {
+        let storage = &self.bit_set.storage;
+        let ptr = storage.as_ptr();
+        let u8_ptr = ptr as *const u8;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 8);
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(u8_ptr, storage.len() * 4);
+        unsafe { &*ptr }
+    }
+
+    /// Return the underlying u16 vector of the Bloom filter.
+    pub fn get_u16_array(&self) -> &[u16] {
+        let storage = &self.bit_set.storage;
+        let ptr = storage.as_ptr() as *const u16;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() * 4);
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
+        unsafe { &*ptr }
+    }
+
+    /// Return the underlying u32 vector of the Bloom filter.
+    pub fn get_u32_array(&self) -> &[u32] {
+        let storage = &self.bit_set.storage;
+        let ptr = storage.as_ptr() as *const u32;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(ptr, storage.len());
+        unsafe { &*ptr }
+    }
+
+    /// Return the underlying u64 vector of the Bloom filter without copying, bit `i` of the
+    /// filter is bit `i % 64` of word `i / 64`.
+    ///
+    /// On 32-bit big endian platforms the words don't follow this layout, use
+    /// [BloomFilter::to_u64_vec] for a copy which does on every platform.
+    pub fn get_u64_array(&self) -> &[u64] {
+        let storage = &self.bit_set.storage;
+        let ptr = storage.as_ptr() as *const u64;
+        #[cfg(target_pointer_width = "64")]
+            let ptr = slice_from_raw_parts(ptr, storage.len());
+        #[cfg(target_pointer_width = "32")]
+        if storage.len() % 2 != 0 {
+            panic!("BloomBitVec with len {} can't export as u64 array!", storage.len())
+        }
+        #[cfg(target_pointer_width = "32")]
+            let ptr = slice_from_raw_parts(ptr, storage.len() / 2usize);
+
+        unsafe { &*ptr }
+    }
+
+
+    /// Returns a copy of the bits of the Bloom filter as `u64` words, bit `i` of the filter is
+    /// bit `i % 64` of word `i / 64` on every platform. The bits past the size of the filter in
+    /// the last word are zero.
+    pub fn to_u64_vec(&self) -> Vec<u64> {
+        self.bit_set.to_u64_words()
+    }
+
+    /// Consumes the filter and returns its bits as `u64` words with the layout of
+    /// [BloomFilter::to_u64_vec]. On 64-bit platforms the storage is moved out without a copy.
+    ///
+    /// The filter can't be used afterwards:
+    ///
+    /// ```compile_fail
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let bloom = FilterBuilder::new(100, 0.01).build_bloom_filter();
+    /// let words = bloom.into_u64_vec();
+    /// bloom.contains(b"hello");
+    /// ```
+    pub fn into_u64_vec(self) -> Vec<u64> {
+        self.into_bit_set().into_u64_vec()
+    }
+
+    /// The same as [BloomFilter::into_u64_vec]: the storage of the filter is not public, its bit
+    /// vector is moved out as `u64` words, which [BloomFilter::from_bit_vec_owned] takes back.
+    pub fn into_bit_vec(self) -> Vec<u64> {
+        self.into_u64_vec()
+    }
+
+    /// Consumes the filter and returns its bytes, the same as [BloomFilter::get_u8_array]. The
+    /// bytes are copied once since the storage is made of words.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.get_u8_array().to_vec()
+    }
+
+    /// Returns the configuration and the sorted positions of the set bits of the filter, which is
+    /// much smaller than [BloomFilter::get_u8_array] for lightly filled filters.
+    pub fn to_sparse(&self) -> SparseBloom {
+        SparseBloom::from_parts(self.config.clone(), self.iter_set_bits().collect())
+    }
+
+    /// Build a Bloom filter from its sparse form, see [BloomFilter::to_sparse]. Positions received
+    /// from elsewhere are validated when creating the [SparseBloom] with [SparseBloom::new].
+    pub fn from_sparse(sparse: SparseBloom) -> Self {
+        let (config, indices) = sparse.into_parts();
+        let mut bit_set = BloomBitVec::with_bits(config.size);
+        for index in indices {
+            bit_set.set(index as usize);
+        }
+        BloomFilter::from_parts(config, bit_set)
+    }
+
+    /// Consumes the filter and returns an immutable [FrozenBloomFilter] with the same bits, for
+    /// filters which are only queried once they are built. Excess capacity of the storage is
+    /// released.
+    pub fn freeze(self) -> FrozenBloomFilter {
+        let config = self.config.clone();
+        FrozenBloomFilter::from_parts(self.into_bit_set().into_u64_vec().into_boxed_slice(), config)
+    }
+
+    /// Performs the union operation on two compatible bloom filters. This is achieved through a
+    /// bitwise OR operation on their bit vectors. This operations is lossless, i.e. no elements
+    /// are lost and the bloom filter is the same that would have resulted if all elements wer
+    /// directly inserted in just one bloom filter.
+    ///
+    /// Returns `false` if the filters are not compatible, see [BloomFilter::try_union].
+    pub fn union(&mut self, other: &BloomFilter) -> bool {
+        self.try_union(other).is_ok()
+    }
+
+    /// Performs the union operation like [BloomFilter::union], returning which parameter
+    /// doesn't match if the filters are not compatible.
+    ///
+    /// The [BloomFilter::len] of the result is the sum of both filters.
+    pub fn try_union(&mut self, other: &BloomFilter) -> Result<(), IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        self.bits_mut().or(&other.bit_set);
+        self.inserted += other.inserted;
+        self.capacity_check.reset();
+        Ok(())
+    }
+
+    /// Performs the union operation on two filters with the same hashes whose sizes are powers
+    /// of two, but not necessarily equal. The larger filter is folded down to the size of the
+    /// smaller one (see [BloomFilter::fold]) before the bits are ORed, so `self` takes the
+    /// smaller size if it was the larger one. No element of either filter is lost, the false
+    /// positive probability is the one of all elements in the smaller size. Filters which can't
+    /// be folded, see [BloomFilter::fold], fail with [IncompatibleFilterError::NotFoldable].
+    ///
+    /// Filters of equal size are merged like [BloomFilter::try_union] whatever their size.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut small = FilterBuilder::from_size_and_hashes(1 << 16, 7).build_bloom_filter();
+    /// let mut big = FilterBuilder::from_size_and_hashes(1 << 18, 7).build_bloom_filter();
+    /// small.add(b"hello");
+    /// big.add(b"world");
+    /// small.union_folding(&big).unwrap();
+    /// assert!(small.contains(b"hello") && small.contains(b"world"));
+    /// ```
+    pub fn union_folding(&mut self, other: &BloomFilter) -> Result<(), IncompatibleFilterError> {
+        let (left, right) = (self.config.size, other.config.size);
+        if left == right {
+            return self.try_union(other);
+        }
+        let mut report = self.config.compatibility_report(&other.config);
+        report.size = None;
+        if let Some(e) = report.first_mismatch() {
+            return Err(e);
+        }
+        if !left.is_power_of_two() || !right.is_power_of_two() {
+            return Err(IncompatibleFilterError::SizeNotFoldable { left, right });
+        }
+        let larger = if left > right { &*self } else { other };
+        larger.check_foldable().map_err(IncompatibleFilterError::NotFoldable)?;
+        if left > right {
+            *self = self.fold_to(right);
+            self.try_union(other)
+        } else {
+            self.try_union(&other.fold_to(left))
+        }
+    }
+
+    /// Returns the words of the filter in which bits were set since the earlier state `since` of
+    /// it, e.g. to replicate a filter by only sending what changed with
+    /// [BloomFilter::apply_delta].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut source = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// let mut replica = source.clone();
+    /// let sent = source.clone();
+    /// source.add(b"hello");
+    ///
+    /// let delta = source.diff(&sent).unwrap();
+    /// replica.apply_delta(&delta).unwrap();
+    /// assert!(replica.contains(b"hello"));
+    /// ```
+    pub fn diff(&self, since: &BloomFilter) -> Result<BloomDelta, IncompatibleFilterError> {
+        self.config.check_compatible(&since.config)?;
+        let words = (0..self.bit_set.u64_len())
+            .filter_map(|i| {
+                let word = self.bit_set.u64_word(i);
+                if word & !since.bit_set.u64_word(i) != 0 { Some((i as u64, word)) } else { None }
+            })
+            .collect();
+        Ok(BloomDelta { config: self.config.clone(), words })
+    }
+
+    /// ORs the words of `delta` (see [BloomFilter::diff]) into the filter. Deltas can be applied
+    /// more than once and in any order.
+    ///
+    /// Fails with [IncompatibleFilterError::DeltaWordOutOfRange] if a word is past the words of
+    /// the filter, leaving it unmodified. The bits of the last word past the size are ignored.
+    pub fn apply_delta(&mut self, delta: &BloomDelta) -> Result<(), IncompatibleFilterError> {
+        self.config.check_compatible(&delta.config)?;
+        let words = self.bit_set.u64_len() as u64;
+        if let Some((index, _)) = delta.words.iter().find(|(index, _)| *index >= words) {
+            return Err(IncompatibleFilterError::DeltaWordOutOfRange { index: *index, words });
+        }
+        let bit_set = self.bits_mut();
+        for (index, word) in delta.words.iter() {
+            bit_set.or_u64_word(*index as usize, *word);
+        }
+        bit_set.clear_padding();
+        Ok(())
+    }
+
+    /// Performs the union operation with all `others` in one pass, e.g. to merge the filters of
+    /// many workers.
+    ///
+    /// The compatibility of every filter is checked first, so on error `self` is left unmodified.
+    pub fn union_all<'a, I>(&mut self, others: I) -> Result<(), IncompatibleFilterError>
+        where I: IntoIterator<Item=&'a BloomFilter> {
+        let others: Vec<&BloomFilter> = others.into_iter().collect();
+        for other in others.iter() {
+            self.config.check_compatible(&other.config)?;
+        }
+        let bit_sets: Vec<&BloomBitVec> = others.iter().map(|o| o.bit_set.as_ref()).collect();
+        self.bits_mut().or_all(&bit_sets);
+        self.inserted += others.iter().map(|o| o.inserted).sum::<u64>();
+        self.capacity_check.reset();
+        Ok(())
+    }
+
+    /// Performs the intersection operation on two compatible bloom filters. This is achieved
+    /// through a bitwise AND operation on their bit vectors. The operations doesn't introduce
+    /// any false negatives but it does raise the false positive probability. The the false
+    /// positive probability in the resulting Bloom filter is at most the false-positive probability
+    /// in one of the constituent bloom filters
+    ///
+    /// Returns `false` if the filters are not compatible, see [BloomFilter::try_intersect].
+    pub fn intersect(&mut self, other: &BloomFilter) -> bool {
+        self.try_intersect(other).is_ok()
+    }
+
+    /// Performs the intersection operation like [BloomFilter::intersect], returning which
+    /// parameter doesn't match if the filters are not compatible.
+    ///
+    /// The exact number of insertions of the result is unknown, so its [BloomFilter::len] becomes
+    /// [BloomFilter::estimate_element_count].
+    pub fn try_intersect(&mut self, other: &BloomFilter) -> Result<(), IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        self.bits_mut().and(&other.bit_set);
+        self.inserted = self.estimate_element_count();
+        self.capacity_check.reset();
+        Ok(())
+    }
+
+    /// Performs a bitwise XOR of the bit vectors of two compatible bloom filters.
+    ///
+    /// Note that the result is **not** a Bloom filter of the symmetric difference of the two
+    /// sets: querying it gives meaningless answers. It is a raw bit diff, useful to sync two
+    /// snapshots of a filter or for telemetry (e.g. `count_ones` of the diff).
+    ///
+    /// Returns `false` if the filters are not compatible, see
+    /// [BloomFilter::try_symmetric_difference].
+    pub fn symmetric_difference(&mut self, other: &BloomFilter) -> bool {
+        self.try_symmetric_difference(other).is_ok()
+    }
+
+    /// Performs the XOR operation like [BloomFilter::symmetric_difference], returning which
+    /// parameter doesn't match if the filters are not compatible.
+    ///
+    /// The [BloomFilter::len] of the result becomes [BloomFilter::estimate_element_count].
+    pub fn try_symmetric_difference(&mut self, other: &BloomFilter) -> Result<(), IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        self.bits_mut().xor(&other.bit_set);
+        self.inserted = self.estimate_element_count();
+        self.capacity_check.reset();
+        Ok(())
+    }
+
+    /// Returns the XOR of two compatible bloom filters as a new filter, leaving both operands
+    /// untouched. See [BloomFilter::symmetric_difference].
+    pub fn symmetric_difference_of(&self, other: &BloomFilter) -> Result<BloomFilter, IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        let mut res = self.clone();
+        res.try_symmetric_difference(other)?;
+        Ok(res)
+    }
+
+    /// Checks whether every bit set in this filter is also set in `other`, which means the
+    /// elements of this filter are (probabilistically) a subset of the elements of `other`.
+    pub fn is_subset_of(&self, other: &BloomFilter) -> Result<bool, IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        Ok(self.bit_set.is_subset(&other.bit_set))
+    }
+
+    /// Checks whether every bit set in `other` is also set in this filter, see
+    /// [BloomFilter::is_subset_of].
+    pub fn is_superset_of(&self, other: &BloomFilter) -> Result<bool, IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        Ok(other.bit_set.is_subset(&self.bit_set))
+    }
+
+    /// Estimates the Jaccard similarity `|A ∩ B| / |A ∪ B|` of the sets of two compatible filters
+    /// without exchanging any element.
+    ///
+    /// The cardinalities of A, B and A ∪ B are estimated from the set bits of A, B and A | B
+    /// (see [BloomFilter::estimate_element_count]) and `|A ∩ B| = |A| + |B| - |A ∪ B|`. The union
+    /// is counted word by word without building it. The result is clamped to `[0.0, 1.0]`; it is
+    /// 1.0 for identical filters (including two empty ones) and close to 0.0 for disjoint ones.
+    pub fn jaccard_estimate(&self, other: &BloomFilter) -> Result<f64, IncompatibleFilterError> {
+        let (a, b, union) = self.estimate_cardinalities(other)?;
+        if union == 0.0 { return Ok(1.0); }
+        let intersection = a + b - union;
+        Ok((intersection / union).clamp(0.0, 1.0))
+    }
+
+    /// Estimates the number of elements in both filters, `|A ∩ B| = |A| + |B| - |A ∪ B|`, from the
+    /// set bits of A, B and A | B. The union is counted on the fly without modifying either
+    /// filter. The estimate is clamped at 0, disjoint filters give a value close to 0.
+    pub fn estimate_intersection_count(&self, other: &BloomFilter) -> Result<u64, IncompatibleFilterError> {
+        let (a, b, union) = self.estimate_cardinalities(other)?;
+        Ok((a + b - union).max(0.0).round() as u64)
+    }
+
+    /// Estimates the number of elements in this filter but not in `other`,
+    /// `|A \ B| = |A ∪ B| - |B|`, from the set bits of B and A | B. The union is counted block by
+    /// block without allocating a temporary filter. The estimate is clamped at 0.
+    pub fn estimate_difference_count(&self, other: &BloomFilter) -> Result<u64, IncompatibleFilterError> {
+        let (_, b, union) = self.estimate_cardinalities(other)?;
+        Ok((union - b).max(0.0).round() as u64)
+    }
+
+    /// Estimates the cardinalities of `self`, `other` and their union.
+    fn estimate_cardinalities(&self, other: &BloomFilter) -> Result<(f64, f64, f64), IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        let m = self.config.size;
+        let k = self.config.hashes;
+        Ok((estimate_from_ones(self.bit_set.count_ones(), m, k),
+            estimate_from_ones(other.bit_set.count_ones(), m, k),
+            estimate_from_ones(self.bit_set.count_ones_or(&other.bit_set), m, k)))
+    }
+
+    /// Returns the union of two compatible bloom filters as a new filter, leaving both operands
+    /// untouched. See [BloomFilter::try_union].
+    pub fn union_of(&self, other: &BloomFilter) -> Result<BloomFilter, IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        let mut res = self.clone();
+        res.try_union(other)?;
+        Ok(res)
+    }
+
+    /// Returns the intersection of two compatible bloom filters as a new filter, leaving both
+    /// operands untouched. See [BloomFilter::try_intersect].
+    pub fn intersect_of(&self, other: &BloomFilter) -> Result<BloomFilter, IncompatibleFilterError> {
+        self.config.check_compatible(&other.config)?;
+        let mut res = self.clone();
+        res.try_intersect(other)?;
+        Ok(res)
+    }
+
+    /// Returns `true` if the filter can be folded by `factor`, see [BloomFilter::fold].
+    pub fn can_fold(&self, factor: u32) -> bool {
+        self.check_fold(factor).is_ok()
+    }
+
+    fn check_fold(&self, factor: u32) -> Result<(), FoldError> {
+        if factor == 0 {
+            return Err(FoldError::ZeroFactor);
+        }
+        self.check_foldable()?;
+        let size = self.config.size;
+        if !size.is_multiple_of(factor as u64) || size < factor as u64 {
+            return Err(FoldError::NotDivisible { size, factor });
+        }
+        let folded = size / factor as u64;
+        if self.config.hash_version == 3 && factor > 1 && folded > 1 && !folded.is_multiple_of(2) {
+            return Err(FoldError::OddSize { size: folded });
+        }
+        Ok(())
+    }
+
+    /// Shrinks the filter to `size / factor` bits by ORing its `factor` slices of that size onto
+    /// each other, e.g. to ship a smaller version of an over-provisioned filter.
+    ///
+    /// The bit indices of an element are computed modulo the size, so when the new size divides
+    /// the old one the index of an element in the folded filter is its old index modulo the new
+    /// size. Every element of the filter is thus still contained in the folded filter, no false
+    /// negatives are introduced, but the false positive probability grows as the same elements
+    /// share fewer bits. Any size divisible by `factor` works, which includes every power-of-two
+    /// size folded by a power-of-two factor smaller than it. Filters of hash version 2, whose
+    /// indices don't stay the same modulo a smaller size, fail with [FoldError::HashVersion],
+    /// filters of hash version 3 folded to an odd size with [FoldError::OddSize], and filters
+    /// of the Guava, RedisBloom and PyBloom [ProbeScheme]s with [FoldError::ProbeScheme].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(1 << 20, 7).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let folded = bloom.fold(4).unwrap();
+    /// assert_eq!(folded.config().size, 1 << 18);
+    /// assert!(folded.contains(b"hello"));
+    /// ```
+    pub fn fold(&self, factor: u32) -> Result<BloomFilter, FoldError> {
+        self.check_fold(factor)?;
+        Ok(self.fold_to(self.config.size / factor as u64))
+    }
+
+    /// Fails if the bit indices of the elements don't stay the same modulo a smaller size.
+    fn check_foldable(&self) -> Result<(), FoldError> {
+        if self.config.hash_version == 2 {
+            return Err(FoldError::HashVersion { version: 2 });
+        }
+        match self.config.probe_scheme {
+            scheme @ (ProbeScheme::Guava | ProbeScheme::RedisBloom | ProbeScheme::PyBloom) =>
+                Err(FoldError::ProbeScheme { scheme }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Folds the filter to `size` bits, which must divide the size of the filter.
+    fn fold_to(&self, size: u64) -> BloomFilter {
+        let mut res = BloomFilter::from_bit_vec(self.bit_set.fold(size), self.config.hashes);
+        res.config.seed = self.config.seed;
+        res.config.hasher = self.config.hasher.clone();
+        res.config.hash_version = self.config.hash_version;
+        res.config.probe_scheme = self.config.probe_scheme;
+        res.inserted = self.inserted;
+        res.capacity_check.interval = self.capacity_check.interval;
+        res
+    }
+
+    /// Returns a hash of the configuration and the bits of the filter, e.g. to detect drift
+    /// between replicas without transferring the bits.
+    ///
+    /// The bits are hashed as the little endian bytes of the `u64` words of
+    /// [BloomFilter::to_u64_vec] after [FilterBuilder::fingerprint], so it is the same on every
+    /// platform and changes with any bit or any parameter the compatibility check compares. The
+    /// [BloomFilter::len] counter is not included. It reads the whole filter once, at the speed
+    /// of xxh3.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Xxh3Default::new();
+        self.config.write_fingerprint(&mut hasher);
+        let mut buf = [0u8; 8 * 512];
+        let len = self.bit_set.u64_len();
+        for start in (0..len).step_by(512) {
+            let end = min(start + 512, len);
+            for (i, chunk) in (start..end).zip(buf.chunks_exact_mut(8)) {
+                chunk.copy_from_slice(&self.bit_set.u64_word(i).to_le_bytes());
+            }
+            hasher.update(&buf[..(end - start) * 8]);
+        }
+        hasher.digest()
+    }
+
+    /// Returns the number of insert operations (`add`, `add_all`, `contains_then_add`, ...)
+    /// performed on the filter since it was created or last cleared.
+    ///
+    /// This counts calls, not distinct elements: adding the same element twice counts twice. Use
+    /// [BloomFilter::estimate_element_count] for the number of distinct elements. Filters built
+    /// from raw arrays start at 0.
+    pub fn len(&self) -> u64 {
+        self.inserted
+    }
+
+    /// Returns the number of bits set to one in the filter.
+    pub fn count_ones(&self) -> u64 {
+        self.bit_set.count_ones()
+    }
+
+    /// Returns the `hashes` bit positions of `element`, in the order and exactly as
+    /// [Membership::add] and [Membership::contains] compute them. This is the same as
+    /// [Membership::get_hash_indices] without a heap allocation for up to 8 hashes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let indices = bloom.hash_indices(b"hello");
+    /// assert_eq!(indices.to_vec(), bloom.get_hash_indices(b"hello"));
+    /// ```
+    pub fn hash_indices(&self, element: &[u8]) -> SmallVec<[u64; 8]> {
+        bit_indices(element, self.config.size, self.config.hashes as u64, self.config.probe())
+            .collect()
+    }
+
+    /// Iterates the positions of the bits set to one in ascending order. The backing words are
+    /// walked one at a time, so sparse filters are iterated quickly however large they are.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let bits: Vec<u64> = bloom.iter_set_bits().collect();
+    /// assert_eq!(bits.len() as u64, bloom.count_ones());
+    /// ```
+    pub fn iter_set_bits(&self) -> impl Iterator<Item=u64> + '_ {
+        self.bit_set.iter_ones()
+    }
+
+    /// Returns the fraction of bits set to one in the filter, i.e. `count_ones / size`.
+    pub fn fill_ratio(&self) -> f64 {
+        self.count_ones() as f64 / self.config.size as f64
+    }
+
+    /// Estimates the current false positive probability from the actual bit occupancy as
+    /// `fill_ratio ^ hashes`. Unlike the configured `false_positive_probability` this stays
+    /// accurate when the filter is filled with more (or fewer) elements than expected.
+    pub fn estimate_current_fpp(&self) -> f64 {
+        self.fill_ratio().powi(self.config.hashes as i32)
+    }
+
+    /// The theoretical false positive probability of the filter once `inserted` distinct
+    /// elements are added, from its size and hashes only, ignoring the current contents. See
+    /// [FilterBuilder::expected_fpp_for].
+    pub fn expected_fpp_for(&self, inserted: u64) -> f64 {
+        self.config.expected_fpp_for(inserted)
+    }
+
+    /// Estimates the number of distinct elements inserted into the filter from the number of set
+    /// bits `X` as `-(m / k) * ln(1 - X / m)`.
+    ///
+    /// Returns 0 for an empty filter. When every bit is set the real count can't be known, the
+    /// estimate then saturates at the value for `m - 1` set bits instead of going to infinity.
+    pub fn estimate_element_count(&self) -> u64 {
+        estimate_count_from_ones(self.count_ones(), self.config.size, self.config.hashes)
+    }
+
+    /// Returns `true` if the estimated current false positive probability (see
+    /// [BloomFilter::estimate_current_fpp]) is larger than `max_fpp`.
+    pub fn is_saturated(&self, max_fpp: f64) -> bool {
+        self.estimate_current_fpp() > max_fpp
+    }
+
+    /// Estimates how many more distinct elements can be inserted before the estimated false
+    /// positive probability exceeds the configured `false_positive_probability`.
+    ///
+    /// The false positive probability is `f ^ k` for a fill ratio `f`, so the limit is reached at
+    /// `f_max = p ^ (1 / k)`. A fill ratio `f` corresponds to `-(m / k) * ln(1 - f)` inserted
+    /// elements and the result is the difference between the counts for `f_max` and the current
+    /// fill ratio, or 0 if the limit is already exceeded.
+    pub fn capacity_remaining(&self) -> u64 {
+        let m = self.config.size as f64;
+        let k = self.config.hashes as f64;
+        let max_fill = self.config.false_positive_probability.powf(1.0 / k);
+        let capacity = -(m / k) * (1.0 - max_fill).ln();
+        let current = -(m / k) * (1.0 - self.fill_ratio()).ln();
+        if current >= capacity { 0 } else { (capacity - current) as u64 }
+    }
+
+    /// Returns [true] if the Bloom filter does not contain any elements
+    pub fn is_empty(&self) -> bool {
+        self.bit_set.is_empty()
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn set_bit_vec(&mut self, bit_vec: BloomBitVec) {
+        assert_eq!(self.config.size, bit_vec.nbits);
+        self.bit_set = Arc::new(bit_vec)
+    }
+
+    /// Checks if two Bloom filters are compatible, i.e. have compatible parameters (hash function,
+    /// size, etc.)
+    pub fn compatible(&self, other: &BloomFilter) -> bool {
+        self.config.is_compatible_to(&other.config)
+    }
+
+    /// Reports every parameter in which the filters differ, see
+    /// [FilterBuilder::compatibility_report].
+    pub fn compatibility_report(&self, other: &BloomFilter) -> CompatibilityReport {
+        self.config.compatibility_report(&other.config)
+    }
+}
+
+/// A Counting Bloom filter works in a similar manner as a regular Bloom filter; however, it is
+/// able to keep track of insertions and deletions. In a counting Bloom filter, each entry in the
+/// Bloom filter is a small counter associated with a basic Bloom filter bit.
+///
+/// It is built from the same [FilterBuilder] as a [BloomFilter] and derives the same indices
+/// for an element, so the parameters are shared: the counters of the indices are incremented by
+/// `add` and decremented by `remove`. Removing an element which was added restores the counters
+/// to their state before it was added, so the other elements stay present.
+///
+/// The counters are 4 bits wide by default, 16 of them in a `u64` word (8 in a `usize` on 32-bit
+/// targets), the first one in the 4 most significant bits: counter `i` is bits
+/// `(15 - i % 16) * 4` to `(15 - i % 16) * 4 + 3` of word `i / 16`. A filter of `size` counters
+/// takes `size / 2` bytes, rounded up to whole words, and [CountingBloomFilter::get_u64_array]
+/// exposes the words. Counters of 8 and 16 bits, see [FilterBuilder::with_counter_size], are
+/// laid out the same way, 8 or 4 of them in a word.
+///
+/// A counter saturates at its maximum, 15 for 4 bits, instead of wrapping to zero: further
+/// increments are lost and counted, see [CountingBloomFilter::overflow_count], and `remove`
+/// doesn't decrement it anymore, since it can't tell how many elements are left on it. Such a
+/// stuck counter behaves like a bit of a plain Bloom filter: no element on it gets a false
+/// negative, but the elements removed from it stay set, which raises the false positive
+/// probability. Overflows mean the counters are too narrow for the workload.
+///
+/// **Reference**: F. Bonomi, M. Mitzenmacher, R. Panigrahy, S. Singh, and G. Varghese, “An Improved
+/// Construction for Counting Bloom Filters,” in 14th Annual European Symposium on
+/// Algorithms, LNCS 4168, 2006
+#[derive(Clone)]
+#[derive(Debug)]
+pub struct CountingBloomFilter {
+    config: FilterBuilder,
+    counting_vec: CountingVec,
+}
+
+macro_rules! get_array {
+    ($name:ident, $native:ty, $len:expr) => {
+        impl CountingBloomFilter {
+            pub fn $name(&self) -> &[$native] {
+                let ptr = self.counting_vec.storage.as_ptr() as *const $native;
+                #[cfg(target_pointer_width = "64")]
+                    let arr = slice_from_raw_parts(ptr, self.counting_vec.storage.len() * $len);
+                #[cfg(target_pointer_width = "32")]
+                    if cfg!(target_pointer_width= "32") {
+                        if self.counting_vec.storage.len() % 2 != 0 {
+                            panic!("CountingVec with len {} can't export as u64 array!", self.counting_vec.storage.len())
+                        }
+                    }
+                #[cfg(target_pointer_width = "32")]
+                    let arr = slice_from_raw_parts(ptr, self.counting_vec.storage.len() * $len / 2);
+                unsafe { &*arr }
+            }
+        }
+    };
+}
+
+get_array!(get_u8_array, u8, 8);
+get_array!(get_u16_array, u16, 4);
+get_array!(get_u32_array, u32, 2);
+get_array!(get_u64_array, u64, 1);
+
+impl CountingBloomFilter {
+    pub fn new(mut config: FilterBuilder) -> Self {
+        config.complete();
+        let slots = CountingVec::slots(config.size, config.counter_size);
+        let counting_vec = CountingVec::new(slots, config.counter_size);
+        CountingBloomFilter { config, counting_vec }
+    }
+
+    /// The number of increments which were lost since their counter was at its maximum, since
+    /// the filter was built or cleared.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{Deletable, FilterBuilder, Membership};
+    ///
+    /// let mut cbf = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter();
+    /// for _ in 0..16 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// assert!(cbf.has_overflowed());
+    /// for _ in 0..16 {
+    ///     cbf.remove(b"hello");
+    /// }
+    /// // stuck at 15
+    /// assert!(cbf.contains(b"hello"));
+    /// ```
+    pub fn overflow_count(&self) -> u64 {
+        self.counting_vec.overflows
+    }
+
+    /// Whether an increment was lost, see [CountingBloomFilter::overflow_count].
+    pub fn has_overflowed(&self) -> bool {
+        self.counting_vec.overflows > 0
+    }
+
+    /// Adds the counters of `other` to the counters of this filter, e.g. to merge the filters of
+    /// several workers. A sum above [CountingBloomFilter::max_count] saturates at it and counts
+    /// as overflows, and the overflows of `other` are added as well. Afterwards the filter
+    /// contains every element either filter contained, and the count of an element added to both
+    /// about adds up.
+    ///
+    /// Returns an error if the filters are not compatible, including the widths of their
+    /// counters, see [CountingBloomFilter::compatibility_report].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// let mut left = builder.build_counting_bloom_filter();
+    /// let mut right = builder.build_counting_bloom_filter();
+    /// left.add(b"hello");
+    /// right.add(b"hello");
+    /// right.add(b"world");
+    /// left.union(&right).unwrap();
+    /// assert_eq!(left.estimate_count(b"hello"), 2);
+    /// assert!(left.contains(b"world"));
+    /// ```
+    pub fn union(&mut self, other: &CountingBloomFilter) -> Result<(), IncompatibleFilterError> {
+        if let Some(e) = self.compatibility_report(other).first_mismatch() {
+            return Err(e);
+        }
+        let max = self.counting_vec.max();
+        for index in 0..self.counting_vec.counters as usize {
+            let sum = self.counting_vec.get(index) + other.counting_vec.get(index);
+            if sum > max {
+                self.counting_vec.overflows += (sum - max) as u64;
+            }
+            self.counting_vec.set(index, sum.min(max));
+        }
+        self.counting_vec.overflows += other.counting_vec.overflows;
+        Ok(())
+    }
+
+    /// Keeps the smaller of the two counters at every index, which approximates the multiset
+    /// intersection: no count gets larger than in either filter, an element added to both stays
+    /// present and an element added to only one of them is gone, unless other elements collide
+    /// with all its counters in the other filter.
+    ///
+    /// Collisions only inflate counters, so the minima are upper bounds of the counts in the
+    /// intersection and are off by as much as the counters of the elements not in it overlap.
+    /// Removing from the result what was added to only one filter can therefore still make
+    /// elements of the intersection absent. The overflow count becomes the smaller of the two,
+    /// as counters stay saturated only where both were.
+    ///
+    /// Returns an error if the filters are not compatible, including the widths of their
+    /// counters, see [CountingBloomFilter::compatibility_report].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// let mut left = builder.build_counting_bloom_filter();
+    /// let mut right = builder.build_counting_bloom_filter();
+    /// left.add(b"hello");
+    /// left.add(b"hello");
+    /// right.add(b"hello");
+    /// right.add(b"world");
+    /// left.intersect(&right).unwrap();
+    /// assert_eq!(left.estimate_count(b"hello"), 1);
+    /// assert!(!left.contains(b"world"));
+    /// ```
+    pub fn intersect(
+        &mut self,
+        other: &CountingBloomFilter,
+    ) -> Result<(), IncompatibleFilterError> {
+        if let Some(e) = self.compatibility_report(other).first_mismatch() {
+            return Err(e);
+        }
+        for index in 0..self.counting_vec.counters as usize {
+            let min = self.counting_vec.get(index).min(other.counting_vec.get(index));
+            self.counting_vec.set(index, min);
+        }
+        self.counting_vec.overflows = self.counting_vec.overflows.min(other.counting_vec.overflows);
+        Ok(())
+    }
+
+    /// A plain filter with a bit set wherever a counter isn't zero, e.g. to distribute the result
+    /// of a build with removals in an eighth of the memory of 4 bit counters. It has the
+    /// configuration of this filter, so it is compatible with the plain filters of the same
+    /// builder, answers [Membership::contains] like this filter does now, and has a
+    /// [BloomFilter::len] of 0.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// let mut counting = builder.build_counting_bloom_filter();
+    /// counting.add(b"hello");
+    /// counting.add(b"world");
+    /// counting.remove(b"world");
+    /// let bloom = counting.to_bloom_filter();
+    /// assert!(bloom.contains(b"hello"));
+    /// assert!(bloom.compatible(&builder.build_bloom_filter()));
+    /// ```
+    pub fn to_bloom_filter(&self) -> BloomFilter {
+        let mut bit_set = BloomBitVec::with_bits(self.config.size);
+        for index in 0..self.config.size as usize {
+            if self.counting_vec.get(index) != 0 {
+                bit_set.set(index);
+            }
+        }
+        BloomFilter::from_parts(self.config.clone(), bit_set)
+    }
+
+    /// The memory the counters take in bytes, see [FilterBuilder::with_counter_size].
+    pub fn memory_bytes(&self) -> usize {
+        self.counting_vec.storage.len() * std::mem::size_of::<usize>()
+    }
+
+    /// Reports every parameter in which the filters differ, see
+    /// [FilterBuilder::compatibility_report], and the widths of their counters.
+    pub fn compatibility_report(&self, other: &CountingBloomFilter) -> CompatibilityReport {
+        let mut report = self.config.compatibility_report(&other.config);
+        if self.config.counter_size != other.config.counter_size {
+            report.counter_size = Some((self.config.counter_size, other.config.counter_size));
+        }
+        report
+    }
+
+    #[allow(dead_code)]
+    pub(crate) fn set_counting_vec(&mut self, counting_vec: CountingVec) {
+        assert_eq!(self.config.size, counting_vec.counters);
+        self.counting_vec = counting_vec
+    }
+
+    /// Checks if two Counting Bloom filters are compatible, i.e. have compatible parameters (hash
+    /// function, size, etc.)
+    #[allow(dead_code)]
+    fn compatible(&self, other: &BloomFilter) -> bool {
+        self.config.is_compatible_to(&other.config)
+    }
+
+    /// Returns the configuration/builder of the Bloom filter.
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
+    ///
+    /// let bloom = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
+    /// let builder = bloom.config();
+    /// ```
+    ///
+    pub fn config(&self) -> FilterBuilder {
+        self.config.clone()
+    }
+}
+
+macro_rules! from_array {
+    ($name:ident, $native:ty, $num:expr) => {
+        impl CountingBloomFilter {
+            /// Build a Counting Bloom filter from the counters of the same `get_*_array`, read as
+            /// [crate::CounterSize::U4] counters since the array doesn't say how wide they are.
+            /// Filters with other widths are read with
+            /// [CountingBloomFilter::from_u8_array_with_config].
+            pub fn $name(array: &[$native], hashes: u32, enable_repeat_insert:bool) -> Self {
+                let size = (array.len() * $num) as u64;
+                let mut config = FilterBuilder::from_size_and_hashes(size, hashes);
+                config.enable_repeat_insert(enable_repeat_insert);
+                let bytes = unsafe {
+                    std::slice::from_raw_parts(array.as_ptr() as *const u8,
+                                               std::mem::size_of_val(array))
+                };
+                CountingBloomFilter::from_raw_bytes(bytes, config)
+            }
+        }
+    };
+}
+
+from_array!(from_u8_array, u8, 2);
+from_array!(from_u16_array, u16, 4);
+from_array!(from_u32_array, u32, 8);
+from_array!(from_u64_array, u64, 16);
+
+impl CountingBloomFilter {
+    /// Build a Counting Bloom filter with the configuration `config` form `&[u8]` with the layout
+    /// of [CountingBloomFilter::get_u8_array]. Unlike [CountingBloomFilter::from_u8_array], which
+    /// reads 4-bit counters with the default hashing, this rebuilds filters of any configuration,
+    /// e.g. with [crate::CounterSize::U8] or [crate::CounterSize::U16] counters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{CounterSize, CountingBloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut cbf = FilterBuilder::new(1000, 0.01).counting(CounterSize::U16);
+    /// cbf.add(b"hello");
+    /// let copy = CountingBloomFilter::from_u8_array_with_config(cbf.get_u8_array(), cbf.config());
+    /// assert_eq!(copy.estimate_count(b"hello"), 1);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `array` is too short for the counters of `config`.
+    pub fn from_u8_array_with_config(array: &[u8], config: FilterBuilder) -> Self {
+        let mut complete = config.clone();
+        complete.complete();
+        let len = CountingVec::slots(complete.size, complete.counter_size)
+            * std::mem::size_of::<usize>();
+        assert!(array.len() >= len, "u8 array with len {} is too short for {} bytes of counters!",
+                array.len(), len);
+        CountingBloomFilter::from_raw_bytes(array, config)
+    }
+
+    /// Builds a filter whose counters start with the native endian `bytes`, the layout of
+    /// [CountingBloomFilter::get_u8_array]. The bytes are copied, so they don't need to be
+    /// aligned like the words of the storage.
+    fn from_raw_bytes(bytes: &[u8], config: FilterBuilder) -> Self {
+        let mut cbf = CountingBloomFilter::new(config);
+        let storage = &mut cbf.counting_vec.storage;
+        let len = min(bytes.len(), storage.len() * std::mem::size_of::<usize>());
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), storage.as_mut_ptr() as *mut u8, len);
+        }
+        cbf
+    }
+
+    /// Get the estimate count for element in this counting bloom filter.
+    /// See: https://github.com/yankun1992/fastbloom/issues/3
+    ///
+    /// The estimate is the minimum of the counters of the element, so it is 0 if the element
+    /// is absent and otherwise at least the number of times it was added, as long as none of
+    /// its counters saturated: collisions with other elements only make it larger, and in a
+    /// sparsely loaded filter it is usually exact. It is at most
+    /// [CountingBloomFilter::max_count], which means at least that many times, as the
+    /// increments beyond it were lost.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut cbf = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter();
+    /// for _ in 0..3 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// assert_eq!(cbf.estimate_count(b"hello"), 3);
+    /// assert_eq!(cbf.estimate_count(b"world"), 0);
+    /// for _ in 0..20 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// assert_eq!(cbf.estimate_count(b"hello"), cbf.max_count());
+    /// ```
+    pub fn estimate_count(&self, element: &[u8]) -> usize {
+        let mut res = usize::MAX;
+        for index in self.bit_indices(element) {
+            let count = self.counting_vec.get(index as usize);
+            if count == 0 { return 0; } else { res = min(count, res) }
+        }
+        res
+    }
+
+    /// The largest count of a counter, 15 for 4-bit counters, see
+    /// [FilterBuilder::with_counter_size].
+    pub fn max_count(&self) -> usize {
+        self.counting_vec.max()
+    }
+
+    /// Removes `element` if it is contained and returns whether it was. Elements which were
+    /// never added are mostly not contained, so their removal changes nothing and the elements
+    /// sharing their counters stay present. Only a false positive is removed although it was
+    /// never added, and takes a count from the elements it collides with.
+    ///
+    /// The counters are decremented down to zero and not below, and counters at their maximum
+    /// are not decremented, see [CountingBloomFilter].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut cbf = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter();
+    /// cbf.add(b"hello");
+    /// assert!(!cbf.remove(b"world"));
+    /// assert!(cbf.remove(b"hello"));
+    /// assert!(!cbf.contains(b"hello"));
+    /// ```
+    pub fn remove(&mut self, element: &[u8]) -> bool {
+        if !self.contains(element) {
+            return false;
+        }
+        self.remove_unchecked(element);
+        true
+    }
+
+    /// Removes `element` without checking that it is contained, for callers which know it was
+    /// added, saving the lookup. Removing an element which was never added this way takes
+    /// counts from other elements and can give them false negatives.
+    pub fn remove_unchecked(&mut self, element: &[u8]) {
+        for index in self.bit_indices(element) {
+            self.counting_vec.decrement(index as usize);
+        }
+    }
+
+    /// Get the underlying counter at index.
+    pub fn counter_at(&self, index: u64) -> usize {
+        self.counting_vec.get(index as usize)
+    }
+
+    #[inline]
+    fn bit_indices(&self, element: &[u8]) -> ProbeSequence {
+        bit_indices(element, self.config.size, self.config.hashes as u64, self.config.probe())
+    }
+}
+
+impl Membership for CountingBloomFilter {
+    fn add(&mut self, element: &[u8]) {
+        let indices = self.bit_indices(element);
+
+        // contains and not enable repeat insert
+        if !self.config.enable_repeat_insert
+            && indices.clone().all(|index| self.counting_vec.get(index as usize) > 0) {
+            return;
+        }
+
+        // insert
+        for index in indices {
+            self.counting_vec.increment(index as usize);
+        }
+    }
+
+    #[inline]
+    fn contains(&self, element: &[u8]) -> bool {
+        self.bit_indices(element).all(|index| self.counting_vec.get(index as usize) > 0)
+    }
+
+    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
+        self.bit_indices(element).collect()
+    }
+
+    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
+        for x in indices.iter() {
+            let index = *x;
+            if self.counting_vec.get(index as usize) == 0 { return false; }
+        }
+        true
+    }
+
+    fn clear(&mut self) {
+        self.counting_vec.clear()
+    }
+
+    /// Estimates the current false positive probability as `f ^ hashes` where `f` is the
+    /// fraction of counters which are not zero.
+    fn estimated_fpp(&self) -> f64 {
+        let nonzero = self.counting_vec.count_nonzero() as f64;
+        (nonzero / self.config.size as f64).powi(self.config.hashes as i32)
+    }
+}
+
+impl Deletable for CountingBloomFilter {
+    fn remove(&mut self, element: &[u8]) {
+        CountingBloomFilter::remove(self, element);
+    }
+}
+
+impl Hashes for CountingBloomFilter {
+    fn hashes(&self) -> u32 {
+        self.config.hashes
+    }
+}
+
+/// A Partitioned Bloom Filter is a variation of a classic Bloom Filter.
+///
+/// This filter works by partitioning the M-sized bit array into k slices of size `m = M/k` bits,
+/// `k = nb of hash functions` in the filter. Each hash function produces an index over `m` for its
+/// respective slice. Thus, each element is described by exactly `k` bits, meaning the distribution
+/// of false positives is uniform across all elements.
+///
+/// Be careful, as a Partitioned Bloom Filter have much higher collison risks that a classic
+/// Bloom Filter on small sets of data.
+///
+/// **Reference**: Chang, F., Feng, W. C., & Li, K. (2004, March). Approximate caches for packet
+/// classification. In INFOCOM 2004. Twenty-third AnnualJoint Conference of the IEEE Computer and
+/// Communications Societies (Vol. 4, pp. 2196-2207). IEEE.
+/// [Full text article](http://citeseerx.ist.psu.edu/viewdoc/download?doi=10.1.1.153.6902&rep=rep1&type=pdf)
+#[derive(Clone)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub(crate) struct PartitionedBloomFilter {}
+
+impl PartitionedBloomFilter {}
+
+/// A Scalable Bloom Filter is a variant of Bloom Filters that can adapt dynamically to the number
+/// of elements stored, while assuring a maximum false positive probability.
+///
+/// **Reference**: ALMEIDA, Paulo Sérgio, BAQUERO, Carlos, PREGUIÇA, Nuno, et al. Scalable bloom
+/// filters. Information Processing Letters, 2007, vol. 101, no 6, p. 255-261.
+/// [Full text article](https://citeseerx.ist.psu.edu/viewdoc/download?doi=10.1.1.725.390&rep=rep1&type=pdf)
+#[derive(Clone)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub(crate) struct ScalableBloomFilter {}
+
+impl ScalableBloomFilter {}
+
+/// An Invertible Bloom Filters (IBLT), also called Invertible Bloom Lookup Table, is a
+/// space-efficient and probabilistic data-structure for solving the set-difference problem
+/// efficiently without the use of logs or other prior context. It computes the set difference
+/// with communication proportional to the size of the difference between the sets being compared.
+/// They can simultaneously calculate D(A−B) and D(B−A) using O(d) space. This data structure
+/// encodes sets in a fashion that is similar in spirit to Tornado codes’ construction, in that it
+/// randomly combines elements using the XOR function.
+///
+/// **Reference**: Eppstein, D., Goodrich, M. T., Uyeda, F., & Varghese, G. (2011). What's the
+/// difference?: efficient set reconciliation without prior context. ACM SIGCOMM Computer
+/// Communication Review, 41(4), 218-229.
+/// [Full text article](http://www.sysnet.ucsd.edu/sysnet/miscpapers/EppGooUye-SIGCOMM-11.pdf)
+#[derive(Clone)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub(crate) struct InvertibleBloomFilter {}
+
+impl InvertibleBloomFilter {}
+
+#[derive(Clone)]
+#[derive(Debug)]
+#[allow(dead_code)]
+pub(crate) struct GarbledBloomFilter {}
+
+impl GarbledBloomFilter {}
+
+
+#[test]
+fn bloom_test() {
+    let mut builder =
+        FilterBuilder::new(10_000_000, 0.01);
+    let mut bloom = builder.build_bloom_filter();
+    println!("{:?}", bloom.config);
+    bloom.add(b"hello");
+    println!("{:?}", &bloom.bit_set.storage[0..300]);
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.contains(b"world"), false);
+    assert_eq!(bloom.add_if_not_contains(b"hello2"), false);
+    assert_eq!(bloom.contains(b"hello2"), true);
+
+    let storage = &bloom.bit_set.storage[0..300];
+    println!("{:?}", storage);
+
+    #[cfg(target_pointer_width = "64")]{
+        let bloom2 = BloomFilter::from_u64_array(bloom.get_u64_array(), bloom.hashes());
+        assert_eq!(bloom2.compatible(&bloom), true);
+        assert_eq!(bloom2.contains(b"hello"), true);
+        assert_eq!(bloom2.contains(b"world"), false);
+    }
+
+    let mut bloom3 =
+        BloomFilter::from_u32_array(bloom.get_u32_array(), bloom.config.hashes);
+    assert_eq!(bloom3.compatible(&bloom), true);
+    assert_eq!(bloom3.contains(b"hello"), true);
+    assert_eq!(bloom3.contains(b"world"), false);
+
+    let u8_array = bloom.get_u8_array();
+    let mut bloom4 = BloomFilter::from_u8_array(u8_array, bloom.config.hashes);
+    println!("{:?}", &bloom4.bit_set.storage[0..300]);
+    assert_eq!(bloom4.compatible(&bloom), true);
+    assert_eq!(bloom4.contains(b"hello"), true);
+    assert_eq!(bloom4.contains(b"world"), false);
+
+    let bloom5 = BloomFilter::from_u16_array(bloom.get_u16_array(), bloom.hashes());
+    assert_eq!(bloom5.compatible(&bloom), true);
+    assert_eq!(bloom5.contains(b"hello"), true);
+    assert_eq!(bloom5.contains(b"world"), false);
+
+    bloom4.add(b"hello world");
+
+    assert_eq!(bloom.intersect(&bloom4), true);
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.contains(b"hello world"), false);
+
+    bloom3.add(b"hello world");
+    bloom3.add(b"hello yankun");
+
+    assert_eq!(bloom3.union(&bloom4), true);
+    assert_eq!(bloom3.contains(b"hello"), true);
+    assert_eq!(bloom3.contains(b"hello world"), true);
+    assert_eq!(bloom3.contains(b"hello yankun"), true);
+}
+
+#[test]
+fn bloom_hash_indices_test() {
+    let mut builder =
+        FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_bloom_filter();
+    println!("{:?}", bloom.config);
+    bloom.add(b"hello");
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.contains(b"world"), false);
+
+    let indices = bloom.get_hash_indices(b"hello");
+    println!("{:?}", indices);
+    assert_eq!(bloom.contains_hash_indices(&indices), true);
+    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"world")), false);
+}
+
+
+#[test]
+fn counting_bloom_test() {
+    let mut builder =
+        FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_counting_bloom_filter();
+
+    bloom.add(b"hello");
+
+    assert_eq!(bloom.contains(b"hello"), true);
+
+    bloom.remove(b"hello");
+    assert_eq!(bloom.contains(b"hello"), false);
+}
+
+#[test]
+fn counting_bloom_repeat_test() {
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    // enable_repeat_insert is true
+    builder.enable_repeat_insert(true);
+    let mut cbf = builder.build_counting_bloom_filter();
+    cbf.add(b"hello"); // modify underlying vector counter.
+    cbf.add(b"hello"); // modify underlying vector counter.
+    assert_eq!(cbf.contains(b"hello"), true);
+    cbf.remove(b"hello");
+    assert_eq!(cbf.contains(b"hello"), true);
+    cbf.remove(b"hello");
+    assert_eq!(cbf.contains(b"hello"), false);
+
+    // enable_repeat_insert is false
+    builder.enable_repeat_insert(false);
+    let mut cbf = builder.build_counting_bloom_filter();
+    cbf.add(b"hello"); // modify underlying vector counter.
+    cbf.add(b"hello"); // not modify underlying vector counter because b"hello" has been added.
+    assert_eq!(cbf.contains(b"hello"), true);
+    cbf.remove(b"hello");
+    assert_eq!(cbf.contains(b"hello"), false);
+}
+
+#[test]
+fn counting_bloom_from_test() {
+    let mut builder = FilterBuilder::new(10_000_000, 0.01);
+    let mut cbf = builder.build_counting_bloom_filter();
+
+    cbf.add(b"hello");
+    cbf.add(b"hello");
+
+    let mut cbf_copy = CountingBloomFilter::from_u8_array(cbf.get_u8_array(), builder.hashes, true);
+    assert_eq!(cbf_copy.contains(b"hello"), true);
+    cbf_copy.remove(b"hello");
+    assert_eq!(cbf_copy.contains(b"hello"), true);
+    cbf_copy.remove(b"hello");
+    assert_eq!(cbf_copy.contains(b"hello"), false);
+
+    let mut cbf_copy = CountingBloomFilter::from_u16_array(cbf.get_u16_array(), builder.hashes, true);
+    assert_eq!(cbf_copy.contains(b"hello"), true);
+    cbf_copy.remove(b"hello");
+    assert_eq!(cbf_copy.contains(b"hello"), true);
+    cbf_copy.remove(b"hello");
+    assert_eq!(cbf_copy.contains(b"hello"), false);
+
+    let mut cbf_copy = CountingBloomFilter::from_u32_array(cbf.get_u32_array(), builder.hashes, true);
+    assert_eq!(cbf_copy.contains(b"hello"), true);
+    cbf_copy.remove(b"hello");
+    assert_eq!(cbf_copy.contains(b"hello"), true);
+    cbf_copy.remove(b"hello");
+    assert_eq!(cbf_copy.contains(b"hello"), false);
+
+    #[cfg(target_pointer_width = "64")]{
+        let mut cbf_copy = CountingBloomFilter::from_u64_array(cbf.get_u64_array(), builder.hashes, true);
+        assert_eq!(cbf_copy.contains(b"hello"), true);
+        cbf_copy.remove(b"hello");
+        assert_eq!(cbf_copy.contains(b"hello"), true);
+        cbf_copy.remove(b"hello");
+        assert_eq!(cbf_copy.contains(b"hello"), false);
+    }
+}
+
+#[test]
+fn counting_bloom_hash_indices_test() {
+    let mut builder =
+        FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_counting_bloom_filter();
+
+    bloom.add(b"hello");
+
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"hello")), true);
+    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"world")), false);
+
+
+    bloom.remove(b"hello");
+    assert_eq!(bloom.contains(b"hello"), false);
+    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"hello")), false);
+}
+
+#[test]
+fn counting_bloom_estimate_count() {
+    let mut builder =
+        FilterBuilder::new(10_000, 0.01);
+    let mut bloom = builder.build_counting_bloom_filter();
+
+    bloom.add(b"hello");
+    bloom.add(b"world");
+
+    assert_eq!(bloom.estimate_count(b"hello"), 1);
+    let indices = bloom.get_hash_indices(b"hello");
+
+    for index in indices {
+        assert_eq!(bloom.counter_at(index), 1)
+    }
+
+    assert_eq!(bloom.estimate_count(b"world"), 1);
+    for index in bloom.get_hash_indices(b"world") {
+        assert!(bloom.counter_at(index) <= 2);
+    }
+}
+
+#[test]
+fn bloom_hashable_test() {
+    #[derive(Hash)]
+    struct Record {
+        id: u32,
+        name: String,
+        tags: Vec<u16>,
+    }
+
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+
+    bloom.add_hashable(&42u64);
+    bloom.add_hashable(&String::from("hello"));
+    bloom.add_hashable(&(7u8, -3i64, "world"));
+    bloom.add_hashable(&Record { id: 1, name: "yankun".to_string(), tags: vec![1, 2] });
+
+    assert_eq!(bloom.contains_hashable(&42u64), true);
+    assert_eq!(bloom.contains_hashable(&43u64), false);
+    assert_eq!(bloom.contains_hashable(&String::from("hello")), true);
+    // `str` and `String` hash the same way.
+    assert_eq!(bloom.contains_hashable("hello"), true);
+    assert_eq!(bloom.contains_hashable(&(7u8, -3i64, "world")), true);
+    assert_eq!(bloom.contains_hashable(&(7u8, -3i64, "worlds")), false);
+    assert_eq!(bloom.contains_hashable(&Record { id: 1, name: "yankun".to_string(), tags: vec![1, 2] }), true);
+    assert_eq!(bloom.contains_hashable(&Record { id: 2, name: "yankun".to_string(), tags: vec![1, 2] }), false);
+
+    // integers are encoded little endian no matter the platform.
+    assert_eq!(bloom.contains(42u64.to_le_bytes()), true);
+}
+
+#[test]
+fn bloom_from_iter_test() {
+    let elements: Vec<Vec<u8>> = (0..1000u32).map(|i| i.to_le_bytes().to_vec()).collect();
+
+    let builder = FilterBuilder::new(1000, 0.01);
+    let bloom = BloomFilter::from_iter_with(builder.clone(), &elements);
+    let mut looped = BloomFilter::new(builder);
+    for element in elements.iter() {
+        looped.add(element);
+    }
+    assert_eq!(bloom.get_u8_array(), looped.get_u8_array());
+
+    let collected: BloomFilter = elements.iter().cloned().collect();
+    assert_eq!(collected.config().expected_elements, 1000);
+    assert_eq!(collected.config().false_positive_probability,
+               BloomFilter::DEFAULT_FALSE_POSITIVE_PROBABILITY);
+    for element in elements.iter() {
+        assert_eq!(bloom.contains(element), true);
+        assert_eq!(collected.contains(element), true);
+    }
+}
+
+#[test]
+fn bloom_extend_test() {
+    use std::collections::HashSet;
+
+    let lines: HashSet<String> = (0..1000).map(|i| format!("line {i}")).collect();
+    let mut bloom = FilterBuilder::new(3000, 0.01).build_bloom_filter();
+    bloom.extend(lines.iter().map(|l| l.as_str()));
+    bloom.extend(vec![b"bytes".as_slice()]);
+    bloom.extend(vec![b"vec".to_vec()]);
+
+    for line in lines.iter() {
+        assert_eq!(bloom.contains(line.as_bytes()), true);
+    }
+    assert_eq!(bloom.contains(b"bytes"), true);
+    assert_eq!(bloom.contains(b"vec"), true);
+}
+
+#[test]
+fn bloom_contains_then_add_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut expected = bloom.clone();
+
+    for i in 0..1000u32 {
+        let element = i.to_le_bytes();
+        let seen = expected.contains(element);
+        expected.add(element);
+        assert_eq!(bloom.contains_then_add(element), seen);
+    }
+    assert_eq!(bloom.get_u8_array(), expected.get_u8_array());
+    assert_eq!(bloom.contains_then_add(0u32.to_le_bytes()), true);
+}
+
+#[test]
+fn bloom_add_all_test() {
+    let elements: Vec<[u8; 8]> =
+        (0..300_000u64).map(|i| xxh3_64_with_seed(&i.to_le_bytes(), 7).to_le_bytes()).collect();
+    let mut sequential = FilterBuilder::new(300_000, 0.01).build_bloom_filter();
+    for element in elements.iter() {
+        sequential.add(element);
+    }
+
+    let mut bloom = sequential.clone();
+    bloom.clear();
+    bloom.add_all(&elements);
+    assert_eq!(bloom.get_u8_array(), sequential.get_u8_array());
+
+    let slices: Vec<&[u8]> = elements.iter().map(|e| e.as_slice()).collect();
+    bloom.clear();
+    bloom.add_slice(&slices);
+    assert_eq!(bloom.get_u8_array(), sequential.get_u8_array());
+}
+
+#[test]
+fn bloom_contains_batch_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    bloom.add(b"hello");
+    bloom.add(b"yankun");
+
+    let elements: [&[u8]; 4] = [b"hello", b"world", b"yankun", b"hello world"];
+    assert_eq!(bloom.contains_batch(&elements), vec![true, false, true, false]);
+    assert_eq!(bloom.contains_batch_iter(["world", "hello"]), vec![false, true]);
+    assert_eq!(bloom.contains_batch(&[]), Vec::<bool>::new());
+    assert_eq!(bloom.contains_batch_iter(Vec::<Vec<u8>>::new()), Vec::<bool>::new());
+}
+
+#[test]
+fn bloom_contains_all_any_test() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the elements hashed.
+    struct CountingHasher(Arc<AtomicUsize>);
+
+    impl BloomHasher for CountingHasher {
+        fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            crate::Xxh128Hasher.hash128(data, seed)
+        }
+
+        fn name(&self) -> &str {
+            "counting"
+        }
+    }
+
+    let hashed = Arc::new(AtomicUsize::new(0));
+    let mut bloom = FilterBuilder::new(10_000, 0.01).with_hasher(CountingHasher(hashed.clone()))
+        .build_bloom_filter();
+    bloom.add(b"hello");
+    bloom.add(b"world");
+
+    assert_eq!(bloom.contains_all(&[b"hello", b"world"]), true);
+    assert_eq!(bloom.contains_all(&[b"hello", b"yankun"]), false);
+    assert_eq!(bloom.contains_all(&[]), true);
+    assert_eq!(bloom.contains_any(&[b"yankun", b"world"]), true);
+    assert_eq!(bloom.contains_any(&[b"yankun", b"hello world"]), false);
+    assert_eq!(bloom.contains_any(&[]), false);
+
+    // the elements after the first missing or present one aren't hashed
+    let elements: [&[u8]; 4] = [b"hello", b"yankun", b"world", b"hello world"];
+    hashed.store(0, Ordering::Relaxed);
+    assert_eq!(bloom.contains_all(&elements), false);
+    assert_eq!(hashed.load(Ordering::Relaxed), 2);
+    hashed.store(0, Ordering::Relaxed);
+    assert_eq!(bloom.contains_any(&elements), true);
+    assert_eq!(hashed.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn bloom_fill_ratio_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.count_ones(), 0);
+    assert_eq!(bloom.fill_ratio(), 0.0);
+
+    let n = 10_000u32;
+    for i in 0..n {
+        bloom.add(i.to_le_bytes());
+    }
+    let m = bloom.config.size as f64;
+    let k = bloom.hashes() as f64;
+    let expected = 1.0 - (-k * n as f64 / m).exp();
+    assert!(bloom.count_ones() <= n as u64 * bloom.hashes() as u64);
+    assert!((bloom.fill_ratio() - expected).abs() < 0.01,
+            "fill ratio {} expected {}", bloom.fill_ratio(), expected);
+}
+
+#[test]
+fn bloom_estimate_current_fpp_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.estimate_current_fpp(), 0.0);
+
+    // overfill the filter so the false positive probability is well above the configured one.
+    for i in 0..20_000u64 {
+        bloom.add(i.to_le_bytes());
+    }
+    let estimate = bloom.estimate_current_fpp();
+    let probes = 100_000u64;
+    let false_positives = (0..probes)
+        .filter(|i| bloom.contains((i + 1_000_000).to_le_bytes()))
+        .count();
+    let measured = false_positives as f64 / probes as f64;
+    assert!(estimate > 0.01);
+    assert!((estimate - measured).abs() < estimate * 0.2,
+            "estimate {} measured {}", estimate, measured);
+
+    bloom.bits_mut().storage.fill(!0);
+    assert_eq!(bloom.estimate_current_fpp(), 1.0);
+}
+
+#[test]
+fn bloom_estimate_element_count_test() {
+    let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.estimate_element_count(), 0);
+
+    let n = 50_000u64;
+    for i in 0..n {
+        bloom.add(xxh3_64_with_seed(&i.to_le_bytes(), 1).to_le_bytes());
+    }
+    let estimate = bloom.estimate_element_count();
+    assert!((estimate as f64 - n as f64).abs() < n as f64 * 0.03, "estimate {}", estimate);
+
+    bloom.bits_mut().storage.fill(!0);
+    let saturated = bloom.estimate_element_count();
+    assert!(saturated > n && saturated < u64::MAX);
+}
+
+#[test]
+fn bloom_len_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.len(), 0);
+    bloom.add(b"hello");
+    bloom.add(b"hello");
+    assert_eq!(bloom.len(), 2);
+    bloom.add_all(["world", "yankun"]);
+    bloom.contains_then_add(b"hello");
+    assert_eq!(bloom.len(), 5);
+    assert_eq!(bloom.clone().len(), 5);
+
+    let mut other = bloom.clone();
+    other.clear();
+    assert_eq!(other.len(), 0);
+    other.add(b"hello world");
+    assert_eq!(bloom.union(&other), true);
+    assert_eq!(bloom.len(), 6);
+
+    assert_eq!(bloom.intersect(&other), true);
+    assert_eq!(bloom.len(), bloom.estimate_element_count());
+    assert_eq!(bloom.len(), 1);
+}
+
+#[test]
+fn bloom_saturation_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let p = bloom.config.false_positive_probability;
+    assert_eq!(bloom.is_saturated(p), false);
+    let capacity = bloom.capacity_remaining();
+    // size and hashes are rounded up, so the capacity is close to but not exactly expected.
+    assert!((9_500..10_500).contains(&capacity), "capacity {}", capacity);
+
+    let mut i = 0u64;
+    let mut last = capacity;
+    while !bloom.is_saturated(p) {
+        bloom.add(i.to_le_bytes());
+        i += 1;
+        let remaining = bloom.capacity_remaining();
+        assert!(remaining <= last);
+        last = remaining;
+    }
+    assert!((9_500..10_500).contains(&i), "saturated after {}", i);
+    assert_eq!(bloom.capacity_remaining(), 0);
+    for _ in 0..1000 {
+        bloom.add(i.to_le_bytes());
+        i += 1;
+    }
+    assert_eq!(bloom.is_saturated(p), true);
+    assert_eq!(bloom.capacity_remaining(), 0);
+}
+
+#[test]
+fn bloom_try_add_test() {
+    let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    bloom.set_capacity_check_interval(10);
+
+    let mut i = 0u64;
+    let exceeded = loop {
+        match bloom.try_add(i.to_le_bytes()) {
+            Ok(()) => i += 1,
+            Err(e) => break e,
+        }
+        assert!(i < 2000, "try_add never failed");
+    };
+    // the limit is reached around the expected elements, checked every 10 inserts.
+    assert!((900..1100).contains(&i), "failed after {}", i);
+    assert!(exceeded.estimated_fpp > 0.01);
+    assert!(exceeded.estimated_elements >= 900);
+    assert_eq!(bloom.len(), i);
+
+    assert!(bloom.try_add(b"hello").is_err());
+    assert_eq!(bloom.contains(b"hello"), false);
+    bloom.add(b"hello");
+    assert_eq!(bloom.contains(b"hello"), true);
+
+    bloom.clear();
+    assert_eq!(bloom.try_add(b"hello"), Ok(()));
+
+    // merging bits in is checked at the next insert, whatever the number of inserts
+    let config = bloom.config();
+    let full = BloomFilter::from_u8_array_with_config(&vec![0xff; bloom.get_u8_array().len()],
+                                                      config.clone());
+    assert_eq!(full.len(), 0);
+    let mut merged = bloom.clone();
+    assert_eq!(merged.try_union(&full), Ok(()));
+    assert_eq!(merged.try_add(b"world").is_err(), true);
+    let mut merged = bloom.clone();
+    assert_eq!(merged.union_all([&full]), Ok(()));
+    assert_eq!(merged.try_add(b"world").is_err(), true);
+}
+
+#[test]
+fn bloom_try_union_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let larger = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    let more_hashes = FilterBuilder::from_size_and_hashes(bloom.config.size, 3).build_bloom_filter();
+
+    assert_eq!(bloom.try_union(&larger),
+               Err(IncompatibleFilterError::Size { left: bloom.config.size, right: larger.config.size }));
+    assert_eq!(bloom.try_intersect(&more_hashes),
+               Err(IncompatibleFilterError::Hashes { left: bloom.hashes(), right: 3 }));
+    assert_eq!(bloom.union(&larger), false);
+    assert_eq!(bloom.intersect(&more_hashes), false);
+
+    let mut other = bloom.clone();
+    other.add(b"hello");
+    assert_eq!(bloom.try_union(&other), Ok(()));
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.try_intersect(&other), Ok(()));
+    assert_eq!(bloom.contains(b"hello"), true);
+}
+
+#[test]
+fn bloom_union_of_test() {
+    let mut a = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut b = a.clone();
+    a.add_all(["hello", "world"]);
+    b.add_all(["hello", "yankun"]);
+    let (a_bits, b_bits) = (a.get_u8_array().to_vec(), b.get_u8_array().to_vec());
+
+    let union = a.union_of(&b).unwrap();
+    let intersection = a.intersect_of(&b).unwrap();
+    assert_eq!(a.get_u8_array(), a_bits.as_slice());
+    assert_eq!(b.get_u8_array(), b_bits.as_slice());
+
+    let mut expected = a.clone();
+    expected.union(&b);
+    assert_eq!(union.get_u8_array(), expected.get_u8_array());
+    assert_eq!(union.contains(b"yankun"), true);
+    let mut expected = a.clone();
+    expected.intersect(&b);
+    assert_eq!(intersection.get_u8_array(), expected.get_u8_array());
+    assert_eq!(intersection.contains(b"hello"), true);
+
+    let other = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    assert!(a.union_of(&other).is_err());
+    assert!(a.intersect_of(&other).is_err());
+}
+
+#[test]
+fn bloom_ops_test() {
+    let mut a = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut b = a.clone();
+    a.add(b"hello");
+    b.add(b"world");
+
+    let union = &a | &b;
+    assert_eq!(union.get_u8_array(), a.union_of(&b).unwrap().get_u8_array());
+    let intersection = &a & &b;
+    assert_eq!(intersection.get_u8_array(), a.intersect_of(&b).unwrap().get_u8_array());
+
+    let owned = a.clone() | b.clone();
+    assert_eq!(owned.get_u8_array(), union.get_u8_array());
+    let owned = a.clone() & b.clone();
+    assert_eq!(owned.get_u8_array(), intersection.get_u8_array());
+
+    let mut c = a.clone();
+    c |= &b;
+    assert_eq!(c.contains(b"hello") && c.contains(b"world"), true);
+    c &= &b;
+    assert_eq!(c.get_u8_array(), b.get_u8_array());
+}
+
+#[test]
+#[should_panic(expected = "incompatible bloom filters")]
+fn bloom_ops_incompatible_test() {
+    let a = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let b = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    let _ = &a | &b;
+}
+
+#[test]
+fn bloom_symmetric_difference_test() {
+    let mut a = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    a.add_all(["hello", "world"]);
+
+    let diff = a.symmetric_difference_of(&a).unwrap();
+    assert_eq!(diff.count_ones(), 0);
+    assert_eq!(a.contains(b"hello"), true);
+
+    let mut b = a.clone();
+    b.add(b"yankun");
+    let diff = a.symmetric_difference_of(&b).unwrap();
+    assert_eq!(diff.count_ones(), b.count_ones() - a.count_ones());
+
+    assert_eq!(b.symmetric_difference(&b.clone()), true);
+    assert_eq!(b.count_ones(), 0);
+    let other = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    assert_eq!(b.symmetric_difference(&other), false);
+    assert!(a.symmetric_difference_of(&other).is_err());
+}
+
+#[test]
+fn bloom_subset_test() {
+    let mut small = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut large = small.clone();
+    for i in 0..1000u32 {
+        large.add(i.to_le_bytes());
+        if i % 2 == 0 {
+            small.add(i.to_le_bytes());
+        }
+    }
+    assert_eq!(small.is_subset_of(&large), Ok(true));
+    assert_eq!(large.is_superset_of(&small), Ok(true));
+    assert_eq!(large.is_subset_of(&small), Ok(false));
+
+    small.add(b"hello");
+    assert_eq!(small.is_subset_of(&large), Ok(false));
+    assert_eq!(large.is_superset_of(&small), Ok(false));
+
+    let other = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    assert!(small.is_subset_of(&other).is_err());
+}
+
+#[test]
+fn bloom_jaccard_estimate_test() {
+    let empty = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    let n = 20_000u64;
+    for shared in [0, n / 2, n] {
+        let mut a = empty.clone();
+        let mut b = empty.clone();
+        for i in 0..n {
+            a.add(i.to_le_bytes());
+            b.add((i + n - shared).to_le_bytes());
+        }
+        let expected = shared as f64 / (2 * n - shared) as f64;
+        let estimate = a.jaccard_estimate(&b).unwrap();
+        assert!((estimate - expected).abs() < 0.02, "estimate {} expected {}", estimate, expected);
+    }
+    assert_eq!(empty.jaccard_estimate(&empty), Ok(1.0));
+
+    let other = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    assert!(empty.jaccard_estimate(&other).is_err());
+}
+
+#[test]
+fn bloom_estimate_intersection_count_test() {
+    let empty = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    let n = 20_000u64;
+    for shared in [0, 5_000, n] {
+        let mut a = empty.clone();
+        let mut b = empty.clone();
+        for i in 0..n {
+            a.add(i.to_le_bytes());
+            b.add((i + n - shared).to_le_bytes());
+        }
+        let estimate = a.estimate_intersection_count(&b).unwrap();
+        assert!((estimate as f64 - shared as f64).abs() < n as f64 * 0.02,
+                "estimate {} expected {}", estimate, shared);
+    }
+    assert_eq!(empty.estimate_intersection_count(&empty), Ok(0));
+}
+
+#[test]
+fn bloom_estimate_difference_count_test() {
+    let empty = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    let n = 20_000u64;
+    let mut a = empty.clone();
+    let mut b = empty.clone();
+    for i in 0..n {
+        a.add(i.to_le_bytes());
+        b.add((i + n / 2).to_le_bytes());
+    }
+    let estimate = a.estimate_difference_count(&b).unwrap();
+    assert!((estimate as f64 - (n / 2) as f64).abs() < n as f64 * 0.02, "estimate {}", estimate);
+    assert_eq!(a.estimate_difference_count(&a), Ok(0));
+    assert_eq!(empty.estimate_difference_count(&a), Ok(0));
+    assert_eq!(empty.estimate_difference_count(&empty), Ok(0));
+}
+
+#[test]
+fn bloom_union_all_test() {
+    let empty = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let filters: Vec<BloomFilter> = (0..10u32).map(|w| {
+        let mut filter = empty.clone();
+        for i in 0..100u32 {
+            filter.add((w * 100 + i).to_le_bytes());
+        }
+        filter
+    }).collect();
+
+    let mut sequential = empty.clone();
+    for filter in filters.iter() {
+        sequential.union(filter);
+    }
+    let mut merged = empty.clone();
+    assert_eq!(merged.union_all(&filters), Ok(()));
+    assert_eq!(merged.get_u8_array(), sequential.get_u8_array());
+    assert_eq!(merged.len(), 1000);
+
+    let mut with_incompatible: Vec<&BloomFilter> = filters.iter().collect();
+    let incompatible = FilterBuilder::new(20_000, 0.01).build_bloom_filter();
+    with_incompatible[4] = &incompatible;
+    let mut merged = filters[0].clone();
+    assert!(merged.union_all(with_incompatible).is_err());
+    assert_eq!(merged.get_u8_array(), filters[0].get_u8_array());
+    assert_eq!(merged.len(), 100);
+}
+
+#[test]
+fn bloom_sum_test() {
+    let empty = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let filters: Vec<BloomFilter> = (0..5u32).map(|i| {
+        let mut filter = empty.clone();
+        filter.add(i.to_le_bytes());
+        filter
+    }).collect();
+
+    let single: BloomFilter = filters[..1].iter().sum();
+    assert_eq!(single.get_u8_array(), filters[0].get_u8_array());
+
+    let borrowed: BloomFilter = filters.iter().sum();
+    let owned: BloomFilter = filters.clone().into_iter().sum();
+    assert_eq!(borrowed.get_u8_array(), owned.get_u8_array());
+    for i in 0..5u32 {
+        assert_eq!(owned.contains(i.to_le_bytes()), true);
+    }
+}
+
+#[test]
+#[should_panic(expected = "can't sum an empty iterator of bloom filters")]
+fn bloom_sum_empty_test() {
+    let _: BloomFilter = Vec::<BloomFilter>::new().into_iter().sum();
+}
+
+#[test]
+fn bloom_eq_test() {
+    let empty = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut a = empty.clone();
+    let mut b = empty.clone();
+    a.add_all(["hello", "world", "yankun"]);
+    b.add_all(["yankun", "hello", "world"]);
+    b.add(b"hello");
+    assert_eq!(a, b);
+    assert_ne!(a, empty);
+
+    // same bits but a different number of hashes.
+    let c = BloomFilter::from_u8_array(a.get_u8_array(), a.hashes() + 1);
+    assert_eq!(c.get_u8_array(), a.get_u8_array());
+    assert_ne!(a, c);
+}
+
+#[test]
+fn bloom_u64_array_test() {
+    for size in [64, 1000, 4096, 100_003] {
+        let mut bloom = FilterBuilder::from_size_and_hashes(size, 5).build_bloom_filter();
+        for i in 0..100u32 {
+            bloom.add(i.to_le_bytes());
+        }
+        let words = bloom.to_u64_vec();
+        assert_eq!(words.len() as u64, size.div_ceil(64));
+        assert_eq!(words.as_slice(), bloom.get_u64_array());
+
+        let copy = BloomFilter::from_u64_array_with_size(&words, size, 5);
+        assert_eq!(copy.config.size, size);
+        assert_eq!(copy, bloom);
+        for i in 0..100u32 {
+            assert_eq!(copy.contains(i.to_le_bytes()), true);
+        }
+    }
+}
+
+#[test]
+fn bloom_raw_padding_test() {
+    // the bits past the size in the last word are ignored
+    let filters = [
+        BloomFilter::from_u64_array_with_size(&[!0; 2], 100, 4),
+        BloomFilter::from_u64_array_with_size(&[!0; 3], 100, 4),
+        BloomFilter::from_u8_array_with_size(&[0xff; 16], 100, 4),
+        BloomFilter::from_u8_array_with_config(&[0xff; 16],
+                                               FilterBuilder::from_size_and_hashes(100, 4)),
+    ];
+    for bloom in filters {
+        assert_eq!(bloom.count_ones(), 100);
+        assert_eq!(bloom.fill_ratio(), 1.0);
+        assert_eq!(bloom.get_u64_array(), &[!0, (1 << 36) - 1]);
+        assert_eq!(bloom.to_sparse().indices().len(), 100);
+    }
+}
+
+#[test]
+fn bloom_from_u64_vec_test() {
+    // the reuse of the allocation doesn't depend on the size, see bloom_from_u64_vec_bench for
+    // the time it saves on a large filter
+    let mut bloom = FilterBuilder::from_size_and_hashes(1 << 20, 7).build_bloom_filter();
+    bloom.add_all(["hello", "world"]);
+    let words = bloom.to_u64_vec();
+
+    let borrowed = BloomFilter::from_u64_array(&words, 7);
+    #[cfg(target_pointer_width = "64")]
+    let ptr = words.as_ptr() as usize;
+    let owned = BloomFilter::from_u64_vec(words, 7);
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(owned.bit_set.storage.as_ptr() as usize, ptr);
+
+    assert_eq!(owned, borrowed);
+    assert_eq!(owned, bloom);
+    assert_eq!(owned.contains(b"hello"), true);
+    assert_eq!(owned.contains(b"yankun"), false);
+
+    let bytes = bloom.get_u8_array().to_vec();
+    assert_eq!(BloomFilter::from_vec_owned(bytes, 7), bloom);
+    assert_eq!(BloomFilter::from_bit_vec_owned(bloom.to_u64_vec(), 7), bloom);
+}
+
+#[test]
+fn bloom_into_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    bloom.add_all(["hello", "world"]);
+    let bytes = bloom.get_u8_array().to_vec();
+    let words = bloom.to_u64_vec();
+    let hashes = bloom.hashes();
+
+    assert_eq!(bloom.clone().into_bytes(), bytes);
+    assert_eq!(bloom.clone().into_bit_vec(), words);
+    let moved = bloom.into_u64_vec();
+    assert_eq!(moved, words);
+    let copy = BloomFilter::from_u64_vec(moved, hashes);
+    assert_eq!(copy.contains(b"hello"), true);
+}
+
+#[test]
+fn bloom_clone_empty_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    bloom.add_all(["hello", "world"]);
+    let mut fresh = bloom.clone_empty();
+    assert_eq!(bloom.compatible(&fresh), true);
+    assert_eq!(fresh.count_ones(), 0);
+    assert_eq!(fresh.len(), 0);
+    assert_eq!(fresh.contains(b"hello"), false);
+
+    fresh.add(b"yankun");
+    assert_eq!(fresh.union(&bloom), true);
+    assert_eq!(fresh.contains(b"hello"), true);
+}
+
+#[test]
+fn bloom_iter_set_bits_test() {
+    let mut bloom = FilterBuilder::from_size_and_hashes(1000, 4).build_bloom_filter();
+    assert_eq!(bloom.iter_set_bits().next(), None);
+    bloom.add_all(["hello", "world", "yankun"]);
+    let expected: Vec<u64> = (0..1000).filter(|i| bloom.bit_set.get(*i as usize)).collect();
+    assert_eq!(bloom.iter_set_bits().collect::<Vec<u64>>(), expected);
+
+    let full = BloomFilter::from_u64_array(&[!0u64; 4], 4);
+    assert_eq!(full.iter_set_bits().collect::<Vec<u64>>(), (0..256).collect::<Vec<u64>>());
+
+    // only the 1.5M backing words are visited, not the 100M bits
+    let mut sparse = FilterBuilder::from_size_and_hashes(100_000_000, 4).build_bloom_filter();
+    sparse.add(b"hello");
+    let bits: Vec<u64> = sparse.iter_set_bits().collect();
+    assert_eq!(bits.len() as u64, sparse.count_ones());
+    assert_eq!(sparse.contains_hash_indices(&bits), true);
+}
+
+#[test]
+fn bloom_sparse_test() {
+    let mut bloom = FilterBuilder::new(1_000_000, 0.01).build_bloom_filter();
+    for i in 0..100u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    let sparse = bloom.to_sparse();
+    assert_eq!(sparse.indices().len() as u64, bloom.count_ones());
+    assert_eq!(sparse.indices().len() * 8 * 100 < bloom.get_u8_array().len(), true);
+
+    let copy = BloomFilter::from_sparse(sparse);
+    assert_eq!(copy, bloom);
+    for i in 0..100u32 {
+        assert_eq!(copy.contains(i.to_le_bytes()), true);
+    }
+}
+
+#[test]
+fn bloom_hash_indices_smallvec_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let indices = bloom.hash_indices(b"hello");
+    assert_eq!(indices.len() as u32, bloom.hashes());
+    assert_eq!(indices.to_vec(), bloom.get_hash_indices(b"hello"));
+
+    let mut manual = bloom.clone_empty();
+    for index in indices.iter() {
+        manual.bits_mut().set(*index as usize);
+    }
+    assert_eq!(manual.contains(b"hello"), true);
+
+    bloom.add(b"hello");
+    let mut expected = indices.to_vec();
+    expected.sort();
+    expected.dedup();
+    assert_eq!(bloom.iter_set_bits().collect::<Vec<u64>>(), expected);
+}
+
+#[test]
+fn bloom_typed_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    bloom.add_str("hello");
+    bloom.add_u64(7);
+    bloom.add_i64(-7);
+    bloom.add_u32(42);
+
+    assert_eq!(bloom.contains(b"hello"), true);
+    assert_eq!(bloom.contains([7, 0, 0, 0, 0, 0, 0, 0]), true);
+    assert_eq!(bloom.contains([0xf9, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]), true);
+    assert_eq!(bloom.contains([42, 0, 0, 0]), true);
+
+    assert_eq!(bloom.contains_str("hello"), true);
+    assert_eq!(bloom.contains_u64(7), true);
+    assert_eq!(bloom.contains_i64(7), true);
+    assert_eq!(bloom.contains_i64(-7), true);
+    assert_eq!(bloom.contains_u32(42), true);
+    assert_eq!(bloom.contains_u32(7), false);
+    assert_eq!(bloom.contains_str("world"), false);
+}
+
+#[test]
+fn bloom_as_ref_args_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let owned = String::from("owned");
+    bloom.add("str");
+    bloom.add(&owned);
+    bloom.add(vec![1u8, 2, 3]);
+    bloom.add([4u8, 5, 6]);
+    bloom.add(&b"slice"[..]);
+    assert_eq!(bloom.add_if_not_contains(String::from("new")), false);
+    let bytes: Vec<u8> = b"bytes".to_vec();
+    assert_eq!(bloom.contains_then_add(bytes), false);
+    assert_eq!(bloom.try_add("try").is_ok(), true);
+
+    assert_eq!(bloom.contains(b"str"), true);
+    assert_eq!(bloom.contains(owned), true);
+    assert_eq!(bloom.contains([1u8, 2, 3]), true);
+    assert_eq!(bloom.contains(vec![4u8, 5, 6]), true);
+    assert_eq!(bloom.contains("slice"), true);
+    assert_eq!(bloom.contains("new"), true);
+    assert_eq!(bloom.contains("bytes"), true);
+    assert_eq!(bloom.contains("try"), true);
+    assert_eq!(bloom.contains(String::new()), false);
+
+    // the trait methods keep taking `&[u8]`
+    Membership::add(&mut bloom, b"trait");
+    assert_eq!(Membership::contains(&bloom, b"trait"), true);
+}
+
+#[test]
+fn bloom_fold_test() {
+    let mut bloom = FilterBuilder::from_size_and_hashes(1 << 20, 7).build_bloom_filter();
+    for i in 0..20_000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    assert_eq!(bloom.can_fold(4), true);
+    assert_eq!(bloom.can_fold(3), false);
+    assert_eq!(bloom.fold(0).unwrap_err(), FoldError::ZeroFactor);
+    assert_eq!(bloom.fold(3).unwrap_err(), FoldError::NotDivisible { size: 1 << 20, factor: 3 });
+
+    let mut previous = bloom.estimate_current_fpp();
+    for factor in [2, 4, 8] {
+        let folded = bloom.fold(factor).unwrap();
+        assert_eq!(folded.config.size, (1 << 20) / factor as u64);
+        for i in 0..20_000u32 {
+            assert_eq!(folded.contains(i.to_le_bytes()), true);
+        }
+        // the fpp of 20_000 elements in the folded size
+        let m = folded.config.size as f64;
+        let expected = (1.0 - (-7.0 * 20_000.0 / m).exp()).powi(7);
+        let fpp = folded.estimate_current_fpp();
+        assert!(fpp > previous);
+        assert!((fpp - expected).abs() < expected * 0.1, "{} vs {}", fpp, expected);
+        previous = fpp;
+    }
+
+    // sizes which are not a multiple of the word size fold bit by bit
+    let mut odd = FilterBuilder::from_size_and_hashes(3000, 4).build_bloom_filter();
+    odd.add_all(["hello", "world"]);
+    let folded = odd.fold(3).unwrap();
+    assert_eq!(folded.config.size, 1000);
+    assert_eq!(folded.contains("hello"), true);
+    assert_eq!(folded.contains("world"), true);
+}
+
+#[test]
+fn bloom_fold_hash_version_test() {
+    // keys whose second hash is a multiple of the folded size but not of the size
+    let config = FilterBuilder::from_size_and_hashes(1024, 4);
+    let keys: Vec<[u8; 4]> = (0..10_000u32).map(|i| i.to_le_bytes())
+        .filter(|key| {
+            let hash2 = config.hasher.hash128(key, 0).1;
+            hash2.is_multiple_of(8) && !hash2.is_multiple_of(1024)
+        })
+        .take(20)
+        .collect();
+    assert_eq!(keys.len(), 20);
+    for scheme in [ProbeScheme::Double, ProbeScheme::EnhancedDouble, ProbeScheme::Triple] {
+        for version in [1, 3] {
+            let config = config.clone().with_probe_scheme(scheme).with_hash_version(version);
+            let mut bloom = config.clone().build_bloom_filter();
+            for key in &keys {
+                let mut single = config.clone().build_bloom_filter();
+                single.add(key);
+                bloom.add(key);
+                for factor in [2, 8, 32, 128] {
+                    let folded = single.fold(factor).unwrap();
+                    assert_eq!(folded.contains(key), true);
+                    assert_eq!(folded.count_ones() <= 4, true);
+                }
+            }
+            let folded = bloom.fold(16).unwrap();
+            assert_eq!(keys.iter().all(|key| folded.contains(key)), true);
+        }
+    }
+
+    // version 2 replaces the second hash of 0 after the reduction, which loses them
+    let mut v2 = config.clone().with_hash_version(2).build_bloom_filter();
+    v2.add(keys[0]);
+    assert_eq!(v2.fold_to(8).contains(keys[0]), false);
+    assert_eq!(v2.can_fold(2), false);
+    assert_eq!(v2.fold(128).unwrap_err(), FoldError::HashVersion { version: 2 });
+    let mut small = FilterBuilder::from_size_and_hashes(512, 4).with_hash_version(2)
+        .build_bloom_filter();
+    assert_eq!(small.union_folding(&v2),
+               Err(IncompatibleFilterError::NotFoldable(FoldError::HashVersion { version: 2 })));
+    assert_eq!(small.union_folding(&small.clone()), Ok(()));
+    let mut big = v2.clone();
+    assert_eq!(big.union_folding(&small).unwrap_err().to_string(),
+               "incompatible bloom filters: can't fold a bloom filter of hash version 2");
+
+    // version 3 is version 2 for odd sizes, so it only folds to even sizes
+    let mut odd = FilterBuilder::from_size_and_hashes(3003, 4).build_bloom_filter();
+    odd.add(b"hello");
+    assert_eq!(odd.fold(3).unwrap_err(), FoldError::OddSize { size: 1001 });
+    assert_eq!(odd.fold(3).unwrap_err().to_string(),
+               "can't fold a bloom filter of hash version 3 to the odd size 1001");
+    assert_eq!(odd.fold(1).unwrap(), odd);
+    assert_eq!(odd.fold(3003).unwrap().contains(b"hello"), true);
+    let even = FilterBuilder::from_size_and_hashes(2002, 4).build_bloom_filter();
+    assert_eq!(even.can_fold(2), false);
+    assert_eq!(even.can_fold(7), true);
+    let legacy = FilterBuilder::from_size_and_hashes(3003, 4).with_hash_version(1)
+        .build_bloom_filter();
+    assert_eq!(legacy.can_fold(3), true);
+}
+
+#[test]
+fn bloom_fold_probe_scheme_test() {
+    for scheme in [ProbeScheme::Guava, ProbeScheme::RedisBloom, ProbeScheme::PyBloom] {
+        let config = FilterBuilder::from_size_and_hashes(1 << 12, 4).with_probe_scheme(scheme);
+        let big = config.clone().build_bloom_filter();
+        assert_eq!(big.can_fold(2), false);
+        assert_eq!(big.fold(2).unwrap_err(), FoldError::ProbeScheme { scheme });
+        let mut small = config.with_size(1 << 10).build_bloom_filter();
+        assert_eq!(small.union_folding(&big),
+                   Err(IncompatibleFilterError::NotFoldable(FoldError::ProbeScheme { scheme })));
+        assert_eq!(small.union_folding(&small.clone()), Ok(()));
+    }
+    let bloom = FilterBuilder::guava_compatible(1000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.fold(1).unwrap_err().to_string(),
+               "can't fold a bloom filter with the guava probe scheme");
+    for scheme in [ProbeScheme::Double, ProbeScheme::EnhancedDouble, ProbeScheme::Triple] {
+        let bloom = FilterBuilder::from_size_and_hashes(1 << 12, 4).with_probe_scheme(scheme)
+            .build_bloom_filter();
+        assert_eq!(bloom.can_fold(2), true);
+    }
+}
+
+#[test]
+fn bloom_union_folding_test() {
+    let mut small = FilterBuilder::from_size_and_hashes(1 << 17, 7).build_bloom_filter();
+    let mut big = FilterBuilder::from_size_and_hashes(1 << 19, 7).build_bloom_filter();
+    for i in 0..5000u32 {
+        small.add(i.to_le_bytes());
+        big.add((i + 5000).to_le_bytes());
+    }
+    let mut big_into_small = small.clone();
+    assert_eq!(big_into_small.union_folding(&big), Ok(()));
+    let mut small_into_big = big.clone();
+    assert_eq!(small_into_big.union_folding(&small), Ok(()));
+
+    for res in [&big_into_small, &small_into_big] {
+        assert_eq!(res.config.size, 1 << 17);
+        assert_eq!(res.len(), 10_000);
+        for i in 0..10_000u32 {
+            assert_eq!(res.contains(i.to_le_bytes()), true);
+        }
+    }
+    assert_eq!(big_into_small, small_into_big);
+
+    let other_hashes = FilterBuilder::from_size_and_hashes(1 << 19, 5).build_bloom_filter();
+    assert_eq!(small.union_folding(&other_hashes),
+               Err(IncompatibleFilterError::Hashes { left: 7, right: 5 }));
+    let odd = FilterBuilder::from_size_and_hashes(3 << 17, 7).build_bloom_filter();
+    assert_eq!(small.union_folding(&odd),
+               Err(IncompatibleFilterError::SizeNotFoldable { left: 1 << 17, right: 3 << 17 }));
+}
+
+#[test]
+fn bloom_snapshot_test() {
+    let mut bloom = FilterBuilder::new(1_000_000, 0.01).build_bloom_filter();
+    bloom.add_all(["hello", "world"]);
+    let before = bloom.clone();
+
+    let snapshot = bloom.snapshot();
+    assert_eq!(Arc::ptr_eq(&bloom.bit_set, &snapshot.bit_set), true);
+    for i in 0..1_000_000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    assert_eq!(Arc::ptr_eq(&bloom.bit_set, &snapshot.bit_set), false);
+    assert_ne!(bloom, before);
+
+    bloom.restore(&snapshot);
+    assert_eq!(bloom, before);
+    assert_eq!(bloom.len(), 2);
+    assert_eq!(bloom.contains(7u32.to_le_bytes()), false);
+
+    // a clone shares the bits too and copies them when written
+    let mut clone = bloom.clone();
+    clone.add(b"yankun");
+    assert_eq!(bloom.contains(b"yankun"), false);
+}
+
+#[test]
+fn bloom_delta_test() {
+    let mut source = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    let mut replica = source.clone();
+    let mut late_replica = source.clone();
+    let mut sent = source.clone();
+    let mut deltas = Vec::new();
+    for batch in 0..10u32 {
+        for i in 0..1000u32 {
+            source.add((batch * 1000 + i).to_le_bytes());
+        }
+        let delta = source.diff(&sent).unwrap();
+        assert_eq!(delta.words().len() < source.to_u64_vec().len(), true);
+        replica.apply_delta(&delta).unwrap();
+        sent = source.clone();
+        deltas.push(delta);
+    }
+    assert_eq!(replica, source);
+    assert_eq!(source.diff(&replica).unwrap().is_empty(), true);
+
+    // out of order and repeated application converges too
+    for delta in deltas.iter().rev().chain(deltas.iter()) {
+        late_replica.apply_delta(delta).unwrap();
+    }
+    assert_eq!(late_replica, source);
+
+    let other = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    assert_eq!(other.diff(&source).is_err(), true);
+    let mut other = other;
+    assert_eq!(other.apply_delta(&deltas[0]).is_err(), true);
+
+    // e.g. a corrupted deserialized delta
+    let words = source.to_u64_vec().len() as u64;
+    let mut corrupt = deltas[0].clone();
+    corrupt.words.push((words, 1));
+    assert_eq!(replica.apply_delta(&corrupt),
+               Err(IncompatibleFilterError::DeltaWordOutOfRange { index: words, words }));
+    assert_eq!(replica, source);
+    corrupt.words = vec![(u64::MAX, 1)];
+    assert_eq!(replica.apply_delta(&corrupt).unwrap_err().to_string(),
+               format!("incompatible bloom filters: word {} of the delta is out of range for {} \
+                        words", u64::MAX, words));
+    let mut small = FilterBuilder::from_size_and_hashes(100, 4).build_bloom_filter();
+    let padded = BloomDelta { config: small.config(), words: vec![(1, !0)] };
+    assert_eq!(small.apply_delta(&padded), Ok(()));
+    assert_eq!(small.count_ones(), 36);
+}
+
+#[test]
+fn bloom_fingerprint_test() {
+    let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    bloom.add_all(["hello", "world"]);
+    let copy = BloomFilter::from_u64_array(&bloom.to_u64_vec(), bloom.hashes());
+    assert_eq!(bloom.clone().fingerprint(), bloom.fingerprint());
+    // same bits and the same parameters placing them
+    assert_eq!(copy.fingerprint(), bloom.fingerprint());
+    // the expected elements and the probability only matter for computing the size
+    let mut other = copy.config();
+    other.expected_elements += 1;
+    other.false_positive_probability /= 2.0;
+    assert_eq!(other.fingerprint(), copy.config().fingerprint());
+    assert_eq!(BloomFilter::from_u64_array(&copy.to_u64_vec(), copy.hashes()).fingerprint(),
+               copy.fingerprint());
+
+    let before = bloom.fingerprint();
+    let mut flipped = bloom.clone();
+    flipped.bits_mut().set(12345);
+    assert_eq!(bloom.contains_hash_indices(&vec![12345]), false);
+    assert_ne!(flipped.fingerprint(), before);
+    assert_eq!(bloom.fingerprint(), before);
+
+    let mut config = bloom.config();
+    assert_eq!(config.fingerprint(), bloom.config().fingerprint());
+    config.enable_repeat_insert(!config.enable_repeat_insert);
+    assert_eq!(config.fingerprint(), bloom.config().fingerprint());
+    let config = config.with_seed(7);
+    assert_ne!(config.fingerprint(), bloom.config().fingerprint());
+}
+
+#[test]
+fn membership_generic_test() {
+    fn dedup(filter: &mut impl Membership, items: &[u32]) -> Vec<u32> {
+        items.iter().copied().filter(|item| {
+            let new = !filter.contains(&item.to_le_bytes());
+            filter.add(&item.to_le_bytes());
+            new
+        }).collect()
     }
 
-    /// Return the underlying u32 vector of the Bloom filter.
-    pub fn get_u32_array(&self) -> &[u32] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr() as *const u32;
-        #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() * 2);
-        #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(ptr, storage.len());
-        unsafe { &*ptr }
+    let items: Vec<u32> = (0..1000).chain(0..1000).collect();
+    let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    let mut counting = FilterBuilder::new(1000, 0.01).build_counting_bloom_filter();
+    for filter in [&mut bloom as &mut dyn Membership, &mut counting] {
+        assert_eq!(filter.estimated_fpp(), 0.0);
     }
+    let unique = dedup(&mut bloom, &items);
+    assert_eq!(unique.len() > 980 && unique.len() <= 1000, true);
+    assert_eq!(dedup(&mut counting, &items), unique);
 
-    /// Return the underlying u64 vector of the Bloom filter.
-    pub fn get_u64_array(&self) -> &[u64] {
-        let storage = &self.bit_set.storage;
-        let ptr = storage.as_ptr() as *const u64;
-        #[cfg(target_pointer_width = "64")]
-            let ptr = slice_from_raw_parts(ptr, storage.len());
-        if cfg!(target_pointer_width= "32") {
-            if storage.len() % 2 != 0 {
-                panic!("BloomBitVec with len {} can't export as u64 array!", storage.len())
-            }
+    let fpp = bloom.estimated_fpp();
+    assert_eq!(fpp > 0.001 && fpp < 0.03, true);
+    assert_eq!((counting.estimated_fpp() - fpp).abs() < 1e-9, true);
+    counting.clear();
+    assert_eq!(counting.estimated_fpp(), 0.0);
+
+    // a filter without an estimate of its own
+    struct Exact(std::collections::HashSet<Vec<u8>>);
+
+    impl Membership for Exact {
+        fn add(&mut self, element: &[u8]) {
+            self.0.insert(element.to_vec());
         }
-        #[cfg(target_pointer_width = "32")]
-            let ptr = slice_from_raw_parts(ptr, storage.len() / 2usize);
 
-        unsafe { &*ptr }
-    }
+        fn contains(&self, element: &[u8]) -> bool {
+            self.0.contains(element)
+        }
+
+        fn get_hash_indices(&self, _: &[u8]) -> Vec<u64> {
+            Vec::new()
+        }
 
+        fn contains_hash_indices(&self, _: &Vec<u64>) -> bool {
+            false
+        }
 
-    /// Performs the union operation on two compatible bloom filters. This is achieved through a
-    /// bitwise OR operation on their bit vectors. This operations is lossless, i.e. no elements
-    /// are lost and the bloom filter is the same that would have resulted if all elements wer
-    /// directly inserted in just one bloom filter.
-    pub fn union(&mut self, other: &BloomFilter) -> bool {
-        if self.compatible(other) {
-            self.bit_set.or(&other.bit_set);
-            true
-        } else { false }
+        fn clear(&mut self) {
+            self.0.clear();
+        }
     }
 
-    /// Performs the intersection operation on two compatible bloom filters. This is achieved
-    /// through a bitwise AND operation on their bit vectors. The operations doesn't introduce
-    /// any false negatives but it does raise the false positive probability. The the false
-    /// positive probability in the resulting Bloom filter is at most the false-positive probability
-    /// in one of the constituent bloom filters
-    pub fn intersect(&mut self, other: &BloomFilter) -> bool {
-        if self.compatible(other) {
-            self.bit_set.and(&other.bit_set);
-            true
-        } else { false }
+    let mut exact = Exact(Default::default());
+    assert_eq!(dedup(&mut exact, &items).len(), 1000);
+    assert_eq!(exact.estimated_fpp().is_nan(), true);
+}
+
+#[test]
+fn bloom_debug_test() {
+    let mut small = FilterBuilder::new(100, 0.01).build_bloom_filter();
+    small.add(b"hello");
+    let mut large = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
+    large.add(b"hello");
+    for bloom in [&small, &large] {
+        let debug = format!("{:?}", bloom);
+        assert_eq!(debug.len() < 300, true, "{}", debug);
+        assert_eq!(debug.contains("set_bits: 7"), true, "{}", debug);
+        assert_eq!(debug.contains("bits elided"), true);
+        let alternate = format!("{:#?}", bloom);
+        assert_eq!(alternate.len() < 1000, true, "{}", alternate);
+        assert_eq!(alternate.contains("first_words"), true);
+        assert_eq!(format!("{:?}", bloom.snapshot()).len() < 300, true);
     }
+}
 
-    /// Returns [true] if the Bloom filter does not contain any elements
-    pub fn is_empty(&self) -> bool {
-        self.bit_set.is_empty()
+#[test]
+fn bloom_repeat_insert_test() {
+    let mut repeat = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    builder.enable_repeat_insert(false);
+    let mut skip = builder.build_bloom_filter();
+    for i in 0..20_000u64 {
+        let element = (i % 2000).to_le_bytes();
+        repeat.add(element);
+        skip.add(element);
     }
+    skip.add_all([b"hello", b"hello"]);
+    repeat.add_all([b"hello", b"hello"]);
+    assert_eq!(skip.to_u64_vec(), repeat.to_u64_vec());
+    assert_eq!(repeat.len(), 20_002);
+    assert_eq!(skip.len() <= 2001, true);
 
-    pub(crate) fn set_bit_vec(&mut self, bit_vec: BloomBitVec) {
-        assert_eq!(self.config.size, bit_vec.nbits as u64);
-        self.bit_set = bit_vec
+    // present elements leave shared bits alone
+    let snapshot = skip.snapshot();
+    assert_eq!(skip.insert(7u64.to_le_bytes()), false);
+    assert_eq!(Arc::ptr_eq(&skip.bit_set, &snapshot.bit_set), true);
+    assert_eq!(skip.insert(b"world"), true);
+    assert_eq!(Arc::ptr_eq(&skip.bit_set, &snapshot.bit_set), false);
+}
+
+#[test]
+fn bloom_power_of_two_test() {
+    for m in [64u64, 1 << 20, 1 << 40] {
+        for hash in [0u64, 1, 12345, u64::MAX, 0xdead_beef_cafe_babe] {
+            assert_eq!(reduce(hash, m), hash % m);
+        }
     }
 
-    /// Checks if two Bloom filters are compatible, i.e. have compatible parameters (hash function,
-    /// size, etc.)
-    fn compatible(&self, other: &BloomFilter) -> bool {
-        self.config.is_compatible_to(&other.config)
+    let mut builder = FilterBuilder::new(100_000, 0.01);
+    builder.force_power_of_two_size(true);
+    let mut bloom = builder.build_bloom_filter();
+    assert_eq!(bloom.config().size.is_power_of_two(), true);
+    for i in 0..100_000u64 {
+        bloom.add(i.to_le_bytes());
+    }
+    for i in 0..100_000u64 {
+        assert_eq!(bloom.contains(i.to_le_bytes()), true);
     }
-}
 
-/// A Counting Bloom filter works in a similar manner as a regular Bloom filter; however, it is
-/// able to keep track of insertions and deletions. In a counting Bloom filter, each entry in the
-/// Bloom filter is a small counter associated with a basic Bloom filter bit.
-///
-/// **Reference**: F. Bonomi, M. Mitzenmacher, R. Panigrahy, S. Singh, and G. Varghese, “An Improved
-/// Construction for Counting Bloom Filters,” in 14th Annual European Symposium on
-/// Algorithms, LNCS 4168, 2006
-#[derive(Clone)]
-#[derive(Debug)]
-pub struct CountingBloomFilter {
-    config: FilterBuilder,
-    counting_vec: CountingVec,
+    // a filter of the same size and hashes built without the option is interchangeable
+    let mut other =
+        FilterBuilder::from_size_and_hashes(bloom.config().size, bloom.hashes()).build_bloom_filter();
+    other.add(b"hello");
+    assert_eq!(other.union(&bloom), true);
+    assert_eq!(other.contains(b"hello") && other.contains(7u64.to_le_bytes()), true);
 }
 
-macro_rules! get_array {
-    ($name:ident, $native:ty, $len:expr) => {
-        impl CountingBloomFilter {
-            pub fn $name(&self) -> &[$native] {
-                let ptr = self.counting_vec.storage.as_ptr() as *const $native;
-                #[cfg(target_pointer_width = "64")]
-                    let arr = slice_from_raw_parts(ptr, self.counting_vec.storage.len() * $len);
-                #[cfg(target_pointer_width = "32")]
-                    if cfg!(target_pointer_width= "32") {
-                        if self.counting_vec.storage.len() % 2 != 0 {
-                            panic!("CountingVec with len {} can't export as u64 array!", self.counting_vec.storage.len())
-                        }
-                    }
-                #[cfg(target_pointer_width = "32")]
-                    let arr = slice_from_raw_parts(ptr, self.counting_vec.storage.len() * $len / 2);
-                unsafe { &*arr }
+#[test]
+fn bloom_exact_size_test() {
+    // not a multiple of 8, of 32 + 8 and of 64 + 32
+    for size in [1001u64, 1000, 1056] {
+        let mut bloom = FilterBuilder::from_size_and_hashes(size, 4).build_bloom_filter();
+        for i in 0..100u64 {
+            bloom.add(i.to_le_bytes());
+        }
+        let copies = [
+            BloomFilter::from_u8_array_with_size(bloom.get_u8_array(), size, 4),
+            BloomFilter::from_u64_array_with_size(&bloom.to_u64_vec(), size, 4),
+        ];
+        for copy in copies {
+            assert_eq!(copy.config().size, size);
+            assert_eq!(copy.compatible(&bloom), true);
+            assert_eq!(copy, bloom);
+            for i in 0..100u64 {
+                assert_eq!(copy.contains(i.to_le_bytes()), true);
             }
         }
-    };
-}
-
-get_array!(get_u8_array, u8, 8);
-get_array!(get_u16_array, u16, 4);
-get_array!(get_u32_array, u32, 2);
-get_array!(get_u64_array, u64, 1);
-
-impl CountingBloomFilter {
-    pub fn new(mut config: FilterBuilder) -> Self {
-        config.complete();
-        #[cfg(target_pointer_width = "64")]
-            let counting_vec = CountingVec::new((config.size >> 4) as usize);
-        #[cfg(target_pointer_width = "32")]
-            let counting_vec = CountingVec::new((config.size >> 3) as usize);
-        CountingBloomFilter { config, counting_vec }
     }
 
-    pub(crate) fn set_counting_vec(&mut self, counting_vec: CountingVec) {
-        assert_eq!(self.config.size, counting_vec.counters as u64);
-        self.counting_vec = counting_vec
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.exact_size(true);
+    let mut cbf = builder.build_counting_bloom_filter();
+    assert_eq!(cbf.config().size, 9586);
+    for i in 0..1000u64 {
+        cbf.add(&i.to_le_bytes());
     }
-
-    /// Checks if two Counting Bloom filters are compatible, i.e. have compatible parameters (hash
-    /// function, size, etc.)
-    fn compatible(&self, other: &BloomFilter) -> bool {
-        self.config.is_compatible_to(&other.config)
+    for i in 0..1000u64 {
+        assert_eq!(cbf.contains(&i.to_le_bytes()), true);
+        cbf.remove(&i.to_le_bytes());
     }
+}
 
-    /// Returns the configuration/builder of the Bloom filter.
-    /// # Examples
-    ///
-    /// ```rust
-    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
-    ///
-    /// let bloom = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
-    /// let builder = bloom.config();
-    /// ```
-    ///
-    pub fn config(&self) -> FilterBuilder {
-        self.config.clone()
+#[test]
+fn bloom_expected_fpp_for_test() {
+    for (size, hashes, n) in [(100_000u64, 5u32, 10_000u64), (64 * 1024, 3, 30_000)] {
+        let mut bloom = FilterBuilder::from_size_and_hashes(size, hashes).build_bloom_filter();
+        for i in 0..n {
+            bloom.add(i.to_le_bytes());
+        }
+        let probes = 100_000u64;
+        let false_positives = (n..n + probes).filter(|i| bloom.contains(i.to_le_bytes())).count();
+        let measured = false_positives as f64 / probes as f64;
+        let expected = bloom.expected_fpp_for(n);
+        assert_eq!(expected, crate::math::fpp(size, n, hashes));
+        assert_eq!((measured - expected).abs() < expected * 0.15, true,
+                   "measured {} expected {}", measured, expected);
     }
 }
 
-macro_rules! from_array {
-    ($name:ident, $native:ty, $num:expr) => {
-        impl CountingBloomFilter {
-            pub fn $name(array: &[$native], hashes: u32, enable_repeat_insert:bool) -> Self {
-                let mut config =
-                    FilterBuilder::from_size_and_hashes((array.len() * $num) as u64, hashes);
-                config.enable_repeat_insert(enable_repeat_insert);
-                config.complete();
-                #[cfg(target_pointer_width = "64")]
-                    let mut counting_vec = CountingVec::new((config.size >> 4) as usize);
-                #[cfg(target_pointer_width = "32")]
-                    let mut counting_vec = CountingVec::new((config.size >> 3) as usize);
+#[test]
+fn bloom_from_bytes_config_test() {
+    let bloom = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
+    let copy = BloomFilter::from_u8_array(bloom.get_u8_array(), bloom.hashes());
+    let config = copy.config();
+    assert_eq!((config.size, config.hashes), (958505856, 7));
+    assert_eq!(config.expected_elements, (958505856f64 * 2f64.ln() / 7.0).ceil() as u64);
+    assert_eq!(config.false_positive_probability, 0.5f64.powi(7));
+    assert_eq!(config.false_positive_probability < 0.01, true);
+    assert_eq!(copy.expected_fpp_for(100_000_000), bloom.expected_fpp_for(100_000_000));
 
-                let ptr = array.as_ptr() as *const usize;
-                #[cfg(target_pointer_width = "64")]
-                    let usize_array = slice_from_raw_parts(ptr, (config.size >> 4) as usize);
-                #[cfg(target_pointer_width = "32")]
-                    let usize_array = slice_from_raw_parts(ptr, (config.size >> 3) as usize);
+    let cbf = CountingBloomFilter::from_u64_array(&[0u64; 128], 4, true);
+    assert_eq!(cbf.config().expected_elements, (2048f64 * 2f64.ln() / 4.0).ceil() as u64);
+}
+
+#[test]
+fn bloom_with_size_and_hashes_test() {
+    let mut bloom = BloomFilter::with_size_and_hashes(958505856, 7);
+    let mut built = FilterBuilder::new(100_000_000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.compatible(&built), true);
+    assert_eq!(bloom.config().fingerprint(),
+               FilterBuilder::from_size_and_hashes(958505856, 7).fingerprint());
+    built.add(b"hello");
+    assert_eq!(bloom.union(&built), true);
+    assert_eq!(bloom.contains(b"hello"), true);
 
-                counting_vec.storage.copy_from_slice(unsafe { &*usize_array });
+    // unaligned bytes
+    let bytes = bloom.get_u8_array().to_vec();
+    let mut shifted = vec![0u8; bytes.len() + 1];
+    shifted[1..].copy_from_slice(&bytes);
+    let config = bloom.config();
+    assert_eq!(BloomFilter::from_u8_array_with_config(&shifted[1..], config), bloom);
 
-                CountingBloomFilter { config, counting_vec }
-            }
-        }
-    };
+    for (size, hashes) in [(0, 7), (1000, 0)] {
+        let res = std::panic::catch_unwind(|| BloomFilter::with_size_and_hashes(size, hashes));
+        assert_eq!(res.is_err(), true);
+    }
 }
 
-from_array!(from_u8_array, u8, 2);
-from_array!(from_u16_array, u16, 4);
-from_array!(from_u32_array, u32, 8);
-from_array!(from_u64_array, u64, 16);
+#[test]
+fn bloom_try_errors_test() {
+    let mut bloom = FilterBuilder::from_size_and_hashes(1000, 4).build_bloom_filter();
+    bloom.add(b"hello");
+    let bytes = bloom.get_u8_array();
+    assert_eq!(BloomFilter::try_from_u8_array_with_size(bytes, 1000, 4).unwrap(), bloom);
+    assert_eq!(BloomFilter::try_from_u8_array_with_size(bytes, 0, 4).unwrap_err(),
+               FastBloomError::InvalidParameters(BuildError::ZeroSize));
+    assert_eq!(BloomFilter::try_from_u8_array_with_size(bytes, 1000, 0).unwrap_err(),
+               FastBloomError::InvalidParameters(BuildError::ZeroHashes));
+    let e = BloomFilter::try_from_u8_array_with_size(&bytes[..100], 1000, 4).unwrap_err();
+    assert_eq!(e.to_string(),
+               "can't deserialize bloom filter: u8 array with len 100 is too short for 1000 bits");
 
-impl CountingBloomFilter {
-    /// Get the estimate count for element in this counting bloom filter.
-    /// See: https://github.com/yankun1992/fastbloom/issues/3
-    pub fn estimate_count(&self, element: &[u8]) -> usize {
-        let m = self.config.size;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
+    let indices = bloom.get_hash_indices(b"hello");
+    assert_eq!(bloom.try_contains_hash_indices(&indices), Ok(true));
+    assert_eq!(bloom.try_contains_hash_indices(&[1, 1000]),
+               Err(FastBloomError::IndexOutOfRange { index: 1000, size: 1000 }));
 
-        let mut res = self.counting_vec.get(hash1 as usize);
-        if res == 0 { return 0; }
+    let other = FilterBuilder::from_size_and_hashes(1000, 5).build_bloom_filter();
+    let e: FastBloomError = bloom.try_union(&other).unwrap_err().into();
+    assert_eq!(e.to_string(), "incompatible bloom filters: hashes 4 != 5");
+}
 
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            let count = self.counting_vec.get(mo);
-            if count == 0 { return 0; } else { res = min(count, res) }
-        }
+#[test]
+fn bloom_seed_test() {
+    let m = 1_000_003;
+    let legacy = FilterBuilder::from_size_and_hashes(m, 7).with_hash_algorithm(HashAlgorithm::Xxh3)
+        .with_hash_version(1);
+    let mut unseeded = legacy.clone().build_bloom_filter();
+    let mut zero = legacy.clone().with_seed(0).build_bloom_filter();
+    let mut seeded = legacy.clone().with_seed(42).build_bloom_filter();
+    assert_eq!((unseeded.seed(), seeded.seed()), (0, 42));
 
-        res
+    // a zero seed places the bits like before seeds existed
+    let hash1 = xxh3_64_with_seed(b"hello", 0) % m;
+    let hash2 = xxh3_64_with_seed(b"hello", 32) % m;
+    let expected: Vec<u64> = (0..7).map(|i| (hash1 + i * hash2) % m).collect();
+    assert_eq!(unseeded.get_hash_indices(b"hello"), expected);
+    assert_eq!(expected, vec![347986, 809797, 271605, 733416, 195224, 657035, 118843]);
+
+    for i in 0..1000u32 {
+        unseeded.add(i.to_le_bytes());
+        zero.add(i.to_le_bytes());
+        seeded.add(i.to_le_bytes());
     }
+    assert_eq!(zero, unseeded);
+    assert_eq!(zero.fingerprint(), unseeded.fingerprint());
+    let copy = BloomFilter::from_u8_array_with_config(unseeded.get_u8_array(), legacy);
+    assert_eq!(copy, unseeded);
 
-    /// Get the underlying counter at index.
-    pub fn counter_at(&self, index: u64) -> usize {
-        self.counting_vec.get(index as usize)
+    // other seeds place the same elements on other bits
+    assert_ne!(seeded.get_hash_indices(b"hello"), unseeded.get_hash_indices(b"hello"));
+    assert_ne!(seeded.to_u64_vec(), unseeded.to_u64_vec());
+    assert_eq!(seeded.count_ones() > 0, true);
+    assert_ne!(seeded.config().fingerprint(), unseeded.config().fingerprint());
+    for i in 0..1000u32 {
+        assert_eq!(seeded.contains(i.to_le_bytes()), true);
+        assert_eq!(seeded.as_filter_ref().contains(i.to_le_bytes()), true);
     }
-}
+    assert_eq!(BloomFilter::from_u8_array_with_size(seeded.get_u8_array(), m, 7)
+                   .contains(0u32.to_le_bytes()), false);
 
-impl Membership for CountingBloomFilter {
-    fn add(&mut self, element: &[u8]) {
-        let m = self.config.size;
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
+    // only filters with the same seed can be combined
+    assert_ne!(seeded, unseeded);
+    let report = seeded.compatibility_report(&unseeded);
+    assert_eq!(report.seed, Some((42, 0)));
+    assert_eq!(report.to_string(), "seed 42 != 0");
+    assert_eq!(seeded.try_union(&unseeded),
+               Err(IncompatibleFilterError::Seed { left: 42, right: 0 }));
+    let mut small = FilterBuilder::from_size_and_hashes(1 << 16, 7).with_seed(42)
+        .build_bloom_filter();
+    let big = FilterBuilder::from_size_and_hashes(1 << 18, 7).build_bloom_filter();
+    assert_eq!(small.union_folding(&big),
+               Err(IncompatibleFilterError::Seed { left: 42, right: 0 }));
+    let mut big = FilterBuilder::from_size_and_hashes(1 << 18, 7).with_seed(42)
+        .build_bloom_filter();
+    big.add(b"hello");
+    assert_eq!(big.fold(4).unwrap().seed(), 42);
+    assert_eq!(small.union_folding(&big), Ok(()));
+    assert_eq!(small.contains(b"hello"), true);
 
-        let mut res = self.counting_vec.get(hash1 as usize) > 0;
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res = res && (self.counting_vec.get(mo) > 0);
-        }
+    let frozen = seeded.clone().freeze();
+    assert_eq!(frozen.seed(), 42);
+    assert_eq!(frozen.contains(0u32.to_le_bytes()), true);
+    assert_eq!(frozen.thaw(), seeded);
 
-        // contains and not enable repeat insert
-        if res && !self.config.enable_repeat_insert {
-            return;
-        }
+    let mut counting = FilterBuilder::new(10_000, 0.01).with_seed(42).build_counting_bloom_filter();
+    let plain = FilterBuilder::new(10_000, 0.01).build_counting_bloom_filter();
+    counting.add(b"hello");
+    assert_eq!(counting.contains(b"hello"), true);
+    assert_eq!(counting.estimate_count(b"hello"), 1);
+    assert_ne!(counting.get_hash_indices(b"hello"), plain.get_hash_indices(b"hello"));
+    counting.remove(b"hello");
+    assert_eq!(counting.contains(b"hello"), false);
+}
 
-        // insert
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            self.counting_vec.increment(mo);
+#[cfg(test)]
+struct TestFnvHasher;
+
+/// FNV-1a with the murmur3 finalizer, which FNV alone needs to spread short keys.
+#[cfg(test)]
+impl BloomHasher for TestFnvHasher {
+    fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+        let fnv = |basis: u64| {
+            let mut h = data.iter()
+                .fold(basis, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+            h = (h ^ (h >> 33)).wrapping_mul(0xff51afd7ed558ccd);
+            h = (h ^ (h >> 33)).wrapping_mul(0xc4ceb9fe1a85ec53);
+            h ^ (h >> 33)
         };
-        self.counting_vec.increment(hash1 as usize);
+        (fnv(0xcbf29ce484222325 ^ seed), fnv(0x84222325cbf29ce4 ^ seed))
     }
 
-    #[inline]
-    fn contains(&self, element: &[u8]) -> bool {
-        let m = self.config.size;
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
-
-        let mut res = self.counting_vec.get(hash1 as usize) > 0;
-        if !res { return false; }
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res = res && (self.counting_vec.get(mo) > 0);
-            if !res { return false; }
-        }
-        res
+    fn name(&self) -> &str {
+        "fnv1a"
     }
+}
 
-    fn get_hash_indices(&self, element: &[u8]) -> Vec<u64> {
-        let m = self.config.size;
-        let mut res = Vec::<u64>::with_capacity(self.config.size as usize);
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
-        res.push(hash1);
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res.push(mo as u64);
-        }
-        res
-    }
+#[test]
+fn bloom_custom_hasher_test() {
+    use crate::hasher::{Murmur3Hasher, Xxh128Hasher};
 
-    fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool {
-        for x in indices.iter() {
-            let index = *x;
-            if self.counting_vec.get(index as usize) == 0 { return false; }
-        }
-        true
+    let mut bloom = FilterBuilder::new(10_000, 0.01).with_hasher(TestFnvHasher)
+        .build_bloom_filter();
+    for i in 0..10_000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    for i in 0..10_000u32 {
+        assert_eq!(bloom.contains(i.to_le_bytes()), true);
     }
+    let false_positives = (10_000..110_000u32).filter(|i| bloom.contains(i.to_le_bytes())).count();
+    assert_eq!(false_positives < 1500, true, "{}", false_positives);
 
-    fn clear(&mut self) {
-        self.counting_vec.clear()
+    let mut default = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    assert_eq!(bloom.try_union(&default), Err(IncompatibleFilterError::Hasher {
+        left: "fnv1a".to_string(),
+        right: "xxh128".to_string(),
+    }));
+    assert_eq!(bloom.compatibility_report(&default).to_string(), "hasher fnv1a != xxh128");
+    assert_ne!(bloom.config().fingerprint(), default.config().fingerprint());
+    let frozen = bloom.clone().freeze();
+    assert_eq!(frozen.contains(1u32.to_le_bytes()), true);
+    assert_eq!(frozen.thaw(), bloom);
+
+    // the built-in hashers
+    let mut xxh128 = FilterBuilder::new(10_000, 0.01).with_hasher(Xxh128Hasher)
+        .build_bloom_filter();
+    xxh128.add(b"hello");
+    default.add(b"hello");
+    assert_eq!(xxh128, default);
+    let murmur = FilterBuilder::new(10_000, 0.01).with_hasher(Murmur3Hasher).build_bloom_filter();
+    let m = murmur.config().size;
+    let hash = fastmurmur3::murmur3_x64_128(b"hello", 0);
+    let (hash1, hash2) = ((hash as u64) % m, ((hash >> 64) as u64) % m);
+    let expected: Vec<u64> = (0..7).map(|i| (hash1 + i * hash2) % m).collect();
+    assert_eq!(murmur.get_hash_indices(b"hello"), expected);
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&bloom).unwrap();
+        let mut copy: BloomFilter = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.config().hasher, HashAlgorithm::Unresolved("fnv1a".to_string()));
+        let unresolved = std::panic::AssertUnwindSafe(&copy);
+        let res = std::panic::catch_unwind(|| unresolved.contains(b"hello"));
+        assert_eq!(res.is_err(), true);
+        assert_eq!(copy.set_hasher(Murmur3Hasher), Err(IncompatibleFilterError::Hasher {
+            left: "fnv1a".to_string(),
+            right: "murmur3_x64_128".to_string(),
+        }));
+        assert_eq!(copy.set_hasher(TestFnvHasher), Ok(()));
+        assert_eq!(copy, bloom);
+        assert_eq!(copy.contains(1u32.to_le_bytes()), true);
+
+        let json = serde_json::to_string(&default).unwrap();
+        let copy: BloomFilter = serde_json::from_str(&json).unwrap();
+        assert_eq!(matches!(copy.config().hasher, HashAlgorithm::Xxh128), true);
+        assert_eq!(copy.contains(b"hello"), true);
     }
 }
 
-impl Deletable for CountingBloomFilter {
-    fn remove(&mut self, element: &[u8]) {
-        let m = self.config.size;
-        // let hash1 = (murmur3_x64_128(element, 0) % m) as u64;
-        // let hash2 = (murmur3_x64_128(element, 32) % m) as u64;
-        let hash1 = xxh3_64_with_seed(element, 0) % m;
-        let hash2 = xxh3_64_with_seed(element, 32) % m;
+#[test]
+fn bloom_reserved_hasher_name_test() {
+    struct Impostor(&'static str);
 
-        let mut res = self.counting_vec.get(hash1 as usize) > 0;
-        // let m = self.config.size;
-        for i in 1..self.config.hashes as u64 {
-            let mo = ((hash1 + i * hash2) % m) as usize;
-            res = res && (self.counting_vec.get(mo) > 0);
+    impl BloomHasher for Impostor {
+        fn hash128(&self, data: &[u8], _: u64) -> (u64, u64) {
+            (data.len() as u64, 1)
         }
 
-        // contains
-        if res {
-            for i in 1..self.config.hashes as u64 {
-                let mo = ((hash1 + i * hash2) % m) as usize;
-                self.counting_vec.decrement(mo);
-            };
-            self.counting_vec.decrement(hash1 as usize);
+        fn name(&self) -> &str {
+            self.0
         }
     }
-}
 
-impl Hashes for CountingBloomFilter {
-    fn hashes(&self) -> u32 {
-        self.config.hashes
+    for name in ["xxh128", "xxh3", "murmur3_x64_128", "murmur64a", "pybloom_md5",
+                 "siphash13:0123456789abcdef"] {
+        let res = std::panic::catch_unwind(|| FilterBuilder::new(1000, 0.01)
+            .with_hasher(Impostor(name)));
+        assert_eq!(res.is_err(), true, "{}", name);
     }
+    let config = FilterBuilder::new(1000, 0.01).with_hasher(Impostor("xxh128-like"));
+    assert_eq!(config.hasher.name(), "xxh128-like");
+    let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        bloom.set_hasher(Impostor("xxh128"))
+    }));
+    assert_eq!(res.is_err(), true);
+    assert_eq!(bloom.set_hasher(crate::Xxh128Hasher), Ok(()));
+    assert_eq!(matches!(bloom.config().hasher, HashAlgorithm::Xxh128), true);
 }
 
-/// A Partitioned Bloom Filter is a variation of a classic Bloom Filter.
-///
-/// This filter works by partitioning the M-sized bit array into k slices of size `m = M/k` bits,
-/// `k = nb of hash functions` in the filter. Each hash function produces an index over `m` for its
-/// respective slice. Thus, each element is described by exactly `k` bits, meaning the distribution
-/// of false positives is uniform across all elements.
-///
-/// Be careful, as a Partitioned Bloom Filter have much higher collison risks that a classic
-/// Bloom Filter on small sets of data.
-///
-/// **Reference**: Chang, F., Feng, W. C., & Li, K. (2004, March). Approximate caches for packet
-/// classification. In INFOCOM 2004. Twenty-third AnnualJoint Conference of the IEEE Computer and
-/// Communications Societies (Vol. 4, pp. 2196-2207). IEEE.
-/// [Full text article](http://citeseerx.ist.psu.edu/viewdoc/download?doi=10.1.1.153.6902&rep=rep1&type=pdf)
-#[derive(Clone)]
-#[derive(Debug)]
-pub(crate) struct PartitionedBloomFilter {}
-
-impl PartitionedBloomFilter {}
-
-/// A Scalable Bloom Filter is a variant of Bloom Filters that can adapt dynamically to the number
-/// of elements stored, while assuring a maximum false positive probability.
-///
-/// **Reference**: ALMEIDA, Paulo Sérgio, BAQUERO, Carlos, PREGUIÇA, Nuno, et al. Scalable bloom
-/// filters. Information Processing Letters, 2007, vol. 101, no 6, p. 255-261.
-/// [Full text article](https://citeseerx.ist.psu.edu/viewdoc/download?doi=10.1.1.725.390&rep=rep1&type=pdf)
-#[derive(Clone)]
-#[derive(Debug)]
-pub(crate) struct ScalableBloomFilter {}
-
-impl ScalableBloomFilter {}
-
-/// An Invertible Bloom Filters (IBLT), also called Invertible Bloom Lookup Table, is a
-/// space-efficient and probabilistic data-structure for solving the set-difference problem
-/// efficiently without the use of logs or other prior context. It computes the set difference
-/// with communication proportional to the size of the difference between the sets being compared.
-/// They can simultaneously calculate D(A−B) and D(B−A) using O(d) space. This data structure
-/// encodes sets in a fashion that is similar in spirit to Tornado codes’ construction, in that it
-/// randomly combines elements using the XOR function.
-///
-/// **Reference**: Eppstein, D., Goodrich, M. T., Uyeda, F., & Varghese, G. (2011). What's the
-/// difference?: efficient set reconciliation without prior context. ACM SIGCOMM Computer
-/// Communication Review, 41(4), 218-229.
-/// [Full text article](http://www.sysnet.ucsd.edu/sysnet/miscpapers/EppGooUye-SIGCOMM-11.pdf)
-#[derive(Clone)]
-#[derive(Debug)]
-pub(crate) struct InvertibleBloomFilter {}
+#[test]
+fn bloom_hash_algorithm_fpp_test() {
+    for algorithm in [HashAlgorithm::Xxh3, HashAlgorithm::Murmur3] {
+        for (len, probes) in [(8usize, 100_000u64), (64, 100_000), (1024, 10_000)] {
+            let key = |i: u64| i.to_le_bytes().repeat(len / 8);
+            let mut bloom = FilterBuilder::new(10_000, 0.01)
+                .with_hash_algorithm(algorithm.clone())
+                .build_bloom_filter();
+            for i in 0..10_000 {
+                bloom.add(key(i));
+            }
+            assert_eq!((0..10_000).all(|i| bloom.contains(key(i))), true);
+            let false_positives = (10_000..10_000 + probes).filter(|&i| bloom.contains(key(i)))
+                .count();
+            let fpp = false_positives as f64 / probes as f64;
+            assert_eq!(fpp > 0.005 && fpp < 0.015, true, "{:?} {} {}", algorithm, len, fpp);
+        }
+    }
 
-impl InvertibleBloomFilter {}
+    let murmur = FilterBuilder::new(10_000, 0.01).with_hash_algorithm(HashAlgorithm::Murmur3)
+        .build_bloom_filter();
+    let custom = FilterBuilder::new(10_000, 0.01).with_hasher(crate::hasher::Murmur3Hasher)
+        .build_bloom_filter();
+    assert_eq!(murmur.compatible(&custom), true);
+    assert_eq!(murmur.compatible(&FilterBuilder::new(10_000, 0.01).build_bloom_filter()), false);
+}
 
-#[derive(Clone)]
-#[derive(Debug)]
-pub(crate) struct GarbledBloomFilter {}
+#[test]
+fn bloom_siphash_test() {
+    let keyed = |key: [u8; 16]| FilterBuilder::new(10_000, 0.01)
+        .with_hash_algorithm(HashAlgorithm::SipHash13 { key })
+        .build_bloom_filter();
+    let mut a = keyed([1; 16]);
+    let b = keyed([2; 16]);
+    assert_ne!(a.get_hash_indices(b"hello"), b.get_hash_indices(b"hello"));
+    assert_eq!(a.get_hash_indices(b"hello"), keyed([1; 16]).get_hash_indices(b"hello"));
+    assert_ne!(a.get_hash_indices(b"hello"),
+               FilterBuilder::new(10_000, 0.01).build_bloom_filter().get_hash_indices(b"hello"));
 
-impl GarbledBloomFilter {}
+    for i in 0..10_000u32 {
+        a.add(i.to_le_bytes());
+    }
+    assert_eq!((0..10_000u32).all(|i| a.contains(i.to_le_bytes())), true);
+    let false_positives = (10_000..110_000u32).filter(|i| a.contains(i.to_le_bytes())).count();
+    assert_eq!(false_positives > 500 && false_positives < 1500, true, "{}", false_positives);
 
+    // only filters with the same key are compatible
+    let report = a.compatibility_report(&b);
+    assert_eq!(report.hasher.is_some(), true);
+    assert_eq!(a.clone().try_union(&b).is_err(), true);
+    assert_eq!(a.config().fingerprint() != b.config().fingerprint(), true);
+    assert_eq!(keyed([1; 16]).compatible(&a), true);
 
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&a).unwrap();
+        let name = a.config().hasher.name().into_owned();
+        assert_eq!(json.contains(&format!(r#""hasher":"{}""#, name)), true);
+        assert_eq!(json.contains("key"), false);
+        let mut copy: BloomFilter = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.config().hasher, HashAlgorithm::Unresolved(name));
+        assert_eq!(copy.set_hash_algorithm(HashAlgorithm::SipHash13 { key: [2; 16] }).is_err(),
+                   true);
+        assert_eq!(copy.set_hash_algorithm(HashAlgorithm::SipHash13 { key: [1; 16] }), Ok(()));
+        assert_eq!(copy.contains(1u32.to_le_bytes()), true);
+        assert_eq!(copy, a);
+    }
+}
+
+/// The bits of `get_u8_array` of a filter of `FilterBuilder::new(10_000, 0.01)` with
+/// `0..1000u32` as little endian bytes added, saved by the version before the hasher was stored.
+#[cfg(target_endian = "little")]
 #[test]
-fn bloom_test() {
-    let mut builder =
-        FilterBuilder::new(10_000_000, 0.01);
-    let mut bloom = builder.build_bloom_filter();
-    println!("{:?}", bloom.config);
-    bloom.add(b"hello");
-    println!("{:?}", &bloom.bit_set.storage[0..300]);
-    assert_eq!(bloom.contains(b"hello"), true);
-    assert_eq!(bloom.contains(b"world"), false);
-    assert_eq!(bloom.add_if_not_contains(b"hello2"), false);
-    assert_eq!(bloom.contains(b"hello2"), true);
+fn bloom_raw_bits_pre_series_test() {
+    let bytes = include_bytes!("../testvectors/bloom_raw_u8_array.bin");
+    assert_eq!(bytes.len(), 11984);
+    // the bits don't say how they were placed, the config of that version does
+    let legacy = FilterBuilder::new(10_000, 0.01).with_hash_algorithm(HashAlgorithm::Xxh3)
+        .with_hash_version(1);
+    let copy = BloomFilter::from_u8_array_with_config(bytes, legacy);
+    assert_eq!(copy.config().size, 95872);
+    assert_eq!(copy.hashes(), 7);
+    assert_eq!((0..1000u32).all(|i| copy.contains(i.to_le_bytes())), true);
+    let false_positives = (1000..101_000u32).filter(|i| copy.contains(i.to_le_bytes())).count();
+    assert_eq!(false_positives < 100, true, "{}", false_positives);
 
-    let storage = &bloom.bit_set.storage[0..300];
-    println!("{:?}", storage);
+    let raw = BloomFilter::from_u8_array(bytes, 7);
+    assert_eq!(raw.to_u64_vec(), copy.to_u64_vec());
+    assert_eq!(raw.compatible(&copy), false);
+}
 
-    #[cfg(target_pointer_width = "64")]{
-        let mut bloom2 = BloomFilter::from_u64_array(bloom.get_u64_array(), bloom.hashes());
-        assert_eq!(bloom2.compatible(&bloom), true);
-        assert_eq!(bloom2.contains(b"hello"), true);
-        assert_eq!(bloom2.contains(b"world"), false);
+#[test]
+fn bloom_legacy_hasher_test() {
+    let legacy = FilterBuilder::new(10_000, 0.01).with_hash_algorithm(HashAlgorithm::Xxh3)
+        .with_hash_version(1);
+    let mut old = legacy.clone().build_bloom_filter();
+    for i in 0..1000u32 {
+        old.add(i.to_le_bytes());
     }
+    let mut new = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    new.add(0u32.to_le_bytes());
+    assert_ne!(new.get_hash_indices(b"hello"), old.get_hash_indices(b"hello"));
+    assert_eq!(new.compatible(&old), false);
 
-    let mut bloom3 =
-        BloomFilter::from_u32_array(bloom.get_u32_array(), bloom.config.hashes);
-    assert_eq!(bloom3.compatible(&bloom), true);
-    assert_eq!(bloom3.contains(b"hello"), true);
-    assert_eq!(bloom3.contains(b"world"), false);
+    // the constructors from raw bits hash like the default config, others need their config
+    let bytes = new.get_u8_array().to_vec();
+    assert_eq!(BloomFilter::from_u8_array(&bytes, new.hashes()), new);
+    assert_eq!(BloomFilter::from_u64_array(new.get_u64_array(), new.hashes()), new);
+    let bytes = old.get_u8_array().to_vec();
+    let copy = BloomFilter::from_u8_array_with_config(&bytes, legacy);
+    assert_eq!(copy, old);
+    assert_eq!((0..1000u32).all(|i| copy.contains(i.to_le_bytes())), true);
+    assert_eq!(BloomFilter::from_u8_array(&bytes, old.hashes()).compatible(&old), false);
 
-    let u8_array = bloom.get_u8_array();
-    let mut bloom4 = BloomFilter::from_u8_array(u8_array, bloom.config.hashes);
-    println!("{:?}", &bloom4.bit_set.storage[0..300]);
-    assert_eq!(bloom4.compatible(&bloom), true);
-    assert_eq!(bloom4.contains(b"hello"), true);
-    assert_eq!(bloom4.contains(b"world"), false);
+    #[cfg(feature = "serde")]
+    {
+        // serialized before the seed, the hasher and the hash version were stored
+        let mut json = serde_json::to_value(&old).unwrap();
+        let config = json["config"].as_object_mut().unwrap();
+        config.remove("seed");
+        config.remove("hasher");
+        config.remove("hash_version");
+        let copy: BloomFilter = serde_json::from_value(json).unwrap();
+        assert_eq!(copy.config().hasher, HashAlgorithm::Xxh3);
+        assert_eq!(copy.to_u64_vec(), old.to_u64_vec());
+        assert_eq!((0..1000u32).all(|i| copy.contains(i.to_le_bytes())), true);
+        let again: BloomFilter = serde_json::from_str(&serde_json::to_string(&copy).unwrap())
+            .unwrap();
+        assert_eq!(again, old);
 
-    let bloom5 = BloomFilter::from_u16_array(bloom.get_u16_array(), bloom.hashes());
-    assert_eq!(bloom5.compatible(&bloom), true);
-    assert_eq!(bloom5.contains(b"hello"), true);
-    assert_eq!(bloom5.contains(b"world"), false);
+        let mut json = serde_json::to_value(old.clone().freeze()).unwrap();
+        let frozen = json.as_object_mut().unwrap();
+        frozen.remove("seed");
+        frozen.remove("hasher");
+        frozen.remove("hash_version");
+        let frozen: FrozenBloomFilter = serde_json::from_value(json).unwrap();
+        assert_eq!((0..1000u32).all(|i| frozen.contains(i.to_le_bytes())), true);
+    }
+}
 
-    bloom4.add(b"hello world");
+#[cfg(feature = "serde")]
+#[test]
+fn bloom_serde_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).with_seed(7)
+        .with_probe_scheme(ProbeScheme::EnhancedDouble)
+        .build_bloom_filter();
+    for i in 0..1000u32 {
+        bloom.add(i.to_le_bytes());
+    }
 
-    assert_eq!(bloom.intersect(&bloom4), true);
-    assert_eq!(bloom.contains(b"hello"), true);
-    assert_eq!(bloom.contains(b"hello world"), false);
+    let json = serde_json::to_value(&bloom).unwrap();
+    assert_eq!(json["bits"].as_array().unwrap().len() as u64, bloom.config().size / 8);
+    assert_eq!(json["inserted"], 1000);
+    let copy: BloomFilter = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), 1000);
+    assert_eq!((0..1000u32).all(|i| copy.contains(i.to_le_bytes())), true);
+    for i in 1000..10_000u32 {
+        assert_eq!(copy.contains(i.to_le_bytes()), bloom.contains(i.to_le_bytes()));
+    }
 
-    bloom3.add(b"hello world");
-    bloom3.add(b"hello yankun");
+    // binary formats store the bits as one byte string
+    let mut cbor = Vec::new();
+    ciborium::into_writer(&bloom, &mut cbor).unwrap();
+    assert_eq!(cbor.len() < bloom.config().size as usize / 8 + 512, true, "{}", cbor.len());
+    let copy: BloomFilter = ciborium::from_reader(&cbor[..]).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!((0..1000u32).all(|i| copy.contains(i.to_le_bytes())), true);
 
-    assert_eq!(bloom3.union(&bloom4), true);
-    assert_eq!(bloom3.contains(b"hello"), true);
-    assert_eq!(bloom3.contains(b"hello world"), true);
-    assert_eq!(bloom3.contains(b"hello yankun"), true);
+    let mut short = json.clone();
+    short["bits"].as_array_mut().unwrap().pop();
+    let e = serde_json::from_value::<BloomFilter>(short).err().unwrap();
+    assert_eq!(e.to_string().contains("bytes of bits for a size of 95872 bits, expected 11984"),
+               true, "{}", e);
+    let mut missing = json.clone();
+    missing.as_object_mut().unwrap().remove("bits");
+    assert_eq!(serde_json::from_value::<BloomFilter>(missing).is_err(), true);
+
+    // serialized before the bits were written as bytes
+    let mut legacy = json;
+    let legacy = legacy.as_object_mut().unwrap();
+    legacy.remove("bits");
+    legacy.remove("inserted");
+    legacy.insert("bit_set".to_string(), serde_json::to_value(&*bloom.bit_set).unwrap());
+    let copy: BloomFilter = serde_json::from_value(legacy.clone().into()).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), 0);
+    legacy["bit_set"]["nbits"] = 64.into();
+    assert_eq!(serde_json::from_value::<BloomFilter>(legacy.clone().into()).is_err(), true);
 }
 
 #[test]
-fn bloom_hash_indices_test() {
-    let mut builder =
-        FilterBuilder::new(10_000, 0.01);
-    let mut bloom = builder.build_bloom_filter();
-    println!("{:?}", bloom.config);
-    bloom.add(b"hello");
-    assert_eq!(bloom.contains(b"hello"), true);
-    assert_eq!(bloom.contains(b"world"), false);
+fn bloom_degenerate_hash2_test() {
+    let m = 64;
+    let mut config = FilterBuilder::from_size_and_hashes(m, 7);
+    // an element whose second hash is a multiple of the size
+    let key = (0..10_000u32).map(|i| i.to_le_bytes())
+        .find(|key| config.hasher.hash128(key, 0).1.is_multiple_of(m)).unwrap();
 
-    let indices = bloom.get_hash_indices(b"hello");
-    println!("{:?}", indices);
-    assert_eq!(bloom.contains_hash_indices(&indices), true);
-    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"world")), false);
+    let mut bloom = config.build_bloom_filter();
+    let mut indices = bloom.get_hash_indices(&key);
+    indices.sort();
+    indices.dedup();
+    assert_eq!(indices.len(), 7);
+    bloom.add(key);
+    assert_eq!(bloom.contains(key), true);
+    assert_eq!(bloom.count_ones(), 7);
+    assert_eq!(bloom.as_filter_ref().contains(key), true);
+    assert_eq!(bloom.clone().freeze().contains(key), true);
+
+    // version 1 puts all of them on one bit
+    let mut legacy = config.clone().with_hash_version(1).build_bloom_filter();
+    let indices = legacy.get_hash_indices(&key);
+    assert_eq!(indices.iter().all(|&index| index == indices[0]), true);
+    legacy.add(key);
+    assert_eq!(legacy.count_ones(), 1);
+    assert_eq!(bloom.try_union(&legacy), Err(IncompatibleFilterError::HashVersion {
+        left: 3,
+        right: 1,
+    }));
+    // version 2 replaces the reduced 0 by 1
+    let v2 = config.clone().with_hash_version(2).build_bloom_filter().get_hash_indices(&key);
+    assert_eq!(v2, (0..7).map(|i| (v2[0] + i) % m).collect::<Vec<u64>>());
+    assert_eq!(legacy.clone().freeze().thaw().config().hash_version, 1);
+
+    // for odd sizes an odd second hash can still be a multiple of the size
+    for m in [63, 101, 1001] {
+        let bloom = FilterBuilder::from_size_and_hashes(m, 7).build_bloom_filter();
+        let degenerate = (0..200_000u32)
+            .filter(|i| {
+                let indices = bloom.get_hash_indices(&i.to_le_bytes());
+                indices.iter().all(|&index| index == indices[0])
+            })
+            .count();
+        assert_eq!(degenerate, 0, "{}", m);
+    }
+    let key = (0..200_000u32).map(|i| i.to_le_bytes())
+        .find(|key| (config.hasher.hash128(key, 0).1 | 1).is_multiple_of(1001)).unwrap();
+    let mut indices = FilterBuilder::from_size_and_hashes(1001, 7).build_bloom_filter()
+        .get_hash_indices(&key);
+    indices.sort();
+    indices.dedup();
+    assert_eq!(indices.len(), 7);
 }
 
+#[test]
+fn bloom_probe_scheme_test() {
+    let (m, k) = (1000, 10);
+    let mut config = FilterBuilder::from_size_and_hashes(m, k as u32);
+    let (h1, h2) = config.hasher.hash128(b"hello", 0);
+    let (h1, h2) = reduce_pair(h1, h2, m, 2);
+    let h3 = config.hasher.hash128(b"hello", 1).0 % m;
+    let indices = |scheme: ProbeScheme| {
+        config.clone().with_probe_scheme(scheme).build_bloom_filter().get_hash_indices(b"hello")
+    };
+    let expected: Vec<u64> = (0..k).map(|i| (h1 + i * h2) % m).collect();
+    assert_eq!(indices(ProbeScheme::Double), expected);
+    let expected: Vec<u64> = (0..k).map(|i| (h1 + i * h2 + (i * i * i - i) / 6) % m).collect();
+    assert_eq!(indices(ProbeScheme::EnhancedDouble), expected);
+    let expected: Vec<u64> = (0..k).map(|i| (h1 + i * h2 + i * i.saturating_sub(1) / 2 * h3) % m)
+        .collect();
+    assert_eq!(indices(ProbeScheme::Triple), expected);
+
+    for scheme in [ProbeScheme::EnhancedDouble, ProbeScheme::Triple] {
+        let mut bloom = config.clone().with_probe_scheme(scheme).build_bloom_filter();
+        let mut counting = config.clone().with_probe_scheme(scheme).build_counting_bloom_filter();
+        for i in 0..100u32 {
+            bloom.add(i.to_le_bytes());
+            counting.add(&i.to_le_bytes());
+        }
+        assert_eq!((0..100u32).all(|i| bloom.contains(i.to_le_bytes())), true);
+        assert_eq!((0..100u32).all(|i| bloom.as_filter_ref().contains(i.to_le_bytes())), true);
+        assert_eq!((0..100u32).all(|i| counting.contains(&i.to_le_bytes())), true);
+        assert_eq!(bloom.clone().freeze().thaw(), bloom);
+        counting.remove(&7u32.to_le_bytes());
+        assert_eq!(counting.estimate_count(&7u32.to_le_bytes()), 0);
+
+        let double = config.build_bloom_filter();
+        assert_eq!(bloom.try_union(&double), Err(IncompatibleFilterError::ProbeScheme {
+            left: scheme,
+            right: ProbeScheme::Double,
+        }));
+    }
+}
 
 #[test]
-fn counting_bloom_test() {
-    let mut builder =
-        FilterBuilder::new(10_000, 0.01);
-    let mut bloom = builder.build_counting_bloom_filter();
+fn bloom_probe_scheme_fpp_test() {
+    // 10 hashes on 1024 bits with the optimal number of elements: a second hash divisible by a
+    // large power of two cycles through a few bits only with plain double hashing
+    let (m, k, n, filters, queries) = (1024, 10, 71, 100u64, 50_000u64);
+    let bound = crate::math::fpp(m, n, k);
+    let measure = |scheme: ProbeScheme| {
+        let mut config = FilterBuilder::from_size_and_hashes(m, k).with_probe_scheme(scheme);
+        let mut false_positives = 0;
+        for filter in 0..filters {
+            let mut bloom = config.build_bloom_filter();
+            for i in 0..n {
+                bloom.add((filter << 32 | i).to_le_bytes());
+            }
+            false_positives += (0..queries)
+                .filter(|i| bloom.contains((filter << 32 | 1 << 31 | i).to_le_bytes()))
+                .count();
+        }
+        false_positives as f64 / (filters * queries) as f64
+    };
+    let double = measure(ProbeScheme::Double);
+    let enhanced = measure(ProbeScheme::EnhancedDouble);
+    let triple = measure(ProbeScheme::Triple);
+    // the formula is for independent hashes on an infinitely large filter, on 1024 bits even
+    // independent hashes are about 2% above it, the rest of the margin is sampling noise
+    assert_eq!(enhanced <= bound * 1.2, true);
+    assert_eq!(triple <= bound * 1.2, true);
+    assert_eq!(double > bound * 1.5, true);
+}
+
+#[test]
+fn bloom_prehashed_test() {
+    // the reference outputs of SplitMix64
+    let mut state = 1234567;
+    assert_eq!([splitmix64(&mut state), splitmix64(&mut state), splitmix64(&mut state)],
+               [6457827717110365317, 3203168211198807973, 9817491932198370423]);
+
+    let hash = 0x8f3a_51c2_77d0_e914u64;
+    let mut config = FilterBuilder::from_size_and_hashes(1000, 7);
+    let mut bloom = config.build_bloom_filter();
+    bloom.add_hash(hash);
+    assert_eq!(bloom.contains_hash(hash), true);
+    assert_eq!(bloom.len(), 1);
+    // the documented derivation
+    let mut state = hash;
+    let (h1, h2) = (splitmix64(&mut state) % 1000, (splitmix64(&mut state) | 1) % 1000);
+    let mut expected: Vec<u64> = (0..7).map(|i| (h1 + i * h2) % 1000).collect();
+    expected.sort();
+    expected.dedup();
+    assert_eq!(bloom.iter_set_bits().collect::<Vec<_>>(), expected);
+    // deterministic across filters, different with another seed
+    let mut copy = config.build_bloom_filter();
+    copy.add_hash(hash);
+    assert_eq!(copy, bloom);
+    let mut seeded = config.clone().with_seed(1).build_bloom_filter();
+    seeded.add_hash(hash);
+    assert_ne!(seeded.to_u64_vec(), bloom.to_u64_vec());
 
+    // byte keys and pre-hashed keys don't see each other
+    let mut bytes = FilterBuilder::new(10_000, 0.001).build_bloom_filter();
+    let mut hashes = FilterBuilder::new(10_000, 0.001).build_bloom_filter();
+    for i in 0..10_000u64 {
+        bytes.add(i.to_le_bytes());
+        hashes.add_hash(i);
+    }
+    assert_eq!((0..10_000u64).all(|i| hashes.contains_hash(i)), true);
+    assert_eq!((0..10_000u64).filter(|&i| bytes.contains_hash(i)).count() < 50, true);
+    assert_eq!((0..10_000u64).filter(|i| hashes.contains(i.to_le_bytes())).count() < 50, true);
+    let key = xxh3_64_with_seed(b"hello", 0);
+    let mut bloom = FilterBuilder::new(10_000, 0.001).build_bloom_filter();
     bloom.add(b"hello");
+    assert_eq!(bloom.contains_hash(key), false);
+}
 
-    assert_eq!(bloom.contains(b"hello"), true);
+#[test]
+fn bloom_precomputed_test() {
+    let mut shards: Vec<BloomFilter> = [1000u64, 4096, 10_007, 1 << 20].iter()
+        .map(|&size| FilterBuilder::from_size_and_hashes(size, 7).build_bloom_filter())
+        .collect();
+    for i in 0..500u32 {
+        let hashes = shards[0].hash_key(&i.to_le_bytes());
+        for shard in shards.iter_mut().filter(|shard| i % 2 == 0 || shard.config().size != 4096) {
+            shard.add_precomputed(&hashes);
+        }
+    }
+    for shard in &shards {
+        let mut direct = FilterBuilder::from_size_and_hashes(shard.config().size, 7)
+            .build_bloom_filter();
+        for i in 0..500u32 {
+            if i % 2 == 0 || shard.config().size != 4096 {
+                direct.add(i.to_le_bytes());
+            }
+        }
+        assert_eq!(shard, &direct);
+        for i in 0..5000u32 {
+            let hashes = shards[0].hash_key(&i.to_le_bytes());
+            assert_eq!(shard.contains_precomputed(&hashes), direct.contains(i.to_le_bytes()));
+        }
+    }
 
-    bloom.remove(b"hello");
-    assert_eq!(bloom.contains(b"hello"), false);
+    for scheme in [ProbeScheme::EnhancedDouble, ProbeScheme::Triple] {
+        let mut bloom = FilterBuilder::from_size_and_hashes(1000, 7).with_probe_scheme(scheme)
+            .build_bloom_filter();
+        let hashes = bloom.hash_key(b"hello");
+        bloom.add_precomputed(&hashes);
+        assert_eq!(bloom.contains(b"hello"), true);
+        assert_eq!(bloom.len(), 1);
+    }
+
+    let bloom = FilterBuilder::from_size_and_hashes(1000, 7).build_bloom_filter();
+    let hashes = bloom.hash_key(b"hello");
+    let seeded = FilterBuilder::from_size_and_hashes(1000, 7).with_seed(1).build_bloom_filter();
+    assert_eq!(seeded.try_contains_precomputed(&hashes),
+               Err(IncompatibleFilterError::Seed { left: 1, right: 0 }));
+    let mut murmur = FilterBuilder::from_size_and_hashes(1000, 7)
+        .with_hash_algorithm(HashAlgorithm::Murmur3).build_bloom_filter();
+    assert_eq!(murmur.try_add_precomputed(&hashes), Err(IncompatibleFilterError::Hasher {
+        left: "murmur3_x64_128".to_string(),
+        right: "xxh128".to_string(),
+    }));
+    let legacy = FilterBuilder::from_size_and_hashes(1000, 7).with_hash_version(1)
+        .build_bloom_filter();
+    assert_eq!(legacy.try_contains_precomputed(&hashes),
+               Err(IncompatibleFilterError::HashVersion { left: 1, right: 3 }));
+    let triple = FilterBuilder::from_size_and_hashes(1000, 7)
+        .with_probe_scheme(ProbeScheme::Triple).build_bloom_filter();
+    assert_eq!(triple.try_contains_precomputed(&hashes),
+               Err(IncompatibleFilterError::ProbeScheme {
+                   left: ProbeScheme::Triple,
+                   right: ProbeScheme::Double,
+               }));
+    // hashes of a triple hashing filter are valid for the other schemes
+    let hashes = triple.hash_key(b"hello");
+    assert_eq!(bloom.try_contains_precomputed(&hashes), Ok(false));
+    let siphash = |key| FilterBuilder::from_size_and_hashes(1000, 7)
+        .with_hash_algorithm(HashAlgorithm::SipHash13 { key }).build_bloom_filter();
+    let hashes = siphash([1; 16]).hash_key(b"hello");
+    assert_eq!(siphash([1; 16]).try_contains_precomputed(&hashes), Ok(false));
+    assert_eq!(siphash([2; 16]).try_contains_precomputed(&hashes).is_err(), true);
 }
 
 #[test]
-fn counting_bloom_repeat_test() {
-    let mut builder = FilterBuilder::new(100_000, 0.01);
-    // enable_repeat_insert is true
-    builder.enable_repeat_insert(true);
-    let mut cbf = builder.build_counting_bloom_filter();
-    cbf.add(b"hello"); // modify underlying vector counter.
-    cbf.add(b"hello"); // modify underlying vector counter.
-    assert_eq!(cbf.contains(b"hello"), true);
-    cbf.remove(b"hello");
-    assert_eq!(cbf.contains(b"hello"), true);
-    cbf.remove(b"hello");
-    assert_eq!(cbf.contains(b"hello"), false);
+fn counting_bloom_remove_test() {
+    use std::collections::HashMap;
 
-    // enable_repeat_insert is false
-    builder.enable_repeat_insert(false);
-    let mut cbf = builder.build_counting_bloom_filter();
-    cbf.add(b"hello"); // modify underlying vector counter.
-    cbf.add(b"hello"); // not modify underlying vector counter because b"hello" has been added.
-    assert_eq!(cbf.contains(b"hello"), true);
-    cbf.remove(b"hello");
+    let mut cbf = FilterBuilder::new(10_000, 0.01).build_counting_bloom_filter();
+    for i in 0..1000u32 {
+        cbf.add(&i.to_le_bytes());
+    }
+    // adding and removing restores the counters
+    let before = cbf.get_u64_array().to_vec();
+    for i in 1000..3000u32 {
+        cbf.add(&i.to_le_bytes());
+    }
+    for i in 1000..3000u32 {
+        cbf.remove(&i.to_le_bytes());
+    }
+    assert_eq!(cbf.get_u64_array(), before);
+
+    // interleaved adds and removes, with the present elements counted aside
+    let mut present: HashMap<u32, u32> = (0..1000).map(|i| (i, 1)).collect();
+    let mut state = 7u64;
+    for _ in 0..50_000 {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let key = (state >> 33) as u32 % 5000;
+        // at most 3 times, so no counter gets stuck at 15
+        if state >> 32 & 1 == 0 && present.get(&key).is_none_or(|count| *count < 3) {
+            cbf.add(&key.to_le_bytes());
+            *present.entry(key).or_default() += 1;
+        } else if let Some(count) = present.get_mut(&key) {
+            cbf.remove(&key.to_le_bytes());
+            *count -= 1;
+            if *count == 0 {
+                present.remove(&key);
+            }
+        }
+        if state >> 40 & 63 == 0 {
+            for key in present.keys() {
+                assert_eq!(cbf.contains(&key.to_le_bytes()), true, "{}", key);
+            }
+        }
+    }
+    for (key, count) in &present {
+        for _ in 0..*count {
+            cbf.remove(&key.to_le_bytes());
+        }
+    }
+    assert_eq!(cbf.get_u64_array().iter().all(|word| *word == 0), true);
+
+    cbf.add(b"hello");
+    cbf.clear();
     assert_eq!(cbf.contains(b"hello"), false);
 }
 
 #[test]
-fn counting_bloom_from_test() {
-    let mut builder = FilterBuilder::new(10_000_000, 0.01);
-    let mut cbf = builder.build_counting_bloom_filter();
+fn counting_bloom_counter_size_test() {
+    let sizes = [CounterSize::U4, CounterSize::U8, CounterSize::U16];
+    let mut filters: Vec<CountingBloomFilter> = sizes.iter()
+        .map(|size| FilterBuilder::new(10_000, 0.01).with_counter_size(*size)
+            .build_counting_bloom_filter())
+        .collect();
+    let mut state = 11u64;
+    for _ in 0..20_000 {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let key = ((state >> 33) % 8000).to_le_bytes();
+        for cbf in &mut filters {
+            if state >> 32 & 3 == 0 {
+                cbf.remove(&key);
+            } else {
+                cbf.add(&key);
+            }
+        }
+    }
+    for key in 0..20_000u64 {
+        let answers: Vec<bool> = filters.iter().map(|cbf| cbf.contains(&key.to_le_bytes()))
+            .collect();
+        assert_eq!(answers, vec![answers[0]; 3], "{}", key);
+    }
 
-    cbf.add(b"hello");
-    cbf.add(b"hello");
+    let size = filters[0].config().size as usize;
+    assert_eq!(filters[0].memory_bytes(), size.div_ceil(64) * 32);
+    assert_eq!(filters[1].memory_bytes(), size.div_ceil(64) * 64);
+    assert_eq!(filters[2].memory_bytes(), size.div_ceil(64) * 128);
 
-    let mut cbf_copy = CountingBloomFilter::from_u8_array(cbf.get_u8_array(), builder.hashes, true);
-    assert_eq!(cbf_copy.contains(b"hello"), true);
-    cbf_copy.remove(b"hello");
-    assert_eq!(cbf_copy.contains(b"hello"), true);
-    cbf_copy.remove(b"hello");
-    assert_eq!(cbf_copy.contains(b"hello"), false);
+    // wider counters count further
+    for (cbf, size) in filters.iter_mut().zip(sizes) {
+        cbf.clear();
+        for _ in 0..300 {
+            cbf.add(b"hello");
+        }
+        assert_eq!(cbf.estimate_count(b"hello") as u64, size.max().min(300));
+    }
 
-    let mut cbf_copy = CountingBloomFilter::from_u16_array(cbf.get_u16_array(), builder.hashes, true);
-    assert_eq!(cbf_copy.contains(b"hello"), true);
-    cbf_copy.remove(b"hello");
-    assert_eq!(cbf_copy.contains(b"hello"), true);
-    cbf_copy.remove(b"hello");
-    assert_eq!(cbf_copy.contains(b"hello"), false);
+    let report = filters[0].compatibility_report(&filters[1]);
+    assert_eq!(report.counter_size, Some((CounterSize::U4, CounterSize::U8)));
+    assert_eq!(report.to_string(), "counter size 4 != 8 bits");
+    assert_eq!(report.first_mismatch(), Some(IncompatibleFilterError::CounterSize {
+        left: CounterSize::U4,
+        right: CounterSize::U8,
+    }));
+    assert_eq!(filters[1].compatibility_report(&filters[1]).is_compatible(), true);
+}
 
-    let mut cbf_copy = CountingBloomFilter::from_u32_array(cbf.get_u32_array(), builder.hashes, true);
-    assert_eq!(cbf_copy.contains(b"hello"), true);
-    cbf_copy.remove(b"hello");
-    assert_eq!(cbf_copy.contains(b"hello"), true);
-    cbf_copy.remove(b"hello");
-    assert_eq!(cbf_copy.contains(b"hello"), false);
+#[test]
+fn counting_bloom_from_config_test() {
+    for size in [CounterSize::U4, CounterSize::U8, CounterSize::U16] {
+        let mut builder = FilterBuilder::new(1000, 0.01);
+        let mut cbf = builder.counting(size);
+        assert_eq!(builder.counter_size, size);
+        for i in 0..100u32 {
+            for _ in 0..i % 20 {
+                cbf.add(&i.to_le_bytes());
+            }
+        }
+        let copy = CountingBloomFilter::from_u8_array_with_config(cbf.get_u8_array(), cbf.config());
+        assert_eq!(copy.config().counter_size, size);
+        assert_eq!(copy.memory_bytes(), cbf.memory_bytes());
+        assert_eq!(copy.get_u8_array(), cbf.get_u8_array());
+        for i in 0..100u32 {
+            assert_eq!(copy.estimate_count(&i.to_le_bytes()), cbf.estimate_count(&i.to_le_bytes()));
+        }
+        assert_eq!(copy.compatibility_report(&cbf).is_compatible(), true);
 
-    #[cfg(target_pointer_width = "64")]{
-        let mut cbf_copy = CountingBloomFilter::from_u64_array(cbf.get_u64_array(), builder.hashes, true);
-        assert_eq!(cbf_copy.contains(b"hello"), true);
-        cbf_copy.remove(b"hello");
-        assert_eq!(cbf_copy.contains(b"hello"), true);
-        cbf_copy.remove(b"hello");
-        assert_eq!(cbf_copy.contains(b"hello"), false);
+        let bytes = &cbf.get_u8_array()[1..];
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            CountingBloomFilter::from_u8_array_with_config(bytes, cbf.config())
+        }));
+        assert_eq!(res.is_err(), true);
     }
+
+    // the counters of get_u8_array alone are read as 4-bit counters
+    let mut wide = FilterBuilder::new(1000, 0.01).counting(CounterSize::U8);
+    wide.add(b"hello");
+    let raw = CountingBloomFilter::from_u8_array(wide.get_u8_array(), wide.config().hashes, true);
+    assert_eq!(raw.config().counter_size, CounterSize::U4);
+    assert_eq!(raw.config().size, wide.config().size * 2);
 }
 
 #[test]
-fn counting_bloom_hash_indices_test() {
-    let mut builder =
-        FilterBuilder::new(10_000, 0.01);
-    let mut bloom = builder.build_counting_bloom_filter();
+fn counting_bloom_overflow_test() {
+    let mut cbf = FilterBuilder::new(10_000, 0.01).build_counting_bloom_filter();
+    for i in 0..1000u32 {
+        cbf.add(&i.to_le_bytes());
+    }
+    assert_eq!(cbf.has_overflowed(), false);
+    let indices: Vec<u64> = cbf.get_hash_indices(b"hello");
+    let mut distinct = indices.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    let before: Vec<usize> = distinct.iter().map(|index| cbf.counter_at(*index)).collect();
+    for _ in 0..20 {
+        cbf.add(b"hello");
+    }
+    for index in &distinct {
+        assert_eq!(cbf.counter_at(*index), 15);
+    }
+    // the increments beyond 15 of every counter
+    let lost: usize = distinct.iter().zip(&before)
+        .map(|(index, count)| {
+            let increments = 20 * indices.iter().filter(|i| *i == index).count();
+            (count + increments).saturating_sub(15)
+        })
+        .sum();
+    assert_eq!(cbf.overflow_count(), lost as u64);
+    assert_eq!(cbf.has_overflowed(), true);
 
-    bloom.add(b"hello");
+    // stuck counters are not decremented, so nothing gets a false negative
+    for _ in 0..40 {
+        cbf.remove(b"hello");
+    }
+    for index in &distinct {
+        assert_eq!(cbf.counter_at(*index), 15);
+    }
+    assert_eq!(cbf.contains(b"hello"), true);
+    for i in 0..1000u32 {
+        cbf.remove(&i.to_le_bytes());
+    }
+    assert_eq!(cbf.contains(b"hello"), true);
 
-    assert_eq!(bloom.contains(b"hello"), true);
-    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"hello")), true);
-    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"world")), false);
+    cbf.clear();
+    assert_eq!((cbf.overflow_count(), cbf.has_overflowed()), (0, false));
+}
 
+#[test]
+fn counting_bloom_remove_absent_test() {
+    let mut cbf = FilterBuilder::new(2000, 0.01).build_counting_bloom_filter();
+    for i in 0..2000u32 {
+        cbf.add(&i.to_le_bytes());
+    }
+    let mut removed = 0;
+    for i in 2000..100_000u32 {
+        let counters = cbf.get_u64_array().to_vec();
+        if cbf.remove(&i.to_le_bytes()) {
+            // a false positive
+            removed += 1;
+            cbf.add(&i.to_le_bytes());
+        } else {
+            assert_eq!(cbf.get_u64_array(), counters);
+        }
+    }
+    assert_eq!(removed > 0 && removed < 2000, true, "{}", removed);
+    for i in 0..2000u32 {
+        assert_eq!(cbf.contains(&i.to_le_bytes()), true);
+    }
+    assert_eq!(cbf.remove(&7u32.to_le_bytes()), true);
+    assert_eq!(cbf.remove(&7u32.to_le_bytes()), false);
 
-    bloom.remove(b"hello");
-    assert_eq!(bloom.contains(b"hello"), false);
-    assert_eq!(bloom.contains_hash_indices(&bloom.get_hash_indices(b"hello")), false);
+    // unchecked removals of absent elements saturate at zero
+    let mut cbf = FilterBuilder::new(100, 0.01).build_counting_bloom_filter();
+    cbf.remove_unchecked(b"hello");
+    assert_eq!(cbf.get_u64_array().iter().all(|word| *word == 0), true);
+    cbf.add(b"hello");
+    cbf.remove_unchecked(b"hello");
+    assert_eq!(cbf.contains(b"hello"), false);
 }
 
 #[test]
-fn counting_bloom_estimate_count() {
-    let mut builder =
-        FilterBuilder::new(10_000, 0.01);
-    let mut bloom = builder.build_counting_bloom_filter();
+fn counting_bloom_estimate_multiplicity_test() {
+    let mut cbf = FilterBuilder::new(10_000, 0.01).with_counter_size(CounterSize::U8)
+        .build_counting_bloom_filter();
+    // key i is added i % 10 + 1 times
+    for i in 0..2000u32 {
+        for _ in 0..i % 10 + 1 {
+            cbf.add(&i.to_le_bytes());
+        }
+    }
+    let mut exact = 0;
+    for i in 0..2000u32 {
+        let estimate = cbf.estimate_count(&i.to_le_bytes());
+        assert_eq!(estimate >= (i % 10 + 1) as usize, true);
+        if estimate == (i % 10 + 1) as usize {
+            exact += 1;
+        }
+    }
+    assert_eq!(exact > 1900, true, "{}", exact);
+    let absent = (2000..12_000u32).filter(|i| cbf.estimate_count(&i.to_le_bytes()) > 0).count();
+    assert_eq!(absent < 200, true, "{}", absent);
 
-    bloom.add(b"hello");
-    bloom.add(b"world");
+    // saturated counters cap the estimate
+    assert_eq!(cbf.max_count(), 255);
+    for _ in 0..300 {
+        cbf.add(b"hello");
+    }
+    assert_eq!(cbf.estimate_count(b"hello"), 255);
+    let mut cbf = FilterBuilder::new(100, 0.01).build_counting_bloom_filter();
+    assert_eq!(cbf.max_count(), 15);
+    for _ in 0..16 {
+        cbf.add(b"hello");
+    }
+    assert_eq!(cbf.estimate_count(b"hello"), 15);
+}
 
-    assert_eq!(bloom.estimate_count(b"hello"), 1);
-    let indices = bloom.get_hash_indices(b"hello");
+#[test]
+fn counting_bloom_union_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut left = builder.build_counting_bloom_filter();
+    let mut right = builder.build_counting_bloom_filter();
+    // 0 .. 3000 and 2000 .. 5000, shared 2000 .. 3000
+    for i in 0..3000u32 {
+        left.add(&i.to_le_bytes());
+    }
+    for i in 2000..5000u32 {
+        right.add(&i.to_le_bytes());
+    }
+    let before = left.clone();
+    left.union(&right).unwrap();
+    for i in 0..5000u32 {
+        assert_eq!(left.contains(&i.to_le_bytes()), true);
+    }
+    let mut exact = 0;
+    for i in 2000..3000u32 {
+        let count = left.estimate_count(&i.to_le_bytes());
+        assert_eq!(count >= 2, true);
+        if count == 2 {
+            exact += 1;
+        }
+    }
+    assert_eq!(exact > 900, true, "{}", exact);
+    for index in 0..left.config().size {
+        let sum = before.counter_at(index) + right.counter_at(index);
+        assert_eq!(left.counter_at(index), sum.min(15));
+    }
+    assert_eq!(left.has_overflowed(), false);
 
-    for index in indices {
-        assert_eq!(bloom.counter_at(index), 1)
+    // saturating sums are counted as overflows
+    let mut hot = builder.build_counting_bloom_filter();
+    for _ in 0..10 {
+        hot.add(b"hello");
     }
+    let mut merged = hot.clone();
+    merged.union(&hot).unwrap();
+    assert_eq!(merged.estimate_count(b"hello"), 15);
+    let indices = merged.get_hash_indices(b"hello");
+    assert_eq!(merged.overflow_count() > 0, true);
+    assert_eq!(merged.overflow_count() <= 5 * indices.len() as u64, true);
+    let overflows = merged.overflow_count();
+    merged.union(&merged.clone()).unwrap();
+    assert_eq!(merged.overflow_count() >= 2 * overflows, true);
 
-    assert_eq!(bloom.estimate_count(b"world"), 1);
-    for index in bloom.get_hash_indices(b"world") {
-        assert!(bloom.counter_at(index) <= 2);
+    let mut wide = builder.clone().with_counter_size(CounterSize::U8).build_counting_bloom_filter();
+    assert_eq!(left.union(&wide), Err(IncompatibleFilterError::CounterSize {
+        left: CounterSize::U4,
+        right: CounterSize::U8,
+    }));
+    let seeded = builder.clone().with_seed(1).build_counting_bloom_filter();
+    assert_eq!(left.union(&seeded), Err(IncompatibleFilterError::Seed { left: 0, right: 1 }));
+    assert_eq!(wide.union(&left).is_err(), true);
+}
+
+#[test]
+fn counting_bloom_intersect_test() {
+    let mut builder = FilterBuilder::new(10_000, 0.01);
+    let mut left = builder.build_counting_bloom_filter();
+    let mut right = builder.build_counting_bloom_filter();
+    // 0 .. 3000 and 2000 .. 5000, shared 2000 .. 3000, the shared ones twice on the left
+    for i in 0..3000u32 {
+        left.add(&i.to_le_bytes());
+    }
+    for i in 2000..3000u32 {
+        left.add(&i.to_le_bytes());
+    }
+    for i in 2000..5000u32 {
+        right.add(&i.to_le_bytes());
+    }
+    let mut both = left.clone();
+    both.intersect(&right).unwrap();
+    for index in 0..both.config().size {
+        let min = left.counter_at(index).min(right.counter_at(index));
+        assert_eq!(both.counter_at(index), min);
+    }
+    for i in 2000..3000u32 {
+        let count = both.estimate_count(&i.to_le_bytes());
+        assert_eq!(count >= 1, true);
+        assert_eq!(count <= left.estimate_count(&i.to_le_bytes()), true);
+        assert_eq!(count <= right.estimate_count(&i.to_le_bytes()), true);
+    }
+    let mut present = 0;
+    for i in (0..2000u32).chain(3000..5000) {
+        if both.contains(&i.to_le_bytes()) {
+            present += 1;
+        }
+    }
+    assert_eq!(present < 80, true, "{}", present);
+
+    let wide = builder.clone().with_counter_size(CounterSize::U8).build_counting_bloom_filter();
+    assert_eq!(both.intersect(&wide), Err(IncompatibleFilterError::CounterSize {
+        left: CounterSize::U4,
+        right: CounterSize::U8,
+    }));
+}
+
+#[test]
+fn counting_bloom_to_bloom_filter_test() {
+    let mut builder = FilterBuilder::new(5_000, 0.01);
+    let mut counting = builder.build_counting_bloom_filter();
+    let mut state = 7u64;
+    let mut added = Vec::new();
+    for _ in 0..10_000 {
+        let key = splitmix64(&mut state) % 8_000;
+        if splitmix64(&mut state).is_multiple_of(3) && !added.is_empty() {
+            let key: u64 = added.swap_remove(key as usize % added.len());
+            counting.remove(&key.to_le_bytes());
+        } else {
+            counting.add(&key.to_le_bytes());
+            added.push(key);
+        }
+    }
+    let bloom = counting.to_bloom_filter();
+    for key in 0..16_000u64 {
+        assert_eq!(bloom.contains(key.to_le_bytes()), counting.contains(&key.to_le_bytes()));
     }
+    let nonzero: Vec<u64> =
+        (0..counting.config().size).filter(|&index| counting.counter_at(index) != 0).collect();
+    assert_eq!(bloom.iter_set_bits().collect::<Vec<u64>>(), nonzero);
+    assert_eq!(bloom.compatible(&builder.build_bloom_filter()), true);
+    assert_eq!(bloom.len(), 0);
+    assert_eq!(bloom.get_u8_array().len() * 4 == counting.memory_bytes(), true);
 }
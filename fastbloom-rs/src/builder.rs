@@ -1,18 +1,252 @@
-use crate::bloom::{BloomFilter, CountingBloomFilter};
-use crate::Membership;
+use std::fmt::{Display, Formatter};
+
+use crate::bloom::{BloomFilter, CountingBloomFilter, Probe};
+use crate::error::{BuildError, IncompatibleFilterError};
+use crate::gcs::GolombCodedSet;
+use crate::hasher::{BloomHasher, HashAlgorithm, ProbeScheme};
+use crate::math::{fpp, half_pow, optimal_k, optimal_m, optimal_m_exact, optimal_n};
+use xxhash_rust::xxh3::Xxh3Default;
 
 /// Builder for Bloom Filters.
+///
+/// With the `serde` feature the configuration can be serialized, e.g. to share it between
+/// workers which must build compatible filters. Every field is serialized, and fields added in
+/// later versions get their default when they are missing, so older configurations still load.
+/// Configurations no filter can be built from fail to deserialize.
 #[derive(Clone)]
 #[derive(Debug)]
+// the derives are the inherent `serialize` and `deserialize` the trait impls below call
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(remote = "Self"))]
 pub struct FilterBuilder {
     pub expected_elements: u64,
     pub false_positive_probability: f64,
+    /// The size of the filter in bits, i.e. the modulus the hashes are reduced by. The storage
+    /// of the bits may be padded beyond it, see [FilterBuilder::exact_size].
     pub size: u64,
     pub hashes: u32,
-    /// Usage for CountingBloomFilter.
+    /// Whether elements which are already present are added again: a CountingBloomFilter counts
+    /// them again, a BloomFilter sets their bits again. Enabled by default.
     pub enable_repeat_insert: bool,
+    /// Whether the computed size is rounded up to a power of two, see
+    /// [FilterBuilder::force_power_of_two_size].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub power_of_two_size: bool,
+    /// Whether the computed size is kept as it is instead of rounded up to whole words, see
+    /// [FilterBuilder::exact_size].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exact_size: bool,
+    /// The seed of the hash functions, see [FilterBuilder::with_seed]. 0 by default.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub seed: u64,
+    /// The hash function, see [FilterBuilder::with_hash_algorithm]. [HashAlgorithm::Xxh128] by
+    /// default, [HashAlgorithm::Xxh3] for configurations serialized before it was stored.
+    #[cfg_attr(feature = "serde", serde(default = "HashAlgorithm::legacy"))]
+    pub hasher: HashAlgorithm,
+    /// The version of the hash scheme, see [FilterBuilder::with_hash_version].
+    /// [FilterBuilder::HASH_VERSION] by default, 1 for configurations serialized before it was
+    /// stored.
+    #[cfg_attr(feature = "serde", serde(default = "FilterBuilder::legacy_hash_version"))]
+    pub hash_version: u8,
+    /// How the bit indices are derived from the hashes, see [FilterBuilder::with_probe_scheme].
+    /// [ProbeScheme::Double] by default.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub probe_scheme: ProbeScheme,
+    /// The width of the counters of a CountingBloomFilter, see
+    /// [FilterBuilder::with_counter_size]. [CounterSize::U4] by default.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub counter_size: CounterSize,
     pub(crate) done: bool,
+    /// Whether `false_positive_probability` was set with
+    /// [FilterBuilder::with_false_positive_probability] and must be met by `complete`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) pinned_fpp: bool,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for FilterBuilder {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FilterBuilder::serialize(self, serializer)
+    }
+}
+
+/// Checks that a completed configuration has a size and hashes, that the expected elements and
+/// the false positive probability are valid for [FilterBuilder::try_new] if the size is still
+/// to be computed from them, and that the false positive probability is one otherwise.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FilterBuilder {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let invalid = |reason: String| {
+            D::Error::custom(crate::FastBloomError::Deserialization { reason })
+        };
+        let config = FilterBuilder::deserialize(deserializer)?;
+        if config.done && (config.size == 0 || config.hashes == 0) {
+            return Err(invalid(format!("invalid size {} with {} hashes", config.size,
+                                       config.hashes)));
+        }
+        if config.size == 0 {
+            FilterBuilder::try_new(config.expected_elements, config.false_positive_probability)
+                .map_err(|e| invalid(e.to_string()))?;
+        } else if !(0.0..=1.0).contains(&config.false_positive_probability) {
+            return Err(invalid(format!("invalid false positive probability {}",
+                                       config.false_positive_probability)));
+        }
+        if !(1..=FilterBuilder::HASH_VERSION).contains(&config.hash_version) {
+            return Err(invalid(format!("unknown hash version {}", config.hash_version)));
+        }
+        Ok(config)
+    }
+}
+
+/// A capacity planning summary of a [FilterBuilder], see [FilterBuilder::plan].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FilterPlan {
+    /// The expected number of elements.
+    pub expected_elements: u64,
+    /// The size of the filter in bits.
+    pub size_bits: u64,
+    /// The memory the bits of a [BloomFilter] take, in bytes.
+    pub size_bytes: u64,
+    /// The number of hash functions.
+    pub hashes: u32,
+    /// The theoretical false positive probability with the expected number of elements.
+    pub fpp: f64,
+    /// The theoretical false positive probability with twice the expected number of elements.
+    pub fpp_2x: f64,
+    /// The theoretical false positive probability with five times the expected number of
+    /// elements.
+    pub fpp_5x: f64,
+}
+
+impl Display for FilterPlan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bloom filter for {} elements: {} bits ({:.2} MiB), {} hashes, false positive \
+                   probability {:.6} ({:.6} at 2x, {:.6} at 5x capacity)", self.expected_elements,
+               self.size_bits, self.size_bytes as f64 / (1024.0 * 1024.0), self.hashes, self.fpp,
+               self.fpp_2x, self.fpp_5x)
+    }
+}
+
+/// The parameters in which two configurations differ so that their filters are not compatible,
+/// see [FilterBuilder::compatibility_report]. Every field holds the values of both sides
+/// (`self` first) if they differ.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CompatibilityReport {
+    /// The sizes in bits.
+    pub size: Option<(u64, u64)>,
+    /// The numbers of hash functions.
+    pub hashes: Option<(u32, u32)>,
+    /// The hash seeds.
+    pub seed: Option<(u64, u64)>,
+    /// The names of the hashers.
+    pub hasher: Option<(String, String)>,
+    /// The versions of the hash scheme.
+    pub hash_version: Option<(u8, u8)>,
+    /// The probe schemes.
+    pub probe_scheme: Option<(ProbeScheme, ProbeScheme)>,
+    /// The widths of the counters, compared for counting filters only, see
+    /// [CountingBloomFilter::compatibility_report].
+    pub counter_size: Option<(CounterSize, CounterSize)>,
+}
+
+impl CompatibilityReport {
+    /// Returns `true` if no parameter differs.
+    pub fn is_compatible(&self) -> bool {
+        self.first_mismatch().is_none()
+    }
+
+    /// The first mismatching parameter as an error, in the order of the fields.
+    pub fn first_mismatch(&self) -> Option<IncompatibleFilterError> {
+        if let Some((left, right)) = self.size {
+            return Some(IncompatibleFilterError::Size { left, right });
+        }
+        if let Some((left, right)) = self.hashes {
+            return Some(IncompatibleFilterError::Hashes { left, right });
+        }
+        if let Some((left, right)) = self.seed {
+            return Some(IncompatibleFilterError::Seed { left, right });
+        }
+        if let Some((left, right)) = &self.hasher {
+            let (left, right) = (left.clone(), right.clone());
+            return Some(IncompatibleFilterError::Hasher { left, right });
+        }
+        if let Some((left, right)) = self.hash_version {
+            return Some(IncompatibleFilterError::HashVersion { left, right });
+        }
+        if let Some((left, right)) = self.probe_scheme {
+            return Some(IncompatibleFilterError::ProbeScheme { left, right });
+        }
+        if let Some((left, right)) = self.counter_size {
+            return Some(IncompatibleFilterError::CounterSize { left, right });
+        }
+        None
+    }
+}
+
+/// Lists every mismatching parameter, or prints `compatible`.
+impl Display for CompatibilityReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut mismatches = Vec::new();
+        if let Some((left, right)) = self.size {
+            mismatches.push(format!("size {} != {}", left, right));
+        }
+        if let Some((left, right)) = self.hashes {
+            mismatches.push(format!("hashes {} != {}", left, right));
+        }
+        if let Some((left, right)) = self.seed {
+            mismatches.push(format!("seed {} != {}", left, right));
+        }
+        if let Some((left, right)) = &self.hasher {
+            mismatches.push(format!("hasher {} != {}", left, right));
+        }
+        if let Some((left, right)) = self.hash_version {
+            mismatches.push(format!("hash version {} != {}", left, right));
+        }
+        if let Some((left, right)) = self.probe_scheme {
+            mismatches.push(format!("probe scheme {} != {}", left.name(), right.name()));
+        }
+        if let Some((left, right)) = self.counter_size {
+            mismatches.push(format!("counter size {} != {} bits", left.bits(), right.bits()));
+        }
+        if mismatches.is_empty() {
+            write!(f, "compatible")
+        } else {
+            write!(f, "{}", mismatches.join(", "))
+        }
+    }
+}
+
+/// The width of the counters of a [CountingBloomFilter], see [FilterBuilder::with_counter_size].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CounterSize {
+    /// 4-bit counters up to 15, two in a byte.
+    #[default]
+    U4,
+    /// 8-bit counters up to 255.
+    U8,
+    /// 16-bit counters up to 65535.
+    U16,
+}
+
+impl CounterSize {
+    /// The width of a counter in bits.
+    pub fn bits(self) -> u32 {
+        match self {
+            CounterSize::U4 => 4,
+            CounterSize::U8 => 8,
+            CounterSize::U16 => 16,
+        }
+    }
+
+    /// The largest count of a counter.
+    pub fn max(self) -> u64 {
+        (1 << self.bits()) - 1
+    }
 }
 
 #[cfg(target_pointer_width = "32")]
@@ -24,48 +258,28 @@ pub(crate) const MASK: u64 = 0b11111111_11111111_11111111_11111111_11111111_1111
 #[cfg(target_pointer_width = "64")]
 pub(crate) const MASK: u64 = 0b11111111_11111111_11111111_11111111_11111111_11111111_11111111_11000000;
 
-/// Calculates the optimal size `m` of the bloom filter in bits given `n` (expected
-/// number of elements in bloom filter) and `p` (tolerable false positive rate).
-#[inline]
-fn optimal_m(n: u64, p: f64) -> u64 {
-    let fact = -(n as f64) * p.ln();
-    let div = 2f64.ln().powi(2);
-    let m: f64 = fact / div;
-    let mut m = m.ceil() as u64;
-    if (m & SUFFIX as u64) != 0 {
-        m = (m & MASK) + SUFFIX as u64 + 1;
-    };
-    m
-}
-
-/// Calculates the optimal `hashes` (number of hash function) given `n` (expected number of
-/// elements in bloom filter) and `m` (size of bloom filter in bits).
-#[inline]
-fn optimal_k(n: u64, m: u64) -> u32 {
-    let k: f64 = (m as f64 * 2f64.ln()) / n as f64;
-    k.ceil() as u32
-}
-
-/// Calculates the amount of elements a Bloom filter for which the given configuration of size `m`
-/// and hashes `k` is optimal.
-#[inline]
-fn optimal_n(k: u32, m: u64) -> u64 {
-    let n = (2f64.ln() * m as f64) / k as f64;
-    n.ceil() as u64
-}
+/// The largest size in bits `optimal_m` may return: it must fit in a `usize` and stay a multiple
+/// of the word size.
+const MAX_SIZE: u64 = usize::MAX as u64 & MASK;
 
+#[allow(dead_code)]
+impl FilterBuilder {
+    /// The largest number of hashes [FilterBuilder::from_elements_and_size] chooses. More hashes
+    /// only make every operation slower once the false positive probability is this small.
+    pub const MAX_HASHES: u32 = 32;
 
-/// Calculates the best-case (uniform hash function) false positive probability.
-/// `k` number of hashes.
-/// `m` The size of the bloom filter in bits.
-/// `n` number of elements inserted in the filter.
-#[inline]
-fn optimal_p(k: u32, m: u64, n: u64) -> f64 {
-    let nk = -(k as f64);
-    (1.0 - (nk * n as f64 / m as f64).exp()).powi(k as i32)
-}
+    /// The version of the hash scheme new filters use, see [FilterBuilder::with_hash_version].
+    ///
+    /// 1. The second hash of double hashing is used as it is, so an element whose second hash is
+    ///    a multiple of the size puts all of its hashes on one bit.
+    /// 2. A second hash of 0 (after the reduction) is replaced by 1. The indices don't stay the
+    ///    same modulo a divisor of the size, so these filters can't be folded.
+    /// 3. The second hash is made odd before the reduction, so it is never 0 for even sizes and
+    ///    the indices stay the same modulo every even divisor of the size. For odd sizes, e.g.
+    ///    of [FilterBuilder::exact_size], it can still be 0 and is then replaced by 1 like in
+    ///    version 2, so the filters can only be folded to even sizes.
+    pub const HASH_VERSION: u8 = 3;
 
-impl FilterBuilder {
     /// Constructs a new Bloom Filter Builder by specifying the expected size of the filter and the
     /// tolerable false positive probability. The size of the BLoom filter in in bits and the
     /// optimal number of hash functions will be inferred from this.
@@ -78,30 +292,499 @@ impl FilterBuilder {
     /// let bloom = builder.build_bloom_filter();
     ///
     /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [FilterBuilder::try_new].
     pub fn new(expected_elements: u64, false_positive_probability: f64) -> Self {
-        FilterBuilder {
+        FilterBuilder::try_new(expected_elements, false_positive_probability)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as [FilterBuilder::new] but returns an error instead of panicking if
+    /// `expected_elements` is zero, `false_positive_probability` is not between 0.0 and 1.0
+    /// (exclusive), or the optimal size for them overflows.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BuildError, FilterBuilder};
+    ///
+    /// assert!(FilterBuilder::try_new(100_000, 0.01).is_ok());
+    /// assert_eq!(FilterBuilder::try_new(100_000, 1.5).unwrap_err(),
+    ///            BuildError::InvalidProbability { probability: 1.5 });
+    /// ```
+    pub fn try_new(expected_elements: u64, false_positive_probability: f64)
+                   -> Result<Self, BuildError> {
+        if expected_elements == 0 {
+            return Err(BuildError::ZeroElements);
+        }
+        if !(false_positive_probability > 0.0 && false_positive_probability < 1.0) {
+            return Err(BuildError::InvalidProbability { probability: false_positive_probability });
+        }
+        let m = -(expected_elements as f64) * false_positive_probability.ln() / 2f64.ln().powi(2);
+        if m.ceil() >= MAX_SIZE as f64 {
+            return Err(BuildError::SizeOverflow { expected_elements, false_positive_probability });
+        }
+        Ok(FilterBuilder {
             expected_elements,
             false_positive_probability,
             size: 0,
             hashes: 0,
             enable_repeat_insert: true,
+            power_of_two_size: false,
+            exact_size: false,
+            seed: 0,
+            hasher: HashAlgorithm::Xxh128,
+            hash_version: FilterBuilder::HASH_VERSION,
+            probe_scheme: ProbeScheme::Double,
             done: false,
-        }
+            counter_size: CounterSize::U4,
+            pinned_fpp: false,
+        })
     }
 
     /// Constructs a new Bloom Filter Builder by specifying the size of the bloom filter in bits
     /// and the number of hashes. The expected size of the filter and the tolerable false positive
     /// probability will be inferred from this.
+    ///
+    /// `expected_elements` is the number of elements for which `hashes` is optimal,
+    /// `ceil(ln(2) * m / k)`, and `false_positive_probability` the theoretical probability with
+    /// `ln(2) * m / k` elements, exactly `0.5^k`. Only integer arithmetic is used, so the result
+    /// is the same on every platform. Filters rebuilt from their bits, e.g. with
+    /// [BloomFilter::from_u8_array], get their configuration from here, so for a filter built
+    /// with [FilterBuilder::new] they are close to the original parameters.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let builder = FilterBuilder::from_size_and_hashes(958505856, 7);
+    /// assert_eq!(builder.expected_elements, 94912234);
+    /// assert_eq!(builder.false_positive_probability, 0.0078125);
+    /// ```
     pub fn from_size_and_hashes(size: u64, hashes: u32) -> Self {
         let n = optimal_n(hashes, size);
-        let p = optimal_p(hashes, size, n);
+        let p = half_pow(hashes);
         FilterBuilder {
             expected_elements: n,
             false_positive_probability: p,
             size,
             hashes,
             enable_repeat_insert: true,
+            power_of_two_size: false,
+            exact_size: false,
+            seed: 0,
+            hasher: HashAlgorithm::Xxh128,
+            hash_version: FilterBuilder::HASH_VERSION,
+            probe_scheme: ProbeScheme::Double,
             done: true,
+            counter_size: CounterSize::U4,
+            pinned_fpp: false,
+        }
+    }
+
+    /// Constructs a new Bloom Filter Builder by specifying the size of the bloom filter in bits
+    /// and the tolerable false positive probability, e.g. for a fixed memory budget. The optimal
+    /// number of hashes is `ceil(-log2(p))` and the expected elements are the number of elements
+    /// after which the false positive probability reaches `p`, `floor(m * ln(2)^2 / -ln(p))`.
+    ///
+    /// For a size computed by [FilterBuilder::new] the result is compatible with the filters it
+    /// builds, as long as rounding the size up to whole words didn't change the optimal hashes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// // 64 MiB of bits
+    /// let builder = FilterBuilder::from_size_and_fpp(64 * 1024 * 1024 * 8, 0.01);
+    /// assert_eq!(builder.hashes, 7);
+    /// assert_eq!(builder.expected_elements, 56_011_230);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero or `false_positive_probability` is not between 0.0 and 1.0
+    /// (exclusive).
+    pub fn from_size_and_fpp(size: u64, false_positive_probability: f64) -> Self {
+        assert!(size > 0, "bloom filter size must be positive!");
+        assert!(false_positive_probability > 0.0 && false_positive_probability < 1.0,
+                "false_positive_probability must between (0.0, 1.0)!");
+        let ln_p = false_positive_probability.ln();
+        let n = (size as f64 * 2f64.ln().powi(2) / -ln_p).floor() as u64;
+        let k = (-ln_p / 2f64.ln()).ceil() as u32;
+        FilterBuilder {
+            expected_elements: n.max(1),
+            false_positive_probability,
+            size,
+            hashes: k.max(1),
+            enable_repeat_insert: true,
+            power_of_two_size: false,
+            exact_size: false,
+            seed: 0,
+            hasher: HashAlgorithm::Xxh128,
+            hash_version: FilterBuilder::HASH_VERSION,
+            probe_scheme: ProbeScheme::Double,
+            done: true,
+            counter_size: CounterSize::U4,
+            pinned_fpp: false,
+        }
+    }
+
+    /// Constructs a new Bloom Filter Builder by specifying the expected size of the filter and
+    /// the size of the bloom filter in bits, e.g. for a fixed memory budget. The optimal number
+    /// of hashes `ceil(ln(2) * m / n)` is chosen, at least 1 and at most
+    /// [FilterBuilder::MAX_HASHES], and `false_positive_probability` is set to the theoretical
+    /// false positive probability after `n` insertions, `(1 - e^(-k * n / m))^k`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let builder = FilterBuilder::from_elements_and_size(1_000_000, 8 * 1024 * 1024);
+    /// assert_eq!(builder.hashes, 6);
+    /// assert!(builder.false_positive_probability < 0.02);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `expected_elements` or `size` is zero.
+    pub fn from_elements_and_size(expected_elements: u64, size: u64) -> Self {
+        assert!(expected_elements > 0, "expected_elements must larger than 0!");
+        assert!(size > 0, "bloom filter size must be positive!");
+        let hashes = optimal_k(expected_elements, size).clamp(1, FilterBuilder::MAX_HASHES);
+        FilterBuilder {
+            expected_elements,
+            false_positive_probability: fpp(size, expected_elements, hashes),
+            size,
+            hashes,
+            enable_repeat_insert: true,
+            power_of_two_size: false,
+            exact_size: false,
+            seed: 0,
+            hasher: HashAlgorithm::Xxh128,
+            hash_version: FilterBuilder::HASH_VERSION,
+            probe_scheme: ProbeScheme::Double,
+            done: true,
+            counter_size: CounterSize::U4,
+            pinned_fpp: false,
+        }
+    }
+
+    /// Constructs a Bloom Filter Builder laid out like the filter of Guava's
+    /// `BloomFilter.create(funnel, expected_insertions, fpp)`: the same size and number of hashes,
+    /// [HashAlgorithm::Murmur3] with seed 0 and [ProbeScheme::Guava]. Such a filter and the one
+    /// built by Guava set the same bits for the same elements, and it can be written in Guava's
+    /// format with [BloomFilter::to_guava_bytes].
+    ///
+    /// The elements are the bytes the Guava funnel writes, e.g. the UTF-8 bytes of a string for
+    /// `Funnels.stringFunnel(UTF_8)`, or the 4 little endian bytes of an `int` for
+    /// `Funnels.integerFunnel()`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::guava_compatible(1000, 0.01).build_bloom_filter();
+    /// assert_eq!((bloom.config().size, bloom.config().hashes), (9600, 7));
+    /// bloom.add("key-1".as_bytes());
+    /// let bytes = bloom.to_guava_bytes().unwrap();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_probability` is not between 0.0 and 1.0 (exclusive).
+    pub fn guava_compatible(expected_insertions: u64, false_positive_probability: f64) -> Self {
+        assert!(false_positive_probability > 0.0 && false_positive_probability < 1.0,
+                "false_positive_probability must between (0.0, 1.0)!");
+        // Guava's optimalNumOfBits and optimalNumOfHashFunctions, with the bits rounded up to
+        // whole longs like its bit array does
+        let n = expected_insertions.max(1);
+        let ln2 = 2f64.ln();
+        let bits = (-(n as f64) * false_positive_probability.ln() / (ln2 * ln2)) as u64;
+        let hashes = ((bits as f64 / n as f64 * ln2).round() as u32).max(1);
+        let size = bits.div_ceil(64).max(1) * 64;
+        FilterBuilder {
+            expected_elements: n,
+            false_positive_probability: fpp(size, n, hashes),
+            hasher: HashAlgorithm::Murmur3,
+            probe_scheme: ProbeScheme::Guava,
+            ..FilterBuilder::from_size_and_hashes(size, hashes)
+        }
+    }
+
+    /// Constructs a Bloom Filter Builder laid out like pybloom_live's
+    /// `BloomFilter(capacity, error_rate)`: `ceil(log2(1 / error_rate))` slices of the same
+    /// number of bits, one per hash, [HashAlgorithm::PyBloomMd5] with seed 0 and
+    /// [ProbeScheme::PyBloom], following the source of pybloom_live 4.0.0. Such a filter can be
+    /// written in pybloom_live's format with [BloomFilter::to_pybloom_bytes], as long as
+    /// it is within the subset [BloomFilter::from_pybloom_bytes] supports, which is all that
+    /// was tested.
+    ///
+    /// The elements are the UTF-8 bytes of the `str` keys of pybloom_live.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::pybloom_compatible(1000, 0.01).build_bloom_filter();
+    /// assert_eq!((bloom.config().size, bloom.config().hashes), (9590, 7));
+    /// bloom.add("key-1".as_bytes());
+    /// let bytes = bloom.to_pybloom_bytes().unwrap();
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero or `error_rate` is not between 0.0 and 1.0 (exclusive).
+    pub fn pybloom_compatible(capacity: u64, error_rate: f64) -> Self {
+        assert!(capacity > 0, "capacity must be larger than 0!");
+        assert!(error_rate > 0.0 && error_rate < 1.0, "error_rate must between (0.0, 1.0)!");
+        // pybloom_live's BloomFilter.__init__, with Python's math.log(x, 2) = ln(x) / ln(2)
+        let ln2 = 2f64.ln();
+        let slices = ((1.0 / error_rate).ln() / ln2).ceil() as u64;
+        let bits_per_slice =
+            (capacity as f64 * error_rate.ln().abs() / (slices as f64 * ln2 * ln2)).ceil() as u64;
+        FilterBuilder {
+            expected_elements: capacity,
+            false_positive_probability: error_rate,
+            hasher: HashAlgorithm::PyBloomMd5,
+            probe_scheme: ProbeScheme::PyBloom,
+            ..FilterBuilder::from_size_and_hashes(slices * bits_per_slice, slices as u32)
+        }
+    }
+
+    /// Sets the expected number of elements, see [FilterBuilder::with_size] for how the other
+    /// parameters are derived.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `expected_elements` is zero.
+    pub fn with_expected_elements(mut self, expected_elements: u64) -> Self {
+        self.expected_elements(expected_elements);
+        self.done = false;
+        self
+    }
+
+    /// Sets the tolerable false positive probability. Unlike the probability given to
+    /// [FilterBuilder::new] it is a requirement: if the size is set as well, completing the
+    /// builder panics when the size and hashes can't achieve it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `false_positive_probability` is not between 0.0 and 1.0 (exclusive).
+    pub fn with_false_positive_probability(mut self, false_positive_probability: f64) -> Self {
+        self.false_positive_probability(false_positive_probability);
+        self.pinned_fpp = true;
+        self.done = false;
+        self
+    }
+
+    /// Sets the size of the bloom filter in bits.
+    ///
+    /// Nothing is derived until the filter is built, so the `with_*` methods can be called in
+    /// any order. Values set explicitly always win, the missing ones are derived from them:
+    ///
+    /// * without a size, the size is derived from the expected elements and the false positive
+    ///   probability like [FilterBuilder::new] does, and the hashes, unless set, from the
+    ///   expected elements and the size. If the hashes are set, the false positive probability
+    ///   is replaced like below, and building panics if a pinned one isn't achieved.
+    /// * with a size, the hashes, unless set, are derived from the expected elements and the size
+    ///   like [FilterBuilder::from_elements_and_size] does, and the false positive probability is
+    ///   replaced by the one the filter achieves with the expected elements. If it was set with
+    ///   [FilterBuilder::with_false_positive_probability] and isn't achieved, building panics.
+    ///
+    /// Values derived when a filter was built before are kept like explicitly set ones.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(1_000_000, 0.01)
+    ///     .with_size(8 * 1024 * 1024)
+    ///     .with_hashes(5);
+    /// let bloom = builder.build_bloom_filter();
+    /// assert_eq!(bloom.config().hashes, 5);
+    /// assert!(bloom.config().false_positive_probability < 0.02);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero.
+    pub fn with_size(mut self, size: u64) -> Self {
+        assert!(size > 0, "bloom filter size must be positive!");
+        self.size = size;
+        self.done = false;
+        self
+    }
+
+    /// Sets the number of hash functions, see [FilterBuilder::with_size] for how the other
+    /// parameters are derived.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hashes` is zero.
+    pub fn with_hashes(mut self, hashes: u32) -> Self {
+        assert!(hashes > 0, "hashes must larger than 0!");
+        self.hashes = hashes;
+        self.done = false;
+        self
+    }
+
+    /// Sets the seed of the hash functions. Filters with different seeds put the same element on
+    /// different bits, so an adversary who doesn't know the seed can't craft elements which
+    /// collide in the filter. Filters can only be combined if their seeds are equal. The default
+    /// of 0 gives the same bits as versions without seeds.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).with_seed(42).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert_eq!(bloom.seed(), 42);
+    /// assert!(bloom.contains(b"hello"));
+    /// ```
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets a custom hash function, see [FilterBuilder::with_hash_algorithm] for the built-in
+    /// ones. Filters can only be combined if their hashers have the same [BloomHasher::name].
+    /// The hashers of this crate, e.g. [crate::Murmur3Hasher], select the built-in hasher.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Murmur3Hasher};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).with_hasher(Murmur3Hasher)
+    ///     .build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert!(bloom.contains(b"hello"));
+    /// assert!(!bloom.compatible(&FilterBuilder::new(10_000, 0.01).build_bloom_filter()));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if any other hasher has the name of a built-in one.
+    pub fn with_hasher<H: BloomHasher + 'static>(mut self, hasher: H) -> Self {
+        self.hasher = HashAlgorithm::custom(hasher);
+        self
+    }
+
+    /// Selects a built-in hash function, see [HashAlgorithm]. [HashAlgorithm::Xxh128] is the
+    /// default and the fastest, [HashAlgorithm::Xxh3] places the bits like versions before it
+    /// was the default.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, HashAlgorithm};
+    ///
+    /// let bloom = FilterBuilder::new(10_000, 0.01).with_hash_algorithm(HashAlgorithm::Murmur3)
+    ///     .build_bloom_filter();
+    /// assert_eq!(bloom.config().hasher, HashAlgorithm::Murmur3);
+    /// ```
+    pub fn with_hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hasher = algorithm;
+        self
+    }
+
+    /// Sets the version of the hash scheme, see [FilterBuilder::HASH_VERSION]. Only needed to
+    /// load or combine with bits written by an older version of the crate, filters can only be
+    /// combined if their versions are equal.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `version` is 0 or larger than [FilterBuilder::HASH_VERSION].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, HashAlgorithm};
+    ///
+    /// // bits written by a version of the crate before the hash version existed
+    /// let bytes = [0u8; 8];
+    /// let config = FilterBuilder::from_size_and_hashes(64, 3)
+    ///     .with_hash_algorithm(HashAlgorithm::Xxh3)
+    ///     .with_hash_version(1);
+    /// let bloom = BloomFilter::from_u8_array_with_config(&bytes, config);
+    /// assert_eq!(bloom.config().hash_version, 1);
+    /// ```
+    pub fn with_hash_version(mut self, version: u8) -> Self {
+        assert!((1..=FilterBuilder::HASH_VERSION).contains(&version),
+                "unknown hash version {}!", version);
+        self.hash_version = version;
+        self
+    }
+
+    /// Selects how the `k` bit indices of an element are derived from its hashes, see
+    /// [ProbeScheme]. [ProbeScheme::Double] is the default, [ProbeScheme::EnhancedDouble] gets
+    /// closer to the false positive probability of independent hashes for many hashes on small
+    /// filters. Filters can only be combined if their schemes are equal.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{FilterBuilder, Membership, ProbeScheme};
+    ///
+    /// let mut bloom = FilterBuilder::new(1000, 0.001)
+    ///     .with_probe_scheme(ProbeScheme::EnhancedDouble)
+    ///     .build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// assert!(bloom.contains(b"hello"));
+    /// assert!(!bloom.compatible(&FilterBuilder::new(1000, 0.001).build_bloom_filter()));
+    /// ```
+    pub fn with_probe_scheme(mut self, scheme: ProbeScheme) -> Self {
+        self.probe_scheme = scheme;
+        self
+    }
+
+    /// Selects the width of the counters of a [CountingBloomFilter] built from this
+    /// configuration. [CounterSize::U4] is the default and the textbook choice, wider counters
+    /// take two or four times the memory but count far more insertions of elements, or of
+    /// elements sharing a counter, before they get stuck at their maximum. It has no effect on a
+    /// [BloomFilter], and counting filters can only be combined if their widths are equal.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{CounterSize, FilterBuilder, Membership};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01).with_counter_size(CounterSize::U8);
+    /// let mut cbf = builder.build_counting_bloom_filter();
+    /// for _ in 0..100 {
+    ///     cbf.add(b"hello");
+    /// }
+    /// assert_eq!(cbf.estimate_count(b"hello"), 100);
+    /// assert_eq!(cbf.memory_bytes(), 9600);
+    /// ```
+    pub fn with_counter_size(mut self, counter_size: CounterSize) -> Self {
+        self.counter_size = counter_size;
+        self
+    }
+
+    /// The hash version of configurations serialized before it was stored.
+    pub(crate) fn legacy_hash_version() -> u8 {
+        1
+    }
+
+    /// The parameters placing the bits of an element besides the size and the hashes.
+    #[inline]
+    pub(crate) fn probe(&self) -> Probe<'_> {
+        Probe {
+            hasher: &self.hasher,
+            seed: self.seed,
+            version: self.hash_version,
+            scheme: self.probe_scheme,
         }
     }
 
@@ -118,7 +801,9 @@ impl FilterBuilder {
         self.false_positive_probability = false_positive_probability;
     }
 
-    /// Use for CountingBloomFilter.
+    /// Enables or disables adding elements which are already present again. When disabled a
+    /// CountingBloomFilter doesn't increment the counters of a present element, and a BloomFilter
+    /// skips writing its bits, see [BloomFilter::insert].
     ///
     /// # Example:
     /// ```rust
@@ -149,6 +834,59 @@ impl FilterBuilder {
         self.enable_repeat_insert = enable;
     }
 
+    /// Rounds the size computed from the expected elements and the false positive probability up
+    /// to the next power of two. The indices of the elements are then reduced with a mask instead
+    /// of a modulo, which makes adding and checking elements faster at the cost of up to twice
+    /// the memory. The number of hashes stays the one for the unrounded size, so the false
+    /// positive probability is only lower than requested.
+    ///
+    /// Any filter whose size is a power of two uses the mask, however it was built, and the mask
+    /// gives the same indices as the modulo, so such a filter is compatible with every other
+    /// filter of the same size and hashes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(1_000_000, 0.01);
+    /// builder.force_power_of_two_size(true);
+    /// let bloom = builder.build_bloom_filter();
+    /// assert_eq!(bloom.config().size, 1 << 24);
+    /// ```
+    pub fn force_power_of_two_size(&mut self, enable: bool) {
+        self.power_of_two_size = enable;
+        self.done = false;
+    }
+
+    /// Keeps the size computed from the expected elements and the false positive probability
+    /// exactly, instead of rounding it up to a multiple of the word size (64 bits, 32 bits on
+    /// 32-bit platforms) as is done by default. Only the storage of the bits is padded to whole
+    /// words, the size in the configuration stays the logical size the hashes are reduced by.
+    ///
+    /// The arrays returned by e.g. [BloomFilter::get_u8_array] include the padding, so rebuild
+    /// such a filter with a constructor taking the size, like
+    /// [BloomFilter::from_u8_array_with_size] or [BloomFilter::from_u64_array_with_size].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder};
+    ///
+    /// let mut builder = FilterBuilder::new(1000, 0.01);
+    /// builder.exact_size(true);
+    /// let mut bloom = builder.build_bloom_filter();
+    /// assert_eq!(bloom.config().size, 9586);
+    /// bloom.add(b"hello");
+    ///
+    /// let copy = BloomFilter::from_u8_array_with_config(bloom.get_u8_array(), bloom.config());
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn exact_size(&mut self, enable: bool) {
+        self.exact_size = enable;
+        self.done = false;
+    }
+
     /// set  the size of the bloom filter in bits.
     fn size(&mut self, size: u64) {
         assert_eq!(size & SUFFIX as u64, 0);
@@ -159,16 +897,60 @@ impl FilterBuilder {
     /// Checks if all necessary parameters were set and tries to infer optimal parameters (e.g.
     /// size and hashes from given expected_elements (`n`) and falsePositiveProbability (`p`)).
     /// This is done automatically.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size and hashes can't achieve a false positive probability set with
+    /// [FilterBuilder::with_false_positive_probability].
     pub(crate) fn complete(&mut self) {
         if !self.done {
             if self.size == 0 {
-                self.size = optimal_m(self.expected_elements, self.false_positive_probability);
-                self.hashes = optimal_k(self.expected_elements, self.size);
+                let size = if self.exact_size {
+                    optimal_m_exact(self.expected_elements, self.false_positive_probability)
+                } else {
+                    optimal_m(self.expected_elements, self.false_positive_probability)
+                };
+                let hashes_set = self.hashes != 0;
+                if !hashes_set {
+                    self.hashes = optimal_k(self.expected_elements, size);
+                }
+                self.size = if self.power_of_two_size {
+                    size.checked_next_power_of_two()
+                        .filter(|&size| size <= usize::MAX as u64)
+                        .expect("bloom filter size overflows when rounded up to a power of two!")
+                } else { size };
+                if hashes_set {
+                    // the size only achieves the probability up to the rounding of the optimal
+                    // hashes, so they set the bound
+                    let optimal = optimal_k(self.expected_elements, size);
+                    let bound = fpp(self.size, self.expected_elements, optimal)
+                        .max(self.false_positive_probability);
+                    self.achieved_fpp(bound);
+                }
+            } else {
+                if self.hashes == 0 {
+                    self.hashes = optimal_k(self.expected_elements, self.size)
+                        .clamp(1, FilterBuilder::MAX_HASHES);
+                }
+                self.achieved_fpp(self.false_positive_probability);
             }
             self.done = true;
         }
     }
 
+    /// Replaces the false positive probability by the one the size and hashes achieve with the
+    /// expected elements, see [FilterBuilder::with_size]. Panics if the probability is pinned
+    /// and the achieved one is above `bound`.
+    fn achieved_fpp(&mut self, bound: f64) {
+        let p = fpp(self.size, self.expected_elements, self.hashes);
+        // leave some room for rounding errors
+        assert!(!self.pinned_fpp || p <= bound * (1.0 + 1e-9),
+                "contradictory bloom filter parameters: size {} and {} hashes give a false \
+                 positive probability of {} for {} elements, not {}", self.size, self.hashes, p,
+                self.expected_elements, self.false_positive_probability);
+        self.false_positive_probability = p;
+    }
+
     /// Constructs a Bloom filter using the specified parameters and computing missing parameters
     /// if possible (e.g. the optimal Bloom filter bit size).
     pub fn build_bloom_filter(&mut self) -> BloomFilter {
@@ -176,6 +958,20 @@ impl FilterBuilder {
         BloomFilter::new(self.clone())
     }
 
+    /// Constructs a [GolombCodedSet] of `keys` for the expected elements and the false positive
+    /// probability, with the seed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range `expected_elements * ceil(1 / false_positive_probability)` of the set
+    /// overflows a `u64`.
+    pub fn build_golomb_coded_set<I, T>(&self, keys: I) -> GolombCodedSet
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+        GolombCodedSet::with_params(keys, self.expected_elements, self.false_positive_probability,
+                                    self.seed)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
     /// Constructs a Counting Bloom filter using the specified parameters and computing missing parameters
     /// if possible (e.g. the optimal Bloom filter bit size).
     pub fn build_counting_bloom_filter(&mut self) -> CountingBloomFilter {
@@ -183,22 +979,142 @@ impl FilterBuilder {
         CountingBloomFilter::new(self.clone())
     }
 
-    /// Checks whether a configuration is compatible to another configuration based on the size of
-    /// the Bloom filter and its hash functions.
-    pub(crate) fn is_compatible_to(&self, other: &FilterBuilder) -> bool {
-        self.size == other.size && self.hashes == other.hashes
+    /// Constructs a Counting Bloom filter with counters of `counter_size`, which the builder
+    /// keeps: the same as [FilterBuilder::with_counter_size] followed by
+    /// [FilterBuilder::build_counting_bloom_filter].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{CounterSize, FilterBuilder};
+    ///
+    /// let cbf = FilterBuilder::new(1000, 0.01).counting(CounterSize::U8);
+    /// assert_eq!(cbf.config().counter_size, CounterSize::U8);
+    /// assert_eq!(cbf.memory_bytes(), 9600);
+    /// ```
+    pub fn counting(&mut self, counter_size: CounterSize) -> CountingBloomFilter {
+        self.counter_size = counter_size;
+        self.build_counting_bloom_filter()
     }
-}
 
-#[test]
-fn optimal_test() {
-    let m = optimal_m(100_000_000, 0.01);
-    let k = optimal_k(100_000_000, m);
-    let n = optimal_n(k, m);
-    let p = optimal_p(k, m, n);
-    println!("{m} {k} {n} {p}");
-    assert_eq!(m, 958505856);
-    assert_eq!(k, 7)
+    /// The theoretical false positive probability `(1 - e^(-k * n / m))^k` of a filter built
+    /// from this configuration once `inserted` distinct elements are added, e.g. to see what
+    /// happens when 3 times the expected elements are inserted. Missing parameters are computed
+    /// like [FilterBuilder::build_bloom_filter] does, without changing the builder.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let builder = FilterBuilder::new(1_000_000, 0.01);
+    /// assert_eq!(builder.expected_fpp_for(0), 0.0);
+    /// assert!(builder.expected_fpp_for(1_000_000) < 0.0101);
+    /// assert!(builder.expected_fpp_for(3_000_000) > 0.2);
+    /// ```
+    pub fn expected_fpp_for(&self, inserted: u64) -> f64 {
+        if inserted == 0 {
+            return 0.0;
+        }
+        let mut config = self.clone();
+        config.complete();
+        fpp(config.size, inserted, config.hashes)
+    }
+
+    /// Computes the size, hashes and false positive probabilities of a filter built from this
+    /// configuration without allocating it or changing the builder, e.g. to check the memory
+    /// cost before building a large filter.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let plan = FilterBuilder::new(100_000_000, 0.01).plan();
+    /// assert_eq!(plan.size_bytes, 119_813_232);
+    /// assert_eq!(plan.hashes, 7);
+    /// println!("{}", plan);
+    /// ```
+    pub fn plan(&self) -> FilterPlan {
+        let mut config = self.clone();
+        config.complete();
+        let n = config.expected_elements;
+        FilterPlan {
+            expected_elements: n,
+            size_bits: config.size,
+            size_bytes: config.size.div_ceil(64) * 8,
+            hashes: config.hashes,
+            fpp: config.expected_fpp_for(n),
+            fpp_2x: config.expected_fpp_for(n.saturating_mul(2)),
+            fpp_5x: config.expected_fpp_for(n.saturating_mul(5)),
+        }
+    }
+
+    /// Returns a hash of the parameters [FilterBuilder::is_compatible_to] compares, the same on
+    /// every platform. Compatible configurations have the same fingerprint, two configurations
+    /// with different fingerprints are incompatible.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = Xxh3Default::new();
+        self.write_fingerprint(&mut hasher);
+        hasher.digest()
+    }
+
+    /// Feeds the compared parameters of the configuration as little endian bytes to `hasher`.
+    pub(crate) fn write_fingerprint(&self, hasher: &mut Xxh3Default) {
+        hasher.update(&self.size.to_le_bytes());
+        hasher.update(&self.hashes.to_le_bytes());
+        hasher.update(&self.seed.to_le_bytes());
+        let name = self.hasher.name();
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update(&[self.hash_version]);
+        hasher.update(self.probe_scheme.name().as_bytes());
+    }
+
+    /// Checks whether a configuration is compatible to another configuration, i.e. filters built
+    /// from them put every element on the same bits and can be combined. Only the parameters
+    /// which place the bits are compared, the size, the hashes, the seed, the hasher, the hash
+    /// version and the probe scheme; the expected elements, the false positive probability and
+    /// the other options only matter for computing those.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::FilterBuilder;
+    ///
+    /// let mut builder = FilterBuilder::new(100_000_000, 0.01);
+    /// // computes the size and hashes of the builder
+    /// builder.build_bloom_filter();
+    /// assert!(builder.is_compatible_to(&FilterBuilder::from_size_and_hashes(958505856, 7)));
+    /// ```
+    pub fn is_compatible_to(&self, other: &FilterBuilder) -> bool {
+        self.compatibility_report(other).is_compatible()
+    }
+
+    /// Same as [FilterBuilder::is_compatible_to] but reports every mismatching parameter.
+    pub fn compatibility_report(&self, other: &FilterBuilder) -> CompatibilityReport {
+        fn differ<T: PartialEq>(left: T, right: T) -> Option<(T, T)> {
+            if left != right { Some((left, right)) } else { None }
+        }
+        CompatibilityReport {
+            size: differ(self.size, other.size),
+            hashes: differ(self.hashes, other.hashes),
+            seed: differ(self.seed, other.seed),
+            hasher: differ(self.hasher.name(), other.hasher.name())
+                .map(|(left, right)| (left.to_string(), right.to_string())),
+            hash_version: differ(self.hash_version, other.hash_version),
+            probe_scheme: differ(self.probe_scheme, other.probe_scheme),
+            counter_size: None,
+        }
+    }
+
+    /// Same as [FilterBuilder::is_compatible_to] but reports the first mismatching parameter.
+    pub(crate) fn check_compatible(&self, other: &FilterBuilder) -> Result<(), IncompatibleFilterError> {
+        match self.compatibility_report(other).first_mismatch() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
 }
 
 #[test]
@@ -208,4 +1124,313 @@ fn builder_test() {
     bloom.add(b"helloworld");
     assert_eq!(bloom.contains(b"helloworld"), true);
     assert_eq!(bloom.contains(b"helloworld!"), false);
-}
\ No newline at end of file
+}
+#[test]
+fn builder_try_new_test() {
+    assert_eq!(FilterBuilder::try_new(0, 0.01).unwrap_err(), BuildError::ZeroElements);
+    for p in [0.0, 1.0, 1.5, -0.1] {
+        assert_eq!(FilterBuilder::try_new(100, p).unwrap_err(),
+                   BuildError::InvalidProbability { probability: p });
+    }
+    assert_eq!(matches!(FilterBuilder::try_new(100, f64::NAN),
+                        Err(BuildError::InvalidProbability { .. })), true);
+    assert_eq!(FilterBuilder::try_new(u64::MAX, 1e-10).unwrap_err(),
+               BuildError::SizeOverflow { expected_elements: u64::MAX,
+                                          false_positive_probability: 1e-10 });
+
+    let mut builder = FilterBuilder::try_new(100_000_000, 0.01).unwrap();
+    builder.complete();
+    assert_eq!((builder.size, builder.hashes), (958505856, 7));
+}
+
+#[test]
+#[should_panic(expected = "false_positive_probability 0 is not between 0.0 and 1.0")]
+fn builder_new_invalid_test() {
+    FilterBuilder::new(100, 0.0);
+}
+
+#[test]
+fn builder_from_size_and_fpp_test() {
+    let builder = FilterBuilder::from_size_and_fpp(958505856, 0.01);
+    assert_eq!((builder.expected_elements, builder.hashes), (100_000_001, 7));
+    let mut equivalent = FilterBuilder::new(100_000_000, 0.01);
+    equivalent.complete();
+    assert_eq!(builder.is_compatible_to(&equivalent), true);
+    let mut a = builder.clone().build_bloom_filter();
+    a.add(b"hello");
+    let mut b = equivalent.build_bloom_filter();
+    assert_eq!(b.union(&a), true);
+    assert_eq!(b.contains(b"hello"), true);
+
+    let builder = FilterBuilder::from_size_and_fpp(1 << 20, 0.001);
+    assert_eq!((builder.expected_elements, builder.hashes), (72_931, 10));
+    assert_eq!(builder.false_positive_probability, 0.001);
+}
+
+#[test]
+fn builder_from_elements_and_size_test() {
+    fn closed_form(k: u32, m: u64, n: u64) -> f64 {
+        (1.0 - (-(k as f64) * n as f64 / m as f64).exp()).powi(k as i32)
+    }
+
+    let builder = FilterBuilder::from_elements_and_size(1_000_000, 8 * 1024 * 1024);
+    assert_eq!(builder.hashes, 6);
+    assert_eq!((builder.false_positive_probability - closed_form(6, 8 * 1024 * 1024, 1_000_000))
+                   .abs() < 1e-12, true);
+
+    // 1000 elements in 64 bits, almost every query is a false positive
+    let builder = FilterBuilder::from_elements_and_size(1000, 64);
+    assert_eq!(builder.hashes, 1);
+    assert_eq!((builder.false_positive_probability - closed_form(1, 64, 1000)).abs() < 1e-12, true);
+
+    let builder = FilterBuilder::from_elements_and_size(1, 1 << 20);
+    assert_eq!(builder.hashes, FilterBuilder::MAX_HASHES);
+    assert_eq!(builder.false_positive_probability, closed_form(32, 1 << 20, 1));
+    assert_eq!(builder.false_positive_probability < 1e-100, true);
+}
+
+#[test]
+fn builder_with_test() {
+    fn completed(mut builder: FilterBuilder) -> FilterBuilder {
+        builder.complete();
+        builder
+    }
+    fn params(builder: &FilterBuilder) -> (u64, u32, u64, f64) {
+        (builder.size, builder.hashes, builder.expected_elements, builder.false_positive_probability)
+    }
+
+    let chained = FilterBuilder::new(1000, 0.5)
+        .with_false_positive_probability(0.01)
+        .with_expected_elements(100_000_000);
+    assert_eq!(params(&completed(chained)),
+               params(&completed(FilterBuilder::new(100_000_000, 0.01))));
+
+    let chained = FilterBuilder::new(1_000_000, 0.01).with_size(8 * 1024 * 1024);
+    assert_eq!(params(&completed(chained)),
+               params(&FilterBuilder::from_elements_and_size(1_000_000, 8 * 1024 * 1024)));
+
+    let chained = FilterBuilder::from_size_and_hashes(958505856, 7).with_expected_elements(1000);
+    let mut expected = FilterBuilder::from_size_and_hashes(958505856, 7);
+    expected.false_positive_probability = fpp(958505856, 1000, 7);
+    expected.expected_elements = 1000;
+    assert_eq!(params(&completed(chained)), params(&expected));
+
+    let chained = FilterBuilder::new(1_000_000, 0.5).with_hashes(3);
+    let mut expected = FilterBuilder::new(1_000_000, 0.5);
+    expected.complete();
+    assert_eq!(params(&completed(chained)),
+               (expected.size, 3, 1_000_000, fpp(expected.size, 1_000_000, 3)));
+    // the optimal hashes, which achieve the probability up to their rounding
+    let chained = FilterBuilder::new(1_000_000, 0.5)
+        .with_false_positive_probability(0.01)
+        .with_hashes(7);
+    let mut expected = FilterBuilder::new(1_000_000, 0.01);
+    expected.complete();
+    assert_eq!(params(&completed(chained)),
+               (expected.size, 7, 1_000_000, fpp(expected.size, 1_000_000, 7)));
+
+    // achievable, so not contradictory
+    let chained = FilterBuilder::new(1_000_000, 0.5)
+        .with_false_positive_probability(0.02)
+        .with_size(8 * 1024 * 1024)
+        .with_hashes(5);
+    assert_eq!(completed(chained).false_positive_probability < 0.02, true);
+}
+
+#[test]
+#[should_panic(expected = "contradictory bloom filter parameters")]
+fn builder_with_contradictory_test() {
+    FilterBuilder::new(1_000_000, 0.5)
+        .with_false_positive_probability(0.001)
+        .with_size(8 * 1024 * 1024)
+        .with_hashes(5)
+        .build_bloom_filter();
+}
+
+#[test]
+#[should_panic(expected = "contradictory bloom filter parameters")]
+fn builder_with_contradictory_hashes_test() {
+    FilterBuilder::new(1_000_000, 0.5)
+        .with_false_positive_probability(0.001)
+        .with_hashes(1)
+        .build_bloom_filter();
+}
+
+#[test]
+fn builder_power_of_two_test() {
+    let mut builder = FilterBuilder::new(1_000_000, 0.01);
+    builder.force_power_of_two_size(true);
+    builder.complete();
+    let mut unrounded = FilterBuilder::new(1_000_000, 0.01);
+    unrounded.complete();
+    assert_eq!(builder.size, unrounded.size.next_power_of_two());
+    assert_eq!(builder.hashes, unrounded.hashes);
+    assert_eq!(fpp(builder.size, 1_000_000, builder.hashes) < 0.01, true);
+    // the option only matters for computing the size
+    assert_eq!(builder.fingerprint(), {
+        let mut other = builder.clone();
+        other.power_of_two_size = false;
+        other.fingerprint()
+    });
+    assert_ne!(builder.fingerprint(), unrounded.fingerprint());
+}
+
+#[test]
+fn builder_exact_size_test() {
+    let mut builder = FilterBuilder::new(1000, 0.01);
+    builder.exact_size(true);
+    builder.complete();
+    assert_eq!(builder.size, optimal_m_exact(1000, 0.01));
+    assert_eq!(builder.size, 9586);
+    assert_eq!(builder.hashes, optimal_k(1000, 9586));
+    let mut rounded = FilterBuilder::new(1000, 0.01);
+    rounded.complete();
+    assert_eq!(rounded.size, 9600);
+}
+
+#[test]
+fn builder_expected_fpp_for_test() {
+    let builder = FilterBuilder::new(1_000_000, 0.01);
+    assert_eq!(builder.done, false);
+    assert_eq!(builder.expected_fpp_for(0), 0.0);
+    assert_eq!(builder.expected_fpp_for(1_000_000), fpp(9585088, 1_000_000, 7));
+    assert_eq!(builder.done, false);
+    assert_eq!(builder.expected_fpp_for(u64::MAX), 1.0);
+    let fpps: Vec<f64> = [1u64, 1000, 1_000_000, 2_000_000, 5_000_000].iter()
+        .map(|&n| builder.expected_fpp_for(n))
+        .collect();
+    assert_eq!(fpps.windows(2).all(|w| w[0] < w[1]), true);
+}
+
+#[test]
+fn builder_plan_test() {
+    let builder = FilterBuilder::new(1_000_000, 0.01);
+    let plan = builder.plan();
+    assert_eq!(builder.done, false);
+    assert_eq!(builder.size, 0);
+    assert_eq!(plan, FilterPlan {
+        expected_elements: 1_000_000,
+        size_bits: optimal_m(1_000_000, 0.01),
+        size_bytes: 9585088 / 8,
+        hashes: 7,
+        fpp: fpp(9585088, 1_000_000, 7),
+        fpp_2x: fpp(9585088, 2_000_000, 7),
+        fpp_5x: fpp(9585088, 5_000_000, 7),
+    });
+    let mut copy = builder.clone();
+    assert_eq!(copy.build_bloom_filter().get_u8_array().len() as u64, plan.size_bytes);
+    assert_eq!(plan.to_string().starts_with("bloom filter for 1000000 elements: 9585088 bits \
+                                             (1.14 MiB), 7 hashes"), true, "{}", plan);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn builder_serde_test() {
+    let mut completed = FilterBuilder::new(100_000, 0.01);
+    completed.complete();
+    for config in [FilterBuilder::new(100_000, 0.01), completed] {
+        let json = serde_json::to_string(&config).unwrap();
+        let mut copy: FilterBuilder = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.fingerprint(), config.fingerprint());
+        let mut a = config.clone().build_bloom_filter();
+        a.add(b"hello");
+        let mut b = copy.build_bloom_filter();
+        assert_eq!(b.compatible(&a), true);
+        assert_eq!(b.union(&a), true);
+        assert_eq!(b.contains(b"hello"), true);
+    }
+
+    let seeded = FilterBuilder::new(100_000, 0.01).with_seed(42);
+    let copy: FilterBuilder = serde_json::from_str(&serde_json::to_string(&seeded).unwrap())
+        .unwrap();
+    assert_eq!(copy.seed, 42);
+    let murmur = FilterBuilder::new(100_000, 0.01).with_hash_algorithm(HashAlgorithm::Murmur3);
+    let json = serde_json::to_string(&murmur).unwrap();
+    assert_eq!(json.contains(r#""hasher":"murmur3_x64_128""#), true);
+    let copy: FilterBuilder = serde_json::from_str(&json).unwrap();
+    assert_eq!(copy.hasher, HashAlgorithm::Murmur3);
+    assert_eq!(matches!(copy.hasher, HashAlgorithm::Murmur3), true);
+    let enhanced = FilterBuilder::new(100_000, 0.01).with_probe_scheme(ProbeScheme::EnhancedDouble);
+    let json = serde_json::to_string(&enhanced).unwrap();
+    assert_eq!(json.contains(r#""probe_scheme":"enhanced_double""#), true);
+    let copy: FilterBuilder = serde_json::from_str(&json).unwrap();
+    assert_eq!(copy.probe_scheme, ProbeScheme::EnhancedDouble);
+
+    // written before power_of_two_size, exact_size, pinned_fpp, seed, hasher, hash_version and
+    // probe_scheme existed
+    let json = r#"{"expected_elements":100000,"false_positive_probability":0.01,"size":958528,
+        "hashes":7,"enable_repeat_insert":true,"done":true}"#;
+    let config: FilterBuilder = serde_json::from_str(json).unwrap();
+    assert_eq!((config.size, config.hashes, config.done), (958528, 7, true));
+    assert_eq!((config.power_of_two_size, config.exact_size, config.pinned_fpp, config.seed),
+               (false, false, false, 0));
+    assert_eq!(matches!(config.hasher, HashAlgorithm::Xxh3), true);
+    assert_eq!((config.hash_version, config.probe_scheme), (1, ProbeScheme::Double));
+    assert_eq!(matches!(FilterBuilder::new(100_000, 0.01).hasher, HashAlgorithm::Xxh128), true);
+    let mut current = FilterBuilder::new(100_000, 0.01);
+    current.complete();
+    assert_eq!(config.compatibility_report(&current).to_string(),
+               "hasher xxh3 != xxh128, hash version 1 != 3");
+    let current = current.with_hash_algorithm(HashAlgorithm::Xxh3).with_hash_version(1);
+    assert_eq!(config.is_compatible_to(&current), true);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn builder_serde_invalid_test() {
+    let mut completed = FilterBuilder::new(100_000, 0.01);
+    completed.complete();
+    let reason = |config: &FilterBuilder, field: &str, value: serde_json::Value| {
+        let mut json = serde_json::to_value(config).unwrap();
+        json[field] = value;
+        serde_json::from_value::<FilterBuilder>(json).unwrap_err().to_string()
+    };
+    assert_eq!(reason(&completed, "size", 0.into()),
+               "can't deserialize bloom filter: invalid size 0 with 7 hashes");
+    assert_eq!(reason(&completed, "hashes", 0.into()),
+               "can't deserialize bloom filter: invalid size 958528 with 0 hashes");
+    assert_eq!(reason(&completed, "false_positive_probability", 1.5.into()),
+               "can't deserialize bloom filter: invalid false positive probability 1.5");
+    assert_eq!(reason(&completed, "hash_version", 9.into()),
+               "can't deserialize bloom filter: unknown hash version 9");
+    let config = FilterBuilder::new(100_000, 0.01);
+    assert_eq!(reason(&config, "false_positive_probability", 0.0.into()),
+               "can't deserialize bloom filter: invalid bloom filter parameters: \
+                false_positive_probability 0 is not between 0.0 and 1.0 (exclusive)");
+    assert_eq!(reason(&config, "expected_elements", 0.into()),
+               "can't deserialize bloom filter: invalid bloom filter parameters: \
+                expected_elements must be larger than 0");
+
+    // a size set without completing the configuration is kept
+    let config = FilterBuilder::from_size_and_hashes(8, 7).with_hashes(3);
+    let copy: FilterBuilder = serde_json::from_str(&serde_json::to_string(&config).unwrap())
+        .unwrap();
+    assert_eq!((copy.size, copy.hashes, copy.done), (8, 3, false));
+}
+
+#[test]
+fn builder_compatibility_report_test() {
+    let mut a = FilterBuilder::new(100_000_000, 0.01);
+    a.complete();
+    // same size and hashes from different inputs
+    let b = FilterBuilder::from_size_and_hashes(958505856, 7);
+    assert_ne!(a.false_positive_probability, b.false_positive_probability);
+    assert_eq!(a.is_compatible_to(&b), true);
+    assert_eq!(a.compatibility_report(&b).to_string(), "compatible");
+
+    let c = FilterBuilder::from_size_and_hashes(1 << 20, 3);
+    let report = a.compatibility_report(&c);
+    assert_eq!(report,
+               CompatibilityReport {
+                   size: Some((958505856, 1 << 20)),
+                   hashes: Some((7, 3)),
+                   seed: None,
+                   hasher: None,
+                   hash_version: None,
+                   probe_scheme: None,
+                   counter_size: None,
+               });
+    assert_eq!(report.to_string(), "size 958505856 != 1048576, hashes 7 != 3");
+    assert_eq!(a.check_compatible(&c),
+               Err(IncompatibleFilterError::Size { left: 958505856, right: 1 << 20 }));
+}
@@ -0,0 +1,423 @@
+//! The chunks of [BloomFilter::export_chunks], to store or send large filters in parts.
+
+use std::cmp::min;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+
+use xxhash_rust::xxh3::Xxh3Default;
+
+use crate::error::FastBloomError;
+use crate::BloomFilter;
+
+/// The magic bytes of [FilterChunk::to_bytes].
+const MAGIC: [u8; 4] = *b"FBLC";
+/// The magic bytes, the index, the number of chunks, the payload length and the checksum.
+const HEADER_LEN: usize = 36;
+/// The `u64` words of bits hashed at a time for the checksum.
+const HASH_WORDS: usize = 512;
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization { reason: format!("invalid filter chunks: {}", reason) }
+}
+
+/// A part of a filter exported by [BloomFilter::export_chunks]. The chunks of an export are
+/// the bytes of [BloomFilter::write_into], the payload, cut into pieces of the same length,
+/// and every chunk carries the fields to put the payload back together and verify it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FilterChunk {
+    /// The position of the chunk in the export, from 0.
+    pub index: u64,
+    /// The number of chunks of the export.
+    pub count: u64,
+    /// The length of the payload, all chunks together.
+    pub payload_len: u64,
+    /// The checksum of the payload, xxh3 64 of all its bytes but the last 8, which hold it
+    /// themselves. It is the same for all chunks of an export, so chunks of another export
+    /// are detected.
+    pub checksum: u64,
+    /// The bytes of the payload from `index` times the chunk length on.
+    pub data: Vec<u8>,
+}
+
+impl FilterChunk {
+    /// Writes the chunk as one blob, e.g. for one part of a multipart upload: the magic bytes
+    /// `FBLC`, the index, the number of chunks, the payload length and the checksum as 8 little
+    /// endian bytes each, followed by the data.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.data.len());
+        bytes.extend_from_slice(&MAGIC);
+        for field in [self.index, self.count, self.payload_len, self.checksum] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Reads a chunk written by [FilterChunk::to_bytes].
+    ///
+    /// Returns [FastBloomError::Deserialization] if the bytes are too short for the header or
+    /// don't start with the magic bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<FilterChunk, FastBloomError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(invalid(format!("{} bytes are too short for the header of a chunk",
+                                       bytes.len())));
+        }
+        if bytes[..4] != MAGIC {
+            return Err(invalid("a chunk doesn't start with the magic bytes".to_string()));
+        }
+        let field = |i: usize| u64::from_le_bytes(bytes[4 + 8 * i..12 + 8 * i].try_into().unwrap());
+        Ok(FilterChunk {
+            index: field(0),
+            count: field(1),
+            payload_len: field(2),
+            checksum: field(3),
+            data: bytes[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+impl BloomFilter {
+    /// Exports the filter in chunks of `chunk_bytes` bytes, the last one shorter, for storage
+    /// and transports which take large data in parts, e.g. the multipart uploads of object
+    /// stores. Read them back with [BloomFilter::import_chunks].
+    ///
+    /// All chunks together are the bytes of [BloomFilter::write_into]. The chunks are made one
+    /// at a time as the iterator is advanced, so only one of them is in memory. The checksum
+    /// every chunk carries is computed up front, in a pass over the bits at memory speed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_bytes` is zero or the name of the hasher is longer than 65535 bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, FilterChunk, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(100_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let parts: Vec<Vec<u8>> = bloom.export_chunks(16 * 1024)
+    ///     .map(|chunk| chunk.to_bytes())
+    ///     .collect();
+    /// assert_eq!(parts.len(), 8);
+    ///
+    /// let chunks = parts.iter().map(|part| FilterChunk::from_bytes(part).unwrap());
+    /// let copy = BloomFilter::import_chunks(chunks).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn export_chunks(&self, chunk_bytes: usize) -> impl Iterator<Item = FilterChunk> + '_ {
+        assert!(chunk_bytes > 0, "chunk_bytes must be larger than 0!");
+        let header = self.dense_header();
+        let words = self.config_ref().size.div_ceil(64) as usize;
+        let mut hasher = Xxh3Default::new();
+        hasher.update(&header);
+        let mut buf = [0u8; 8 * HASH_WORDS];
+        for start in (0..words).step_by(HASH_WORDS) {
+            let end = min(start + HASH_WORDS, words);
+            for (i, word) in (start..end).zip(buf.chunks_exact_mut(8)) {
+                word.copy_from_slice(&self.bit_vec().u64_word(i).to_le_bytes());
+            }
+            hasher.update(&buf[..(end - start) * 8]);
+        }
+        let checksum = hasher.digest();
+        let payload_len = header.len() + words * 8 + 8;
+        let count = payload_len.div_ceil(chunk_bytes) as u64;
+        (0..count).map(move |index| {
+            let start = index as usize * chunk_bytes;
+            let end = min(start + chunk_bytes, payload_len);
+            FilterChunk {
+                index,
+                count,
+                payload_len: payload_len as u64,
+                checksum,
+                data: self.payload(&header, checksum, start, end),
+            }
+        })
+    }
+
+    /// The bytes `start..end` of the payload of [BloomFilter::export_chunks]: the header, the
+    /// bits as `u64` words and the checksum.
+    fn payload(&self, header: &[u8], checksum: u64, start: usize, end: usize) -> Vec<u8> {
+        let bits_end = header.len() + self.config_ref().size.div_ceil(64) as usize * 8;
+        let mut data = Vec::with_capacity(end - start);
+        if start < header.len() {
+            data.extend_from_slice(&header[start..min(end, header.len())]);
+        }
+        // the words overlapping the range, cut to it
+        let (from, to) = (start.max(header.len()), end.min(bits_end));
+        if from < to {
+            let bit_vec = self.bit_vec();
+            for i in (from - header.len()) / 8..(to - header.len()).div_ceil(8) {
+                let offset = header.len() + i * 8;
+                let word = bit_vec.u64_word(i).to_le_bytes();
+                let (from, to) = (from.max(offset) - offset, to.min(offset + 8) - offset);
+                data.extend_from_slice(&word[from..to]);
+            }
+        }
+        if end > bits_end {
+            let checksum = checksum.to_le_bytes();
+            data.extend_from_slice(&checksum[start.max(bits_end) - bits_end..end - bits_end]);
+        }
+        data
+    }
+
+    /// Reads a filter exported by [BloomFilter::export_chunks] from its chunks. Chunks in order
+    /// are read as they come, and chunks which come early are kept until the chunks before them
+    /// arrived, so any order works.
+    ///
+    /// Once the filter is read, the chunks must be complete, with no chunk left and no data
+    /// after the filter, and the checksum of the payload is verified like
+    /// [BloomFilter::from_bytes] does.
+    ///
+    /// Returns [FastBloomError::Deserialization] if a chunk is missing, comes twice, belongs to
+    /// another export or the chunks don't hold the payload length, and
+    /// [FastBloomError::CorruptData] if the checksum doesn't match, e.g. as a chunk was modified.
+    pub fn import_chunks<I>(chunks: I) -> Result<BloomFilter, FastBloomError>
+        where I: IntoIterator<Item = FilterChunk> {
+        let mut reader = ChunkReader {
+            chunks: chunks.into_iter(),
+            export: None,
+            next: 0,
+            pending: BTreeMap::new(),
+            data: Vec::new(),
+            pos: 0,
+            read: 0,
+            tail: [0; 8],
+            error: None,
+        };
+        let bloom = BloomFilter::from_reader(&mut reader);
+        if let Some(e) = reader.error.take() {
+            return Err(e);
+        }
+        let bloom = bloom?;
+        reader.finish()?;
+        Ok(bloom)
+    }
+}
+
+/// The fields all chunks of an export share.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct Export {
+    count: u64,
+    payload_len: u64,
+    checksum: u64,
+}
+
+/// Reads the payload from the chunks in the order of their index.
+struct ChunkReader<I> {
+    chunks: I,
+    /// The fields of the first chunk, which the others must have.
+    export: Option<Export>,
+    /// The index of the next chunk to read.
+    next: u64,
+    /// The data of the chunks which came before their turn.
+    pending: BTreeMap<u64, Vec<u8>>,
+    /// The data of the chunk being read, from `pos` on.
+    data: Vec<u8>,
+    pos: usize,
+    /// The bytes of the payload read so far.
+    read: u64,
+    /// The last 8 bytes of the payload, its checksum.
+    tail: [u8; 8],
+    /// The error of the chunks which failed the read.
+    error: Option<FastBloomError>,
+}
+
+impl<I: Iterator<Item = FilterChunk>> ChunkReader<I> {
+    /// The data of the next chunk, or `None` after the last one.
+    fn next_data(&mut self) -> Result<Option<Vec<u8>>, FastBloomError> {
+        if self.export.is_some_and(|export| self.next == export.count) {
+            return Ok(None);
+        }
+        loop {
+            if let Some(data) = self.pending.remove(&self.next) {
+                self.next += 1;
+                return Ok(Some(data));
+            }
+            let Some(chunk) = self.chunks.next() else {
+                return Err(match self.export {
+                    None => invalid("no chunks".to_string()),
+                    Some(_) => invalid(format!("chunk {} is missing", self.next)),
+                });
+            };
+            self.check(&chunk)?;
+            if chunk.index < self.next || self.pending.contains_key(&chunk.index) {
+                return Err(invalid(format!("chunk {} twice", chunk.index)));
+            }
+            self.pending.insert(chunk.index, chunk.data);
+        }
+    }
+
+    fn check(&mut self, chunk: &FilterChunk) -> Result<(), FastBloomError> {
+        let export = Export {
+            count: chunk.count,
+            payload_len: chunk.payload_len,
+            checksum: chunk.checksum,
+        };
+        if *self.export.get_or_insert(export) != export {
+            return Err(invalid(format!("chunk {} belongs to another export", chunk.index)));
+        }
+        if chunk.index >= chunk.count {
+            return Err(invalid(format!("chunk {} of {} chunks", chunk.index, chunk.count)));
+        }
+        Ok(())
+    }
+
+    /// Checks that nothing follows the filter and that the payload is the one of the chunks.
+    fn finish(mut self) -> Result<(), FastBloomError> {
+        let export = self.export.expect("the chunks of a filter were read");
+        let read = self.read;
+        if io::copy(&mut self, &mut io::sink()).is_err() {
+            return Err(self.error.take().expect("only the chunks fail"));
+        }
+        if let Some(chunk) = self.chunks.next() {
+            self.check(&chunk)?;
+            return Err(invalid(format!("chunk {} twice", chunk.index)));
+        }
+        if self.read != read {
+            return Err(invalid(format!("{} bytes after the filter", self.read - read)));
+        }
+        if self.read != export.payload_len {
+            return Err(invalid(format!("the filter is {} bytes, not the payload length {}",
+                                       self.read, export.payload_len)));
+        }
+        let found = u64::from_le_bytes(self.tail);
+        if found != export.checksum {
+            return Err(FastBloomError::CorruptData { expected: export.checksum, found });
+        }
+        Ok(())
+    }
+}
+
+impl<I: Iterator<Item = FilterChunk>> Read for ChunkReader<I> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.data.len() {
+            match self.next_data() {
+                Ok(Some(data)) => (self.data, self.pos) = (data, 0),
+                Ok(None) => return Ok(0),
+                Err(e) => {
+                    self.error = Some(e);
+                    return Err(io::Error::other("invalid filter chunks"));
+                }
+            }
+        }
+        let n = min(buf.len(), self.data.len() - self.pos);
+        buf[..n].copy_from_slice(&self.data[self.pos..self.pos + n]);
+        let tail_start = self.export.map_or(0, |export| export.payload_len.saturating_sub(8));
+        for (offset, &byte) in (self.read..).zip(&buf[..n]) {
+            if let Some(i) = offset.checked_sub(tail_start).filter(|&i| i < 8) {
+                self.tail[i as usize] = byte;
+            }
+        }
+        self.pos += n;
+        self.read += n as u64;
+        Ok(n)
+    }
+}
+
+#[test]
+fn chunk_test() {
+    use crate::FilterBuilder;
+
+    let mut bloom = FilterBuilder::new(1000, 0.01).with_seed(3).build_bloom_filter();
+    for i in 0..500u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    let mut payload = vec![0u8; bloom.serialized_size()];
+    bloom.write_into(&mut payload).unwrap();
+    for chunk_bytes in [1, 7, 8, 100, 1000, payload.len() - 1, payload.len(), payload.len() + 1] {
+        let chunks: Vec<FilterChunk> = bloom.export_chunks(chunk_bytes).collect();
+        assert_eq!(chunks.len(), payload.len().div_ceil(chunk_bytes));
+        let data: Vec<u8> = chunks.iter().flat_map(|chunk| chunk.data.clone()).collect();
+        assert_eq!(data, payload);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!((chunk.index, chunk.count), (i as u64, chunks.len() as u64));
+            assert_eq!(chunk.payload_len, payload.len() as u64);
+            assert_eq!(chunk.checksum.to_le_bytes(), payload[payload.len() - 8..]);
+            assert_eq!(FilterChunk::from_bytes(&chunk.to_bytes()).as_ref(), Ok(chunk));
+        }
+        assert_eq!(BloomFilter::import_chunks(chunks.clone()), Ok(bloom.clone()));
+
+        // any order
+        let mut reordered = chunks.clone();
+        reordered.reverse();
+        assert_eq!(BloomFilter::import_chunks(reordered), Ok(bloom.clone()));
+        let mut reordered = chunks;
+        reordered.rotate_left(1);
+        assert_eq!(BloomFilter::import_chunks(reordered), Ok(bloom.clone()));
+    }
+    let copy = BloomFilter::import_chunks(bloom.export_chunks(64)).unwrap();
+    assert_eq!(copy.contains(7u32.to_le_bytes()), true);
+    assert_eq!(copy.len(), 500);
+}
+
+#[test]
+fn chunk_invalid_test() {
+    use crate::FilterBuilder;
+
+    let mut bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    bloom.add(b"hello");
+    let chunks: Vec<FilterChunk> = bloom.export_chunks(100).collect();
+    assert_eq!(chunks.len(), 13);
+    let reason = |chunks: Vec<FilterChunk>| match BloomFilter::import_chunks(chunks).unwrap_err() {
+        FastBloomError::Deserialization { reason } => reason,
+        e => panic!("unexpected error {:?}", e),
+    };
+    let without = |i: usize| {
+        let mut chunks = chunks.clone();
+        chunks.remove(i);
+        chunks
+    };
+    assert_eq!(reason(Vec::new()), "invalid filter chunks: no chunks");
+    assert_eq!(reason(without(0)), "invalid filter chunks: chunk 0 is missing");
+    assert_eq!(reason(without(5)), "invalid filter chunks: chunk 5 is missing");
+    assert_eq!(reason(without(12)), "invalid filter chunks: chunk 12 is missing");
+    let mut twice = chunks.clone();
+    twice.insert(3, chunks[2].clone());
+    assert_eq!(reason(twice), "invalid filter chunks: chunk 2 twice");
+    let mut twice = chunks.clone();
+    twice.push(chunks[12].clone());
+    assert_eq!(reason(twice), "invalid filter chunks: chunk 12 twice");
+
+    // chunks of another export
+    let mut other = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    other.add(b"world");
+    let mut mixed = chunks.clone();
+    mixed[4] = other.export_chunks(100).nth(4).unwrap();
+    assert_eq!(reason(mixed), "invalid filter chunks: chunk 4 belongs to another export");
+    let mut resized = chunks.clone();
+    resized[4] = bloom.export_chunks(50).nth(4).unwrap();
+    assert_eq!(reason(resized), "invalid filter chunks: chunk 4 belongs to another export");
+    let mut out_of_range = chunks.clone();
+    out_of_range[4].index = 13;
+    assert_eq!(reason(out_of_range), "invalid filter chunks: chunk 13 of 13 chunks");
+    let mut longer = chunks.clone();
+    longer[12].data.push(0);
+    assert_eq!(reason(longer), "invalid filter chunks: 1 bytes after the filter");
+    let mut lengths = chunks.clone();
+    for chunk in &mut lengths {
+        chunk.payload_len += 1;
+    }
+    assert_eq!(reason(lengths),
+               "invalid filter chunks: the filter is 1245 bytes, not the payload length 1246");
+
+    // modified data and checksums
+    let mut modified = chunks.clone();
+    modified[6].data[10] ^= 1;
+    assert_eq!(matches!(BloomFilter::import_chunks(modified),
+                        Err(FastBloomError::CorruptData { .. })), true);
+    let mut checksums = chunks.clone();
+    for chunk in &mut checksums {
+        chunk.checksum ^= 1;
+    }
+    let found = chunks[0].checksum;
+    assert_eq!(BloomFilter::import_chunks(checksums),
+               Err(FastBloomError::CorruptData { expected: found ^ 1, found }));
+
+    assert_eq!(FilterChunk::from_bytes(&[0; 35]).unwrap_err().to_string(),
+               "can't deserialize bloom filter: invalid filter chunks: 35 bytes are too short for \
+                the header of a chunk");
+    assert_eq!(FilterChunk::from_bytes(&[0; 36]).unwrap_err().to_string(),
+               "can't deserialize bloom filter: invalid filter chunks: a chunk doesn't start \
+                with the magic bytes");
+}
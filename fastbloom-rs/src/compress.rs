@@ -0,0 +1,112 @@
+//! The compression of the bits of [crate::BloomFilter::to_bytes_compressed].
+//!
+//! The set bits of a filter are spread uniformly, so the gaps between them are geometrically
+//! distributed, and a Rice code of the gaps (the quotient by `2^k` in unary, then the `k` low
+//! bits) is within a few percent of the entropy of the bits. The data is `k` as one byte, the
+//! number of set bits as 8 little endian bytes and the codes of the gaps, least significant bit
+//! first.
+
+use crate::bits::{BitReader, BitWriter};
+use crate::vec::BloomBitVec;
+
+/// The Rice parameter for `ones` set bits out of `size`, `floor(log2(mean gap))`.
+fn rice_parameter(ones: u64, size: u64) -> u32 {
+    let mean = (size - ones) / ones.max(1);
+    if mean == 0 { 0 } else { 63 - mean.leading_zeros() }
+}
+
+/// Compresses the first `size` bits of `bit_vec`.
+pub(crate) fn encode(bit_vec: &BloomBitVec, size: u64) -> Vec<u8> {
+    let words = size.div_ceil(64) as usize;
+    let ones: u64 = (0..words).map(|i| bit_vec.u64_word(i).count_ones() as u64).sum();
+    let k = rice_parameter(ones, size);
+    let mut header = vec![k as u8];
+    header.extend_from_slice(&ones.to_le_bytes());
+    let mut writer = BitWriter::new(header);
+
+    let mut next = 0;
+    for i in 0..words {
+        let mut word = bit_vec.u64_word(i);
+        while word != 0 {
+            let index = i as u64 * 64 + word.trailing_zeros() as u64;
+            let gap = index - next;
+            writer.write_rice(gap, k);
+            next = index + 1;
+            word &= word - 1;
+        }
+    }
+    writer.finish()
+}
+
+/// Decompresses the bits of a filter of `size` bits as `u64` words. Fails with a description of
+/// the problem if the data is not the compression of such a filter.
+pub(crate) fn decode(data: &[u8], size: u64) -> Result<Vec<u64>, String> {
+    if data.len() < 9 {
+        return Err(format!("{} bytes are too short for the compressed bits", data.len()));
+    }
+    let k = data[0] as u32;
+    let ones = u64::from_le_bytes(data[1..9].try_into().unwrap());
+    if k > 63 || ones > size {
+        return Err(format!("invalid parameter {} for {} set bits", k, ones));
+    }
+    let mut words = vec![0u64; size.div_ceil(64) as usize];
+    let mut reader = BitReader::new(&data[9..]);
+    let mut next = 0u64;
+    for _ in 0..ones {
+        let index = reader.read_rice(k)?
+            .and_then(|gap| next.checked_add(gap))
+            .filter(|index| *index < size)
+            .ok_or_else(|| format!("a set bit is out of range for {} bits", size))?;
+        words[(index >> 6) as usize] |= 1 << (index & 63);
+        next = index + 1;
+    }
+    if !reader.is_at_end() {
+        return Err("the data continues after the last set bit".to_string());
+    }
+    Ok(words)
+}
+
+#[test]
+fn compress_test() {
+    for (size, ones) in [(64u64, 0u64), (1000, 1), (1000, 999), (4096, 4096), (100_003, 5000),
+                         (1 << 20, 10)] {
+        let mut bit_vec = BloomBitVec::with_bits(size);
+        let mut state = size ^ ones;
+        let mut set = 0;
+        while set < ones {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let index = ((state >> 32) % size) as usize;
+            if !bit_vec.get(index) {
+                bit_vec.set(index);
+                set += 1;
+            }
+        }
+        let data = encode(&bit_vec, size);
+        let words = size.div_ceil(64) as usize;
+        assert_eq!(decode(&data, size).unwrap(), bit_vec.to_u64_words()[..words], "{}", size);
+    }
+
+    // a single bit gets a large parameter instead of a long unary code
+    let mut bit_vec = BloomBitVec::with_bits(1 << 26);
+    bit_vec.set((1 << 26) - 1);
+    let data = encode(&bit_vec, 1 << 26);
+    assert_eq!(data[0], 25);
+    assert_eq!(data.len(), 13);
+    assert_eq!(decode(&data, 1 << 26).unwrap(), bit_vec.to_u64_words());
+
+    let mut bit_vec = BloomBitVec::with_bits(1000);
+    for i in [3, 500, 999] {
+        bit_vec.set(i);
+    }
+    let data = encode(&bit_vec, 1000);
+    assert_eq!(decode(&data, 999), Err("a set bit is out of range for 999 bits".to_string()));
+    assert_eq!(decode(&data[..data.len() - 1], 1000),
+               Err("the data ends within a gap".to_string()));
+    assert_eq!(decode(&[&data[..], &[1]].concat(), 1000),
+               Err("the data continues after the last set bit".to_string()));
+    assert_eq!(decode(&data[..5], 1000),
+               Err("5 bytes are too short for the compressed bits".to_string()));
+    let mut many = data.clone();
+    many[1] = 200;
+    assert_eq!(decode(&many, 100), Err("invalid parameter 8 for 200 set bits".to_string()));
+}
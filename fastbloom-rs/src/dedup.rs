@@ -0,0 +1,91 @@
+//! Approximate deduplication of a stream with a [BloomFilter].
+//!
+//! ```rust
+//! use fastbloom_rs::FilterBuilder;
+//!
+//! let bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+//! let unique: Vec<&str> = bloom.dedup(["a", "b", "a", "c", "b"]).collect();
+//! assert_eq!(unique, vec!["a", "b", "c"]);
+//! ```
+
+use crate::{BloomFilter, FilterBuilder};
+
+/// An iterator yielding only the items of the inner iterator which were not seen before,
+/// see [BloomFilter::dedup].
+///
+/// An item is dropped when [BloomFilter::contains_then_add] reports it as present, so a new
+/// item is dropped with the false positive probability of the filter, but a duplicate is never
+/// yielded twice.
+#[derive(Debug)]
+pub struct DedupIter<I> {
+    filter: BloomFilter,
+    iter: I,
+}
+
+impl<I> DedupIter<I>
+    where I: Iterator, I::Item: AsRef<[u8]> {
+    /// Deduplicates `iter` with `filter`. Items already in the filter are dropped too.
+    pub fn new<T>(filter: BloomFilter, iter: T) -> Self
+        where T: IntoIterator<IntoIter=I> {
+        DedupIter { filter, iter: iter.into_iter() }
+    }
+
+    /// Deduplicates `iter` with a new filter built from `config`.
+    pub fn from_builder<T>(config: FilterBuilder, iter: T) -> Self
+        where T: IntoIterator<IntoIter=I> {
+        DedupIter::new(BloomFilter::new(config), iter)
+    }
+
+    /// Returns the filter holding every item seen so far.
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+
+    /// Consumes the iterator and gives the filter back.
+    pub fn into_inner(self) -> BloomFilter {
+        self.filter
+    }
+}
+
+impl<I> Iterator for DedupIter<I>
+    where I: Iterator, I::Item: AsRef<[u8]> {
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filter = &mut self.filter;
+        self.iter.find(|item| !filter.contains_then_add(item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl BloomFilter {
+    /// Consumes the filter and returns an iterator over the items of `iter` which are not in the
+    /// filter yet, adding them as they go. See [DedupIter].
+    pub fn dedup<T>(self, iter: T) -> DedupIter<T::IntoIter>
+        where T: IntoIterator, T::Item: AsRef<[u8]> {
+        DedupIter::new(self, iter)
+    }
+}
+
+#[test]
+fn dedup_test() {
+    let bloom = FilterBuilder::new(1000, 0.01).build_bloom_filter();
+    let mut iter = bloom.dedup(vec!["a", "b", "a", "c", "b", "a"]);
+    assert_eq!(iter.by_ref().collect::<Vec<&str>>(), vec!["a", "b", "c"]);
+    let bloom = iter.into_inner();
+    assert_eq!(bloom.contains("c"), true);
+    assert_eq!(bloom.len(), 6);
+}
+
+#[test]
+fn dedup_false_positive_test() {
+    let n = 100_000u64;
+    let config = FilterBuilder::new(n, 0.01);
+    // every item is distinct, so every dropped item is a false positive
+    let kept = DedupIter::from_builder(config, (0..n).map(|i| (i * 7919).to_le_bytes())).count();
+    let dropped = n - kept as u64;
+    assert!((dropped as f64) < n as f64 * 0.01, "dropped {} of {}", dropped, n);
+}
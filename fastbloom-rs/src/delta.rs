@@ -0,0 +1,33 @@
+use crate::builder::FilterBuilder;
+
+/// The words of a [crate::BloomFilter] in which bits were set since an earlier state of it, see
+/// [crate::BloomFilter::diff].
+///
+/// Applying a delta ORs its words into a filter, so applying it twice or applying several
+/// deltas in any order gives the same result.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BloomDelta {
+    pub(crate) config: FilterBuilder,
+    /// `(index, word)` pairs in ascending index order, with the `u64` words of
+    /// [crate::BloomFilter::to_u64_vec].
+    pub(crate) words: Vec<(u64, u64)>,
+}
+
+impl BloomDelta {
+    /// Returns the configuration of the filter the delta was taken from.
+    pub fn config(&self) -> &FilterBuilder {
+        &self.config
+    }
+
+    /// Returns the changed words as `(index, word)` pairs in ascending index order, where
+    /// `word` is the whole `u64` word `index` of [crate::BloomFilter::to_u64_vec].
+    pub fn words(&self) -> &[(u64, u64)] {
+        &self.words
+    }
+
+    /// Returns `true` if no bit was set.
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+}
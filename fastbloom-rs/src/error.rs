@@ -0,0 +1,378 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+/// Error returned by [crate::BloomFilter::try_add] once the estimated false positive probability
+/// of the filter exceeds the configured `false_positive_probability`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CapacityExceeded {
+    /// The estimated current false positive probability.
+    pub estimated_fpp: f64,
+    /// The estimated number of distinct elements in the filter.
+    pub estimated_elements: u64,
+}
+
+impl Display for CapacityExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bloom filter is over capacity: estimated false positive probability {} \
+                   with about {} elements", self.estimated_fpp, self.estimated_elements)
+    }
+}
+
+impl Error for CapacityExceeded {}
+
+/// Error returned when combining two filters whose parameters don't match, carrying the values
+/// of both sides (`left` is `self`, `right` is the other filter).
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum IncompatibleFilterError {
+    /// The filters have a different size in bits.
+    Size { left: u64, right: u64 },
+    /// The filters use a different number of hash functions.
+    Hashes { left: u32, right: u32 },
+    /// The filters use different hash seeds, see [crate::FilterBuilder::with_seed].
+    Seed { left: u64, right: u64 },
+    /// The filters use different hash functions, see [crate::BloomHasher::name].
+    Hasher { left: String, right: String },
+    /// The filters use different versions of the hash scheme, see
+    /// [crate::FilterBuilder::HASH_VERSION].
+    HashVersion { left: u8, right: u8 },
+    /// The filters derive the bit indices differently, see [crate::ProbeScheme].
+    ProbeScheme { left: crate::ProbeScheme, right: crate::ProbeScheme },
+    /// The counting filters have counters of different widths, see [crate::CounterSize].
+    CounterSize { left: crate::CounterSize, right: crate::CounterSize },
+    /// The filters have different sizes which are not both powers of two, so the larger one
+    /// can't be folded onto the smaller one, see [crate::BloomFilter::union_folding].
+    SizeNotFoldable { left: u64, right: u64 },
+    /// The [crate::BloomSet]s hold keys of different types, see [crate::BloomKey::KEY_TAG].
+    KeyTag { left: &'static str, right: &'static str },
+    /// The filters have different sizes and the larger one can't be folded onto the smaller one
+    /// for another reason than its size, see [crate::BloomFilter::union_folding].
+    NotFoldable(FoldError),
+    /// A word of the [crate::BloomDelta] is past the `words` of the filter, e.g. in a corrupted
+    /// deserialized delta, see [crate::BloomFilter::apply_delta].
+    DeltaWordOutOfRange { index: u64, words: u64 },
+}
+
+impl Display for IncompatibleFilterError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IncompatibleFilterError::Size { left, right } =>
+                write!(f, "incompatible bloom filters: size {} != {}", left, right),
+            IncompatibleFilterError::Hashes { left, right } =>
+                write!(f, "incompatible bloom filters: hashes {} != {}", left, right),
+            IncompatibleFilterError::Seed { left, right } =>
+                write!(f, "incompatible bloom filters: seed {} != {}", left, right),
+            IncompatibleFilterError::Hasher { left, right } =>
+                write!(f, "incompatible bloom filters: hasher {} != {}", left, right),
+            IncompatibleFilterError::HashVersion { left, right } =>
+                write!(f, "incompatible bloom filters: hash version {} != {}", left, right),
+            IncompatibleFilterError::ProbeScheme { left, right } =>
+                write!(f, "incompatible bloom filters: probe scheme {} != {}", left.name(),
+                       right.name()),
+            IncompatibleFilterError::CounterSize { left, right } =>
+                write!(f, "incompatible bloom filters: counter size {} != {} bits", left.bits(),
+                       right.bits()),
+            IncompatibleFilterError::SizeNotFoldable { left, right } =>
+                write!(f, "incompatible bloom filters: sizes {} and {} are not both powers of two",
+                       left, right),
+            IncompatibleFilterError::KeyTag { left, right } =>
+                write!(f, "incompatible bloom filters: key type {} != {}", left, right),
+            IncompatibleFilterError::NotFoldable(e) =>
+                write!(f, "incompatible bloom filters: {}", e),
+            IncompatibleFilterError::DeltaWordOutOfRange { index, words } =>
+                write!(f, "incompatible bloom filters: word {} of the delta is out of range for \
+                           {} words", index, words),
+        }
+    }
+}
+
+impl Error for IncompatibleFilterError {}
+
+/// Error returned by [crate::SparseBloom::new] when the set bit positions can't describe a
+/// filter of the given configuration.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InvalidSparseBloom {
+    /// A position is not smaller than the size of the filter.
+    IndexOutOfRange { index: u64, size: u64 },
+    /// The position at `position` in the list is not larger than the one before it, i.e. the
+    /// list is not sorted or has duplicates.
+    NotSorted { position: usize },
+}
+
+impl Display for InvalidSparseBloom {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidSparseBloom::IndexOutOfRange { index, size } =>
+                write!(f, "invalid sparse bloom filter: bit {} is out of range for size {}",
+                       index, size),
+            InvalidSparseBloom::NotSorted { position } =>
+                write!(f, "invalid sparse bloom filter: bits are not sorted and deduplicated \
+                           at position {}", position),
+        }
+    }
+}
+
+impl Error for InvalidSparseBloom {}
+
+/// Error returned by [crate::BloomFilter::fold] when a filter can't be folded by a factor.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FoldError {
+    /// The factor is zero.
+    ZeroFactor,
+    /// The size of the filter is not a multiple of the factor, or smaller than it.
+    NotDivisible { size: u64, factor: u32 },
+    /// The bit indices of the hash version don't stay the same modulo a smaller size: version 2
+    /// replaces a second hash of 0 after the reduction, see [crate::FilterBuilder::HASH_VERSION].
+    HashVersion { version: u8 },
+    /// The probe scheme reduces the combined hashes or cuts the filter into slices, so its bit
+    /// indices don't stay the same modulo a smaller size: [crate::ProbeScheme::Guava],
+    /// [crate::ProbeScheme::RedisBloom] and [crate::ProbeScheme::PyBloom].
+    ProbeScheme { scheme: crate::ProbeScheme },
+    /// The folded size is odd, and the bit indices of hash version 3 only stay the same modulo
+    /// even sizes: it replaces a second hash of 0 after the reduction, which only happens for
+    /// odd sizes, see [crate::FilterBuilder::HASH_VERSION].
+    OddSize { size: u64 },
+}
+
+impl Display for FoldError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FoldError::ZeroFactor => write!(f, "can't fold a bloom filter by a factor of 0"),
+            FoldError::NotDivisible { size, factor } =>
+                write!(f, "can't fold a bloom filter of size {} by a factor of {}", size, factor),
+            FoldError::HashVersion { version } =>
+                write!(f, "can't fold a bloom filter of hash version {}", version),
+            FoldError::ProbeScheme { scheme } =>
+                write!(f, "can't fold a bloom filter with the {} probe scheme", scheme.name()),
+            FoldError::OddSize { size } =>
+                write!(f, "can't fold a bloom filter of hash version 3 to the odd size {}", size),
+        }
+    }
+}
+
+impl Error for FoldError {}
+
+/// Error returned by [crate::FilterBuilder::try_new] for parameters no filter can be built from.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum BuildError {
+    /// The false positive probability is not in the open interval `(0.0, 1.0)` (or is NaN).
+    InvalidProbability { probability: f64 },
+    /// The expected number of elements is zero.
+    ZeroElements,
+    /// The optimal size in bits for the parameters doesn't fit in a `u64` or a `usize`.
+    SizeOverflow { expected_elements: u64, false_positive_probability: f64 },
+    /// The size in bits is zero.
+    ZeroSize,
+    /// The number of hashes is zero.
+    ZeroHashes,
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::InvalidProbability { probability } =>
+                write!(f, "invalid bloom filter parameters: false_positive_probability {} is not \
+                           between 0.0 and 1.0 (exclusive)", probability),
+            BuildError::ZeroElements =>
+                write!(f, "invalid bloom filter parameters: expected_elements must be larger than 0"),
+            BuildError::SizeOverflow { expected_elements, false_positive_probability } =>
+                write!(f, "invalid bloom filter parameters: the size for {} elements with false \
+                           positive probability {} overflows", expected_elements,
+                       false_positive_probability),
+            BuildError::ZeroSize =>
+                write!(f, "invalid bloom filter parameters: the size must be larger than 0"),
+            BuildError::ZeroHashes =>
+                write!(f, "invalid bloom filter parameters: hashes must be larger than 0"),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// The error type of the crate, wrapping the error of every fallible operation so they can be
+/// propagated with `?` into one type.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, FastBloomError, FilterBuilder};
+///
+/// fn merge(bytes: &[u8], size: u64, hashes: u32) -> Result<BloomFilter, FastBloomError> {
+///     let mut merged = FilterBuilder::try_new(100_000, 0.01)?.build_bloom_filter();
+///     merged.try_union(&BloomFilter::try_from_u8_array_with_size(bytes, size, hashes)?)?;
+///     Ok(merged)
+/// }
+///
+/// assert!(matches!(merge(&[0; 8], 64, 7), Err(FastBloomError::IncompatibleFilter(_))));
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum FastBloomError {
+    /// Two filters can't be combined, see [IncompatibleFilterError].
+    IncompatibleFilter(IncompatibleFilterError),
+    /// No filter can be built from the parameters, see [BuildError].
+    InvalidParameters(BuildError),
+    /// The filter is over capacity, see [CapacityExceeded].
+    CapacityExceeded(CapacityExceeded),
+    /// The set bit positions are invalid, see [InvalidSparseBloom].
+    InvalidSparse(InvalidSparseBloom),
+    /// The filter can't be folded, see [FoldError].
+    Fold(FoldError),
+    /// Serialized data can't be turned back into a filter.
+    Deserialization { reason: String },
+    /// A bit position is not smaller than the size of the filter.
+    IndexOutOfRange { index: u64, size: u64 },
+    /// The data doesn't start with the magic bytes of the binary format, i.e. is not a filter
+    /// written by [crate::BloomFilter::to_bytes].
+    NotABloomFilter,
+    /// The data was written in a newer version of the binary format than
+    /// [crate::BloomFilter::FORMAT_VERSION], see [crate::BloomFilter::from_bytes].
+    UnsupportedVersion { version: u8, supported: u8 },
+    /// The checksum of the data doesn't match the one stored with it, i.e. the data was
+    /// truncated or modified, see [crate::BloomFilter::from_bytes].
+    CorruptData { expected: u64, found: u64 },
+    /// The bits are compressed and the crate was built without the `compression` feature to
+    /// decompress them, see [crate::BloomFilter::from_bytes].
+    CompressionDisabled,
+    /// The compressed bits can't be decompressed, i.e. the compressed data was modified.
+    CorruptCompressedData { reason: String },
+    /// Reading or writing the data failed, see [crate::BloomFilter::from_reader]. The
+    /// [std::io::Error] is kept as its kind and message, so the error stays `Clone` and
+    /// `PartialEq`.
+    Io { kind: std::io::ErrorKind, reason: String },
+    /// The buffer passed to [crate::BloomFilter::to_compact_bytes] or
+    /// [crate::BloomFilter::write_into] is shorter than the `needed` bytes of the filter.
+    BufferTooSmall { needed: usize, len: usize },
+    /// The filter can't be written in the foreign `format`, e.g. the one of Guava for
+    /// [crate::BloomFilter::to_guava_bytes], as it places its bits differently.
+    NotRepresentable { format: &'static str, reason: String },
+}
+
+impl Display for FastBloomError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FastBloomError::IncompatibleFilter(e) => Display::fmt(e, f),
+            FastBloomError::InvalidParameters(e) => Display::fmt(e, f),
+            FastBloomError::CapacityExceeded(e) => Display::fmt(e, f),
+            FastBloomError::InvalidSparse(e) => Display::fmt(e, f),
+            FastBloomError::Fold(e) => Display::fmt(e, f),
+            FastBloomError::Deserialization { reason } =>
+                write!(f, "can't deserialize bloom filter: {}", reason),
+            FastBloomError::IndexOutOfRange { index, size } =>
+                write!(f, "bit {} is out of range for a bloom filter of size {}", index, size),
+            FastBloomError::NotABloomFilter =>
+                write!(f, "can't deserialize bloom filter: the data is not a bloom filter"),
+            FastBloomError::UnsupportedVersion { version, supported } =>
+                write!(f, "can't deserialize bloom filter: format version {} is not supported, \
+                           only versions up to {}", version, supported),
+            FastBloomError::CorruptData { expected, found } =>
+                write!(f, "can't deserialize bloom filter: checksum {:016x} doesn't match the \
+                           stored {:016x}", found, expected),
+            FastBloomError::CompressionDisabled =>
+                write!(f, "can't deserialize bloom filter: the bits are compressed and the \
+                           compression feature is disabled"),
+            FastBloomError::CorruptCompressedData { reason } =>
+                write!(f, "can't deserialize bloom filter: corrupt compressed bits: {}", reason),
+            FastBloomError::Io { reason, .. } => write!(f, "bloom filter I/O failed: {}", reason),
+            FastBloomError::BufferTooSmall { needed, len } =>
+                write!(f, "can't serialize bloom filter: the buffer of {} bytes is too small for \
+                           {} bytes", len, needed),
+            FastBloomError::NotRepresentable { format, reason } =>
+                write!(f, "can't serialize bloom filter in the {} format: {}", format, reason),
+        }
+    }
+}
+
+impl Error for FastBloomError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FastBloomError::IncompatibleFilter(e) => Some(e),
+            FastBloomError::InvalidParameters(e) => Some(e),
+            FastBloomError::CapacityExceeded(e) => Some(e),
+            FastBloomError::InvalidSparse(e) => Some(e),
+            FastBloomError::Fold(e) => Some(e),
+            FastBloomError::Deserialization { .. }
+            | FastBloomError::IndexOutOfRange { .. }
+            | FastBloomError::NotABloomFilter
+            | FastBloomError::UnsupportedVersion { .. }
+            | FastBloomError::CorruptData { .. }
+            | FastBloomError::CompressionDisabled
+            | FastBloomError::CorruptCompressedData { .. }
+            | FastBloomError::Io { .. }
+            | FastBloomError::BufferTooSmall { .. }
+            | FastBloomError::NotRepresentable { .. } => None,
+        }
+    }
+}
+
+macro_rules! from_error {
+    ($error:ty, $variant:ident) => {
+        impl From<$error> for FastBloomError {
+            fn from(e: $error) -> Self {
+                FastBloomError::$variant(e)
+            }
+        }
+    };
+}
+
+from_error!(IncompatibleFilterError, IncompatibleFilter);
+from_error!(BuildError, InvalidParameters);
+from_error!(CapacityExceeded, CapacityExceeded);
+from_error!(InvalidSparseBloom, InvalidSparse);
+from_error!(FoldError, Fold);
+
+impl From<std::io::Error> for FastBloomError {
+    fn from(e: std::io::Error) -> Self {
+        FastBloomError::Io { kind: e.kind(), reason: e.to_string() }
+    }
+}
+
+#[test]
+fn fast_bloom_error_test() {
+    let e = FastBloomError::from(IncompatibleFilterError::Size { left: 64, right: 128 });
+    assert_eq!(e, FastBloomError::IncompatibleFilter(IncompatibleFilterError::Size {
+        left: 64,
+        right: 128,
+    }));
+    assert_eq!(e.to_string(), "incompatible bloom filters: size 64 != 128");
+    assert_eq!(e.source().unwrap().to_string(), e.to_string());
+
+    let e = FastBloomError::from(BuildError::ZeroHashes);
+    assert_eq!(e.to_string(), "invalid bloom filter parameters: hashes must be larger than 0");
+    assert_eq!(FastBloomError::from(FoldError::ZeroFactor).to_string(),
+               "can't fold a bloom filter by a factor of 0");
+
+    let e = FastBloomError::Deserialization { reason: "u8 array is too short".to_string() };
+    assert_eq!(e.to_string(), "can't deserialize bloom filter: u8 array is too short");
+    assert_eq!(e.source().is_none(), true);
+    let e = FastBloomError::IndexOutOfRange { index: 64, size: 64 };
+    assert_eq!(e.to_string(), "bit 64 is out of range for a bloom filter of size 64");
+    assert_eq!(FastBloomError::NotABloomFilter.to_string(),
+               "can't deserialize bloom filter: the data is not a bloom filter");
+    let e = FastBloomError::UnsupportedVersion { version: 9, supported: 1 };
+    assert_eq!(e.to_string(), "can't deserialize bloom filter: format version 9 is not \
+                               supported, only versions up to 1");
+    let e = FastBloomError::CorruptData { expected: 0xff, found: 1 };
+    assert_eq!(e.to_string(), "can't deserialize bloom filter: checksum 0000000000000001 doesn't \
+                               match the stored 00000000000000ff");
+    assert_eq!(FastBloomError::CompressionDisabled.to_string(),
+               "can't deserialize bloom filter: the bits are compressed and the compression \
+                feature is disabled");
+    let e = FastBloomError::CorruptCompressedData { reason: "the data ends".to_string() };
+    assert_eq!(e.to_string(), "can't deserialize bloom filter: corrupt compressed bits: the data \
+                               ends");
+    let e = FastBloomError::from(std::io::Error::new(std::io::ErrorKind::NotFound, "no file"));
+    assert_eq!(e, FastBloomError::Io {
+        kind: std::io::ErrorKind::NotFound,
+        reason: "no file".to_string(),
+    });
+    assert_eq!(e.to_string(), "bloom filter I/O failed: no file");
+    let e = FastBloomError::BufferTooSmall { needed: 164, len: 100 };
+    assert_eq!(e.to_string(), "can't serialize bloom filter: the buffer of 100 bytes is too small \
+                               for 164 bytes");
+    let e = FastBloomError::NotRepresentable { format: "Guava", reason: "seed 1".to_string() };
+    assert_eq!(e.to_string(), "can't serialize bloom filter in the Guava format: seed 1");
+}
@@ -0,0 +1,229 @@
+use crate::bloom::{bit_check_with, Probe};
+use crate::builder::FilterBuilder;
+#[cfg(feature = "serde")]
+use crate::error::FastBloomError;
+use crate::hasher::{HashAlgorithm, ProbeScheme};
+use crate::vec::BloomBitVec;
+use crate::{BloomFilter, Hashes};
+
+/// An immutable Bloom filter for the query phase, built with [BloomFilter::freeze].
+///
+/// The bits are kept in a boxed slice of `u64` words without excess capacity and only the size,
+/// number of hashes and the parameters placing the bits (seed, hasher, hash version and probe
+/// scheme) are kept of the configuration. The filter can't be modified, use
+/// [FrozenBloomFilter::thaw] to get a [BloomFilter] back. It is `Send + Sync` and can be shared
+/// between threads behind an `Arc`.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+/// bloom.add(b"hello");
+/// let frozen = bloom.freeze();
+/// assert!(frozen.contains(b"hello"));
+/// assert!(!frozen.contains(b"world"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FrozenBloomFilter {
+    words: Box<[u64]>,
+    size: u64,
+    hashes: u32,
+    #[cfg_attr(feature = "serde", serde(default))]
+    seed: u64,
+    #[cfg_attr(feature = "serde", serde(default = "HashAlgorithm::legacy"))]
+    hasher: HashAlgorithm,
+    #[cfg_attr(feature = "serde", serde(default = "FilterBuilder::legacy_hash_version"))]
+    hash_version: u8,
+    #[cfg_attr(feature = "serde", serde(default))]
+    probe_scheme: ProbeScheme,
+}
+
+/// Checks what the filter relies on when probing: a positive size and number of hashes, the
+/// words of exactly that size and a known hash version. The bits past the size are cleared.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FrozenBloomFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            words: Box<[u64]>,
+            size: u64,
+            hashes: u32,
+            #[serde(default)]
+            seed: u64,
+            #[serde(default = "HashAlgorithm::legacy")]
+            hasher: HashAlgorithm,
+            #[serde(default = "FilterBuilder::legacy_hash_version")]
+            hash_version: u8,
+            #[serde(default)]
+            probe_scheme: ProbeScheme,
+        }
+
+        let invalid = |reason: String| D::Error::custom(FastBloomError::Deserialization { reason });
+        let mut repr = Repr::deserialize(deserializer)?;
+        if repr.size == 0 || repr.hashes == 0 {
+            return Err(invalid(format!("invalid size {} with {} hashes", repr.size,
+                                       repr.hashes)));
+        }
+        let expected = repr.size.div_ceil(64);
+        if repr.words.len() as u64 != expected {
+            return Err(invalid(format!("{} words of bits for a size of {} bits, expected {}",
+                                       repr.words.len(), repr.size, expected)));
+        }
+        if !(1..=FilterBuilder::HASH_VERSION).contains(&repr.hash_version) {
+            return Err(invalid(format!("unknown hash version {}", repr.hash_version)));
+        }
+        if !repr.size.is_multiple_of(64) {
+            repr.words[expected as usize - 1] &= (1 << (repr.size % 64)) - 1;
+        }
+        Ok(FrozenBloomFilter {
+            words: repr.words,
+            size: repr.size,
+            hashes: repr.hashes,
+            seed: repr.seed,
+            hasher: repr.hasher,
+            hash_version: repr.hash_version,
+            probe_scheme: repr.probe_scheme,
+        })
+    }
+}
+
+impl FrozenBloomFilter {
+    pub(crate) fn from_parts(words: Box<[u64]>, config: FilterBuilder) -> Self {
+        FrozenBloomFilter {
+            words,
+            size: config.size,
+            hashes: config.hashes,
+            seed: config.seed,
+            hasher: config.hasher,
+            hash_version: config.hash_version,
+            probe_scheme: config.probe_scheme,
+        }
+    }
+
+    /// The size of the filter in bits.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The hash seed of the filter, see [FilterBuilder::with_seed].
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The bits of the filter as `u64` words with the layout of [BloomFilter::to_u64_vec], e.g.
+    /// to serialize them and load them with [BloomFilter::from_u64_array_with_size].
+    pub fn as_u64_slice(&self) -> &[u64] {
+        &self.words
+    }
+
+    #[inline]
+    fn get(&self, index: usize) -> bool {
+        self.words[index >> 6] & (1 << (index & 63)) != 0
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains<T: AsRef<[u8]>>(&self, element: T) -> bool {
+        let probe = Probe {
+            hasher: &self.hasher,
+            seed: self.seed,
+            version: self.hash_version,
+            scheme: self.probe_scheme,
+        };
+        bit_check_with(|index| self.get(index), element.as_ref(), self.size, self.hashes as u64,
+                       probe)
+    }
+
+    /// Tests whether each element of the slice is present in the filter, see
+    /// [BloomFilter::contains_batch].
+    pub fn contains_batch(&self, elements: &[&[u8]]) -> Vec<bool> {
+        elements.iter().map(|element| self.contains(element)).collect()
+    }
+
+    /// Turns the filter back into a mutable [BloomFilter] with the same bits, reusing the words
+    /// on 64-bit platforms. The configuration is rebuilt from the size and hashes like
+    /// [FilterBuilder::from_size_and_hashes] does with the seed, hasher, hash version and probe
+    /// scheme of the filter, and
+    /// [BloomFilter::len] starts at 0.
+    pub fn thaw(self) -> BloomFilter {
+        let bit_set = BloomBitVec::from_u64_vec(self.words.into_vec(), self.size);
+        let mut config = FilterBuilder::from_size_and_hashes(self.size, self.hashes)
+            .with_seed(self.seed)
+            .with_hash_version(self.hash_version)
+            .with_probe_scheme(self.probe_scheme);
+        config.hasher = self.hasher;
+        BloomFilter::from_parts(config, bit_set)
+    }
+}
+
+impl Hashes for FrozenBloomFilter {
+    ///  Returns the hash function number of the Bloom filter.
+    fn hashes(&self) -> u32 {
+        self.hashes
+    }
+}
+
+#[test]
+fn frozen_bloom_test() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<FrozenBloomFilter>();
+
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in 0..1000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    let words = bloom.to_u64_vec();
+    let copy = bloom.clone();
+
+    let frozen = bloom.freeze();
+    assert_eq!(frozen.as_u64_slice(), &words[..]);
+    for i in 0..5000u32 {
+        assert_eq!(frozen.contains(i.to_le_bytes()), copy.contains(i.to_le_bytes()));
+    }
+    assert_eq!(frozen.contains_batch(&[b"hello", &7u32.to_le_bytes()]), vec![false, true]);
+
+    let thawed = frozen.thaw();
+    assert_eq!(thawed.to_u64_vec(), words);
+    assert_eq!(thawed, copy);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn frozen_bloom_serde_test() {
+    let mut bloom = FilterBuilder::from_size_and_hashes(100, 4).build_bloom_filter();
+    bloom.add(b"hello");
+    let frozen = bloom.freeze();
+    let json = serde_json::to_value(&frozen).unwrap();
+    let copy: FrozenBloomFilter = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(copy, frozen);
+
+    let reason = |field: &str, value: serde_json::Value| {
+        let mut json = json.clone();
+        json[field] = value;
+        serde_json::from_value::<FrozenBloomFilter>(json).unwrap_err().to_string()
+    };
+    assert_eq!(reason("size", 0.into()),
+               "can't deserialize bloom filter: invalid size 0 with 4 hashes");
+    assert_eq!(reason("hashes", 0.into()),
+               "can't deserialize bloom filter: invalid size 100 with 0 hashes");
+    assert_eq!(reason("size", 1000.into()),
+               "can't deserialize bloom filter: 2 words of bits for a size of 1000 bits, \
+                expected 16");
+    assert_eq!(reason("words", serde_json::json!([])),
+               "can't deserialize bloom filter: 0 words of bits for a size of 100 bits, \
+                expected 2");
+    assert_eq!(reason("hash_version", 9.into()),
+               "can't deserialize bloom filter: unknown hash version 9");
+
+    // the bits past the size are cleared
+    let mut padded = json.clone();
+    padded["words"][1] = (u64::MAX - 1).into();
+    let copy: FrozenBloomFilter = serde_json::from_value(padded).unwrap();
+    assert_eq!(copy.as_u64_slice()[1], (1 << 36) - 2);
+}
@@ -0,0 +1,296 @@
+use xxhash_rust::xxh3::xxh3_64_with_seed;
+
+use crate::bits::{BitReader, BitWriter};
+use crate::error::{BuildError, FastBloomError};
+use crate::FilterBuilder;
+
+/// The Rice parameter, the seed, the range and the number of values.
+const HEADER_LEN: usize = 25;
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization { reason: format!("invalid golomb coded set: {}", reason) }
+}
+
+/// A Golomb-coded set (GCS), the static set BIP-158 block filters use, to distribute a fixed set
+/// of keys, e.g. a block list, to clients. A Bloom filter needs `1.44 * log2(1 / p)` bits per key
+/// for the false positive probability `p`, a GCS about `log2(1 / p) + 1.5`, so it is smaller for
+/// `p` below about 0.1. Keys can't be added to it, it is built once from all of them, and
+/// [GolombCodedSet::contains] decodes the values up to the key instead of probing a few bits, so
+/// it is rather for sets of up to some million keys.
+///
+/// For `n` elements with the false positive probability `p`, every key is hashed with xxh3 64
+/// with the seed and its hash scaled into the range `F = n * M` for `M = ceil(1 / p)`,
+/// `(hash * F) >> 64`. The distinct values are sorted, and the differences between them, of the
+/// first value to 0, are Rice coded with the parameter `P = floor(log2(M))`: the difference
+/// shifted right by `P` in unary as ones followed by a zero, then its `P` low bits.
+///
+/// [GolombCodedSet::to_bytes] writes `P` as one byte, the seed, `F` and the number of values as
+/// 8 little endian bytes each, and the codes least significant bit first, the last byte padded
+/// with zeros.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, FilterBuilder, GolombCodedSet};
+///
+/// let keys: Vec<String> = (0..10_000).map(|i| format!("key-{}", i)).collect();
+/// let gcs = GolombCodedSet::build(&keys, 10_000, 0.01);
+/// assert!(gcs.contains("key-42"));
+///
+/// let bloom = BloomFilter::from_iter_with(FilterBuilder::new(10_000, 0.01), &keys);
+/// assert!(gcs.to_bytes().len() < bloom.get_u8_array().len());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GolombCodedSet {
+    k: u32,
+    seed: u64,
+    range: u64,
+    len: u64,
+    codes: Vec<u8>,
+}
+
+impl GolombCodedSet {
+    /// Builds the set of `keys` for `expected_elements` elements with the false positive
+    /// probability `false_positive_probability`, with seed 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the parameters are invalid, see [GolombCodedSet::try_build].
+    pub fn build<I, T>(keys: I, expected_elements: u64, false_positive_probability: f64) -> Self
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+        GolombCodedSet::try_build(keys, expected_elements, false_positive_probability)
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Same as [GolombCodedSet::build] but returns an error instead of panicking if the
+    /// parameters are invalid for [FilterBuilder::try_new] or the range `n * ceil(1 / p)`
+    /// overflows a `u64`.
+    pub fn try_build<I, T>(keys: I, expected_elements: u64, false_positive_probability: f64)
+                           -> Result<Self, BuildError>
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+        FilterBuilder::try_new(expected_elements, false_positive_probability)?;
+        GolombCodedSet::with_params(keys, expected_elements, false_positive_probability, 0)
+    }
+
+    /// Builds the set, see [FilterBuilder::build_golomb_coded_set].
+    pub(crate) fn with_params<I, T>(keys: I, n: u64, p: f64, seed: u64)
+                                    -> Result<Self, BuildError>
+        where I: IntoIterator<Item=T>, T: AsRef<[u8]> {
+        let m = (1.0 / p).ceil();
+        let range = (m as u64).checked_mul(n).filter(|_| m < u64::MAX as f64)
+            .ok_or(BuildError::SizeOverflow {
+                expected_elements: n,
+                false_positive_probability: p,
+            })?;
+        let k = 63 - (m as u64).leading_zeros();
+        let mut values: Vec<u64> = keys.into_iter()
+            .map(|key| scale(xxh3_64_with_seed(key.as_ref(), seed), range))
+            .collect();
+        values.sort_unstable();
+        values.dedup();
+
+        let mut writer = BitWriter::new(Vec::with_capacity(values.len() * (k as usize + 2) / 8));
+        let mut last = 0;
+        for &value in &values {
+            writer.write_rice(value - last, k);
+            last = value;
+        }
+        Ok(GolombCodedSet { k, seed, range, len: values.len() as u64, codes: writer.finish() })
+    }
+
+    /// Returns `false` if `key` is not in the set, `true` if it probably is.
+    pub fn contains<T: AsRef<[u8]>>(&self, key: T) -> bool {
+        let target = scale(xxh3_64_with_seed(key.as_ref(), self.seed), self.range);
+        let mut reader = BitReader::new(&self.codes);
+        let mut value = 0;
+        for _ in 0..self.len {
+            value += reader.read_rice(self.k).ok().flatten().expect("the codes are valid");
+            if value >= target {
+                return value == target;
+            }
+        }
+        false
+    }
+
+    /// The number of distinct values, the keys the set was built of less those with the same
+    /// value.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the set was built of no keys.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The length of [GolombCodedSet::to_bytes].
+    pub fn num_bytes(&self) -> usize {
+        HEADER_LEN + self.codes.len()
+    }
+
+    /// Writes the set in the format described on [GolombCodedSet].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.num_bytes());
+        bytes.push(self.k as u8);
+        for field in [self.seed, self.range, self.len] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.codes);
+        bytes
+    }
+
+    /// Reads a set written by [GolombCodedSet::to_bytes].
+    ///
+    /// Returns [FastBloomError::Deserialization] if the bytes are not such a set: the header is
+    /// truncated, the values are not increasing within the range or the codes don't end with
+    /// the last value.
+    pub fn from_bytes(bytes: &[u8]) -> Result<GolombCodedSet, FastBloomError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(invalid(format!("{} bytes are too short for the header", bytes.len())));
+        }
+        let field = |i: usize| u64::from_le_bytes(bytes[1 + 8 * i..9 + 8 * i].try_into().unwrap());
+        let (k, seed, range, len) = (bytes[0] as u32, field(0), field(1), field(2));
+        if k > 63 || range == 0 {
+            return Err(invalid(format!("invalid parameter {} for the range {}", k, range)));
+        }
+        let codes = &bytes[HEADER_LEN..];
+        let mut reader = BitReader::new(codes);
+        let mut last = 0u64;
+        for i in 0..len {
+            let delta = reader.read_rice(k).map_err(invalid)?;
+            let value = delta.filter(|&delta| delta > 0 || i == 0)
+                .and_then(|delta| last.checked_add(delta))
+                .filter(|&value| value < range)
+                .ok_or_else(|| invalid(format!("value {} is not increasing within the range {}",
+                                               i, range)))?;
+            last = value;
+        }
+        if !reader.is_at_end() {
+            return Err(invalid("the data continues after the last value".to_string()));
+        }
+        Ok(GolombCodedSet { k, seed, range, len, codes: codes.to_vec() })
+    }
+}
+
+/// Scales a hash into `0..range`.
+#[inline]
+fn scale(hash: u64, range: u64) -> u64 {
+    ((hash as u128 * range as u128) >> 64) as u64
+}
+
+#[test]
+fn gcs_test() {
+    use crate::BloomFilter;
+
+    let keys: Vec<String> = (0..2000).map(|i| format!("key-{}", i)).collect();
+    for p in [0.5, 0.1, 0.05, 0.01, 0.001] {
+        let gcs = GolombCodedSet::build(&keys, 2000, p);
+        assert_eq!(gcs.len() <= 2000, true);
+        for key in &keys {
+            assert_eq!(gcs.contains(key), true);
+        }
+        let false_positives = (0..20_000).filter(|i| gcs.contains(format!("other-{}", i))).count();
+        let expected = 20_000.0 * p;
+        assert_eq!((false_positives as f64) < expected * 1.3 + 10.0, true, "{} {}", p,
+                   false_positives);
+
+        let copy = GolombCodedSet::from_bytes(&gcs.to_bytes()).unwrap();
+        assert_eq!(copy, gcs);
+        assert_eq!(copy.to_bytes(), gcs.to_bytes());
+        assert_eq!(copy.num_bytes(), gcs.to_bytes().len());
+
+        // smaller than a bloom filter with the same false positive probability
+        if p > 0.1 {
+            continue;
+        }
+        let bloom = BloomFilter::from_iter_with(FilterBuilder::new(2000, p), &keys);
+        let bloom_bytes = bloom.config().size.div_ceil(8) as usize;
+        assert_eq!(gcs.num_bytes() < bloom_bytes, true, "{} {} {}", p, gcs.num_bytes(),
+                   bloom_bytes);
+    }
+    // 0.01: M = 100, P = 6, about 8 bits per key instead of 9.6
+    let gcs = GolombCodedSet::build(&keys, 2000, 0.01);
+    assert_eq!(gcs.to_bytes()[0], 6);
+    assert_eq!(gcs.range, 200_000);
+    assert_eq!(gcs.num_bytes() * 8 < 2000 * 85 / 10, true, "{}", gcs.num_bytes());
+
+    let empty = GolombCodedSet::build(Vec::<&[u8]>::new(), 100, 0.01);
+    assert_eq!(empty.is_empty(), true);
+    assert_eq!(empty.contains(b"hello"), false);
+    assert_eq!(empty.num_bytes(), HEADER_LEN);
+    assert_eq!(GolombCodedSet::from_bytes(&empty.to_bytes()), Ok(empty));
+
+    let twice = GolombCodedSet::build(["a", "a", "b"], 2, 0.01);
+    assert_eq!(twice.len(), 2);
+
+    assert_eq!(GolombCodedSet::try_build(&keys, 0, 0.01), Err(BuildError::ZeroElements));
+    assert_eq!(GolombCodedSet::try_build(&keys, 10, 1.0),
+               Err(BuildError::InvalidProbability { probability: 1.0 }));
+    assert_eq!(GolombCodedSet::try_build(&keys, 1 << 40, 1e-9),
+               Err(BuildError::SizeOverflow {
+                   expected_elements: 1 << 40,
+                   false_positive_probability: 1e-9,
+               }));
+}
+
+#[test]
+fn gcs_encoding_test() {
+    // the values of the keys, their differences and codes by hand
+    let gcs = GolombCodedSet::build(["a", "b", "c"], 3, 0.25);
+    let mut values: Vec<u64> = ["a", "b", "c"].iter()
+        .map(|key| scale(xxh3_64_with_seed(key.as_bytes(), 0), 12))
+        .collect();
+    values.sort_unstable();
+    values.dedup();
+    let mut bits = Vec::new();
+    let mut last = 0;
+    for value in &values {
+        let delta = value - last;
+        bits.extend(std::iter::repeat_n(1u8, (delta >> 2) as usize));
+        bits.extend([0, (delta & 1) as u8, (delta >> 1 & 1) as u8]);
+        last = *value;
+    }
+    let mut codes = vec![0u8; bits.len().div_ceil(8)];
+    for (i, bit) in bits.iter().enumerate() {
+        codes[i / 8] |= bit << (i % 8);
+    }
+    let mut expected = vec![2];
+    expected.extend_from_slice(&0u64.to_le_bytes());
+    expected.extend_from_slice(&12u64.to_le_bytes());
+    expected.extend_from_slice(&(values.len() as u64).to_le_bytes());
+    expected.extend_from_slice(&codes);
+    assert_eq!(gcs.to_bytes(), expected);
+
+    let seeded = FilterBuilder::new(3, 0.25).with_seed(7).build_golomb_coded_set(["a", "b", "c"]);
+    assert_eq!(seeded.to_bytes()[1..9], 7u64.to_le_bytes());
+    assert_eq!(seeded.contains("a"), true);
+}
+
+#[test]
+fn gcs_invalid_test() {
+    let gcs = GolombCodedSet::build((0..100u32).map(|i| i.to_le_bytes()), 100, 0.01);
+    let bytes = gcs.to_bytes();
+    let reason = |bytes: &[u8]| match GolombCodedSet::from_bytes(bytes).unwrap_err() {
+        FastBloomError::Deserialization { reason } => reason,
+        e => panic!("unexpected error {:?}", e),
+    };
+    assert_eq!(reason(&bytes[..24]), "invalid golomb coded set: 24 bytes are too short for the \
+                                      header");
+    assert_eq!(reason(&bytes[..bytes.len() - 1]),
+               "invalid golomb coded set: the data ends within a gap");
+    assert_eq!(reason(&[&bytes[..], &[0]].concat()),
+               "invalid golomb coded set: the data continues after the last value");
+    let mut k = bytes.clone();
+    k[0] = 64;
+    assert_eq!(reason(&k), "invalid golomb coded set: invalid parameter 64 for the range 10000");
+    let mut range = bytes.clone();
+    range[9..17].copy_from_slice(&100u64.to_le_bytes());
+    assert_eq!(reason(&range).ends_with("is not increasing within the range 100"), true);
+    // a second value of 0 repeats the first one
+    let mut repeated = vec![0; 17];
+    repeated.extend_from_slice(&2u64.to_le_bytes());
+    repeated.push(0);
+    repeated[9] = 10;
+    assert_eq!(reason(&repeated),
+               "invalid golomb coded set: value 1 is not increasing within the range 10");
+}
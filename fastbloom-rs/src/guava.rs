@@ -0,0 +1,219 @@
+//! The serialized form of Guava's `BloomFilter`, see [BloomFilter::to_guava_bytes].
+
+use crate::error::FastBloomError;
+use crate::hasher::{HashAlgorithm, ProbeScheme};
+use crate::vec::BloomBitVec;
+use crate::{BloomFilter, BloomHasher, FilterBuilder, Hashes, Murmur3Hasher};
+
+/// The ordinal of `BloomFilterStrategies.MURMUR128_MITZ_32`, the strategy of filters written by
+/// Guava before version 13.
+const MURMUR128_MITZ_32: u8 = 0;
+/// The ordinal of `BloomFilterStrategies.MURMUR128_MITZ_64`, [ProbeScheme::Guava].
+const MURMUR128_MITZ_64: u8 = 1;
+/// The strategy ordinal, the number of hashes and the number of longs.
+const HEADER_LEN: usize = 6;
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization { reason: format!("invalid Guava bloom filter: {}", reason) }
+}
+
+impl BloomFilter {
+    /// Writes the filter in the format of Guava's `BloomFilter.writeTo`, for JVM services to
+    /// read it with `BloomFilter.readFrom`: the strategy ordinal, the number of hashes as a byte,
+    /// the number of longs as an `int` and the longs of the bits, all big endian.
+    ///
+    /// Only filters which place their bits like Guava can be written, i.e. those built with
+    /// [FilterBuilder::guava_compatible] or read with [BloomFilter::from_guava_bytes]. Guava
+    /// doesn't store the number of insert operations, so [BloomFilter::len] is lost.
+    ///
+    /// Returns [FastBloomError::NotRepresentable] for any other filter: one with another hasher,
+    /// a seed, another [ProbeScheme], a size which is not a multiple of 64 or more than 255
+    /// hashes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::guava_compatible(10_000, 0.01).build_bloom_filter();
+    /// bloom.add("hello".as_bytes());
+    /// let bytes = bloom.to_guava_bytes().unwrap();
+    ///
+    /// let copy = BloomFilter::from_guava_bytes(&bytes).unwrap();
+    /// assert!(copy.contains("hello".as_bytes()));
+    /// assert!(FilterBuilder::new(10_000, 0.01).build_bloom_filter().to_guava_bytes().is_err());
+    /// ```
+    pub fn to_guava_bytes(&self) -> Result<Vec<u8>, FastBloomError> {
+        let config = self.config_ref();
+        let unrepresentable = |reason: String| {
+            Err(FastBloomError::NotRepresentable { format: "Guava", reason })
+        };
+        if config.hasher.name() != Murmur3Hasher.name() {
+            return unrepresentable(format!("the hasher is {}, not {}", config.hasher.name(),
+                                           Murmur3Hasher.name()));
+        }
+        if config.seed != 0 {
+            return unrepresentable(format!("the seed is {}, not 0", config.seed));
+        }
+        if config.probe_scheme != ProbeScheme::Guava {
+            return unrepresentable(format!("the probe scheme is {}, not {}",
+                                           config.probe_scheme.name(), ProbeScheme::Guava.name()));
+        }
+        if !config.size.is_multiple_of(64) {
+            return unrepresentable(format!("the size of {} bits is not a multiple of 64",
+                                           config.size));
+        }
+        let words = config.size / 64;
+        let Ok(guava_words) = i32::try_from(words) else {
+            return unrepresentable(format!("the size of {} bits is more than {} longs",
+                                           config.size, i32::MAX));
+        };
+        let Ok(hashes) = u8::try_from(self.hashes()) else {
+            return unrepresentable(format!("{} hashes are more than 255", self.hashes()));
+        };
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + words as usize * 8);
+        bytes.push(MURMUR128_MITZ_64);
+        bytes.push(hashes);
+        bytes.extend_from_slice(&guava_words.to_be_bytes());
+        for i in 0..words as usize {
+            bytes.extend_from_slice(&self.bit_vec().u64_word(i).to_be_bytes());
+        }
+        Ok(bytes)
+    }
+
+    /// Reads a filter written by Guava's `BloomFilter.writeTo`, see [BloomFilter::to_guava_bytes].
+    /// It answers membership queries like the Guava filter for the bytes its funnel writes, see
+    /// [FilterBuilder::guava_compatible], and [BloomFilter::len] is 0.
+    ///
+    /// The format is tested against bytes written by a port of Guava's code
+    /// (testvectors/GuavaFixture.java), not against Guava itself.
+    ///
+    /// Returns [FastBloomError::Deserialization] if the data is truncated or followed by more
+    /// bytes, or if the filter uses another strategy than `MURMUR128_MITZ_64`, the one of every
+    /// filter Guava creates since version 13.
+    pub fn from_guava_bytes(bytes: &[u8]) -> Result<BloomFilter, FastBloomError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(invalid("the data ends within the header".to_string()));
+        }
+        match bytes[0] {
+            MURMUR128_MITZ_64 => {}
+            MURMUR128_MITZ_32 => return Err(invalid(format!(
+                "strategy MURMUR128_MITZ_32 (ordinal {}) is not supported, only \
+                 MURMUR128_MITZ_64 (ordinal {})", MURMUR128_MITZ_32, MURMUR128_MITZ_64))),
+            ordinal => return Err(invalid(format!("unknown strategy ordinal {}", ordinal))),
+        }
+        let hashes = bytes[1] as u32;
+        if hashes == 0 {
+            return Err(invalid("0 hash functions".to_string()));
+        }
+        let words = i32::from_be_bytes(bytes[2..HEADER_LEN].try_into().unwrap());
+        if words <= 0 {
+            return Err(invalid(format!("invalid number of longs {}", words)));
+        }
+        let data = &bytes[HEADER_LEN..];
+        let len = words as usize * 8;
+        if data.len() < len {
+            return Err(invalid("the data ends within the bits".to_string()));
+        }
+        if data.len() > len {
+            return Err(invalid(format!("{} bytes after the filter", data.len() - len)));
+        }
+
+        let size = words as u64 * 64;
+        let config = FilterBuilder::from_size_and_hashes(size, hashes)
+            .with_hash_algorithm(HashAlgorithm::Murmur3)
+            .with_probe_scheme(ProbeScheme::Guava);
+        let words = data.chunks_exact(8)
+            .map(|word| u64::from_be_bytes(word.try_into().unwrap()))
+            .collect();
+        Ok(BloomFilter::from_parts(config, BloomBitVec::from_u64_vec(words, size)))
+    }
+}
+
+#[test]
+fn guava_test() {
+    // BloomFilter.create(Funnels.stringFunnel(UTF_8), 1000, 0.01) with key-0 .. key-999 put,
+    // and the keys of other-0 .. other-9999 it reports present, as the port of Guava in
+    // testvectors/GuavaFixture.java writes them
+    let fixture = include_bytes!("../testvectors/guava_mitz64.bin");
+    let positives = include_str!("../testvectors/guava_mitz64.txt");
+    let bloom = BloomFilter::from_guava_bytes(fixture).unwrap();
+    assert_eq!((bloom.config().size, bloom.hashes()), (9600, 7));
+    assert_eq!(bloom.config().probe_scheme, ProbeScheme::Guava);
+    for i in 0..1000 {
+        assert_eq!(bloom.contains(format!("key-{}", i).as_bytes()), true);
+    }
+    let found: Vec<String> = (0..10_000)
+        .map(|i| format!("other-{}", i))
+        .filter(|key| bloom.contains(key.as_bytes()))
+        .collect();
+    assert_eq!(found, positives.lines().collect::<Vec<_>>());
+    assert_eq!(found.len(), 88);
+    assert_eq!(bloom.to_guava_bytes().unwrap(), fixture);
+
+    // the same filter built here
+    let mut built = FilterBuilder::guava_compatible(1000, 0.01).build_bloom_filter();
+    for i in 0..1000 {
+        built.add(format!("key-{}", i).as_bytes());
+    }
+    assert_eq!(built.to_guava_bytes().unwrap(), fixture);
+    assert_eq!(built.compatible(&bloom), true);
+
+    // the sizes Guava computes
+    let sized = FilterBuilder::guava_compatible(0, 0.5);
+    assert_eq!((sized.size, sized.hashes), (64, 1));
+    let sized = FilterBuilder::guava_compatible(5_000_000, 1e-12).build_bloom_filter();
+    assert_eq!((sized.config().size, sized.hashes()), (287_551_808, 40));
+}
+
+#[test]
+fn guava_invalid_test() {
+    let fixture = include_bytes!("../testvectors/guava_mitz64.bin");
+    let reason = |bytes: &[u8]| match BloomFilter::from_guava_bytes(bytes).unwrap_err() {
+        FastBloomError::Deserialization { reason } => reason,
+        e => panic!("unexpected error {:?}", e),
+    };
+    let patched = |i: usize, value: u8| {
+        let mut bytes = fixture.to_vec();
+        bytes[i] = value;
+        bytes
+    };
+    assert_eq!(reason(&fixture[..5]),
+               "invalid Guava bloom filter: the data ends within the header");
+    assert_eq!(reason(&patched(0, 0)),
+               "invalid Guava bloom filter: strategy MURMUR128_MITZ_32 (ordinal 0) is not \
+                supported, only MURMUR128_MITZ_64 (ordinal 1)");
+    assert_eq!(reason(&patched(0, 2)), "invalid Guava bloom filter: unknown strategy ordinal 2");
+    assert_eq!(reason(&patched(1, 0)), "invalid Guava bloom filter: 0 hash functions");
+    assert_eq!(reason(&patched(2, 0x80)),
+               "invalid Guava bloom filter: invalid number of longs -2147483498");
+    assert_eq!(reason(&[1, 7, 0, 0, 0, 0]),
+               "invalid Guava bloom filter: invalid number of longs 0");
+    assert_eq!(reason(&fixture[..fixture.len() - 1]),
+               "invalid Guava bloom filter: the data ends within the bits");
+    assert_eq!(reason(&[&fixture[..], &[0, 0]].concat()),
+               "invalid Guava bloom filter: 2 bytes after the filter");
+}
+
+#[test]
+fn guava_not_representable_test() {
+    let reason = |mut config: FilterBuilder| {
+        match config.build_bloom_filter().to_guava_bytes().unwrap_err() {
+            FastBloomError::NotRepresentable { format: "Guava", reason } => reason,
+            e => panic!("unexpected error {:?}", e),
+        }
+    };
+    let guava = FilterBuilder::guava_compatible(1000, 0.01);
+    assert_eq!(reason(FilterBuilder::from_size_and_hashes(9600, 7)),
+               "the hasher is xxh128, not murmur3_x64_128");
+    assert_eq!(reason(guava.clone().with_seed(1)), "the seed is 1, not 0");
+    assert_eq!(reason(guava.clone().with_probe_scheme(ProbeScheme::Double)),
+               "the probe scheme is double, not guava");
+    assert_eq!(reason(guava.clone().with_size(9601)),
+               "the size of 9601 bits is not a multiple of 64");
+    assert_eq!(reason(guava.with_hashes(256)), "256 hashes are more than 255");
+    let custom = FilterBuilder::from_size_and_hashes(640, 3).with_hasher(Murmur3Hasher)
+        .with_probe_scheme(ProbeScheme::Guava);
+    assert_eq!(custom.clone().build_bloom_filter().to_guava_bytes().is_ok(), true);
+}
@@ -0,0 +1,648 @@
+use std::any::TypeId;
+use std::borrow::Cow;
+use std::fmt::{Debug, Formatter};
+use std::hash::Hasher;
+use std::sync::Arc;
+
+use fastmurmur3::murmur3_x64_128;
+use siphasher::sip128::{Hasher128, SipHasher13};
+use xxhash_rust::xxh3::{xxh3_128_with_seed, xxh3_64_with_seed};
+
+/// A hash function for Bloom filters. The bit indices of an element are derived from the two
+/// 64-bit hashes returned by [BloomHasher::hash128] by double hashing, so they should be
+/// independent of each other.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomHasher, FilterBuilder};
+///
+/// struct Fnv;
+///
+/// impl BloomHasher for Fnv {
+///     fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+///         let fnv = |basis: u64| data.iter()
+///             .fold(basis, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3));
+///         (fnv(0xcbf29ce484222325 ^ seed), fnv(0x84222325cbf29ce4 ^ seed))
+///     }
+///
+///     fn name(&self) -> &str {
+///         "fnv1a"
+///     }
+/// }
+///
+/// let mut bloom = FilterBuilder::new(10_000, 0.01).with_hasher(Fnv).build_bloom_filter();
+/// bloom.add(b"hello");
+/// assert!(bloom.contains(b"hello"));
+/// assert_eq!(bloom.config().hasher.name(), "fnv1a");
+/// ```
+pub trait BloomHasher: Send + Sync {
+    /// Returns two 64-bit hashes of `data` for the seed of the filter.
+    fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64);
+
+    /// The name of the hash function. Filters are only compatible if the names of their hashers
+    /// are equal, so it must identify the function and any parameters it has. The name is what
+    /// is serialized of a custom hasher. The names of the built-in hashers are reserved.
+    fn name(&self) -> &str;
+}
+
+/// The default hasher, the two halves of the 128-bit xxh3 hash (XXH128), so every element is
+/// hashed once.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Xxh128Hasher;
+
+impl BloomHasher for Xxh128Hasher {
+    #[inline]
+    fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+        let hash = xxh3_128_with_seed(data, seed);
+        (hash as u64, (hash >> 64) as u64)
+    }
+
+    fn name(&self) -> &str {
+        "xxh128"
+    }
+}
+
+/// The 64-bit xxh3 hash seeded with the seed and the seed plus 32, so every element is hashed
+/// twice. It was the only hasher before [Xxh128Hasher] became the default, filters saved by
+/// those versions need it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Xxh3Hasher;
+
+impl BloomHasher for Xxh3Hasher {
+    #[inline]
+    fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+        (xxh3_64_with_seed(data, seed), xxh3_64_with_seed(data, seed.wrapping_add(32)))
+    }
+
+    fn name(&self) -> &str {
+        "xxh3"
+    }
+}
+
+/// MurmurHash3 x64 128, the two halves of its 128-bit hash. The seed is folded to the 32 bits
+/// murmur3 takes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Murmur3Hasher;
+
+impl BloomHasher for Murmur3Hasher {
+    #[inline]
+    fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+        let hash = murmur3_x64_128(data, (seed ^ (seed >> 32)) as u32);
+        (hash as u64, (hash >> 64) as u64)
+    }
+
+    fn name(&self) -> &str {
+        "murmur3_x64_128"
+    }
+}
+
+/// MurmurHash64A, the 64-bit hash of MurmurHash2.
+fn murmur64a(data: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xc6a4_a793_5bd1_e995;
+    const R: u32 = 47;
+    let mut h = seed ^ (data.len() as u64).wrapping_mul(M);
+    let mut chunks = data.chunks_exact(8);
+    for chunk in &mut chunks {
+        let mut k = u64::from_le_bytes(chunk.try_into().unwrap()).wrapping_mul(M);
+        k ^= k >> R;
+        h = (h ^ k.wrapping_mul(M)).wrapping_mul(M);
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, &byte) in tail.iter().enumerate() {
+            h ^= (byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^ (h >> R)
+}
+
+/// MurmurHash64A, and MurmurHash64A again seeded with the first hash, the hashes of RedisBloom.
+/// Its filters use the seed [Murmur64AHasher::REDISBLOOM_SEED], see
+/// [crate::BloomFilter::from_redisbloom_chunks].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Murmur64AHasher;
+
+impl Murmur64AHasher {
+    /// The seed of the first hash of RedisBloom.
+    pub const REDISBLOOM_SEED: u64 = 0xc6a4_a793_5bd1_e995;
+}
+
+impl BloomHasher for Murmur64AHasher {
+    #[inline]
+    fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+        let hash1 = murmur64a(data, seed);
+        (hash1, murmur64a(data, hash1))
+    }
+
+    fn name(&self) -> &str {
+        "murmur64a"
+    }
+}
+
+/// The constants of the 64 steps of MD5, `floor(abs(sin(i + 1)) * 2^32)`.
+const MD5_K: [u32; 64] = [
+    0xd76aa478, 0xe8c7b756, 0x242070db, 0xc1bdceee, 0xf57c0faf, 0x4787c62a, 0xa8304613, 0xfd469501,
+    0x698098d8, 0x8b44f7af, 0xffff5bb1, 0x895cd7be, 0x6b901122, 0xfd987193, 0xa679438e, 0x49b40821,
+    0xf61e2562, 0xc040b340, 0x265e5a51, 0xe9b6c7aa, 0xd62f105d, 0x02441453, 0xd8a1e681, 0xe7d3fbc8,
+    0x21e1cde6, 0xc33707d6, 0xf4d50d87, 0x455a14ed, 0xa9e3e905, 0xfcefa3f8, 0x676f02d9, 0x8d2a4c8a,
+    0xfffa3942, 0x8771f681, 0x6d9d6122, 0xfde5380c, 0xa4beea44, 0x4bdecfa9, 0xf6bb4b60, 0xbebfbc70,
+    0x289b7ec6, 0xeaa127fa, 0xd4ef3085, 0x04881d05, 0xd9d4d039, 0xe6db99e5, 0x1fa27cf8, 0xc4ac5665,
+    0xf4292244, 0x432aff97, 0xab9423a7, 0xfc93a039, 0x655b59c3, 0x8f0ccc92, 0xffeff47d, 0x85845dd1,
+    0x6fa87e4f, 0xfe2ce6e0, 0xa3014314, 0x4e0811a1, 0xf7537e82, 0xbd3af235, 0x2ad7d2bb, 0xeb86d391,
+];
+/// The rotations of the steps of MD5, four per round.
+const MD5_R: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+
+/// The MD5 digest (RFC 1321) of the concatenated `parts`.
+fn md5(parts: &[&[u8]]) -> [u8; 16] {
+    let len: usize = parts.iter().map(|part| part.len()).sum();
+    let zeros = [0u8; 64];
+    let bits = (len as u64).wrapping_mul(8).to_le_bytes();
+    let padding: [&[u8]; 3] = [&[0x80], &zeros[..(119 - len % 64) % 64], &bits];
+    let mut state = [0x6745_2301u32, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    let mut block = [0u8; 64];
+    let mut filled = 0;
+    for mut part in parts.iter().copied().chain(padding) {
+        while !part.is_empty() {
+            let n = part.len().min(64 - filled);
+            block[filled..filled + n].copy_from_slice(&part[..n]);
+            filled += n;
+            part = &part[n..];
+            if filled == 64 {
+                md5_block(&mut state, &block);
+                filled = 0;
+            }
+        }
+    }
+    let mut digest = [0u8; 16];
+    for (bytes, word) in digest.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+    digest
+}
+
+fn md5_block(state: &mut [u32; 4], block: &[u8; 64]) {
+    let mut m = [0u32; 16];
+    for (word, bytes) in m.iter_mut().zip(block.chunks_exact(4)) {
+        *word = u32::from_le_bytes(bytes.try_into().unwrap());
+    }
+    let [mut a, mut b, mut c, mut d] = *state;
+    for i in 0..64 {
+        let (f, g) = match i / 16 {
+            0 => ((b & c) | (!b & d), i),
+            1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+            2 => (b ^ c ^ d, (3 * i + 5) % 16),
+            _ => (c ^ (b | !d), 7 * i % 16),
+        };
+        let f = f.wrapping_add(a).wrapping_add(MD5_K[i]).wrapping_add(m[g]);
+        (a, d, c) = (d, c, b);
+        b = b.wrapping_add(f.rotate_left(MD5_R[i / 16 * 4 + i % 4]));
+    }
+    for (word, x) in state.iter_mut().zip([a, b, c, d]) {
+        *word = word.wrapping_add(x);
+    }
+}
+
+/// MD5 of a salt followed by the element, the hash of pybloom_live's `BloomFilter` when one
+/// digest has the bits of all its hashes, see [crate::BloomFilter::from_pybloom_bytes]. The
+/// salt is the digest of the seed folded to 32 bits like for [Murmur3Hasher] and written little
+/// endian, so seed 0 gives pybloom_live's first salt. The hashes are the two halves of the
+/// digest, read little endian.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PyBloomMd5Hasher;
+
+impl BloomHasher for PyBloomMd5Hasher {
+    #[inline]
+    fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+        let salt = md5(&[&((seed ^ (seed >> 32)) as u32).to_le_bytes()]);
+        let digest = md5(&[&salt, data]);
+        (u64::from_le_bytes(digest[..8].try_into().unwrap()),
+         u64::from_le_bytes(digest[8..].try_into().unwrap()))
+    }
+
+    fn name(&self) -> &str {
+        "pybloom_md5"
+    }
+}
+
+/// The hash function of a filter, stored in its [crate::FilterBuilder]. Only the name of the
+/// hasher is serialized, and configurations serialized before the hasher was stored get
+/// [HashAlgorithm::Xxh3], the hasher they were built with.
+#[derive(Clone, Default)]
+#[non_exhaustive]
+pub enum HashAlgorithm {
+    /// [Xxh128Hasher], the default. It is the fastest for keys of any length.
+    #[default]
+    Xxh128,
+    /// [Xxh3Hasher], the hasher of filters built before [HashAlgorithm::Xxh128] was the default.
+    /// It needs no cargo feature, the `xxhash` feature is a no-op.
+    Xxh3,
+    /// [Murmur3Hasher], e.g. to match filters built by other murmur3 based implementations.
+    Murmur3,
+    /// [Murmur64AHasher], e.g. to match filters of RedisBloom.
+    Murmur64A,
+    /// [PyBloomMd5Hasher], after the hash of pybloom_live.
+    PyBloomMd5,
+    /// SipHash 1-3 keyed with the secret `key`, for filters queried by untrusted clients: without
+    /// the key, elements which collide in the filter can't be computed in advance.
+    ///
+    /// The key is never serialized or printed, only a fingerprint of it in the
+    /// [HashAlgorithm::name], and a deserialized filter gets a [HashAlgorithm::Unresolved] hasher
+    /// until the key is supplied again with [crate::BloomFilter::set_hash_algorithm].
+    SipHash13 { key: [u8; 16] },
+    /// Any other [BloomHasher], see [crate::FilterBuilder::with_hasher].
+    Custom(Arc<dyn BloomHasher>),
+    /// A custom hasher known only by its name, e.g. in a deserialized configuration. Filters
+    /// with it panic when hashing until the hasher is supplied again with
+    /// [crate::BloomFilter::set_hasher] or [crate::BloomFilter::set_hash_algorithm].
+    Unresolved(String),
+}
+
+impl HashAlgorithm {
+    /// The name of the hasher, see [BloomHasher::name]. For [HashAlgorithm::SipHash13] it is
+    /// `siphash13:` followed by a fingerprint of the key, which is a keyed hash itself and
+    /// reveals nothing about the key.
+    pub fn name(&self) -> Cow<'_, str> {
+        match self {
+            HashAlgorithm::Xxh128 => Cow::Borrowed("xxh128"),
+            HashAlgorithm::Xxh3 => Cow::Borrowed("xxh3"),
+            HashAlgorithm::Murmur3 => Cow::Borrowed("murmur3_x64_128"),
+            HashAlgorithm::Murmur64A => Cow::Borrowed("murmur64a"),
+            HashAlgorithm::PyBloomMd5 => Cow::Borrowed("pybloom_md5"),
+            HashAlgorithm::SipHash13 { key } => {
+                let mut hasher = SipHasher13::new_with_key(key);
+                hasher.write(b"fastbloom key fingerprint");
+                Cow::Owned(format!("siphash13:{:016x}", hasher.finish()))
+            }
+            HashAlgorithm::Custom(hasher) => Cow::Borrowed(hasher.name()),
+            HashAlgorithm::Unresolved(name) => Cow::Borrowed(name),
+        }
+    }
+
+    /// Whether `other` is the same hasher, like `==` without formatting the names of
+    /// [HashAlgorithm::SipHash13] hashers.
+    #[inline]
+    pub(crate) fn same(&self, other: &HashAlgorithm) -> bool {
+        match (self, other) {
+            (HashAlgorithm::Xxh128, HashAlgorithm::Xxh128)
+            | (HashAlgorithm::Xxh3, HashAlgorithm::Xxh3)
+            | (HashAlgorithm::Murmur3, HashAlgorithm::Murmur3)
+            | (HashAlgorithm::Murmur64A, HashAlgorithm::Murmur64A)
+            | (HashAlgorithm::PyBloomMd5, HashAlgorithm::PyBloomMd5) => true,
+            (HashAlgorithm::SipHash13 { key }, HashAlgorithm::SipHash13 { key: other }) =>
+                key == other,
+            (HashAlgorithm::SipHash13 { .. }, _) | (_, HashAlgorithm::SipHash13 { .. }) => false,
+            _ => self.name() == other.name(),
+        }
+    }
+
+    /// The hasher of configurations serialized without one.
+    #[cfg(feature = "serde")]
+    pub(crate) fn legacy() -> Self {
+        HashAlgorithm::Xxh3
+    }
+
+    /// Resolves a serialized name to a built-in hasher.
+    /// The hasher of [crate::FilterBuilder::with_hasher]: the built-in variant for the built-in
+    /// hashers, so they are compatible with [crate::FilterBuilder::with_hash_algorithm], and
+    /// [HashAlgorithm::Custom] for the others.
+    ///
+    /// Panics if another hasher has the name of a built-in one, which would make it compatible
+    /// with filters of that hasher.
+    pub(crate) fn custom<H: BloomHasher + 'static>(hasher: H) -> Self {
+        let built_in = [
+            (TypeId::of::<Xxh128Hasher>(), HashAlgorithm::Xxh128),
+            (TypeId::of::<Xxh3Hasher>(), HashAlgorithm::Xxh3),
+            (TypeId::of::<Murmur3Hasher>(), HashAlgorithm::Murmur3),
+            (TypeId::of::<Murmur64AHasher>(), HashAlgorithm::Murmur64A),
+            (TypeId::of::<PyBloomMd5Hasher>(), HashAlgorithm::PyBloomMd5),
+        ];
+        let id = TypeId::of::<H>();
+        if let Some((_, algorithm)) = built_in.into_iter().find(|(built_in, _)| *built_in == id) {
+            return algorithm;
+        }
+        let name = hasher.name();
+        assert!(matches!(HashAlgorithm::from_name(name.to_string()), HashAlgorithm::Unresolved(_))
+                    && !name.starts_with("siphash13:"),
+                "the hasher name {} is reserved for a built-in hasher!", name);
+        HashAlgorithm::Custom(Arc::new(hasher))
+    }
+
+    pub(crate) fn from_name(name: String) -> Self {
+        match name.as_str() {
+            "xxh128" => HashAlgorithm::Xxh128,
+            "xxh3" => HashAlgorithm::Xxh3,
+            "murmur3_x64_128" => HashAlgorithm::Murmur3,
+            "murmur64a" => HashAlgorithm::Murmur64A,
+            "pybloom_md5" => HashAlgorithm::PyBloomMd5,
+            _ => HashAlgorithm::Unresolved(name),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn hash128(&self, data: &[u8], seed: u64) -> (u64, u64) {
+        match self {
+            HashAlgorithm::Xxh128 => Xxh128Hasher.hash128(data, seed),
+            HashAlgorithm::Xxh3 => Xxh3Hasher.hash128(data, seed),
+            HashAlgorithm::Murmur3 => Murmur3Hasher.hash128(data, seed),
+            HashAlgorithm::Murmur64A => Murmur64AHasher.hash128(data, seed),
+            HashAlgorithm::PyBloomMd5 => PyBloomMd5Hasher.hash128(data, seed),
+            HashAlgorithm::SipHash13 { key } => {
+                let mut hasher = SipHasher13::new_with_key(key);
+                hasher.write(&seed.to_le_bytes());
+                hasher.write(data);
+                let hash = hasher.finish128();
+                (hash.h1, hash.h2)
+            }
+            HashAlgorithm::Custom(hasher) => hasher.hash128(data, seed),
+            HashAlgorithm::Unresolved(name) =>
+                panic!("hasher {} is not available, supply it with set_hasher!", name),
+        }
+    }
+}
+
+/// Hashers are equal if their names are.
+impl PartialEq for HashAlgorithm {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name()
+    }
+}
+
+impl Eq for HashAlgorithm {}
+
+impl Debug for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HashAlgorithm::Xxh128 => write!(f, "Xxh128"),
+            HashAlgorithm::Xxh3 => write!(f, "Xxh3"),
+            HashAlgorithm::Murmur3 => write!(f, "Murmur3"),
+            HashAlgorithm::Murmur64A => write!(f, "Murmur64A"),
+            HashAlgorithm::PyBloomMd5 => write!(f, "PyBloomMd5"),
+            HashAlgorithm::SipHash13 { .. } =>
+                f.debug_tuple("SipHash13").field(&self.name()).finish(),
+            HashAlgorithm::Custom(hasher) => f.debug_tuple("Custom").field(&hasher.name()).finish(),
+            HashAlgorithm::Unresolved(name) => f.debug_tuple("Unresolved").field(name).finish(),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for HashAlgorithm {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for HashAlgorithm {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(HashAlgorithm::from_name)
+    }
+}
+
+/// How the `k` bit indices of an element are derived from its hashes `h1`, `h2` (and `h3`), see
+/// [crate::FilterBuilder::with_probe_scheme]. Index `i` in `0..k` is, modulo the size:
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum ProbeScheme {
+    /// `h1 + i * h2`, the default. The indices of two elements with the same `h1` and `h2`
+    /// modulo the size collide completely, which raises the false positive probability above
+    /// the one of independent hashes for large `k` and small filters.
+    #[default]
+    Double,
+    /// `h1 + i * h2 + (i^3 - i) / 6`, enhanced double hashing. The cubic term separates the
+    /// sequences of such elements at no measurable cost.
+    EnhancedDouble,
+    /// `h1 + i * h2 + i * (i - 1) / 2 * h3`, triple hashing with a third hash from a second call
+    /// of the hasher. The closest to independent hashes, at the cost of hashing twice.
+    Triple,
+    /// `(h1 + i * h2) & i64::MAX` modulo the size, in wrapping 64-bit arithmetic: the
+    /// `MURMUR128_MITZ_64` strategy of Guava's `BloomFilter`, see
+    /// [crate::FilterBuilder::guava_compatible]. Unlike the other schemes the hashes are only
+    /// reduced after they are combined.
+    Guava,
+    /// `h1 + i * h2` modulo the size, in wrapping 64-bit arithmetic: the scheme of RedisBloom,
+    /// see [crate::BloomFilter::from_redisbloom_chunks]. Like [ProbeScheme::Guava] the hashes
+    /// are only reduced after they are combined.
+    RedisBloom,
+    /// `i * s + c_i mod s` with the slice size `s = size / k`, where `c_i` is the `i`-th chunk
+    /// of the 128 bits `h1 | h2 << 64`: 16 bits for slices below 2^15 bits, 32 bits below 2^31
+    /// and 64 bits otherwise, wrapping around after 128 bits. This is the scheme of
+    /// pybloom_live, which gives every hash a slice of its own, see
+    /// [crate::BloomFilter::from_pybloom_bytes].
+    PyBloom,
+}
+
+impl ProbeScheme {
+    /// The name of the scheme in messages and serialized configurations.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProbeScheme::Double => "double",
+            ProbeScheme::EnhancedDouble => "enhanced_double",
+            ProbeScheme::Triple => "triple",
+            ProbeScheme::Guava => "guava",
+            ProbeScheme::RedisBloom => "redisbloom",
+            ProbeScheme::PyBloom => "pybloom",
+        }
+    }
+}
+
+/// A [Hasher] which records everything written to it in a fixed, platform independent byte
+/// layout, so that a `T: Hash` can be turned into the bytes a filter hashes.
+///
+/// [std::collections::hash_map::DefaultHasher] can not be used for this: its algorithm is
+/// unspecified and may change between Rust releases, and the default `write_*` methods of
+/// [Hasher] use native endian and native `usize` width. Here every integer is written little
+/// endian and `usize`/`isize` are always widened to 64 bits, so the same value produces the same
+/// bytes on every run and every architecture.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct StableHasher {
+    buf: Vec<u8>,
+}
+
+impl StableHasher {
+    pub(crate) fn new() -> Self {
+        StableHasher { buf: Vec::new() }
+    }
+
+    /// The canonical bytes written so far.
+    #[inline]
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        xxh3_64_with_seed(&self.buf, 0)
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    #[inline]
+    fn write_u8(&mut self, i: u8) {
+        self.buf.push(i);
+    }
+
+    #[inline]
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    #[inline]
+    fn write_i8(&mut self, i: i8) {
+        self.write_u8(i as u8);
+    }
+
+    #[inline]
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    #[inline]
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+#[test]
+fn hash_algorithm_test() {
+    let data = b"hello";
+    assert_eq!(HashAlgorithm::Xxh3.hash128(data, 7),
+               (xxh3_64_with_seed(data, 7), xxh3_64_with_seed(data, 39)));
+    let murmur = HashAlgorithm::Custom(Arc::new(Murmur3Hasher));
+    let hash = murmur3_x64_128(data, 0);
+    assert_eq!(murmur.hash128(data, 0), (hash as u64, (hash >> 64) as u64));
+    assert_ne!(murmur.hash128(data, 1), murmur.hash128(data, 0));
+
+    let hash = xxh3_128_with_seed(data, 7);
+    assert_eq!(HashAlgorithm::Xxh128.hash128(data, 7), (hash as u64, (hash >> 64) as u64));
+    assert_ne!(HashAlgorithm::Xxh128.hash128(data, 7), HashAlgorithm::Xxh3.hash128(data, 7));
+
+    assert_eq!(HashAlgorithm::default(), HashAlgorithm::Xxh128);
+    assert_ne!(HashAlgorithm::Xxh128, HashAlgorithm::Xxh3);
+    assert_eq!(HashAlgorithm::Unresolved("murmur3_x64_128".to_string()), murmur);
+    assert_eq!(HashAlgorithm::Murmur3, murmur);
+    assert_eq!(HashAlgorithm::Murmur3.hash128(data, 1), murmur.hash128(data, 1));
+    assert_ne!(HashAlgorithm::Xxh3, murmur);
+    assert_eq!(format!("{:?}", murmur), r#"Custom("murmur3_x64_128")"#);
+}
+
+#[test]
+fn murmur64a_test() {
+    // the verification value of MurmurHash64A in SMHasher
+    let mut hashes = Vec::new();
+    let key: Vec<u8> = (0..=255).collect();
+    for i in 0..256 {
+        hashes.extend_from_slice(&murmur64a(&key[..i], 256 - i as u64).to_le_bytes());
+    }
+    assert_eq!(murmur64a(&hashes, 0) as u32, 0x1f0d3804);
+
+    let hash1 = murmur64a(b"hello", Murmur64AHasher::REDISBLOOM_SEED);
+    assert_eq!(HashAlgorithm::Murmur64A.hash128(b"hello", Murmur64AHasher::REDISBLOOM_SEED),
+               (hash1, murmur64a(b"hello", hash1)));
+    assert_eq!(HashAlgorithm::from_name("murmur64a".to_string()),
+               HashAlgorithm::Custom(Arc::new(Murmur64AHasher)));
+}
+
+#[test]
+fn md5_test() {
+    // the test suite of RFC 1321
+    let hex = |digest: [u8; 16]| digest.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    assert_eq!(hex(md5(&[])), "d41d8cd98f00b204e9800998ecf8427e");
+    assert_eq!(hex(md5(&[b"a"])), "0cc175b9c0f1b6a831c399e269772661");
+    assert_eq!(hex(md5(&[b"abc"])), "900150983cd24fb0d6963f7d28e17f72");
+    assert_eq!(hex(md5(&[b"message digest"])), "f96b697d7cb7938d525a2f31aaf161d0");
+    assert_eq!(hex(md5(&[b"abcdefghijklmnopqrstuvwxyz"])), "c3fcd3d76192e4007dfb496cca67e13b");
+    assert_eq!(hex(md5(&[b"ABCDEFGHIJKLMNOPQRSTUVWXYZ", b"abcdefghijklmnopqrstuvwxyz0123456789"])),
+               "d174ab98d277d9f5a5611c2c9f419d9f");
+    let digits = "1234567890".repeat(8);
+    assert_eq!(hex(md5(&[digits.as_bytes()])), "57edf4a22be3c955ac49da2e2107b67a");
+
+    let salt = md5(&[&[0, 0, 0, 0]]);
+    let digest = md5(&[&salt, b"hello"]);
+    assert_eq!(PyBloomMd5Hasher.hash128(b"hello", 0),
+               (u64::from_le_bytes(digest[..8].try_into().unwrap()),
+                u64::from_le_bytes(digest[8..].try_into().unwrap())));
+    assert_eq!(HashAlgorithm::from_name("pybloom_md5".to_string()),
+               HashAlgorithm::Custom(Arc::new(PyBloomMd5Hasher)));
+}
+
+#[test]
+#[should_panic(expected = "hasher fnv1a is not available")]
+fn hash_algorithm_unresolved_test() {
+    HashAlgorithm::Unresolved("fnv1a".to_string()).hash128(b"hello", 0);
+}
+
+#[test]
+fn hash_algorithm_siphash_test() {
+    let a = HashAlgorithm::SipHash13 { key: [1; 16] };
+    let b = HashAlgorithm::SipHash13 { key: [2; 16] };
+    let same = HashAlgorithm::SipHash13 { key: [1; 16] };
+    assert_eq!(a.hash128(b"hello", 0), same.hash128(b"hello", 0));
+    assert_ne!(a.hash128(b"hello", 0), b.hash128(b"hello", 0));
+    assert_ne!(a.hash128(b"hello", 0), a.hash128(b"hello", 1));
+    assert_ne!(a, b);
+    assert_eq!(a.name().starts_with("siphash13:"), true);
+    assert_eq!(a.name().len(), "siphash13:".len() + 16);
+    // the key is not printed
+    assert_eq!(format!("{:?}", a), format!("SipHash13({:?})", a.name()));
+    assert_eq!(HashAlgorithm::Unresolved(a.name().into_owned()), a);
+}
+
+#[test]
+fn stable_hasher_test() {
+    use std::hash::Hash;
+
+    let mut hasher = StableHasher::new();
+    42usize.hash(&mut hasher);
+    assert_eq!(hasher.as_bytes(), &42u64.to_le_bytes());
+
+    let mut hasher = StableHasher::new();
+    (1u16, -1i32).hash(&mut hasher);
+    assert_eq!(hasher.as_bytes(), &[1, 0, 0xff, 0xff, 0xff, 0xff]);
+}
@@ -0,0 +1,44 @@
+//! Hexadecimal text, for [crate::BloomFilter::to_hex].
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Encodes `bytes` as lower case hex digits, two per byte.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut res = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        res.push(DIGITS[(b >> 4) as usize] as char);
+        res.push(DIGITS[(b & 15) as usize] as char);
+    }
+    res
+}
+
+/// Decodes upper or lower case hex digits. Fails with a description of the first invalid
+/// character, or if the number of digits is odd.
+pub(crate) fn decode(text: &str) -> Result<Vec<u8>, String> {
+    let data = text.as_bytes();
+    if !data.len().is_multiple_of(2) {
+        return Err(format!("odd number of digits {}", data.len()));
+    }
+    let digit = |i: usize| match data[i] {
+        c @ b'0'..=b'9' => Ok(c - b'0'),
+        c @ b'a'..=b'f' => Ok(c - b'a' + 10),
+        c @ b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(format!("invalid character {:?} at {}",
+                         text[i..].chars().next().unwrap_or('?'), i)),
+    };
+    (0..data.len()).step_by(2).map(|i| Ok(digit(i)? << 4 | digit(i + 1)?)).collect()
+}
+
+#[test]
+fn hex_test() {
+    assert_eq!(encode(b""), "");
+    assert_eq!(encode(&[0x00, 0x7f, 0xab, 0xff]), "007fabff");
+    assert_eq!(decode("007fabff").unwrap(), vec![0x00, 0x7f, 0xab, 0xff]);
+    assert_eq!(decode("007FABFF").unwrap(), vec![0x00, 0x7f, 0xab, 0xff]);
+    let bytes: Vec<u8> = (0..=255).collect();
+    assert_eq!(decode(&encode(&bytes)).unwrap(), bytes);
+
+    assert_eq!(decode("abc"), Err("odd number of digits 3".to_string()));
+    assert_eq!(decode("0g"), Err("invalid character 'g' at 1".to_string()));
+    assert_eq!(decode("aéa"), Err("invalid character 'é' at 1".to_string()));
+}
@@ -0,0 +1,563 @@
+//! The JSON representation of [BloomFilter::to_json], written and read without the serde
+//! feature.
+
+use crate::error::FastBloomError;
+use crate::hasher::{HashAlgorithm, ProbeScheme};
+use crate::vec::BloomBitVec;
+use crate::{base64, BloomFilter, FilterBuilder, Hashes};
+
+/// How deep arrays and objects of unknown fields may be nested.
+const MAX_DEPTH: usize = 128;
+
+/// A value of a field, only numbers and strings are kept.
+#[derive(Debug, PartialEq)]
+enum Value {
+    /// The digits of a number as written.
+    Number(String),
+    String(String),
+    /// Any other value, with its kind for messages.
+    Other(&'static str),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "a number",
+            Value::String(_) => "a string",
+            Value::Other(kind) => kind,
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, what: &str) -> String {
+        match self.bytes.get(self.pos) {
+            Some(_) => format!("{} at {}", what, self.pos),
+            None => format!("{} at the end", what),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.bytes.get(self.pos), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skips whitespace and consumes `c` if it is next.
+    fn eat(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+        if self.bytes.get(self.pos) == Some(&c) {
+            self.pos += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        if self.eat(c) { Ok(()) } else { Err(self.error(&format!("expected '{}'", c as char))) }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.bytes.get(self.pos) {
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => Ok(Value::Number(self.number()?)),
+            Some(b'{' | b'[') if depth >= MAX_DEPTH => Err(self.error("nested too deeply")),
+            Some(b'{') => {
+                self.members(depth + 1, |_, _| Ok(()))?;
+                Ok(Value::Other("an object"))
+            }
+            Some(b'[') => {
+                self.pos += 1;
+                if !self.eat(b']') {
+                    loop {
+                        self.value(depth + 1)?;
+                        if self.eat(b']') {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Value::Other("an array"))
+            }
+            _ => {
+                for (literal, kind) in [("true", "a boolean"), ("false", "a boolean"),
+                                        ("null", "null")] {
+                    if self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+                        self.pos += literal.len();
+                        return Ok(Value::Other(kind));
+                    }
+                }
+                Err(self.error("expected a value"))
+            }
+        }
+    }
+
+    /// Reads an object, calling `member` with the name and value of each member.
+    fn members<F>(&mut self, depth: usize, mut member: F) -> Result<(), String>
+        where F: FnMut(String, Value) -> Result<(), String> {
+        self.expect(b'{')?;
+        if self.eat(b'}') {
+            return Ok(());
+        }
+        loop {
+            self.skip_whitespace();
+            if self.bytes.get(self.pos) != Some(&b'"') {
+                return Err(self.error("expected a field name"));
+            }
+            let name = self.string()?;
+            self.expect(b':')?;
+            let value = self.value(depth)?;
+            member(name, value)?;
+            if self.eat(b'}') {
+                return Ok(());
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut res = Vec::new();
+        loop {
+            let start = self.pos;
+            while !matches!(self.bytes.get(self.pos), None | Some(b'"' | b'\\' | 0..=0x1f)) {
+                self.pos += 1;
+            }
+            res.extend_from_slice(&self.bytes[start..self.pos]);
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(String::from_utf8(res).expect("copied from a str"));
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let c = match self.bytes.get(self.pos) {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => self.unicode_escape()?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.pos += 1;
+                    res.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(_) => return Err(self.error("control character in a string")),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    /// Reads the digits of a `\u` escape, and of the low surrogate following a high one. The
+    /// position is left on the last digit.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        let code = if (0xd800..0xdc00).contains(&high) {
+            if !self.bytes[self.pos + 1..].starts_with(b"\\u") {
+                return Err(self.error("unpaired surrogate"));
+            }
+            self.pos += 2;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("unpaired surrogate"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.bytes.get(self.pos + 1..self.pos + 5)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .filter(|digits| digits.bytes().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("invalid escape"))?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    fn number(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        let digits = |parser: &mut Self| {
+            let start = parser.pos;
+            while matches!(parser.bytes.get(parser.pos), Some(b'0'..=b'9')) {
+                parser.pos += 1;
+            }
+            parser.pos > start
+        };
+        if self.bytes[self.pos] == b'-' {
+            self.pos += 1;
+        }
+        if self.bytes.get(self.pos) == Some(&b'0') {
+            self.pos += 1;
+        } else if !digits(self) {
+            return Err(self.error("invalid number"));
+        }
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            self.pos += 1;
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        if matches!(self.bytes.get(self.pos), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.bytes.get(self.pos), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !digits(self) {
+                return Err(self.error("invalid number"));
+            }
+        }
+        Ok(String::from_utf8(self.bytes[start..self.pos].to_vec()).unwrap())
+    }
+}
+
+/// Parses a JSON object, returning its members in order.
+fn parse_object(text: &str) -> Result<Vec<(String, Value)>, String> {
+    let mut parser = Parser { bytes: text.as_bytes(), pos: 0 };
+    let mut members = Vec::new();
+    parser.skip_whitespace();
+    if parser.bytes.get(parser.pos) != Some(&b'{') {
+        return Err(parser.error("expected an object"));
+    }
+    parser.members(1, |name, value| {
+        members.push((name, value));
+        Ok(())
+    })?;
+    parser.skip_whitespace();
+    if parser.pos < parser.bytes.len() {
+        return Err(parser.error("trailing characters"));
+    }
+    Ok(members)
+}
+
+/// Writes `s` as a JSON string.
+fn quote(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization { reason }
+}
+
+/// The members of the object of [BloomFilter::from_json].
+struct Fields(Vec<(String, Value)>);
+
+impl Fields {
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.0.iter().find(|(field, _)| field == name).map(|(_, value)| value)
+    }
+
+    fn required(&self, name: &str) -> Result<&Value, FastBloomError> {
+        self.get(name).ok_or_else(|| invalid(format!("missing field `{}`", name)))
+    }
+
+    fn integer<T: std::str::FromStr>(name: &str, value: &Value) -> Result<T, FastBloomError> {
+        let kind = match value {
+            Value::Number(digits) => match digits.parse() {
+                Ok(value) => return Ok(value),
+                Err(_) => format!("the number {}", digits),
+            },
+            value => value.kind().to_string(),
+        };
+        Err(invalid(format!("field `{}` must be an integer in the range of {}, found {}", name,
+                            std::any::type_name::<T>(), kind)))
+    }
+
+    fn string<'a>(name: &str, value: &'a Value) -> Result<&'a str, FastBloomError> {
+        match value {
+            Value::String(s) => Ok(s),
+            value => Err(invalid(format!("field `{}` must be a string, found {}", name,
+                                         value.kind()))),
+        }
+    }
+}
+
+impl BloomFilter {
+    /// The version of the JSON representation written by [BloomFilter::to_json].
+    pub const JSON_VERSION: u8 = 1;
+
+    /// Writes the filter as a JSON object, e.g. for a config file or a snapshot which can be
+    /// read and diffed by people. Read it back with [BloomFilter::from_json]. It doesn't need
+    /// the `serde` feature, and its schema is stable:
+    ///
+    /// | field | value |
+    /// |-------|-------|
+    /// | `version` | [BloomFilter::JSON_VERSION] |
+    /// | `size` | size in bits |
+    /// | `hashes` | number of hashes |
+    /// | `seed` | the 64-bit seed, see [FilterBuilder::with_seed] |
+    /// | `hasher` | [HashAlgorithm::name] |
+    /// | `hash_version` | see [FilterBuilder::with_hash_version] |
+    /// | `probe_scheme` | [ProbeScheme::name] |
+    /// | `len` | [BloomFilter::len] |
+    /// | `bits_base64` | the bits, see below |
+    ///
+    /// The bits are the little endian bytes of the `u64` words of [BloomFilter::to_u64_vec],
+    /// `8 * ceil(size / 64)` bytes, in standard base64 with padding. Every field is on a line of
+    /// its own. The integers are written exactly, readers in
+    /// languages which parse JSON numbers as doubles may need to read the seed as a big integer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::from_size_and_hashes(64, 2).with_seed(7)
+    ///     .build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let json = bloom.to_json();
+    /// assert!(json.starts_with("{\n  \"version\": 1,\n  \"size\": 64,\n  \"hashes\": 2,\n"));
+    ///
+    /// let copy = BloomFilter::from_json(&json).unwrap();
+    /// assert_eq!(copy, bloom);
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn to_json(&self) -> String {
+        let config = self.config();
+        let words = config.size.div_ceil(64) as usize;
+        let bits: Vec<u8> = (0..words)
+            .flat_map(|i| self.bit_vec().u64_word(i).to_le_bytes())
+            .collect();
+        format!("{{\n  \"version\": {},\n  \"size\": {},\n  \"hashes\": {},\n  \"seed\": {},\n  \
+                 \"hasher\": {},\n  \"hash_version\": {},\n  \"probe_scheme\": \"{}\",\n  \
+                 \"len\": {},\n  \"bits_base64\": \"{}\"\n}}\n",
+                BloomFilter::JSON_VERSION, config.size, self.hashes(), config.seed,
+                quote(&config.hasher.name()), config.hash_version, config.probe_scheme.name(),
+                self.len(), base64::encode(&bits))
+    }
+
+    /// Reads a filter written by [BloomFilter::to_json].
+    ///
+    /// `version`, `size`, `hashes`, `seed` and `bits_base64` are required, the other fields
+    /// default to those of [FilterBuilder::from_size_and_hashes]: the xxh128 hasher, the current
+    /// hash version, the double probe scheme, and a [BloomFilter::len] of 0. A hasher which is
+    /// not built in is read as [HashAlgorithm::Unresolved] like with serde. Fields which are not
+    /// in the schema are ignored, so they can hold e.g. comments.
+    ///
+    /// Returns [FastBloomError::Deserialization] naming the field if a field is missing or
+    /// invalid, [FastBloomError::UnsupportedVersion] for a newer `version`, and
+    /// [FastBloomError::Deserialization] with the position if the text is not a JSON object.
+    pub fn from_json(text: &str) -> Result<BloomFilter, FastBloomError> {
+        let fields = parse_object(text).map_err(|reason| invalid(format!("invalid JSON: {}",
+                                                                         reason)))?;
+        for (i, (name, _)) in fields.iter().enumerate() {
+            if fields[..i].iter().any(|(other, _)| other == name) {
+                return Err(invalid(format!("duplicate field `{}`", name)));
+            }
+        }
+        let fields = Fields(fields);
+        let version: u8 = Fields::integer("version", fields.required("version")?)?;
+        if version == 0 || version > BloomFilter::JSON_VERSION {
+            return Err(FastBloomError::UnsupportedVersion {
+                version,
+                supported: BloomFilter::JSON_VERSION,
+            });
+        }
+        let size: u64 = Fields::integer("size", fields.required("size")?)?;
+        let hashes: u32 = Fields::integer("hashes", fields.required("hashes")?)?;
+        let seed: u64 = Fields::integer("seed", fields.required("seed")?)?;
+        let bits = Fields::string("bits_base64", fields.required("bits_base64")?)?;
+        if size == 0 || hashes == 0 {
+            return Err(invalid(format!("invalid size {} with {} hashes", size, hashes)));
+        }
+
+        let mut config = FilterBuilder::from_size_and_hashes(size, hashes).with_seed(seed);
+        if let Some(value) = fields.get("hasher") {
+            config.hasher = HashAlgorithm::from_name(Fields::string("hasher", value)?.to_string());
+        }
+        if let Some(value) = fields.get("hash_version") {
+            let version: u8 = Fields::integer("hash_version", value)?;
+            if !(1..=FilterBuilder::HASH_VERSION).contains(&version) {
+                return Err(invalid(format!("unknown hash version {}", version)));
+            }
+            config = config.with_hash_version(version);
+        }
+        if let Some(value) = fields.get("probe_scheme") {
+            let name = Fields::string("probe_scheme", value)?;
+            let scheme = [ProbeScheme::Double, ProbeScheme::EnhancedDouble, ProbeScheme::Triple,
+                          ProbeScheme::Guava, ProbeScheme::RedisBloom, ProbeScheme::PyBloom]
+                .into_iter()
+                .find(|scheme| scheme.name() == name)
+                .ok_or_else(|| invalid(format!("unknown probe scheme `{}`", name)))?;
+            config = config.with_probe_scheme(scheme);
+        }
+        let len: u64 = match fields.get("len") {
+            Some(value) => Fields::integer("len", value)?,
+            None => 0,
+        };
+
+        let bits = base64::decode(bits)
+            .map_err(|reason| invalid(format!("field `bits_base64` is invalid base64: {}",
+                                              reason)))?;
+        let expected = size.div_ceil(64) * 8;
+        if bits.len() as u64 != expected {
+            return Err(invalid(format!("field `bits_base64` has {} bytes for a size of {} bits, \
+                                        expected {}", bits.len(), size, expected)));
+        }
+        let words = bits.chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let mut bloom = BloomFilter::from_parts(config, BloomBitVec::from_u64_vec(words, size));
+        bloom.set_len(len);
+        Ok(bloom)
+    }
+}
+
+#[test]
+fn json_parse_test() {
+    let members = parse_object(r#" {"a": 1, "b" : "x\"\\\/\u00e9\ud83d\ude00\n", "c": [1, [], {}],
+        "d": {"e": [true, false, null]}, "f": -0.5e+3, "g": {}} "#).unwrap();
+    assert_eq!(members, vec![
+        ("a".to_string(), Value::Number("1".to_string())),
+        ("b".to_string(), Value::String("x\"\\/é😀\n".to_string())),
+        ("c".to_string(), Value::Other("an array")),
+        ("d".to_string(), Value::Other("an object")),
+        ("f".to_string(), Value::Number("-0.5e+3".to_string())),
+        ("g".to_string(), Value::Other("an object")),
+    ]);
+    assert_eq!(parse_object("{}"), Ok(vec![]));
+
+    for (text, error) in [
+        ("", "expected an object at the end"),
+        ("[1]", "expected an object at 0"),
+        ("{\"a\" 1}", "expected ':' at 5"),
+        ("{\"a\": 1,}", "expected a field name at 8"),
+        ("{\"a\": 1} x", "trailing characters at 9"),
+        ("{\"a\": 01}", "expected ',' at 7"),
+        ("{\"a\": 1.}", "invalid number at 8"),
+        ("{\"a\": nul}", "expected a value at 6"),
+        ("{\"a\": \"b}", "unterminated string at the end"),
+        ("{\"a\": \"\\x\"}", "invalid escape at 8"),
+        ("{\"a\": \"\\ud83d\"}", "unpaired surrogate at 12"),
+        ("{\"a\": \"\t\"}", "control character in a string at 7"),
+    ] {
+        assert_eq!(parse_object(text), Err(error.to_string()), "{}", text);
+    }
+    let deep = format!("{{\"a\": {}{}}}", "[".repeat(200), "]".repeat(200));
+    assert_eq!(parse_object(&deep), Err("nested too deeply at 133".to_string()));
+
+    assert_eq!(quote("a\"b\\c\n\u{1}é"), r#""a\"b\\c\n\u0001é""#);
+}
+
+#[test]
+fn json_test() {
+    let bloom = crate::binary::golden_filter();
+    let json = bloom.to_json();
+    let golden = include_str!("../testvectors/bloom_v1.json");
+    assert_eq!(json, golden);
+    let copy = BloomFilter::from_json(golden).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), 3);
+    for key in ["apple", "banana", "cherry"] {
+        assert_eq!(copy.contains(key.as_bytes()), true);
+    }
+
+    // other configurations and a size which is not a multiple of 64
+    let mut murmur = FilterBuilder::from_size_and_hashes(1001, 3).with_seed(u64::MAX)
+        .with_hasher(crate::Murmur3Hasher).with_probe_scheme(ProbeScheme::Triple)
+        .build_bloom_filter();
+    murmur.add(b"hello");
+    let copy = BloomFilter::from_json(&murmur.to_json()).unwrap();
+    assert_eq!(copy, murmur);
+    assert_eq!(copy.config().seed, u64::MAX);
+    assert_eq!(copy.contains(b"hello"), true);
+    let legacy = FilterBuilder::from_size_and_hashes(64, 1).with_hash_version(1)
+        .with_hasher(crate::Xxh3Hasher).build_bloom_filter();
+    assert_eq!(BloomFilter::from_json(&legacy.to_json()).unwrap(), legacy);
+
+    // unknown fields are ignored, anywhere and whatever they hold
+    let extra = golden
+        .replacen("{", r#"{"comment": "urls seen", "stats": {"fpp": [0.01, null]},"#, 1)
+        .replacen("\n}", ",\n  \"zzz\": true\n}", 1);
+    assert_eq!(BloomFilter::from_json(&extra).unwrap(), bloom);
+    // the optional fields can be left out
+    let minimal = format!(r#"{{"bits_base64": "{}", "seed": 9, "hashes": 2, "size": 64,
+                           "version": 1}}"#, crate::base64::encode(&[0x81, 0, 0, 0, 0, 0, 0, 0x40]));
+    let minimal = BloomFilter::from_json(&minimal).unwrap();
+    assert_eq!(minimal.to_u64_vec(), vec![0x4000_0000_0000_0081]);
+    assert_eq!(minimal.len(), 0);
+    assert_eq!(minimal.config().seed, 9);
+    assert_eq!(minimal.config().hasher, HashAlgorithm::Xxh128);
+    assert_eq!(minimal.config().hash_version, FilterBuilder::HASH_VERSION);
+    assert_eq!(minimal.config().probe_scheme, ProbeScheme::Double);
+    let named = golden.replace("\"xxh128\"", "\"fnv1a\"");
+    assert_eq!(BloomFilter::from_json(&named).unwrap().config().hasher,
+               HashAlgorithm::Unresolved("fnv1a".to_string()));
+}
+
+#[test]
+fn json_invalid_test() {
+    let golden = include_str!("../testvectors/bloom_v1.json");
+    let reason = |json: &str| match BloomFilter::from_json(json) {
+        Err(FastBloomError::Deserialization { reason }) => reason,
+        res => panic!("{:?}", res.map(|_| ())),
+    };
+    let without = |field: &str| {
+        let line = golden.lines().find(|line| line.contains(&format!("\"{}\"", field))).unwrap();
+        golden.replace(&format!("{}\n", line), "").replace(",\n}", "\n}")
+    };
+
+    for field in ["version", "size", "hashes", "seed", "bits_base64"] {
+        assert_eq!(reason(&without(field)), format!("missing field `{}`", field));
+    }
+    for field in ["hasher", "hash_version", "probe_scheme", "len"] {
+        assert_eq!(BloomFilter::from_json(&without(field)).is_ok(), true, "{}", field);
+    }
+    let replaced = |from: &str, to: &str| {
+        assert_eq!(golden.matches(from).count(), 1, "{}", from);
+        golden.replace(from, to)
+    };
+    assert_eq!(reason(&replaced("\"seed\": 42", "\"seed\": \"42\"")),
+               "field `seed` must be an integer in the range of u64, found a string");
+    assert_eq!(reason(&replaced("\"hashes\": 5", "\"hashes\": 5000000000")),
+               "field `hashes` must be an integer in the range of u32, found the number 5000000000");
+    assert_eq!(reason(&replaced("\"size\": 1000", "\"size\": -1")),
+               "field `size` must be an integer in the range of u64, found the number -1");
+    assert_eq!(reason(&replaced("\"len\": 3", "\"len\": null")),
+               "field `len` must be an integer in the range of u64, found null");
+    assert_eq!(reason(&replaced("\"hasher\": \"xxh128\"", "\"hasher\": 1")),
+               "field `hasher` must be a string, found a number");
+    assert_eq!(reason(&replaced("\"size\": 1000", "\"size\": 0")),
+               "invalid size 0 with 5 hashes");
+    assert_eq!(reason(&replaced("\"hash_version\": 2", "\"hash_version\": 9")),
+               "unknown hash version 9");
+    assert_eq!(reason(&replaced("\"enhanced_double\"", "\"quadruple\"")),
+               "unknown probe scheme `quadruple`");
+    assert_eq!(reason(&replaced("\"size\": 1000", "\"size\": 1100")),
+               "field `bits_base64` has 128 bytes for a size of 1100 bits, expected 144");
+    assert_eq!(reason(&replaced("\"bits_base64\": \"AAAA", "\"bits_base64\": \"A!AA")),
+               "field `bits_base64` is invalid base64: invalid character '!' at 1");
+    assert_eq!(reason(&replaced("\"len\"", "\"size\"")), "duplicate field `size`");
+    assert_eq!(BloomFilter::from_json(&replaced("\"version\": 1", "\"version\": 2")).err(),
+               Some(FastBloomError::UnsupportedVersion {
+                   version: 2,
+                   supported: BloomFilter::JSON_VERSION,
+               }));
+    assert_eq!(reason(&golden[..golden.len() - 3]), "invalid JSON: expected ',' at the end");
+    assert_eq!(reason(&golden[..golden.len() - 4]), "invalid JSON: unterminated string at the end");
+    assert_eq!(reason("[]"), "invalid JSON: expected an object at 0");
+}
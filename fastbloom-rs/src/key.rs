@@ -0,0 +1,119 @@
+use crate::BloomFilter;
+
+/// Builds a composite key for [BloomFilter::key] piece by piece, without concatenating the
+/// pieces into a new buffer for every insert.
+///
+/// The key is the concatenation of its pieces with this framing:
+///
+/// * [KeyWriter::write] and [KeyWriter::write_str] write the length of the piece as 8 little
+///   endian bytes followed by the bytes of the piece (UTF-8 for strings).
+/// * [KeyWriter::write_u64], [KeyWriter::write_i64] and [KeyWriter::write_u32] write the integer
+///   as little endian bytes at the width of the type, without a length.
+///
+/// Because variable length pieces carry their length, keys written with the same sequence of
+/// calls are equal only if all their pieces are equal: `("ab", "c")` and `("a", "bc")` give
+/// different keys. Keys written with different sequences of calls (e.g. a `write_u64` where
+/// the other key has a `write`) are not guaranteed to differ, so use one sequence per kind of
+/// key.
+pub struct KeyWriter<'a> {
+    filter: &'a mut BloomFilter,
+    buf: Vec<u8>,
+}
+
+impl<'a> KeyWriter<'a> {
+    pub(crate) fn new(filter: &'a mut BloomFilter) -> Self {
+        let mut buf = filter.take_key_buf();
+        buf.clear();
+        KeyWriter { filter, buf }
+    }
+
+    /// Writes a variable length piece, prefixed with its length.
+    pub fn write(&mut self, piece: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(&(piece.len() as u64).to_le_bytes());
+        self.buf.extend_from_slice(piece);
+        self
+    }
+
+    /// Writes a string piece as its UTF-8 bytes, prefixed with its length.
+    pub fn write_str(&mut self, piece: &str) -> &mut Self {
+        self.write(piece.as_bytes())
+    }
+
+    /// Writes a `u64` as 8 little endian bytes.
+    pub fn write_u64(&mut self, piece: u64) -> &mut Self {
+        self.buf.extend_from_slice(&piece.to_le_bytes());
+        self
+    }
+
+    /// Writes an `i64` as 8 little endian bytes.
+    pub fn write_i64(&mut self, piece: i64) -> &mut Self {
+        self.buf.extend_from_slice(&piece.to_le_bytes());
+        self
+    }
+
+    /// Writes a `u32` as 4 little endian bytes.
+    pub fn write_u32(&mut self, piece: u32) -> &mut Self {
+        self.buf.extend_from_slice(&piece.to_le_bytes());
+        self
+    }
+
+    /// The key written so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Adds the key to the filter.
+    pub fn add(self) {
+        self.filter.add(&self.buf);
+    }
+
+    /// Tests whether the key is present in the filter (subject to the specified false positive
+    /// rate).
+    pub fn contains(self) -> bool {
+        self.filter.contains(&self.buf)
+    }
+}
+
+impl Drop for KeyWriter<'_> {
+    /// Hands the buffer back to the filter for the next key.
+    fn drop(&mut self) {
+        self.filter.put_key_buf(std::mem::take(&mut self.buf));
+    }
+}
+
+#[test]
+fn key_writer_test() {
+    use crate::FilterBuilder;
+
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    let mut key = bloom.key();
+    key.write(b"ab").write(b"c");
+    let ab_c = key.as_bytes().to_vec();
+    key.add();
+
+    let mut key = bloom.key();
+    key.write(b"a").write(b"bc");
+    assert_ne!(key.as_bytes(), &ab_c[..]);
+    assert_eq!(key.contains(), false);
+
+    let mut manual = Vec::new();
+    for piece in [&b"ab"[..], b"c"] {
+        manual.extend_from_slice(&(piece.len() as u64).to_le_bytes());
+        manual.extend_from_slice(piece);
+    }
+    assert_eq!(ab_c, manual);
+    assert_eq!(bloom.contains(&manual), true);
+
+    let mut key = bloom.key();
+    key.write_str("tenant").write_u64(7).write_i64(-1).write_u32(3);
+    let mut expected = Vec::new();
+    expected.extend_from_slice(&6u64.to_le_bytes());
+    expected.extend_from_slice(b"tenant");
+    expected.extend_from_slice(&7u64.to_le_bytes());
+    expected.extend_from_slice(&(-1i64).to_le_bytes());
+    expected.extend_from_slice(&3u32.to_le_bytes());
+    assert_eq!(key.as_bytes(), &expected[..]);
+    key.add();
+    assert_eq!(bloom.contains(&expected), true);
+    assert_eq!(bloom.len(), 2);
+}
@@ -1,15 +1,84 @@
+#![cfg_attr(test, allow(clippy::bool_assert_comparison))]
+
 extern crate core;
 
-pub use bloom::{BloomFilter, CountingBloomFilter};
-pub use builder::FilterBuilder;
+pub use bloom::{BloomFilter, BloomSnapshot, CountingBloomFilter, PrecomputedHashes};
+pub use builder::{CompatibilityReport, CounterSize, FilterBuilder, FilterPlan};
+pub use chunk::FilterChunk;
+pub use delta::BloomDelta;
+pub use error::{BuildError, CapacityExceeded, FastBloomError, FoldError, IncompatibleFilterError,
+                InvalidSparseBloom};
+pub use frozen::FrozenBloomFilter;
+pub use gcs::GolombCodedSet;
+pub use hasher::{BloomHasher, HashAlgorithm, Murmur3Hasher, Murmur64AHasher, ProbeScheme,
+                 PyBloomMd5Hasher, Xxh128Hasher, Xxh3Hasher};
+pub use key::KeyWriter;
+#[cfg(feature = "prost")]
+pub use proto::BloomFilterProto;
+pub use sbbf::SbbfFilter;
+pub use set::{BloomKey, BloomSet};
+pub use sparse::SparseBloom;
+pub use view::BloomFilterRef;
+
+pub mod dedup;
+pub mod math;
+pub mod testvectors;
 
+mod base64;
+mod binary;
+mod bits;
 mod builder;
 mod bloom;
+mod chunk;
+#[cfg(feature = "compression")]
+mod compress;
+mod delta;
+mod error;
+mod frozen;
+mod gcs;
+mod guava;
+mod hasher;
+mod hex;
+mod json;
+mod key;
+#[cfg(feature = "prost")]
+mod proto;
+mod pybloom;
+mod redisbloom;
+mod sbbf;
+mod set;
+mod sparse;
+mod varint;
 mod vec;
+mod view;
 mod cuckoo;
 mod sketch;
 
 /// filter for check whether membership.
+///
+/// Implemented by every filter of the crate ([BloomFilter] and [CountingBloomFilter]), so code
+/// can be generic over the kind of filter:
+///
+/// ```rust
+/// use fastbloom_rs::{FilterBuilder, Membership};
+///
+/// fn dedup<'a>(filter: &mut impl Membership, items: &[&'a str]) -> Vec<&'a str> {
+///     let mut res = Vec::new();
+///     for item in items {
+///         if !filter.contains(item.as_bytes()) {
+///             filter.add(item.as_bytes());
+///             res.push(*item);
+///         }
+///     }
+///     res
+/// }
+///
+/// let items = ["a", "b", "a"];
+/// let mut bloom = FilterBuilder::new(100, 0.01).build_bloom_filter();
+/// let mut counting = FilterBuilder::new(100, 0.01).build_counting_bloom_filter();
+/// assert_eq!(dedup(&mut bloom, &items), vec!["a", "b"]);
+/// assert_eq!(dedup(&mut counting, &items), vec!["a", "b"]);
+/// ```
 pub trait Membership {
     fn add(&mut self, element: &[u8]);
 
@@ -17,9 +86,18 @@ pub trait Membership {
 
     fn get_hash_indices(&self, element: &[u8]) -> Vec<u64>;
 
+    #[allow(clippy::ptr_arg)]
     fn contains_hash_indices(&self, indices: &Vec<u64>) -> bool;
 
     fn clear(&mut self);
+
+    /// Estimates the current false positive probability from the occupancy of the filter.
+    ///
+    /// The default is NaN, "unknown", for implementations which don't track their occupancy,
+    /// so implementations written before this method existed keep compiling.
+    fn estimated_fpp(&self) -> f64 {
+        f64::NAN
+    }
 }
 
 pub trait Hashes {
@@ -0,0 +1,124 @@
+//! The formulas [crate::FilterBuilder] uses to size a Bloom filter, for capacity planning without
+//! building one.
+//!
+//! ```rust
+//! use fastbloom_rs::math::{fpp, optimal_k, optimal_m};
+//!
+//! let m = optimal_m(1_000_000, 0.01);
+//! let k = optimal_k(1_000_000, m);
+//! assert_eq!((m, k), (9585088, 7));
+//! println!("{} MiB, false positive probability {}", m / 8 / 1024 / 1024, fpp(m, 1_000_000, k));
+//! ```
+//!
+//! The number of hashes is rounded up to an integer, so the false positive probability of a
+//! filter with `optimal_m(n, p)` bits and `optimal_k` hashes can be slightly above `p`: by up to
+//! 3% for `p <= 0.1` and up to 12.5% for `p = 0.5`.
+
+use crate::builder::{MASK, SUFFIX};
+
+/// Calculates the optimal size `m` of the bloom filter in bits given `n` (expected
+/// number of elements in bloom filter) and `p` (tolerable false positive rate, between 0.0 and
+/// 1.0 exclusive), `m = -n * ln(p) / ln(2)^2`. The size is rounded up to a multiple of the word
+/// size (64 bits, 32 bits on 32-bit platforms) like [crate::FilterBuilder::new] does.
+#[inline]
+pub fn optimal_m(n: u64, p: f64) -> u64 {
+    let mut m = optimal_m_exact(n, p);
+    if (m & SUFFIX as u64) != 0 {
+        m = (m & MASK) + SUFFIX as u64 + 1;
+    };
+    m
+}
+
+/// Same as [optimal_m] without rounding the size up to whole words.
+#[inline]
+pub(crate) fn optimal_m_exact(n: u64, p: f64) -> u64 {
+    let fact = -(n as f64) * p.ln();
+    let div = 2f64.ln().powi(2);
+    let m: f64 = fact / div;
+    m.ceil() as u64
+}
+
+/// Calculates the optimal `hashes` (number of hash function) given `n` (expected number of
+/// elements in bloom filter) and `m` (size of bloom filter in bits), `k = ceil(m * ln(2) / n)`.
+#[inline]
+pub fn optimal_k(n: u64, m: u64) -> u32 {
+    let k: f64 = (m as f64 * 2f64.ln()) / n as f64;
+    k.ceil() as u32
+}
+
+/// `ln(2)` as a 0.64 fixed point number.
+const LN_2_FIXED: u128 = 0xB172_17F7_D1CF_79AB;
+
+/// Calculates the amount of elements a Bloom filter for which the given configuration of size `m`
+/// and hashes `k` is optimal, `ceil(ln(2) * m / k)`. Only integer arithmetic is used, so the
+/// result is the same on every platform.
+#[inline]
+pub(crate) fn optimal_n(k: u32, m: u64) -> u64 {
+    let div = (k as u128) << 64;
+    (m as u128 * LN_2_FIXED).div_ceil(div) as u64
+}
+
+/// Calculates `0.5^k` from the bits of the float, the false positive probability of a filter
+/// with `optimal_n(k, m)` elements, at which half of the bits are set.
+#[inline]
+pub(crate) fn half_pow(k: u32) -> f64 {
+    match k {
+        0..=1022 => f64::from_bits((1023 - k as u64) << 52),
+        // subnormal
+        1023..=1074 => f64::from_bits(1 << (1074 - k)),
+        _ => 0.0,
+    }
+}
+
+/// Calculates the best-case (uniform hash function) false positive probability of a Bloom
+/// filter of `m` bits with `k` hashes after inserting `n` elements, `(1 - e^(-k * n / m))^k`.
+#[inline]
+pub fn fpp(m: u64, n: u64, k: u32) -> f64 {
+    let nk = -(k as f64);
+    (1.0 - (nk * n as f64 / m as f64).exp()).powi(k as i32)
+}
+
+#[test]
+fn optimal_test() {
+    let m = optimal_m(100_000_000, 0.01);
+    let k = optimal_k(100_000_000, m);
+    let n = optimal_n(k, m);
+    let p = fpp(m, n, k);
+    assert_eq!(m, 958505856);
+    assert_eq!(k, 7);
+    assert_eq!(n, 94912234);
+    // half of the bits are set at the optimal number of elements
+    assert_eq!((p - 0.5f64.powi(7)).abs() < 1e-8, true);
+
+    assert_eq!(optimal_m_exact(1_000_000, 0.01), 9585059);
+    assert_eq!(optimal_m(1_000_000, 0.01), 9585088);
+    assert_eq!(optimal_k(1_000_000, 9585088), 7);
+    assert_eq!((fpp(9585088, 1_000_000, 7) - 0.01).abs() < 0.0001, true);
+    assert_eq!(fpp(1000, 0, 7), 0.0);
+
+    for (k, m) in [(7u32, 958505856u64), (1, 64), (3, 1 << 40), (30, 999)] {
+        assert_eq!(optimal_n(k, m), (2f64.ln() * m as f64 / k as f64).ceil() as u64);
+    }
+    // exact where the float is not
+    assert_eq!(optimal_n(1, u64::MAX), 12786308645202655659);
+    for k in [0u32, 1, 7, 1022, 1023, 1074, 1075] {
+        assert_eq!(half_pow(k), 0.5f64.powi(k as i32));
+    }
+    assert_eq!(half_pow(u32::MAX), 0.0);
+}
+
+#[test]
+fn optimal_fpp_bound_test() {
+    for n in [1u64, 10, 1000, 100_000, 10_000_000, 1_000_000_000] {
+        for p in [0.5, 0.3, 0.1, 0.05, 0.01, 0.001, 1e-4, 1e-6, 1e-9] {
+            let m = optimal_m(n, p);
+            let k = optimal_k(n, m);
+            let bound = if p <= 0.1 { 1.03 } else { 1.125 };
+            assert_eq!(fpp(m, n, k) <= p * bound, true, "n={} p={}", n, p);
+            // with the real valued optimum of k the probability is met
+            let k = m as f64 * 2f64.ln() / n as f64;
+            let exact = (1.0 - (-k * n as f64 / m as f64).exp()).powf(k);
+            assert_eq!(exact <= p * (1.0 + 1e-9), true, "n={} p={}", n, p);
+        }
+    }
+}
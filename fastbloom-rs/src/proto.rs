@@ -0,0 +1,346 @@
+//! The protobuf message `fastbloom.BloomFilter` of `proto/fastbloom.proto`, encoded and decoded
+//! without a protobuf library.
+
+use crate::error::FastBloomError;
+use crate::hasher::{HashAlgorithm, ProbeScheme};
+use crate::varint::{self, VarintError};
+use crate::vec::BloomBitVec;
+use crate::{BloomFilter, FilterBuilder, Hashes};
+
+/// The wire type of varints.
+const VARINT: u8 = 0;
+/// The wire type of 8 byte values.
+const FIXED64: u8 = 1;
+/// The wire type of strings, bytes and messages.
+const LEN: u8 = 2;
+/// The wire type of 4 byte values.
+const FIXED32: u8 = 5;
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization { reason: format!("invalid protobuf: {}", reason) }
+}
+
+/// The message `fastbloom.BloomFilter` of `proto/fastbloom.proto`, see [BloomFilter::to_proto].
+/// The fields are those of the message, with their proto3 defaults when they are missing.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BloomFilterProto {
+    /// The version of the message, [BloomFilterProto::VERSION].
+    pub version: u32,
+    /// The size of the filter in bits.
+    pub size: u64,
+    /// The number of hashes.
+    pub hashes: u32,
+    /// The seed of the hash function.
+    pub seed: u64,
+    /// The name of the hash function, [HashAlgorithm::name].
+    pub hasher: String,
+    /// The version of the hash scheme, see [FilterBuilder::with_hash_version].
+    pub hash_version: u32,
+    /// The value of the enum `fastbloom.ProbeScheme`: 0 double, 1 enhanced double, 2 triple,
+    /// 3 guava, 4 redisbloom, 5 pybloom.
+    pub probe_scheme: i32,
+    /// The number of insert operations, [BloomFilter::len].
+    pub len: u64,
+    /// The bits as little endian `u64` words, see [BloomFilter::to_u64_vec].
+    pub bits: Vec<u8>,
+}
+
+impl BloomFilterProto {
+    /// The version of the message written by [BloomFilter::to_proto].
+    pub const VERSION: u32 = 1;
+
+    /// Encodes the message in the protobuf wire format. Fields with their default value are left
+    /// out like protobuf libraries do, the others are written in the order of their numbers.
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.bits.len() + self.hasher.len() + 64);
+        let varint_field = |buf: &mut Vec<u8>, number: u64, value: u64| {
+            if value != 0 {
+                varint::write(number << 3 | VARINT as u64, buf);
+                varint::write(value, buf);
+            }
+        };
+        varint_field(&mut buf, 1, self.version as u64);
+        varint_field(&mut buf, 2, self.size);
+        varint_field(&mut buf, 3, self.hashes as u64);
+        varint_field(&mut buf, 4, self.seed);
+        if !self.hasher.is_empty() {
+            varint::write(5 << 3 | LEN as u64, &mut buf);
+            varint::write(self.hasher.len() as u64, &mut buf);
+            buf.extend_from_slice(self.hasher.as_bytes());
+        }
+        varint_field(&mut buf, 6, self.hash_version as u64);
+        // negative enum values are sign extended to 64 bits
+        varint_field(&mut buf, 7, self.probe_scheme as i64 as u64);
+        varint_field(&mut buf, 8, self.len);
+        if !self.bits.is_empty() {
+            varint::write(9 << 3 | LEN as u64, &mut buf);
+            varint::write(self.bits.len() as u64, &mut buf);
+            buf.extend_from_slice(&self.bits);
+        }
+        buf
+    }
+
+    /// Decodes a message in the protobuf wire format. Unknown fields are skipped, and the last
+    /// value of a field which is repeated wins, like protobuf libraries do.
+    ///
+    /// Returns [FastBloomError::Deserialization] if the data is not a valid message, e.g. is
+    /// truncated or has a field of the wrong wire type.
+    pub fn decode(mut buf: &[u8]) -> Result<BloomFilterProto, FastBloomError> {
+        let varint = |buf: &mut &[u8], what: &str| varint::read(buf).map_err(|e| match e {
+            VarintError::Truncated => invalid(format!("the data ends within the {}", what)),
+            VarintError::Overflow => invalid(format!("the {} overflows", what)),
+        });
+        let mut proto = BloomFilterProto::default();
+        while !buf.is_empty() {
+            let key = varint(&mut buf, "key")?;
+            let (number, wire_type) = (key >> 3, (key & 7) as u8);
+            let expected = match number {
+                1..=4 | 6..=8 => Some(VARINT),
+                5 | 9 => Some(LEN),
+                _ => None,
+            };
+            if expected.is_some_and(|expected| expected != wire_type) {
+                return Err(invalid(format!("field {} has wire type {}", number, wire_type)));
+            }
+            match wire_type {
+                VARINT => {
+                    let value = varint(&mut buf, "varint")?;
+                    // like protobuf libraries, 32-bit fields keep the low bits
+                    match number {
+                        1 => proto.version = value as u32,
+                        2 => proto.size = value,
+                        3 => proto.hashes = value as u32,
+                        4 => proto.seed = value,
+                        6 => proto.hash_version = value as u32,
+                        7 => proto.probe_scheme = value as i32,
+                        8 => proto.len = value,
+                        _ => {}
+                    }
+                }
+                LEN => {
+                    let len = varint(&mut buf, "length")?;
+                    if len > buf.len() as u64 {
+                        return Err(invalid(format!("field {} of {} bytes is longer than the \
+                                                    {} bytes left", number, len, buf.len())));
+                    }
+                    let (value, rest) = buf.split_at(len as usize);
+                    buf = rest;
+                    match number {
+                        5 => proto.hasher = String::from_utf8(value.to_vec())
+                            .map_err(|_| invalid("field 5 is not UTF-8".to_string()))?,
+                        9 => proto.bits = value.to_vec(),
+                        _ => {}
+                    }
+                }
+                FIXED64 | FIXED32 => {
+                    let len = if wire_type == FIXED64 { 8 } else { 4 };
+                    if buf.len() < len {
+                        return Err(invalid(format!("the data ends within field {}", number)));
+                    }
+                    buf = &buf[len..];
+                }
+                _ => return Err(invalid(format!("field {} has wire type {}", number,
+                                                wire_type))),
+            }
+        }
+        Ok(proto)
+    }
+}
+
+impl BloomFilter {
+    /// Converts the filter to the protobuf message `fastbloom.BloomFilter`, defined in
+    /// `proto/fastbloom.proto` in the repository, to exchange it with services in other languages.
+    /// Encode it with [BloomFilterProto::encode_to_vec] and read it back with
+    /// [BloomFilter::from_proto].
+    ///
+    /// Every parameter placing the bits is kept: the size, hashes, seed, hasher, hash version
+    /// and probe scheme. So a filter decoded and added to in another language can be merged
+    /// back with [BloomFilter::union]. The comments of the `.proto` file describe how those
+    /// implementations derive the bit indices of an element from its hashes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, BloomFilterProto, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    /// bloom.add(b"hello");
+    /// let bytes = bloom.to_proto().encode_to_vec();
+    ///
+    /// let proto = BloomFilterProto::decode(&bytes).unwrap();
+    /// assert_eq!(proto.hasher, "xxh128");
+    /// let copy = BloomFilter::from_proto(&proto).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn to_proto(&self) -> BloomFilterProto {
+        let config = self.config_ref();
+        let words = config.size.div_ceil(64) as usize;
+        BloomFilterProto {
+            version: BloomFilterProto::VERSION,
+            size: config.size,
+            hashes: self.hashes(),
+            seed: config.seed,
+            hasher: config.hasher.name().into_owned(),
+            hash_version: config.hash_version as u32,
+            probe_scheme: match config.probe_scheme {
+                ProbeScheme::Double => 0,
+                ProbeScheme::EnhancedDouble => 1,
+                ProbeScheme::Triple => 2,
+                ProbeScheme::Guava => 3,
+                ProbeScheme::RedisBloom => 4,
+                ProbeScheme::PyBloom => 5,
+            },
+            len: self.len(),
+            bits: (0..words).flat_map(|i| self.bit_vec().u64_word(i).to_le_bytes()).collect(),
+        }
+    }
+
+    /// Builds a filter from the protobuf message written by [BloomFilter::to_proto] or by a
+    /// service in another language. A hasher which is not built in is read as
+    /// [HashAlgorithm::Unresolved] like with serde.
+    ///
+    /// The fields have no defaults: returns [FastBloomError::Deserialization] naming the field
+    /// if the version, hasher or hash version is missing (0 or empty), if the size or hashes is
+    /// 0 or a field is invalid, and [FastBloomError::UnsupportedVersion] for a newer version.
+    pub fn from_proto(proto: &BloomFilterProto) -> Result<BloomFilter, FastBloomError> {
+        let error = |reason: String| FastBloomError::Deserialization { reason };
+        let missing = |field: &str| error(format!("missing field `{}`", field));
+        match proto.version {
+            0 => return Err(missing("version")),
+            1 => {}
+            version => return Err(FastBloomError::UnsupportedVersion {
+                version: u8::try_from(version).unwrap_or(u8::MAX),
+                supported: BloomFilterProto::VERSION as u8,
+            }),
+        }
+        if proto.size == 0 || proto.hashes == 0 {
+            return Err(error(format!("invalid size {} with {} hashes", proto.size,
+                                       proto.hashes)));
+        }
+        if proto.hasher.is_empty() {
+            return Err(missing("hasher"));
+        }
+        let hash_version = match proto.hash_version {
+            0 => return Err(missing("hash_version")),
+            version if version <= FilterBuilder::HASH_VERSION as u32 => version as u8,
+            version => return Err(error(format!("unknown hash version {}", version))),
+        };
+        let probe_scheme = match proto.probe_scheme {
+            0 => ProbeScheme::Double,
+            1 => ProbeScheme::EnhancedDouble,
+            2 => ProbeScheme::Triple,
+            3 => ProbeScheme::Guava,
+            4 => ProbeScheme::RedisBloom,
+            5 => ProbeScheme::PyBloom,
+            id => return Err(error(format!("unknown probe scheme {}", id))),
+        };
+        let expected = proto.size.div_ceil(64) * 8;
+        if proto.bits.len() as u64 != expected {
+            return Err(error(format!("{} bytes of bits for a size of {} bits, expected {}",
+                                       proto.bits.len(), proto.size, expected)));
+        }
+
+        let mut config = FilterBuilder::from_size_and_hashes(proto.size, proto.hashes)
+            .with_seed(proto.seed)
+            .with_hash_version(hash_version)
+            .with_probe_scheme(probe_scheme);
+        config.hasher = HashAlgorithm::from_name(proto.hasher.clone());
+        let words = proto.bits.chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let mut bloom = BloomFilter::from_parts(config,
+                                                BloomBitVec::from_u64_vec(words, proto.size));
+        bloom.set_len(proto.len);
+        Ok(bloom)
+    }
+}
+
+#[test]
+fn proto_test() {
+    let bloom = crate::binary::golden_filter();
+    let proto = bloom.to_proto();
+    assert_eq!(proto.size, 1000);
+    assert_eq!(proto.probe_scheme, 1);
+    let bytes = proto.encode_to_vec();
+    assert_eq!(bytes, include_bytes!("../testvectors/bloom_v1.pb").to_vec());
+    // the fields of the golden bytes up to the bits
+    assert_eq!(bytes[..16], [0x08, 0x01, 0x10, 0xe8, 0x07, 0x18, 0x05, 0x20, 0x2a, 0x2a, 0x06,
+                             b'x', b'x', b'h', b'1', b'2']);
+    assert_eq!(bytes[16..26], [b'8', 0x30, 0x02, 0x38, 0x01, 0x40, 0x03, 0x4a, 0x80, 0x01]);
+    assert_eq!(bytes.len(), 26 + 128);
+    assert_eq!(BloomFilterProto::decode(&bytes).unwrap(), proto);
+    let copy = BloomFilter::from_proto(&proto).unwrap();
+    assert_eq!(copy, bloom);
+    assert_eq!(copy.len(), 3);
+    for key in ["apple", "banana", "cherry"] {
+        assert_eq!(copy.contains(key.as_bytes()), true);
+    }
+
+    // other configurations, and a filter added to elsewhere merges back
+    let mut murmur = FilterBuilder::from_size_and_hashes(1001, 3).with_seed(u64::MAX)
+        .with_hasher(crate::Murmur3Hasher).with_probe_scheme(ProbeScheme::Triple)
+        .build_bloom_filter();
+    murmur.add(b"hello");
+    let mut copy = BloomFilter::from_proto(&BloomFilterProto::decode(
+        &murmur.to_proto().encode_to_vec()).unwrap()).unwrap();
+    assert_eq!(copy, murmur);
+    copy.add(b"world");
+    assert_eq!(murmur.union(&copy), true);
+    assert_eq!(murmur.contains(b"world"), true);
+    let unresolved = BloomFilterProto { hasher: "fnv1a".to_string(), ..bloom.to_proto() };
+    assert_eq!(BloomFilter::from_proto(&unresolved).unwrap().config().hasher,
+               HashAlgorithm::Unresolved("fnv1a".to_string()));
+
+    // defaults are left out, unknown fields of every wire type are skipped
+    let empty = BloomFilterProto::default();
+    assert_eq!(empty.encode_to_vec(), Vec::<u8>::new());
+    assert_eq!(BloomFilterProto::decode(&[]).unwrap(), empty);
+    let unknown = [&bytes[..], &[0x50, 0x96, 0x01, 0x59], &[0; 8], &[0x62, 0x02, 0xff, 0xff],
+                   &[0x6d, 1, 2, 3, 4]].concat();
+    assert_eq!(BloomFilterProto::decode(&unknown).unwrap(), proto);
+    // the last value wins
+    let repeated = [&bytes[..], &[0x20, 0x07]].concat();
+    assert_eq!(BloomFilterProto::decode(&repeated).unwrap().seed, 7);
+}
+
+#[test]
+fn proto_invalid_test() {
+    let bloom = crate::binary::golden_filter();
+    let bytes = bloom.to_proto().encode_to_vec();
+    let reason = |bytes: &[u8]| match BloomFilterProto::decode(bytes) {
+        Err(FastBloomError::Deserialization { reason }) => reason,
+        res => panic!("{:?}", res),
+    };
+    assert_eq!(reason(&bytes[..bytes.len() - 1]),
+               "invalid protobuf: field 9 of 128 bytes is longer than the 127 bytes left");
+    assert_eq!(reason(&bytes[..4]), "invalid protobuf: the data ends within the varint");
+    assert_eq!(reason(&[0x80]), "invalid protobuf: the data ends within the key");
+    assert_eq!(reason(&[0xff; 11]),
+               "invalid protobuf: the key overflows");
+    assert_eq!(reason(&[0x0a, 0x00]), "invalid protobuf: field 1 has wire type 2");
+    assert_eq!(reason(&[0x13]), "invalid protobuf: field 2 has wire type 3");
+    assert_eq!(reason(&[0x51, 0, 0]), "invalid protobuf: the data ends within field 10");
+    assert_eq!(reason(&[0x2a, 0x01, 0xff]), "invalid protobuf: field 5 is not UTF-8");
+
+    let reason = |proto: BloomFilterProto| match BloomFilter::from_proto(&proto) {
+        Err(FastBloomError::Deserialization { reason }) => reason,
+        res => panic!("{:?}", res.map(|_| ())),
+    };
+    let proto = bloom.to_proto();
+    assert_eq!(reason(BloomFilterProto { version: 0, ..proto.clone() }),
+               "missing field `version`");
+    assert_eq!(reason(BloomFilterProto { hasher: String::new(), ..proto.clone() }),
+               "missing field `hasher`");
+    assert_eq!(reason(BloomFilterProto { hash_version: 0, ..proto.clone() }),
+               "missing field `hash_version`");
+    assert_eq!(reason(BloomFilterProto { hash_version: 4, ..proto.clone() }),
+               "unknown hash version 4");
+    assert_eq!(reason(BloomFilterProto { hashes: 0, ..proto.clone() }),
+               "invalid size 1000 with 0 hashes");
+    assert_eq!(reason(BloomFilterProto { probe_scheme: -1, ..proto.clone() }),
+               "unknown probe scheme -1");
+    assert_eq!(reason(BloomFilterProto { bits: vec![0; 8], ..proto.clone() }),
+               "8 bytes of bits for a size of 1000 bits, expected 128");
+    assert_eq!(BloomFilter::from_proto(&BloomFilterProto { version: 2, ..proto }).err(),
+               Some(FastBloomError::UnsupportedVersion { version: 2, supported: 1 }));
+}
@@ -0,0 +1,301 @@
+//! The files of pybloom_live's `BloomFilter`, see [BloomFilter::from_pybloom_bytes].
+
+use crate::error::FastBloomError;
+use crate::hasher::{HashAlgorithm, ProbeScheme};
+use crate::vec::BloomBitVec;
+use crate::{BloomFilter, BloomHasher, FilterBuilder, Hashes, PyBloomMd5Hasher};
+
+/// The `struct` format `<dQQQQ`: error rate, number of slices, bits per slice, capacity and
+/// count.
+const HEADER_LEN: usize = 40;
+/// The bytes of an MD5 digest.
+const MD5_BYTES: u64 = 16;
+/// The bits per slice below which pybloom_live cuts its digest into 2-byte integers, the only
+/// ones the fixture covers.
+const MAX_BITS_PER_SLICE: u64 = 1 << 15;
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization {
+        reason: format!("invalid pybloom_live bloom filter: {}", reason),
+    }
+}
+
+/// The bytes of the integers pybloom_live cuts its digests into, see [ProbeScheme::PyBloom].
+fn chunk_bytes(bits_per_slice: u64) -> u64 {
+    if bits_per_slice >= 1 << 31 { 8 } else if bits_per_slice >= 1 << 15 { 4 } else { 2 }
+}
+
+/// The hash function pybloom_live picks for the bytes of all chunks.
+fn digest_name(hash_bytes: u64) -> &'static str {
+    match hash_bytes * 8 {
+        0..=128 => "md5",
+        129..=160 => "sha1",
+        161..=256 => "sha256",
+        257..=384 => "sha384",
+        _ => "sha512",
+    }
+}
+
+impl BloomFilter {
+    /// Writes the filter in the format of `BloomFilter.tofile` of pybloom_live, for Python
+    /// services to read it with `BloomFilter.fromfile`, see [BloomFilter::from_pybloom_bytes].
+    /// The [FilterBuilder::false_positive_probability] is written as the error rate, the
+    /// [FilterBuilder::expected_elements] as the capacity and [BloomFilter::len] as the count.
+    ///
+    /// Only filters which place their bits like pybloom_live can be written, i.e. those built
+    /// with [FilterBuilder::pybloom_compatible] or read with [BloomFilter::from_pybloom_bytes].
+    ///
+    /// Returns [FastBloomError::NotRepresentable] for any other filter: one with another hasher,
+    /// a seed, another [ProbeScheme], a size which is not a multiple of the number of hashes, or
+    /// one outside of the supported subset of [BloomFilter::from_pybloom_bytes].
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership};
+    ///
+    /// let mut bloom = FilterBuilder::pybloom_compatible(1000, 0.01).build_bloom_filter();
+    /// bloom.add("hello".as_bytes());
+    /// let bytes = bloom.to_pybloom_bytes().unwrap();
+    ///
+    /// let copy = BloomFilter::from_pybloom_bytes(&bytes).unwrap();
+    /// assert!(copy.contains("hello".as_bytes()));
+    /// assert!(FilterBuilder::new(1000, 0.01).build_bloom_filter().to_pybloom_bytes().is_err());
+    /// ```
+    pub fn to_pybloom_bytes(&self) -> Result<Vec<u8>, FastBloomError> {
+        let config = self.config_ref();
+        let unrepresentable = |reason: String| {
+            Err(FastBloomError::NotRepresentable { format: "pybloom_live", reason })
+        };
+        if config.hasher.name() != PyBloomMd5Hasher.name() {
+            return unrepresentable(format!("the hasher is {}, not {}", config.hasher.name(),
+                                           PyBloomMd5Hasher.name()));
+        }
+        if config.seed != 0 {
+            return unrepresentable(format!("the seed is {}, not 0", config.seed));
+        }
+        if config.probe_scheme != ProbeScheme::PyBloom {
+            return unrepresentable(format!("the probe scheme is {}, not {}",
+                                           config.probe_scheme.name(),
+                                           ProbeScheme::PyBloom.name()));
+        }
+        let slices = self.hashes() as u64;
+        if !config.size.is_multiple_of(slices) {
+            return unrepresentable(format!("the size of {} bits is not a multiple of {} hashes",
+                                           config.size, slices));
+        }
+        let bits_per_slice = config.size / slices;
+        let digest = digest_name(slices * chunk_bytes(bits_per_slice));
+        if digest != "md5" {
+            return unrepresentable(format!("pybloom_live hashes {} slices of {} bits with {}",
+                                           slices, bits_per_slice, digest));
+        }
+        if bits_per_slice >= MAX_BITS_PER_SLICE {
+            return unrepresentable(format!("only slices of less than {} bits are supported, not \
+                                            {}", MAX_BITS_PER_SLICE, bits_per_slice));
+        }
+
+        let len = config.size.div_ceil(8) as usize;
+        let mut bytes = Vec::with_capacity(HEADER_LEN + len);
+        bytes.extend_from_slice(&config.false_positive_probability.to_le_bytes());
+        bytes.extend_from_slice(&slices.to_le_bytes());
+        bytes.extend_from_slice(&bits_per_slice.to_le_bytes());
+        bytes.extend_from_slice(&config.expected_elements.to_le_bytes());
+        bytes.extend_from_slice(&self.len().to_le_bytes());
+        for i in 0..config.size.div_ceil(64) as usize {
+            bytes.extend_from_slice(&self.bit_vec().u64_word(i).to_le_bytes());
+        }
+        bytes.truncate(HEADER_LEN + len);
+        Ok(bytes)
+    }
+
+    /// Reads a filter written by `BloomFilter.tofile` of pybloom_live: a header of the `struct`
+    /// format `<dQQQQ` with the error rate, `num_slices`, `bits_per_slice`, the capacity and
+    /// the count, followed by the little endian `bitarray` of the bits. The error rate becomes
+    /// the [FilterBuilder::false_positive_probability], the capacity the
+    /// [FilterBuilder::expected_elements] and the count [BloomFilter::len].
+    ///
+    /// The elements are the UTF-8 bytes of the `str` keys of pybloom_live, which hashes every
+    /// other key as the UTF-8 of `str(key)`, e.g. `"42"` for `42` and `"b'abc'"` for `b"abc"`,
+    /// so those are the bytes to query for them.
+    ///
+    /// The format and the bit layout follow the source of pybloom_live 4.0.0, and are only
+    /// tested against a file written by a port of it (testvectors/pybloom_fixture.py), not by
+    /// pybloom_live itself.
+    ///
+    /// Only the subset that file covers is supported: at most 8 slices of less than 2^15 bits,
+    /// which pybloom_live hashes with one MD5 digest cut into an integer of 2 bytes per slice.
+    /// These are the filters of error rates of at least 2^-8 (e.g. 0.01 or 0.005) with less
+    /// than 2^15 bits per slice, e.g. of capacities up to about 23,900 for 0.01. pybloom_live
+    /// hashes all other filters with wider integers, with SHA-1 or SHA-2 and several salts,
+    /// e.g. those of its default error rate 0.001, and reading them returns an error. The files
+    /// of `ScalableBloomFilter` and of other Python libraries like bloom-filter2 are not
+    /// supported.
+    ///
+    /// Returns [FastBloomError::Deserialization] if the data is truncated or followed by more
+    /// bytes, if the header is invalid, if bits after the filter are set or if the filter is
+    /// outside of the supported subset.
+    pub fn from_pybloom_bytes(bytes: &[u8]) -> Result<BloomFilter, FastBloomError> {
+        if bytes.len() < HEADER_LEN {
+            return Err(invalid("the data ends within the header".to_string()));
+        }
+        let field = |i: usize| u64::from_le_bytes(bytes[8 * i..8 * i + 8].try_into().unwrap());
+        let error_rate = f64::from_bits(field(0));
+        let (slices, bits_per_slice, capacity, count) = (field(1), field(2), field(3), field(4));
+        if !(error_rate > 0.0 && error_rate < 1.0) {
+            return Err(invalid(format!("invalid error rate {}", error_rate)));
+        }
+        if slices == 0 || bits_per_slice == 0 {
+            return Err(invalid(format!("{} slices of {} bits", slices, bits_per_slice)));
+        }
+        let Some(size) = slices.checked_mul(bits_per_slice)
+            .filter(|_| slices <= u32::MAX as u64) else {
+            return Err(invalid(format!("{} slices of {} bits are too large", slices,
+                                       bits_per_slice)));
+        };
+        let hash_bytes = slices.saturating_mul(chunk_bytes(bits_per_slice));
+        if hash_bytes > MD5_BYTES {
+            return Err(invalid(format!(
+                "{} slices of {} bits are hashed with {}, only filters hashed with one md5 \
+                 digest of up to {} bytes are supported", slices, bits_per_slice,
+                digest_name(hash_bytes), MD5_BYTES)));
+        }
+        if bits_per_slice >= MAX_BITS_PER_SLICE {
+            return Err(invalid(format!("only slices of less than {} bits are supported, not {}",
+                                       MAX_BITS_PER_SLICE, bits_per_slice)));
+        }
+        let data = &bytes[HEADER_LEN..];
+        let len = size.div_ceil(8);
+        if (data.len() as u64) < len {
+            return Err(invalid("the data ends within the bits".to_string()));
+        }
+        if data.len() as u64 > len {
+            return Err(invalid(format!("{} bytes after the filter", data.len() as u64 - len)));
+        }
+        if size % 8 != 0 && data[data.len() - 1] >> (size % 8) != 0 {
+            return Err(invalid(format!("bits after bit {} are set", size)));
+        }
+
+        let mut config = FilterBuilder::from_size_and_hashes(size, slices as u32)
+            .with_hash_algorithm(HashAlgorithm::PyBloomMd5)
+            .with_probe_scheme(ProbeScheme::PyBloom);
+        config.expected_elements = capacity;
+        config.false_positive_probability = error_rate;
+        let words = data.chunks(8)
+            .map(|word| {
+                let mut bytes = [0u8; 8];
+                bytes[..word.len()].copy_from_slice(word);
+                u64::from_le_bytes(bytes)
+            })
+            .collect();
+        let mut bloom = BloomFilter::from_parts(config, BloomBitVec::from_u64_vec(words, size));
+        bloom.set_len(count);
+        Ok(bloom)
+    }
+}
+
+#[test]
+fn pybloom_test() {
+    // BloomFilter(capacity=1000, error_rate=0.01) with key-0 .. key-999 added, and the keys of
+    // other-0 .. other-9999 it reports present, written by the port of pybloom_live in
+    // testvectors/pybloom_fixture.py
+    let fixture = include_bytes!("../testvectors/pybloom_live_1000.bloom");
+    let positives = include_str!("../testvectors/pybloom_live_1000.txt");
+    let bloom = BloomFilter::from_pybloom_bytes(fixture).unwrap();
+    let config = bloom.config();
+    assert_eq!((config.size, bloom.hashes()), (9590, 7));
+    assert_eq!((config.expected_elements, config.false_positive_probability), (1000, 0.01));
+    assert_eq!(config.probe_scheme, ProbeScheme::PyBloom);
+    assert_eq!(bloom.len(), 1000);
+    for i in 0..1000 {
+        assert_eq!(bloom.contains(format!("key-{}", i).as_bytes()), true);
+    }
+    let found: Vec<String> = (0..10_000)
+        .map(|i| format!("other-{}", i))
+        .filter(|key| bloom.contains(key.as_bytes()))
+        .collect();
+    assert_eq!(found, positives.lines().collect::<Vec<_>>());
+    assert_eq!(found.len(), 120);
+    assert_eq!(bloom.to_pybloom_bytes().unwrap(), fixture);
+
+    // the same filter built here
+    let mut built = FilterBuilder::pybloom_compatible(1000, 0.01).build_bloom_filter();
+    for i in 0..1000 {
+        built.add(format!("key-{}", i).as_bytes());
+    }
+    assert_eq!(built.to_pybloom_bytes().unwrap(), fixture);
+    assert_eq!(built.compatible(&bloom), true);
+
+    // the sizes pybloom_live computes
+    let sized = FilterBuilder::pybloom_compatible(1000, 0.001);
+    assert_eq!((sized.size, sized.hashes), (14380, 10));
+    let sized = FilterBuilder::pybloom_compatible(1, 0.5);
+    assert_eq!((sized.size, sized.hashes), (2, 1));
+}
+
+#[test]
+fn pybloom_invalid_test() {
+    let fixture = include_bytes!("../testvectors/pybloom_live_1000.bloom");
+    let reason = |bytes: &[u8]| match BloomFilter::from_pybloom_bytes(bytes).unwrap_err() {
+        FastBloomError::Deserialization { reason } => reason,
+        e => panic!("unexpected error {:?}", e),
+    };
+    let patched = |i: usize, value: u64| {
+        let mut bytes = fixture.to_vec();
+        bytes[8 * i..8 * i + 8].copy_from_slice(&value.to_le_bytes());
+        bytes
+    };
+    assert_eq!(reason(&fixture[..39]),
+               "invalid pybloom_live bloom filter: the data ends within the header");
+    assert_eq!(reason(&patched(0, 1f64.to_bits())),
+               "invalid pybloom_live bloom filter: invalid error rate 1");
+    assert_eq!(reason(&patched(1, 0)), "invalid pybloom_live bloom filter: 0 slices of 1370 bits");
+    assert_eq!(reason(&patched(2, u64::MAX)),
+               "invalid pybloom_live bloom filter: 7 slices of 18446744073709551615 bits are too \
+                large");
+    // the default error rate of pybloom_live, 0.001, needs 10 slices
+    assert_eq!(reason(&patched(1, 10)),
+               "invalid pybloom_live bloom filter: 10 slices of 1370 bits are hashed with sha1, \
+                only filters hashed with one md5 digest of up to 16 bytes are supported");
+    assert_eq!(reason(&patched(2, 1 << 15)),
+               "invalid pybloom_live bloom filter: 7 slices of 32768 bits are hashed with \
+                sha256, only filters hashed with one md5 digest of up to 16 bytes are supported");
+    // one md5 digest cut into 4-byte integers, which the fixture doesn't cover
+    let mut wide = patched(1, 4);
+    wide[16..24].copy_from_slice(&(1u64 << 15).to_le_bytes());
+    assert_eq!(reason(&wide),
+               "invalid pybloom_live bloom filter: only slices of less than 32768 bits are \
+                supported, not 32768");
+    assert_eq!(reason(&fixture[..fixture.len() - 1]),
+               "invalid pybloom_live bloom filter: the data ends within the bits");
+    assert_eq!(reason(&[&fixture[..], &[0, 0]].concat()),
+               "invalid pybloom_live bloom filter: 2 bytes after the filter");
+    let mut padded = fixture.to_vec();
+    *padded.last_mut().unwrap() |= 0x80;
+    assert_eq!(reason(&padded), "invalid pybloom_live bloom filter: bits after bit 9590 are set");
+}
+
+#[test]
+fn pybloom_not_representable_test() {
+    let reason = |mut config: FilterBuilder| {
+        match config.build_bloom_filter().to_pybloom_bytes().unwrap_err() {
+            FastBloomError::NotRepresentable { format: "pybloom_live", reason } => reason,
+            e => panic!("unexpected error {:?}", e),
+        }
+    };
+    let pybloom = FilterBuilder::pybloom_compatible(1000, 0.01);
+    assert_eq!(reason(FilterBuilder::from_size_and_hashes(9590, 7)),
+               "the hasher is xxh128, not pybloom_md5");
+    assert_eq!(reason(pybloom.clone().with_seed(1)), "the seed is 1, not 0");
+    assert_eq!(reason(pybloom.clone().with_probe_scheme(ProbeScheme::Double)),
+               "the probe scheme is double, not pybloom");
+    assert_eq!(reason(pybloom.clone().with_size(9591)),
+               "the size of 9591 bits is not a multiple of 7 hashes");
+    assert_eq!(reason(FilterBuilder::pybloom_compatible(1000, 0.001)),
+               "pybloom_live hashes 10 slices of 1438 bits with sha1");
+    let custom = FilterBuilder::from_size_and_hashes(640, 8).with_hasher(PyBloomMd5Hasher)
+        .with_probe_scheme(ProbeScheme::PyBloom);
+    assert_eq!(custom.clone().build_bloom_filter().to_pybloom_bytes().is_ok(), true);
+    assert_eq!(reason(FilterBuilder::pybloom_compatible(30_000, 0.1)),
+               "only slices of less than 32768 bits are supported, not 35944");
+}
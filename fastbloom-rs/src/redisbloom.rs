@@ -0,0 +1,354 @@
+//! The chunks of RedisBloom's `BF.SCANDUMP` and `BF.LOADCHUNK`, see
+//! [BloomFilter::from_redisbloom_chunks].
+
+use crate::error::FastBloomError;
+use crate::hasher::{HashAlgorithm, Murmur64AHasher, ProbeScheme};
+use crate::vec::BloomBitVec;
+use crate::{BloomFilter, BloomHasher, FilterBuilder, Hashes};
+
+/// The filter is sized without rounding up to a power of two.
+const BLOOM_OPT_NOROUND: u32 = 1;
+/// The filter hashes with [Murmur64AHasher] instead of 32-bit MurmurHash2.
+const BLOOM_OPT_FORCE64: u32 = 4;
+/// The length of the chain header: the number of items, of filters, the options and the growth.
+const CHAIN_LEN: usize = 20;
+/// The length of the header of every filter of the chain: bytes, bits, items, error rate,
+/// bits per entry, hashes, capacity and the power of two of the size.
+const LINK_LEN: usize = 53;
+/// `ln(2)^2` as RedisBloom writes it, for the bits per entry.
+const LN2_SQUARED: f64 = 0.480453013918201;
+/// The growth factor of a scaling filter, the default of `BF.RESERVE`.
+const GROWTH: u32 = 2;
+
+fn invalid(reason: String) -> FastBloomError {
+    FastBloomError::Deserialization { reason: format!("invalid RedisBloom dump: {}", reason) }
+}
+
+/// The fields of the header chunk which are read.
+struct Header {
+    bytes: u64,
+    bits: u64,
+    items: u64,
+    error: f64,
+    hashes: u32,
+    entries: u64,
+}
+
+impl Header {
+    /// Parses the packed little endian `dumpedChainHeader` of `sb.c` with its only link.
+    fn parse(data: &[u8]) -> Result<Header, FastBloomError> {
+        if data.len() < CHAIN_LEN {
+            return Err(invalid(format!("the header of {} bytes is too short", data.len())));
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(data[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_le_bytes(data[i..i + 8].try_into().unwrap());
+        let filters = u32_at(8);
+        let options = u32_at(12);
+        if data.len() as u64 != CHAIN_LEN as u64 + filters as u64 * LINK_LEN as u64 {
+            return Err(invalid(format!("the header of {} bytes doesn't hold {} filters",
+                                       data.len(), filters)));
+        }
+        if filters != 1 {
+            return Err(invalid(format!("the filter scaled to {} sub-filters, only filters \
+                                        with one can be read", filters)));
+        }
+        if options & BLOOM_OPT_FORCE64 == 0 {
+            return Err(invalid("the filter hashes with 32 bits, only filters with the FORCE64 \
+                                option can be read".to_string()));
+        }
+        let link = CHAIN_LEN;
+        Ok(Header {
+            bytes: u64_at(link),
+            bits: u64_at(link + 8),
+            items: u64_at(link + 16),
+            error: f64::from_le_bytes(data[link + 24..link + 32].try_into().unwrap()),
+            hashes: u32_at(link + 40),
+            entries: u64_at(link + 44),
+        })
+    }
+}
+
+impl BloomFilter {
+    /// Reads a filter of RedisBloom from the chunks of `BF.SCANDUMP`, as `(iterator, data)`
+    /// pairs in the order they were returned. The first chunk is the header with iterator 1,
+    /// and a last chunk with iterator 0 ends the dump.
+    ///
+    /// The filter keeps the size, number of hashes and bits of the RedisBloom filter, and its
+    /// hashes with [HashAlgorithm::Murmur64A] and [ProbeScheme::RedisBloom], so it answers
+    /// `BF.EXISTS` identically. Its expected elements and false positive probability are the
+    /// capacity and error rate of the RedisBloom filter, and [BloomFilter::len] the number of
+    /// items added to it. The chunks of the tests come from a port of the RedisBloom code in
+    /// testvectors/RedisBloomFixture.c, no dump of a Redis server has been checked.
+    ///
+    /// Returns [FastBloomError::Deserialization] if the chunks don't make up a complete filter,
+    /// or for filters RedisBloom scaled to more than one sub-filter, and those without the
+    /// `FORCE64` option, which RedisBloom sets on every filter since version 2.0.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use fastbloom_rs::{BloomFilter, FilterBuilder, HashAlgorithm, Membership, Murmur64AHasher,
+    ///                    ProbeScheme};
+    ///
+    /// let mut bloom = FilterBuilder::new(1000, 0.01)
+    ///     .with_hash_algorithm(HashAlgorithm::Murmur64A)
+    ///     .with_seed(Murmur64AHasher::REDISBLOOM_SEED)
+    ///     .with_probe_scheme(ProbeScheme::RedisBloom)
+    ///     .build_bloom_filter();
+    /// bloom.add(b"hello");
+    ///
+    /// // e.g. BF.LOADCHUNK every chunk, and read back what BF.SCANDUMP returns
+    /// let chunks = bloom.to_redisbloom_chunks(1 << 20).unwrap();
+    /// let copy = BloomFilter::from_redisbloom_chunks(chunks).unwrap();
+    /// assert!(copy.contains(b"hello"));
+    /// ```
+    pub fn from_redisbloom_chunks(chunks: impl IntoIterator<Item = (i64, Vec<u8>)>)
+                                  -> Result<BloomFilter, FastBloomError> {
+        let mut chunks = chunks.into_iter();
+        let (iter, header) = chunks.next()
+            .ok_or_else(|| invalid("there are no chunks".to_string()))?;
+        if iter != 1 {
+            return Err(invalid(format!("the header has iterator {}, not 1", iter)));
+        }
+        let header = Header::parse(&header)?;
+        if header.bits == 0 || header.hashes == 0 {
+            return Err(invalid(format!("invalid size {} with {} hashes", header.bits,
+                                       header.hashes)));
+        }
+        let words = header.bits.div_ceil(64);
+        if header.bytes.div_ceil(8) != words {
+            return Err(invalid(format!("{} bytes for a size of {} bits", header.bytes,
+                                       header.bits)));
+        }
+
+        // the iterator of a chunk is 1 plus the offset of its end
+        let mut bytes = Vec::new();
+        for (iter, data) in chunks {
+            if iter == 0 {
+                break;
+            }
+            let end = bytes.len() + data.len();
+            if iter != 1 + end as i64 {
+                return Err(invalid(format!("the chunk of {} bytes with iterator {} doesn't \
+                                            follow the {} bytes before", data.len(), iter,
+                                           bytes.len())));
+            }
+            if end as u64 > header.bytes {
+                return Err(invalid(format!("the chunks are longer than the {} bytes of the \
+                                            filter", header.bytes)));
+            }
+            bytes.extend_from_slice(&data);
+        }
+        if bytes.len() as u64 != header.bytes {
+            return Err(invalid(format!("the chunks end after {} of the {} bytes", bytes.len(),
+                                       header.bytes)));
+        }
+
+        let mut config = FilterBuilder::from_size_and_hashes(header.bits, header.hashes)
+            .with_seed(Murmur64AHasher::REDISBLOOM_SEED)
+            .with_hash_algorithm(HashAlgorithm::Murmur64A)
+            .with_probe_scheme(ProbeScheme::RedisBloom);
+        if header.entries > 0 {
+            config.expected_elements = header.entries;
+        }
+        if header.error > 0.0 && header.error < 1.0 {
+            config.false_positive_probability = header.error;
+        }
+        bytes.resize(words as usize * 8, 0);
+        let words = bytes.chunks_exact(8)
+            .map(|word| u64::from_le_bytes(word.try_into().unwrap()))
+            .collect();
+        let mut bloom = BloomFilter::from_parts(config, BloomBitVec::from_u64_vec(words,
+                                                                                   header.bits));
+        bloom.set_len(header.items);
+        Ok(bloom)
+    }
+
+    /// Splits the filter into chunks for RedisBloom's `BF.LOADCHUNK`, as `(iterator, data)`
+    /// pairs to load in order: the header, then the bits in chunks of at most `chunk_size`
+    /// bytes. It is the reverse of [BloomFilter::from_redisbloom_chunks].
+    ///
+    /// The filter is written as a scaling filter with the default growth of `BF.RESERVE`, with
+    /// the [FilterBuilder::expected_elements] as its capacity, the
+    /// [FilterBuilder::false_positive_probability] as its error rate and [BloomFilter::len] as
+    /// the number of items.
+    ///
+    /// Returns [FastBloomError::NotRepresentable] unless the filter places its bits like
+    /// RedisBloom: with [HashAlgorithm::Murmur64A], the seed
+    /// [Murmur64AHasher::REDISBLOOM_SEED], [ProbeScheme::RedisBloom] and a size which is a
+    /// multiple of 64.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn to_redisbloom_chunks(&self, chunk_size: usize)
+                                -> Result<Vec<(i64, Vec<u8>)>, FastBloomError> {
+        assert!(chunk_size > 0, "chunk_size must be larger than 0!");
+        let config = self.config_ref();
+        let unrepresentable = |reason: String| {
+            Err(FastBloomError::NotRepresentable { format: "RedisBloom", reason })
+        };
+        if config.hasher.name() != Murmur64AHasher.name() {
+            return unrepresentable(format!("the hasher is {}, not {}", config.hasher.name(),
+                                           Murmur64AHasher.name()));
+        }
+        if config.seed != Murmur64AHasher::REDISBLOOM_SEED {
+            return unrepresentable(format!("the seed is {}, not {}", config.seed,
+                                           Murmur64AHasher::REDISBLOOM_SEED));
+        }
+        if config.probe_scheme != ProbeScheme::RedisBloom {
+            return unrepresentable(format!("the probe scheme is {}, not {}",
+                                           config.probe_scheme.name(),
+                                           ProbeScheme::RedisBloom.name()));
+        }
+        if !config.size.is_multiple_of(64) {
+            return unrepresentable(format!("the size of {} bits is not a multiple of 64",
+                                           config.size));
+        }
+
+        let words = config.size / 64;
+        let error = config.false_positive_probability;
+        let mut header = Vec::with_capacity(CHAIN_LEN + LINK_LEN);
+        header.extend_from_slice(&self.len().to_le_bytes());
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&(BLOOM_OPT_NOROUND | BLOOM_OPT_FORCE64).to_le_bytes());
+        header.extend_from_slice(&GROWTH.to_le_bytes());
+        header.extend_from_slice(&(words * 8).to_le_bytes());
+        header.extend_from_slice(&config.size.to_le_bytes());
+        header.extend_from_slice(&self.len().to_le_bytes());
+        header.extend_from_slice(&error.to_le_bytes());
+        header.extend_from_slice(&(-(error.ln() / LN2_SQUARED)).to_le_bytes());
+        header.extend_from_slice(&self.hashes().to_le_bytes());
+        header.extend_from_slice(&config.expected_elements.to_le_bytes());
+        // the power of two of the size, 0 as it is not rounded
+        header.push(0);
+
+        let bytes: Vec<u8> = (0..words as usize)
+            .flat_map(|i| self.bit_vec().u64_word(i).to_le_bytes())
+            .collect();
+        let mut chunks = vec![(1, header)];
+        let mut end = 0;
+        for chunk in bytes.chunks(chunk_size) {
+            end += chunk.len();
+            chunks.push((1 + end as i64, chunk.to_vec()));
+        }
+        Ok(chunks)
+    }
+}
+
+/// The chunks of `testvectors/redisbloom_scandump.txt`, one per line as the iterator and the
+/// data in hex.
+#[cfg(test)]
+fn fixture_chunks() -> Vec<(i64, Vec<u8>)> {
+    include_str!("../testvectors/redisbloom_scandump.txt").lines()
+        .map(|line| {
+            let (iter, data) = line.split_once(' ').unwrap();
+            (iter.parse().unwrap(), crate::hex::decode(data).unwrap())
+        })
+        .collect()
+}
+
+#[test]
+fn redisbloom_test() {
+    // BF.RESERVE f 0.01 1000 with key-0 .. key-999 added, and the keys of other-0 .. other-9999
+    // for which BF.EXISTS is 1, as printed by the port of RedisBloom in
+    // testvectors/RedisBloomFixture.c
+    let chunks = fixture_chunks();
+    let exists = include_str!("../testvectors/redisbloom_exists.txt");
+    assert_eq!(chunks.iter().map(|(iter, data)| (*iter, data.len())).collect::<Vec<_>>(),
+               vec![(1, 73), (1201, 1200), (0, 0)]);
+    let bloom = BloomFilter::from_redisbloom_chunks(chunks.clone()).unwrap();
+    let config = bloom.config();
+    assert_eq!((config.size, config.hashes, bloom.len()), (9600, 7, 998));
+    assert_eq!((config.expected_elements, config.false_positive_probability), (1000, 0.01));
+    for i in 0..1000 {
+        assert_eq!(bloom.contains(format!("key-{}", i).as_bytes()), true);
+    }
+    let found: Vec<String> = (0..10_000)
+        .map(|i| format!("other-{}", i))
+        .filter(|key| bloom.contains(key.as_bytes()))
+        .collect();
+    assert_eq!(found, exists.lines().collect::<Vec<_>>());
+    assert_eq!(found.len(), 85);
+
+    // written back, the chunks are the dumped ones without the end
+    assert_eq!(bloom.to_redisbloom_chunks(10 << 20).unwrap(), &chunks[..2]);
+    let small = bloom.to_redisbloom_chunks(500).unwrap();
+    assert_eq!(small.iter().map(|(iter, data)| (*iter, data.len())).collect::<Vec<_>>(),
+               vec![(1, 73), (501, 500), (1001, 500), (1201, 200)]);
+    let copy = BloomFilter::from_redisbloom_chunks(small).unwrap();
+    assert_eq!(copy.get_u8_array(), bloom.get_u8_array());
+    assert_eq!(copy.compatible(&bloom), true);
+}
+
+#[test]
+fn redisbloom_invalid_test() {
+    let chunks = fixture_chunks();
+    let reason = |chunks: Vec<(i64, Vec<u8>)>| {
+        match BloomFilter::from_redisbloom_chunks(chunks).unwrap_err() {
+            FastBloomError::Deserialization { reason } => reason,
+            e => panic!("unexpected error {:?}", e),
+        }
+    };
+    let with_header = |i: usize, value: u8| {
+        let mut chunks = chunks.clone();
+        chunks[0].1[i] = value;
+        chunks
+    };
+    assert_eq!(reason(vec![]), "invalid RedisBloom dump: there are no chunks");
+    assert_eq!(reason(chunks[1..].to_vec()),
+               "invalid RedisBloom dump: the header has iterator 1201, not 1");
+    assert_eq!(reason(vec![(1, chunks[0].1[..19].to_vec())]),
+               "invalid RedisBloom dump: the header of 19 bytes is too short");
+    assert_eq!(reason(with_header(8, 2)),
+               "invalid RedisBloom dump: the header of 73 bytes doesn't hold 2 filters");
+    let mut scaled = chunks[0].1.clone();
+    scaled[8] = 2;
+    scaled.extend_from_slice(&chunks[0].1[20..]);
+    assert_eq!(reason(vec![(1, scaled)]),
+               "invalid RedisBloom dump: the filter scaled to 2 sub-filters, only filters with \
+                one can be read");
+    assert_eq!(reason(with_header(12, 1)),
+               "invalid RedisBloom dump: the filter hashes with 32 bits, only filters with the \
+                FORCE64 option can be read");
+    assert_eq!(reason(with_header(60, 0)), "invalid RedisBloom dump: invalid size 9600 with 0 \
+                                            hashes");
+    assert_eq!(reason(with_header(21, 0)), "invalid RedisBloom dump: 176 bytes for a size of \
+                                            9600 bits");
+
+    let data = &chunks[1].1;
+    assert_eq!(reason(vec![chunks[0].clone(), (1001, data[..1000].to_vec())]),
+               "invalid RedisBloom dump: the chunks end after 1000 of the 1200 bytes");
+    assert_eq!(reason(vec![chunks[0].clone(), (1001, data[..1000].to_vec()), (0, vec![]),
+                           (1201, data[1000..].to_vec())]),
+               "invalid RedisBloom dump: the chunks end after 1000 of the 1200 bytes");
+    assert_eq!(reason(vec![chunks[0].clone(), (1201, data[..1000].to_vec())]),
+               "invalid RedisBloom dump: the chunk of 1000 bytes with iterator 1201 doesn't \
+                follow the 0 bytes before");
+    let mut longer = data.clone();
+    longer.push(0);
+    assert_eq!(reason(vec![chunks[0].clone(), (1202, longer)]),
+               "invalid RedisBloom dump: the chunks are longer than the 1200 bytes of the filter");
+}
+
+#[test]
+fn redisbloom_not_representable_test() {
+    let reason = |mut config: FilterBuilder| {
+        match config.build_bloom_filter().to_redisbloom_chunks(1024).unwrap_err() {
+            FastBloomError::NotRepresentable { format: "RedisBloom", reason } => reason,
+            e => panic!("unexpected error {:?}", e),
+        }
+    };
+    let redisbloom = FilterBuilder::from_size_and_hashes(9600, 7)
+        .with_hash_algorithm(HashAlgorithm::Murmur64A)
+        .with_seed(Murmur64AHasher::REDISBLOOM_SEED)
+        .with_probe_scheme(ProbeScheme::RedisBloom);
+    assert_eq!(reason(FilterBuilder::from_size_and_hashes(9600, 7)),
+               "the hasher is xxh128, not murmur64a");
+    assert_eq!(reason(redisbloom.clone().with_seed(0)),
+               "the seed is 0, not 14313749767032793493");
+    assert_eq!(reason(redisbloom.clone().with_probe_scheme(ProbeScheme::Double)),
+               "the probe scheme is double, not redisbloom");
+    assert_eq!(reason(redisbloom.with_size(9601)),
+               "the size of 9601 bits is not a multiple of 64");
+}
@@ -0,0 +1,235 @@
+use xxhash_rust::xxh64::xxh64;
+
+use crate::error::FastBloomError;
+
+/// The salts of the eight words of a block, from the Parquet spec.
+const SALT: [u32; 8] = [
+    0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d, 0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31,
+];
+/// The bytes of a block, eight 32-bit words.
+const BLOCK_BYTES: usize = 32;
+/// The largest bitset [SbbfFilter::new] sizes, 128 MiB like Parquet writers.
+const MAX_BYTES: usize = 128 * 1024 * 1024;
+
+/// A split block Bloom filter (SBBF) as Parquet stores one per column chunk, see the
+/// [Parquet spec](https://github.com/apache/parquet-format/blob/master/BloomFilter.md).
+///
+/// It is a distinct type from [crate::BloomFilter] and can't be converted to or from one: an
+/// element sets one bit in each of the eight 32-bit words of a single 256-bit block, chosen by
+/// its 64-bit xxh64 hash, and the words are multiplied by fixed salts instead of probing `k`
+/// indices of one bit array.
+///
+/// The elements are the plain encoded values of the column: the 4 or 8 little endian bytes of
+/// `INT32`, `INT64`, `FLOAT` and `DOUBLE` values, and the bytes of `BYTE_ARRAY` and
+/// `FIXED_LEN_BYTE_ARRAY` values without a length.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::SbbfFilter;
+///
+/// let mut sbbf = SbbfFilter::new(1000, 0.01);
+/// sbbf.insert(&42i64.to_le_bytes());
+/// assert!(sbbf.check(&42i64.to_le_bytes()));
+///
+/// // the bitset after the BloomFilterHeader of a column chunk
+/// let copy = SbbfFilter::from_bytes(&sbbf.to_bytes()).unwrap();
+/// assert!(copy.check(&42i64.to_le_bytes()));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SbbfFilter {
+    blocks: Vec<[u32; 8]>,
+}
+
+/// Checks that there is at least one block, which every hash is mapped to.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SbbfFilter {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            blocks: Vec<[u32; 8]>,
+        }
+
+        let repr = Repr::deserialize(deserializer)?;
+        if repr.blocks.is_empty() {
+            return Err(D::Error::custom(FastBloomError::Deserialization {
+                reason: "a split block bloom filter needs at least one block".to_string(),
+            }));
+        }
+        Ok(SbbfFilter { blocks: repr.blocks })
+    }
+}
+
+impl SbbfFilter {
+    /// Creates an empty filter sized for `ndv` distinct values with the false positive
+    /// probability `fpp`, like Parquet writers do: `-8 * ndv / ln(1 - fpp^(1/8))` bits, rounded
+    /// up to a power of two of at least 32 bytes and at most 128 MiB.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `fpp` is not between 0.0 and 1.0 (exclusive).
+    pub fn new(ndv: u64, fpp: f64) -> Self {
+        assert!(fpp > 0.0 && fpp < 1.0, "fpp must between (0.0, 1.0)!");
+        let bits = -8.0 * ndv as f64 / (1.0 - fpp.powf(1.0 / 8.0)).ln();
+        let bytes = ((bits as u64 / 8).min(MAX_BYTES as u64) as usize).max(BLOCK_BYTES);
+        SbbfFilter::with_num_bytes(bytes.next_power_of_two())
+    }
+
+    /// Creates an empty filter of `num_bytes` bytes, rounded up to whole blocks of 32 bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_bytes` is zero.
+    pub fn with_num_bytes(num_bytes: usize) -> Self {
+        assert!(num_bytes > 0, "num_bytes must be larger than 0!");
+        SbbfFilter { blocks: vec![[0; 8]; num_bytes.div_ceil(BLOCK_BYTES)] }
+    }
+
+    /// Reads the bitset of a filter, e.g. the `numBytes` bytes following the Thrift
+    /// `BloomFilterHeader` at the `bloom_filter_offset` of a column chunk: the blocks one after
+    /// the other, every word little endian.
+    ///
+    /// The layout follows the Parquet spec and is tested against a bitset written from it by
+    /// testvectors/sbbf_fixture.py, not against files of a Parquet writer.
+    ///
+    /// Returns [FastBloomError::Deserialization] if the length is not a positive multiple of 32.
+    pub fn from_bytes(bitset: &[u8]) -> Result<SbbfFilter, FastBloomError> {
+        if bitset.is_empty() || !bitset.len().is_multiple_of(BLOCK_BYTES) {
+            return Err(FastBloomError::Deserialization {
+                reason: format!("the bitset of a split block bloom filter of {} bytes is not a \
+                                 positive multiple of {}", bitset.len(), BLOCK_BYTES),
+            });
+        }
+        let blocks = bitset.chunks_exact(BLOCK_BYTES)
+            .map(|block| {
+                let mut words = [0u32; 8];
+                for (word, bytes) in words.iter_mut().zip(block.chunks_exact(4)) {
+                    *word = u32::from_le_bytes(bytes.try_into().unwrap());
+                }
+                words
+            })
+            .collect();
+        Ok(SbbfFilter { blocks })
+    }
+
+    /// The bitset of the filter as Parquet stores it, see [SbbfFilter::from_bytes].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.blocks.iter().flatten().flat_map(|word| word.to_le_bytes()).collect()
+    }
+
+    /// The size of the bitset in bytes.
+    pub fn num_bytes(&self) -> usize {
+        self.blocks.len() * BLOCK_BYTES
+    }
+
+    /// Adds the plain encoded `value`.
+    pub fn insert(&mut self, value: &[u8]) {
+        self.insert_hash(xxh64(value, 0));
+    }
+
+    /// Returns `false` if the plain encoded `value` was never added, `true` if it probably was.
+    pub fn check(&self, value: &[u8]) -> bool {
+        self.check_hash(xxh64(value, 0))
+    }
+
+    /// Adds a value by its xxh64 hash with seed 0.
+    pub fn insert_hash(&mut self, hash: u64) {
+        let index = self.block_index(hash);
+        let block = &mut self.blocks[index];
+        for (word, mask) in block.iter_mut().zip(mask(hash as u32)) {
+            *word |= mask;
+        }
+    }
+
+    /// Checks a value by its xxh64 hash with seed 0, see [SbbfFilter::check].
+    pub fn check_hash(&self, hash: u64) -> bool {
+        let block = &self.blocks[self.block_index(hash)];
+        block.iter().zip(mask(hash as u32)).all(|(word, mask)| word & mask != 0)
+    }
+
+    /// The block of a hash: its upper 32 bits scaled to the number of blocks.
+    #[inline]
+    fn block_index(&self, hash: u64) -> usize {
+        (((hash >> 32) * self.blocks.len() as u64) >> 32) as usize
+    }
+}
+
+/// The bit of every word of a block for the lower 32 bits of a hash.
+#[inline]
+fn mask(key: u32) -> [u32; 8] {
+    SALT.map(|salt| 1 << (key.wrapping_mul(salt) >> 27))
+}
+
+#[test]
+fn sbbf_test() {
+    assert_eq!(xxh64(b"", 0), 0xef46db3751d8e999);
+    for key in (0..1_000_000u32).step_by(7) {
+        assert_eq!(mask(key).iter().all(|word| word.count_ones() == 1), true);
+    }
+    assert_eq!(mask(0), [1; 8]);
+
+    assert_eq!(SbbfFilter::new(1000, 0.01).num_bytes(), 2048);
+    assert_eq!(SbbfFilter::new(0, 0.01).num_bytes(), 32);
+    assert_eq!(SbbfFilter::new(u64::MAX, 0.01).num_bytes(), MAX_BYTES);
+    assert_eq!(SbbfFilter::with_num_bytes(33).num_bytes(), 64);
+
+    let mut sbbf = SbbfFilter::new(10_000, 0.01);
+    for i in 0..10_000u32 {
+        sbbf.insert(format!("value-{}", i).as_bytes());
+    }
+    for i in 0..10_000u32 {
+        assert_eq!(sbbf.check(format!("value-{}", i).as_bytes()), true);
+    }
+    let false_positives = (0..100_000u32)
+        .filter(|i| sbbf.check(format!("other-{}", i).as_bytes()))
+        .count();
+    assert_eq!(false_positives < 1000, true, "{}", false_positives);
+
+    let copy = SbbfFilter::from_bytes(&sbbf.to_bytes()).unwrap();
+    assert_eq!(copy, sbbf);
+    for len in [0, 31, 48] {
+        assert_eq!(SbbfFilter::from_bytes(&vec![0; len]).unwrap_err().to_string(),
+                   format!("can't deserialize bloom filter: the bitset of a split block bloom \
+                            filter of {} bytes is not a positive multiple of 32", len));
+    }
+}
+
+#[test]
+fn sbbf_fixture_test() {
+    // the filter of an INT64 column chunk with the values 0 .. 999, and the values of
+    // 1000 .. 10999 it reports present, written from the spec by testvectors/sbbf_fixture.py
+    let fixture = include_bytes!("../testvectors/sbbf_int64.bin");
+    let positives: Vec<i64> = include_str!("../testvectors/sbbf_int64.txt").lines()
+        .map(|line| line.parse().unwrap())
+        .collect();
+    let sbbf = SbbfFilter::from_bytes(fixture).unwrap();
+    for value in 0..1000i64 {
+        assert_eq!(sbbf.check(&value.to_le_bytes()), true);
+    }
+    let found: Vec<i64> = (1000..11_000i64)
+        .filter(|value| sbbf.check(&value.to_le_bytes()))
+        .collect();
+    assert_eq!(found, positives);
+    assert_eq!(sbbf.to_bytes(), fixture);
+
+    let mut built = SbbfFilter::new(1000, 0.01);
+    for value in 0..1000i64 {
+        built.insert(&value.to_le_bytes());
+    }
+    assert_eq!(built, sbbf);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn sbbf_serde_test() {
+    let mut sbbf = SbbfFilter::with_num_bytes(64);
+    sbbf.insert(&42i64.to_le_bytes());
+    let copy: SbbfFilter = serde_json::from_str(&serde_json::to_string(&sbbf).unwrap()).unwrap();
+    assert_eq!(copy, sbbf);
+    assert_eq!(serde_json::from_str::<SbbfFilter>(r#"{"blocks":[]}"#).unwrap_err().to_string(),
+               "can't deserialize bloom filter: a split block bloom filter needs at least one \
+                block");
+}
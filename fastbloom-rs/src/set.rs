@@ -0,0 +1,249 @@
+use std::marker::PhantomData;
+
+use crate::{BloomFilter, FilterBuilder};
+use crate::error::IncompatibleFilterError;
+
+/// Encodes a value into the bytes a [BloomSet] adds to its filter.
+///
+/// The implementations of the crate use the encoding of the typed helpers of [BloomFilter]
+/// (see [BloomFilter::add_str]): strings and byte slices are their bytes, integers their little
+/// endian bytes at the width of the type (`usize`/`isize` as 64 bits).
+pub trait BloomKey {
+    /// Names the kind of key, sets with different tags refuse to be merged. Types with the same
+    /// encoding may share a tag, like `str` and `String`.
+    const KEY_TAG: &'static str;
+
+    /// Appends the encoding of `self` to `buf`.
+    fn write_key(&self, buf: &mut Vec<u8>);
+
+    /// Calls `f` with the encoding of `self`, which [BloomSet::contains] hashes. The default
+    /// writes it into a new buffer with [BloomKey::write_key], the implementations of the crate
+    /// pass it without allocating.
+    fn with_key<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        let mut buf = Vec::new();
+        self.write_key(&mut buf);
+        f(&buf)
+    }
+}
+
+impl<T: BloomKey + ?Sized> BloomKey for &T {
+    const KEY_TAG: &'static str = T::KEY_TAG;
+
+    fn write_key(&self, buf: &mut Vec<u8>) {
+        (**self).write_key(buf)
+    }
+
+    fn with_key<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        (**self).with_key(f)
+    }
+}
+
+impl BloomKey for str {
+    const KEY_TAG: &'static str = "str";
+
+    fn write_key(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn with_key<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        f(self.as_bytes())
+    }
+}
+
+impl BloomKey for String {
+    const KEY_TAG: &'static str = "str";
+
+    fn write_key(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn with_key<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        f(self.as_bytes())
+    }
+}
+
+impl BloomKey for [u8] {
+    const KEY_TAG: &'static str = "bytes";
+
+    fn write_key(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn with_key<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        f(self)
+    }
+}
+
+impl BloomKey for Vec<u8> {
+    const KEY_TAG: &'static str = "bytes";
+
+    fn write_key(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(self);
+    }
+
+    fn with_key<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+        f(self)
+    }
+}
+
+macro_rules! int_bloom_key {
+    ($($int:ty => $wide:ty),*) => {
+        $(
+            impl BloomKey for $int {
+                const KEY_TAG: &'static str = stringify!($wide);
+
+                fn write_key(&self, buf: &mut Vec<u8>) {
+                    buf.extend_from_slice(&(*self as $wide).to_le_bytes());
+                }
+
+                fn with_key<R, F: FnOnce(&[u8]) -> R>(&self, f: F) -> R {
+                    f(&(*self as $wide).to_le_bytes())
+                }
+            }
+        )*
+    };
+}
+
+int_bloom_key!(u8 => u8, u16 => u16, u32 => u32, u64 => u64, u128 => u128, usize => u64,
+    i8 => i8, i16 => i16, i32 => i32, i64 => i64, i128 => i128, isize => i64);
+
+/// A [BloomFilter] holding keys of one type `T`, encoded with [BloomKey], so keys of different
+/// types can't be mixed up in the same filter.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomSet, FilterBuilder};
+///
+/// let mut users: BloomSet<str> = BloomSet::new(FilterBuilder::new(10_000, 0.01));
+/// assert!(users.insert("alice"));
+/// assert!(!users.insert("alice"));
+/// assert!(users.contains("alice"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct BloomSet<T: BloomKey + ?Sized> {
+    filter: BloomFilter,
+    buf: Vec<u8>,
+    _key: PhantomData<fn(&T)>,
+}
+
+impl<T: BloomKey + ?Sized> BloomSet<T> {
+    /// Creates an empty set from a [FilterBuilder].
+    pub fn new(config: FilterBuilder) -> Self {
+        BloomSet::from_filter(BloomFilter::new(config))
+    }
+
+    /// Wraps a filter whose elements were added with the [BloomKey] encoding of `T`, e.g. a
+    /// filter loaded from bytes written with [BloomSet::filter].
+    pub fn from_filter(filter: BloomFilter) -> Self {
+        BloomSet { filter, buf: Vec::new(), _key: PhantomData }
+    }
+
+    /// Adds `key` to the set, returns `true` if it was not in the set yet (subject to the false
+    /// positive rate, see [BloomFilter::contains_then_add]).
+    pub fn insert(&mut self, key: &T) -> bool {
+        self.buf.clear();
+        key.write_key(&mut self.buf);
+        !self.filter.contains_then_add(&self.buf)
+    }
+
+    /// Tests whether `key` is present in the set (subject to the specified false positive
+    /// rate).
+    pub fn contains(&self, key: &T) -> bool {
+        key.with_key(|bytes| self.filter.contains(bytes))
+    }
+
+    /// Merges `other` into `self`, see [BloomFilter::try_union]. Sets whose keys have a
+    /// different [BloomKey::KEY_TAG] are refused even if their filters are compatible.
+    pub fn union<U: BloomKey + ?Sized>(&mut self, other: &BloomSet<U>)
+                                       -> Result<(), IncompatibleFilterError> {
+        if T::KEY_TAG != U::KEY_TAG {
+            return Err(IncompatibleFilterError::KeyTag { left: T::KEY_TAG, right: U::KEY_TAG });
+        }
+        self.filter.try_union(&other.filter)
+    }
+
+    /// The tag of the keys of this set.
+    pub fn key_tag(&self) -> &'static str {
+        T::KEY_TAG
+    }
+
+    /// Returns the underlying filter, e.g. to serialize it.
+    pub fn filter(&self) -> &BloomFilter {
+        &self.filter
+    }
+
+    /// Consumes the set and returns the underlying filter.
+    pub fn into_filter(self) -> BloomFilter {
+        self.filter
+    }
+}
+
+/// A [BloomSet] is serialized as its [BloomKey::KEY_TAG] and its filter. Deserializing a set
+/// whose tag differs from the one of `T` fails.
+#[cfg(feature = "serde")]
+impl<T: BloomKey + ?Sized> serde::Serialize for BloomSet<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("BloomSet", 2)?;
+        state.serialize_field("key_tag", T::KEY_TAG)?;
+        state.serialize_field("filter", &self.filter)?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: BloomKey + ?Sized> serde::Deserialize<'de> for BloomSet<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        struct Repr {
+            key_tag: String,
+            filter: BloomFilter,
+        }
+        let repr = Repr::deserialize(deserializer)?;
+        if repr.key_tag != T::KEY_TAG {
+            return Err(serde::de::Error::custom(format!(
+                "bloom set key type {} != {}", repr.key_tag, T::KEY_TAG)));
+        }
+        Ok(BloomSet::from_filter(repr.filter))
+    }
+}
+
+#[test]
+fn bloom_set_test() {
+    let mut set: BloomSet<u64> = BloomSet::new(FilterBuilder::new(10_000, 0.01));
+    assert_eq!(set.insert(&7), true);
+    assert_eq!(set.insert(&7), false);
+    assert_eq!(set.contains(&7), true);
+    assert_eq!(set.contains(&8), false);
+    assert_eq!(set.filter().contains_u64(7), true);
+
+    let mut strs: BloomSet<str> = BloomSet::new(FilterBuilder::new(10_000, 0.01));
+    strs.insert("hello");
+    let mut strings: BloomSet<String> = BloomSet::new(FilterBuilder::new(10_000, 0.01));
+    strings.insert(&"world".to_string());
+    assert_eq!(strings.union(&strs), Ok(()));
+    assert_eq!(strings.contains(&"hello".to_string()), true);
+    assert_eq!(strings.filter().contains_str("world"), true);
+
+    fn written<K: BloomKey + ?Sized>(key: &K) -> Vec<u8> {
+        let mut buf = Vec::new();
+        key.write_key(&mut buf);
+        buf
+    }
+    assert_eq!(7u16.with_key(<[u8]>::to_vec), written(&7u16));
+    assert_eq!((-7i8).with_key(<[u8]>::to_vec), written(&-7i8));
+    assert_eq!("key".with_key(<[u8]>::to_vec), written("key"));
+    assert_eq!(vec![1u8, 2].with_key(<[u8]>::to_vec), written(&vec![1u8, 2]));
+    assert_eq!((&"key").with_key(<[u8]>::to_vec), written(&"key"));
+}
+
+#[test]
+fn bloom_set_union_tag_test() {
+    let config = FilterBuilder::new(10_000, 0.01);
+    let mut ints: BloomSet<u64> = BloomSet::new(config.clone());
+    let strs: BloomSet<str> = BloomSet::new(config);
+    assert_eq!(ints.filter().compatible(strs.filter()), true);
+    assert_eq!(ints.union(&strs),
+               Err(IncompatibleFilterError::KeyTag { left: "u64", right: "str" }));
+}
@@ -0,0 +1,79 @@
+use crate::builder::FilterBuilder;
+use crate::error::InvalidSparseBloom;
+
+/// A Bloom filter stored as the sorted positions of its set bits, see
+/// [crate::BloomFilter::to_sparse].
+///
+/// For a lightly filled filter this is much smaller than the bytes of
+/// [crate::BloomFilter::get_u8_array], as it takes 8 bytes per set bit instead of one bit per
+/// bit of the filter.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilter, FilterBuilder, Membership, SparseBloom};
+///
+/// let mut bloom = FilterBuilder::new(1_000_000, 0.01).build_bloom_filter();
+/// bloom.add(b"hello");
+/// let sparse = bloom.to_sparse();
+///
+/// // e.g. send `sparse.config()` and `sparse.indices()` over the network
+/// let received = SparseBloom::new(sparse.config().clone(), sparse.indices().to_vec()).unwrap();
+/// let copy = BloomFilter::from_sparse(received);
+/// assert!(copy.contains(b"hello"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct SparseBloom {
+    config: FilterBuilder,
+    indices: Vec<u64>,
+}
+
+impl SparseBloom {
+    /// Creates a sparse filter from its configuration and the positions of its set bits.
+    ///
+    /// Returns an error if a position is not smaller than the size of the filter or if the
+    /// positions are not strictly ascending.
+    pub fn new(mut config: FilterBuilder, indices: Vec<u64>)
+               -> Result<Self, InvalidSparseBloom> {
+        config.complete();
+        for (position, index) in indices.iter().enumerate() {
+            if *index >= config.size {
+                return Err(InvalidSparseBloom::IndexOutOfRange { index: *index, size: config.size });
+            }
+            if position > 0 && indices[position - 1] >= *index {
+                return Err(InvalidSparseBloom::NotSorted { position });
+            }
+        }
+        Ok(SparseBloom { config, indices })
+    }
+
+    pub(crate) fn from_parts(config: FilterBuilder, indices: Vec<u64>) -> Self {
+        SparseBloom { config, indices }
+    }
+
+    /// Returns the configuration of the filter.
+    pub fn config(&self) -> &FilterBuilder {
+        &self.config
+    }
+
+    /// Returns the positions of the set bits in ascending order.
+    pub fn indices(&self) -> &[u64] {
+        &self.indices
+    }
+
+    pub(crate) fn into_parts(self) -> (FilterBuilder, Vec<u64>) {
+        (self.config, self.indices)
+    }
+}
+
+#[test]
+fn sparse_bloom_new_test() {
+    let config = FilterBuilder::from_size_and_hashes(1000, 4);
+    assert_eq!(SparseBloom::new(config.clone(), vec![1, 5, 999]).is_ok(), true);
+    assert_eq!(SparseBloom::new(config.clone(), vec![1, 1000]).unwrap_err(),
+               InvalidSparseBloom::IndexOutOfRange { index: 1000, size: 1000 });
+    assert_eq!(SparseBloom::new(config.clone(), vec![1, 5, 5]).unwrap_err(),
+               InvalidSparseBloom::NotSorted { position: 2 });
+    assert_eq!(SparseBloom::new(config, vec![5, 1]).unwrap_err(),
+               InvalidSparseBloom::NotSorted { position: 1 });
+}
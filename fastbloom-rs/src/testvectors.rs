@@ -0,0 +1,112 @@
+//! Known-answer vectors pinning the bits every filter of the crate puts a key on, for other
+//! implementations (the Python bindings, a C consumer, ...) to check that they agree with it.
+//!
+//! [indices_for] is the exact index derivation of a [crate::BloomFilter] with the default
+//! hasher, hash version and probe scheme, and [known_answers] are vectors computed with it,
+//! committed as data in `testvectors/indices.txt`. Any change to the hashing fails the tests
+//! asserting against them, unless [FilterBuilder::HASH_VERSION] is bumped and the vectors are
+//! regenerated for the new version.
+//!
+//! ```rust
+//! use fastbloom_rs::testvectors::{indices_for, known_answers};
+//!
+//! for answer in known_answers() {
+//!     assert_eq!(indices_for(&answer.key, answer.m, answer.k, answer.seed), answer.indices);
+//! }
+//! ```
+
+use crate::bloom::bit_indices;
+use crate::FilterBuilder;
+
+/// The vectors as committed, one per line as `key m k seed indices` with the key as hex prefixed
+/// by `0x` and the indices separated by commas. The `hash_version` line names the
+/// [FilterBuilder::HASH_VERSION] they were computed with.
+pub const KNOWN_ANSWERS: &str = include_str!("../testvectors/indices.txt");
+
+/// One known-answer vector: the bit indices, in probe order, of `key` in a filter of `m` bits
+/// with `k` hashes and hash seed `seed`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnownAnswer {
+    pub key: Vec<u8>,
+    pub m: u64,
+    pub k: u32,
+    pub seed: u64,
+    pub indices: Vec<u64>,
+}
+
+/// The bit indices, in probe order, of `key` in a filter of `m` bits with `k` hashes built with
+/// `FilterBuilder::from_size_and_hashes(m, k).with_seed(seed)`, i.e. the result of
+/// [crate::Membership::get_hash_indices] without building the filter.
+///
+/// # Panics
+///
+/// Panics if `m` is zero.
+pub fn indices_for(key: &[u8], m: u64, k: u32, seed: u64) -> Vec<u64> {
+    assert!(m > 0, "bloom filter size must be positive!");
+    let config = FilterBuilder::from_size_and_hashes(m, k).with_seed(seed);
+    bit_indices(key, m, k as u64, config.probe()).collect()
+}
+
+/// The hash version [KNOWN_ANSWERS] were computed with.
+pub fn known_answers_hash_version() -> u8 {
+    KNOWN_ANSWERS.lines()
+        .find_map(|line| line.strip_prefix("hash_version "))
+        .and_then(|version| version.trim().parse().ok())
+        .expect("the known answers name their hash version")
+}
+
+/// Parses [KNOWN_ANSWERS].
+pub fn known_answers() -> Vec<KnownAnswer> {
+    KNOWN_ANSWERS.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#')
+            && !line.starts_with("hash_version"))
+        .map(parse_answer)
+        .collect()
+}
+
+fn parse_answer(line: &str) -> KnownAnswer {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    assert_eq!(fields.len(), 5, "invalid known answer {}", line);
+    let hex = fields[0].strip_prefix("0x").expect("keys are prefixed by 0x");
+    let key = (0..hex.len()).step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("keys are hex"))
+        .collect();
+    KnownAnswer {
+        key,
+        m: fields[1].parse().unwrap(),
+        k: fields[2].parse().unwrap(),
+        seed: fields[3].parse().unwrap(),
+        indices: fields[4].split(',').map(|index| index.parse().unwrap()).collect(),
+    }
+}
+
+#[test]
+fn known_answers_test() {
+    assert_eq!(known_answers_hash_version(), FilterBuilder::HASH_VERSION,
+               "regenerate the known answers for the new hash version");
+    let answers = known_answers();
+    assert_eq!(answers.len(), 105);
+    assert_eq!(answers[2], KnownAnswer {
+        key: vec![],
+        m: 64,
+        k: 3,
+        seed: u64::MAX,
+        indices: vec![29, 42, 55],
+    });
+    for answer in &answers {
+        assert_eq!(indices_for(&answer.key, answer.m, answer.k, answer.seed), answer.indices,
+                   "{:?}", answer);
+    }
+    // the same as the filters
+    for answer in answers.iter().filter(|answer| answer.m <= 10_007) {
+        let mut bloom = FilterBuilder::from_size_and_hashes(answer.m, answer.k)
+            .with_seed(answer.seed).build_bloom_filter();
+        assert_eq!(crate::Membership::get_hash_indices(&bloom, &answer.key), answer.indices);
+        bloom.add(&answer.key);
+        let mut set: Vec<u64> = answer.indices.clone();
+        set.sort();
+        set.dedup();
+        assert_eq!(bloom.iter_set_bits().collect::<Vec<_>>(), set);
+    }
+}
@@ -0,0 +1,136 @@
+//! The sparse encoding of the bits of [crate::BloomFilter::to_bytes] for filters with few bits
+//! set.
+//!
+//! The positions of the set bits, those of [crate::SparseBloom::indices], are written in
+//! increasing order as the gaps between them, each the distance from the bit after the previous
+//! one as an unsigned LEB128 varint: 7 bits per byte, least significant first, with the high bit
+//! set on all but the last byte. Unlike the Rice code of the `compression` feature it takes whole
+//! bytes, and is cheap to decode.
+
+use crate::vec::BloomBitVec;
+
+/// The most bytes of the varint of a `u64`.
+const MAX_VARINT_LEN: usize = 10;
+
+/// Why [read] failed.
+#[derive(Debug, PartialEq)]
+pub(crate) enum VarintError {
+    /// The data ends before the last byte of the varint.
+    Truncated,
+    /// The value doesn't fit into a `u64`.
+    Overflow,
+}
+
+/// Appends `value` as a varint to `out`.
+#[inline]
+pub(crate) fn write(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a varint from the start of `bytes`, advancing it past the varint.
+#[inline]
+pub(crate) fn read(bytes: &mut &[u8]) -> Result<u64, VarintError> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(MAX_VARINT_LEN) {
+        if i == MAX_VARINT_LEN - 1 && byte > 1 {
+            return Err(VarintError::Overflow);
+        }
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            *bytes = &bytes[i + 1..];
+            return Ok(value);
+        }
+    }
+    Err(VarintError::Truncated)
+}
+
+/// Encodes the set bits of the first `size` bits of `bit_vec`.
+pub(crate) fn encode(bit_vec: &BloomBitVec, size: u64) -> Vec<u8> {
+    let words = size.div_ceil(64) as usize;
+    let mut bytes = Vec::new();
+    let mut next = 0;
+    for i in 0..words {
+        let mut word = bit_vec.u64_word(i);
+        while word != 0 {
+            let index = i as u64 * 64 + word.trailing_zeros() as u64;
+            write(index - next, &mut bytes);
+            next = index + 1;
+            word &= word - 1;
+        }
+    }
+    bytes
+}
+
+/// Decodes the bits of a filter of `size` bits as `u64` words. Fails with a description of the
+/// problem if the data is not the encoding of such a filter.
+pub(crate) fn decode(mut data: &[u8], size: u64) -> Result<Vec<u64>, String> {
+    let mut words = vec![0u64; size.div_ceil(64) as usize];
+    let mut next = 0u64;
+    while !data.is_empty() {
+        let gap = read(&mut data).map_err(|e| match e {
+            VarintError::Truncated => "the sparse bits end within an index".to_string(),
+            VarintError::Overflow => "an index of the sparse bits overflows".to_string(),
+        })?;
+        let index = next.checked_add(gap)
+            .filter(|index| *index < size)
+            .ok_or_else(|| format!("a set bit is out of range for {} bits", size))?;
+        words[(index >> 6) as usize] |= 1 << (index & 63);
+        next = index + 1;
+    }
+    Ok(words)
+}
+
+#[test]
+fn varint_test() {
+    for (size, ones) in [(64u64, 0u64), (1000, 1), (1000, 999), (4096, 4096), (100_003, 5000),
+                         (1 << 20, 10)] {
+        let mut bit_vec = BloomBitVec::with_bits(size);
+        let mut state = size ^ ones;
+        let mut set = 0;
+        while set < ones {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let index = ((state >> 32) % size) as usize;
+            if !bit_vec.get(index) {
+                bit_vec.set(index);
+                set += 1;
+            }
+        }
+        let data = encode(&bit_vec, size);
+        let words = size.div_ceil(64) as usize;
+        assert_eq!(decode(&data, size).unwrap(), bit_vec.to_u64_words()[..words], "{}", size);
+    }
+
+    let mut bit_vec = BloomBitVec::with_bits(1000);
+    for i in [3, 4, 500, 999] {
+        bit_vec.set(i);
+    }
+    let data = encode(&bit_vec, 1000);
+    // 3, 0, 495 in two bytes and 498 in two bytes
+    assert_eq!(data, vec![3, 0, 0xef, 0x03, 0xf2, 0x03]);
+    assert_eq!(decode(&data, 999), Err("a set bit is out of range for 999 bits".to_string()));
+    assert_eq!(decode(&data[..data.len() - 1], 1000),
+               Err("the sparse bits end within an index".to_string()));
+    assert_eq!(decode(&[], 1000).unwrap(), vec![0u64; 16]);
+
+    let max = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+    assert_eq!(decode(&max, 1000), Err("a set bit is out of range for 1000 bits".to_string()));
+    let mut long = max;
+    long[9] = 0x02;
+    assert_eq!(decode(&long, 1000), Err("an index of the sparse bits overflows".to_string()));
+
+    for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+        let mut bytes = Vec::new();
+        write(value, &mut bytes);
+        bytes.push(0xaa);
+        let mut rest = &bytes[..];
+        assert_eq!(read(&mut rest), Ok(value));
+        assert_eq!(rest, [0xaa]);
+    }
+    assert_eq!(read(&mut &max[..]), Ok(u64::MAX));
+    assert_eq!(read(&mut &max[..9]), Err(VarintError::Truncated));
+    assert_eq!(read(&mut &[0x80; 11][..]), Err(VarintError::Overflow));
+}
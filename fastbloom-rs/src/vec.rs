@@ -1,6 +1,4 @@
-use core::mem::size_of;
-
-use crate::builder::SUFFIX;
+use crate::builder::{CounterSize, SUFFIX};
 
 #[inline(always)]
 fn get_usize_len() -> usize {
@@ -11,6 +9,7 @@ fn get_usize_len() -> usize {
 #[derive(Debug)]
 #[derive(Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[allow(dead_code)]
 pub(crate) struct BloomBitVec {
     /// Internal representation of the bit vector
     pub(crate) storage: Vec<usize>,
@@ -18,6 +17,7 @@ pub(crate) struct BloomBitVec {
     pub(crate) nbits: u64,
 }
 
+#[allow(dead_code)]
 impl BloomBitVec {
     pub fn new(slots: usize) -> Self {
         BloomBitVec {
@@ -25,6 +25,117 @@ impl BloomBitVec {
             nbits: (slots * get_usize_len()) as u64,
         }
     }
+    /// Creates a bit vector holding `nbits` bits. The storage is rounded up to a multiple of 64
+    /// bits so it can always be viewed as `u64` words.
+    pub fn with_bits(nbits: u64) -> Self {
+        let words = ((nbits + 63) >> 6) as usize;
+        BloomBitVec {
+            storage: vec![0; words * 64 / get_usize_len()],
+            nbits,
+        }
+    }
+
+    /// Creates a bit vector of `nbits` bits from `u64` words where bit `i` is bit `i % 64` of
+    /// word `i / 64`. The bits of the words past `nbits` are ignored.
+    pub fn from_u64_words(words: &[u64], nbits: u64) -> Self {
+        let mut vec = BloomBitVec::with_bits(nbits);
+        for (i, word) in words.iter().take(vec.u64_len()).enumerate() {
+            #[cfg(target_pointer_width = "64")]
+            {
+                vec.storage[i] = *word as usize;
+            }
+            #[cfg(target_pointer_width = "32")]
+            {
+                vec.storage[2 * i] = *word as u32 as usize;
+                vec.storage[2 * i + 1] = (*word >> 32) as usize;
+            }
+        }
+        vec.clear_padding();
+        vec
+    }
+
+    /// Same as [BloomBitVec::from_u64_words] but takes ownership of the words, reusing their
+    /// allocation on 64-bit platforms.
+    pub fn from_u64_vec(words: Vec<u64>, nbits: u64) -> Self {
+        #[cfg(target_pointer_width = "64")]
+        {
+            let mut words = std::mem::ManuallyDrop::new(words);
+            // u64 and usize have the same size and alignment on 64-bit platforms.
+            let mut storage = unsafe {
+                Vec::from_raw_parts(words.as_mut_ptr() as *mut usize, words.len(),
+                                    words.capacity())
+            };
+            storage.resize(((nbits + 63) >> 6) as usize, 0);
+            let mut vec = BloomBitVec { storage, nbits };
+            vec.clear_padding();
+            vec
+        }
+        #[cfg(target_pointer_width = "32")]
+        BloomBitVec::from_u64_words(&words, nbits)
+    }
+
+    /// Clears the bits of the storage past `nbits`, which raw words or bytes may have set, so
+    /// they aren't counted by [BloomBitVec::count_ones].
+    pub fn clear_padding(&mut self) {
+        let usize_len = get_usize_len() as u64;
+        let full = (self.nbits / usize_len) as usize;
+        if full < self.storage.len() {
+            self.storage[full] &= (1 << (self.nbits % usize_len)) - 1;
+            self.storage[full + 1..].fill(0);
+        }
+    }
+
+    /// Moves the storage out as `u64` words, reusing the allocation on 64-bit platforms.
+    pub fn into_u64_vec(self) -> Vec<u64> {
+        #[cfg(target_pointer_width = "64")]
+        {
+            let mut storage = std::mem::ManuallyDrop::new(self.storage);
+            unsafe {
+                Vec::from_raw_parts(storage.as_mut_ptr() as *mut u64, storage.len(),
+                                    storage.capacity())
+            }
+        }
+        #[cfg(target_pointer_width = "32")]
+        self.to_u64_words()
+    }
+
+    /// Returns the storage as `u64` words, see [BloomBitVec::from_u64_words] for the layout.
+    pub fn to_u64_words(&self) -> Vec<u64> {
+        #[cfg(target_pointer_width = "64")]
+        return self.storage.iter().map(|w| *w as u64).collect();
+        #[cfg(target_pointer_width = "32")]
+        return self.storage.chunks(2).map(|w| w[0] as u64 | ((w[1] as u64) << 32)).collect();
+    }
+
+    /// Returns the `u64` word `index`, see [BloomBitVec::from_u64_words] for the layout.
+    #[inline]
+    pub fn u64_word(&self, index: usize) -> u64 {
+        #[cfg(target_pointer_width = "64")]
+        return self.storage[index] as u64;
+        #[cfg(target_pointer_width = "32")]
+        return self.storage[2 * index] as u64 | ((self.storage[2 * index + 1] as u64) << 32);
+    }
+
+    /// ORs `word` into the `u64` word `index`.
+    #[inline]
+    pub fn or_u64_word(&mut self, index: usize, word: u64) {
+        #[cfg(target_pointer_width = "64")]
+        {
+            self.storage[index] |= word as usize;
+        }
+        #[cfg(target_pointer_width = "32")]
+        {
+            self.storage[2 * index] |= word as u32 as usize;
+            self.storage[2 * index + 1] |= (word >> 32) as usize;
+        }
+    }
+
+    /// Number of `u64` words of the storage.
+    #[inline]
+    pub fn u64_len(&self) -> usize {
+        self.storage.len() * get_usize_len() / 64
+    }
+
     pub fn from_elem(slots: usize, bit: bool) -> Self {
         BloomBitVec {
             storage: vec![if bit { !0 } else { 0 }; slots],
@@ -40,7 +151,7 @@ impl BloomBitVec {
             let w = index >> 5;
         let b = index & SUFFIX;
         let flag = 1usize << b;
-        self.storage[w] = self.storage[w] | flag;
+        self.storage[w] |= flag;
     }
 
     #[inline]
@@ -60,6 +171,15 @@ impl BloomBitVec {
         }
     }
 
+    /// ORs all `others` into `self`, visiting each word of `self` only once.
+    pub fn or_all(&mut self, others: &[&BloomBitVec]) {
+        for (i, m) in self.storage.iter_mut().enumerate() {
+            for o in others {
+                *m |= o.storage[i];
+            }
+        }
+    }
+
     pub fn xor(&mut self, other: &BloomBitVec) {
         for (m, o) in self.storage.iter_mut().zip(&other.storage) {
             *m ^= *o;
@@ -97,18 +217,73 @@ impl BloomBitVec {
     }
 
 
+    /// Returns `true` if every bit set in `self` is also set in `other`.
+    pub fn is_subset(&self, other: &Self) -> bool {
+        self.storage.iter().zip(&other.storage).all(|(m, o)| *m & !*o == 0)
+    }
+
     pub fn clear(&mut self) {
         self.storage.fill(0);
     }
 
+    /// Counts the set bits word by word.
+    pub fn count_ones(&self) -> u64 {
+        self.storage.iter().map(|w| w.count_ones() as u64).sum()
+    }
+
+    /// Counts the set bits of `self | other` word by word without building it.
+    pub fn count_ones_or(&self, other: &Self) -> u64 {
+        self.storage.iter().zip(&other.storage).map(|(m, o)| (*m | *o).count_ones() as u64).sum()
+    }
+
+    /// Returns a bit vector of `nbits` bits where bit `i % nbits` is set for every bit `i` set in
+    /// `self`. `nbits` must divide the size of `self`. When `nbits` is a multiple of the word
+    /// size whole words are ORed together.
+    pub fn fold(&self, nbits: u64) -> BloomBitVec {
+        let mut res = BloomBitVec::with_bits(nbits);
+        if nbits.is_multiple_of(get_usize_len() as u64) {
+            let words = nbits as usize / get_usize_len();
+            let used = (self.nbits as usize).div_ceil(get_usize_len());
+            for chunk in self.storage[..used].chunks(words) {
+                for (m, o) in res.storage.iter_mut().zip(chunk) {
+                    *m |= *o;
+                }
+            }
+        } else {
+            for index in self.iter_ones() {
+                res.set((index % nbits) as usize);
+            }
+        }
+        res
+    }
+
+    /// Iterates the indices of the set bits in ascending order, skipping over zero words and
+    /// finding the set bits of a word with `trailing_zeros`.
+    pub fn iter_ones(&self) -> impl Iterator<Item=u64> + '_ {
+        let usize_len = get_usize_len();
+        self.storage.iter().enumerate().flat_map(move |(i, word)| {
+            let base = (i * usize_len) as u64;
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 { return None; }
+                let bit = word.trailing_zeros() as u64;
+                word &= word - 1;
+                Some(base + bit)
+            })
+        })
+    }
+
     pub fn is_empty(&self) -> bool {
         self.storage.is_empty()
     }
 }
 
 /// counter vector for counting bloom filter.
+///
+/// The counters are `bits` wide, the first one of every slot in its most significant bits.
 #[derive(Debug)]
 #[derive(Clone)]
+#[allow(dead_code)]
 pub(crate) struct CountingVec {
     /// Internal representation of the vector
     pub(crate) storage: Vec<usize>,
@@ -116,84 +291,94 @@ pub(crate) struct CountingVec {
     pub(crate) counters: u64,
     /// The number of valid counter in a slot which mean usize.
     pub(crate) counter_per_slot: usize,
+    /// The width of a counter in bits.
+    pub(crate) bits: usize,
+    /// The increments of counters at their maximum, which were lost.
+    pub(crate) overflows: u64,
 }
 
 impl CountingVec {
     /// create a CountingVec
-    pub fn new(slots: usize) -> Self {
-        let counter_per_slot = get_usize_len() >> 2;
+    pub fn new(slots: usize, counter_size: CounterSize) -> Self {
+        let bits = counter_size.bits() as usize;
+        let counter_per_slot = get_usize_len() / bits;
         CountingVec {
             storage: vec![0; slots],
             counters: (slots * counter_per_slot) as u64,
             counter_per_slot,
+            bits,
+            overflows: 0,
         }
     }
 
+    /// The slots of `counters` counters.
+    pub fn slots(counters: u64, counter_size: CounterSize) -> usize {
+        counters.div_ceil((get_usize_len() / counter_size.bits() as usize) as u64) as usize
+    }
+
+    /// The largest count of a counter.
+    #[inline]
+    pub fn max(&self) -> usize {
+        (1 << self.bits) - 1
+    }
+
+    /// The slot of counter `index` and the shift of the counter in it.
+    #[inline]
+    fn position(&self, index: usize) -> (usize, usize) {
+        let b = index % self.counter_per_slot;
+        (index / self.counter_per_slot, (self.counter_per_slot - 1 - b) * self.bits)
+    }
+
+    /// Sets counter `index` to `count`, which must be at most [CountingVec::max].
+    #[inline]
+    pub fn set(&mut self, index: usize, count: usize) {
+        let (w, move_bits) = self.position(index);
+        self.storage[w] = (self.storage[w] & !(self.max() << move_bits)) | (count << move_bits)
+    }
+
+    /// Increments counter `index`, unless it is at its maximum: then it stays there and the
+    /// increment is counted as an overflow.
     #[inline]
     pub fn increment(&mut self, index: usize) {
         let current = self.get(index);
-        #[cfg(target_pointer_width = "64")]
-        if current != 0b1111 {
-            let current = current + 1;
-            let w = index >> 4;
-            let b = index & 0b1111;
-            let move_bits = (15 - b) * 4;
-            self.storage[w] =
-                (self.storage[w] & !(0b1111 << move_bits)) | (current << move_bits)
-        }
-
-        #[cfg(target_pointer_width = "32")]
-        if current != 0b111 {
-            let current = current + 1;
-            let w = index >> 3;
-            let b = index & 0b111;
-            let move_bits = (7 - b) * 4;
-            self.storage[w] =
-                (self.storage[w] & !(0b1111 << move_bits)) | (current << move_bits)
+        if current != self.max() {
+            self.set(index, current + 1);
+        } else {
+            self.overflows += 1;
         }
     }
 
+    /// Decrements counter `index`, unless it is zero or at its maximum, where it is stuck as its
+    /// increments may have been lost.
     #[inline]
     pub fn decrement(&mut self, index: usize) {
         let current = self.get(index);
-        if current > 0 {
-            if cfg!(target_pointer_width="64") {
-                let current = current - 1;
-                let w = index >> 4;
-                let b = index & 0b1111;
-                let move_bits = (15 - b) * 4;
-                self.storage[w] =
-                    (self.storage[w] & !(0b1111 << move_bits)) | (current << move_bits)
-            } else if cfg!(target_pointer_width="32") {
-                let current = current - 1;
-                let w = index >> 3;
-                let b = index & 0b111;
-                let move_bits = (7 - b) * 4;
-                self.storage[w] =
-                    (self.storage[w] & !(0b1111 << move_bits)) | (current << move_bits)
-            }
+        if current > 0 && current != self.max() {
+            self.set(index, current - 1);
         }
     }
 
     #[inline]
     pub fn get(&self, index: usize) -> usize {
-        #[cfg(target_pointer_width = "64")]
-            let w = index >> 4;
-        #[cfg(target_pointer_width = "64")]
-            let b = index & 0b1111;
-        #[cfg(target_pointer_width = "32")]
-            let w = index >> 3;
-        #[cfg(target_pointer_width = "32")]
-            let b = index & 0b111;
-        let slot = self.storage[w];
-        #[cfg(target_pointer_width = "64")]
-        return (slot >> ((15 - b) * 4)) & 0b1111;
-        #[cfg(target_pointer_width = "32")]
-        return (slot >> ((7 - b) * 4)) & 0b111;
+        let (w, move_bits) = self.position(index);
+        (self.storage[w] >> move_bits) & self.max()
     }
 
     pub fn clear(&mut self) {
         self.storage.fill(0);
+        self.overflows = 0;
+    }
+
+    /// Counts the counters which are not zero, word by word.
+    pub fn count_nonzero(&self) -> u64 {
+        // the lowest bit of every counter
+        let low_bits = usize::MAX / self.max();
+        self.storage.iter()
+            .map(|&w| {
+                let folded = (1..self.bits).fold(w, |folded, shift| folded | w >> shift);
+                (folded & low_bits).count_ones() as u64
+            })
+            .sum()
     }
 }
 
@@ -218,8 +403,56 @@ fn test_size() {
 
 #[test]
 fn test_count_vec() {
-    let mut vec = CountingVec::new(10);
+    let mut vec = CountingVec::new(10, CounterSize::U4);
+    vec.increment(7);
+
+    assert_eq!(1, vec.get(7));
     vec.increment(7);
+    vec.increment(8);
+    vec.increment(20);
+    assert_eq!(vec.count_nonzero(), 3);
+}
+#[test]
+fn test_u64_words() {
+    let mut vec = BloomBitVec::with_bits(100);
+    assert_eq!(vec.u64_len(), 2);
+    vec.set(3);
+    vec.set(64);
+    vec.set(99);
+    let words = vec.to_u64_words();
+    assert_eq!(words, vec![1 << 3, 1 | (1 << 35)]);
+    let copy = BloomBitVec::from_u64_words(&words, 100);
+    assert_eq!(copy.storage, vec.storage);
+    assert_eq!(copy.nbits, 100);
+    for nbits in [100, 128, 64, 1] {
+        let words = vec![!0u64; 3];
+        for vec in [BloomBitVec::from_u64_words(&words, nbits),
+                    BloomBitVec::from_u64_vec(words.clone(), nbits)] {
+            assert_eq!(vec.count_ones(), nbits);
+            assert_eq!(vec.u64_len() as u64, nbits.div_ceil(64));
+        }
+    }
+}
 
-    assert_eq!(1, vec.get(7))
-}
\ No newline at end of file
+#[test]
+fn test_count_vec_sizes() {
+    for counter_size in [CounterSize::U4, CounterSize::U8, CounterSize::U16] {
+        let slots = CountingVec::slots(100, counter_size);
+        let mut vec = CountingVec::new(slots, counter_size);
+        assert_eq!(vec.counters >= 100, true);
+        for _ in 0..counter_size.max() + 5 {
+            vec.increment(37);
+        }
+        vec.increment(38);
+        vec.increment(36);
+        vec.decrement(36);
+        assert_eq!(vec.get(37) as u64, counter_size.max());
+        assert_eq!(vec.overflows, 5);
+        vec.decrement(37);
+        assert_eq!(vec.get(37) as u64, counter_size.max());
+        assert_eq!((vec.get(36), vec.get(38), vec.get(39)), (0, 1, 0));
+        assert_eq!(vec.count_nonzero(), 2);
+    }
+    assert_eq!(CountingVec::slots(160, CounterSize::U4) * 2,
+               CountingVec::slots(160, CounterSize::U8));
+}
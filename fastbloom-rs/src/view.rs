@@ -0,0 +1,219 @@
+use std::mem::size_of;
+
+use crate::bloom::{bit_check_with, Probe};
+use crate::builder::FilterBuilder;
+use crate::error::IncompatibleFilterError;
+use crate::hasher::{HashAlgorithm, ProbeScheme};
+use crate::Hashes;
+
+/// A read-only Bloom filter borrowing its bits, e.g. straight from a network buffer, without
+/// copying them into a [crate::BloomFilter].
+///
+/// The bytes have the layout of [crate::BloomFilter::get_u8_array] and the same hashing is used,
+/// so `contains` answers exactly like the owned filter the bytes were taken from.
+///
+/// # Examples
+///
+/// ```rust
+/// use fastbloom_rs::{BloomFilterRef, FilterBuilder, Hashes, Membership};
+///
+/// let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+/// bloom.add(b"hello");
+/// let bytes = bloom.get_u8_array().to_vec();
+///
+/// let view = BloomFilterRef::new(&bytes, bloom.config().size, bloom.hashes());
+/// assert!(view.contains(b"hello"));
+/// assert!(!view.contains(b"world"));
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct BloomFilterRef<'a> {
+    bytes: &'a [u8],
+    size: u64,
+    hashes: u32,
+    seed: u64,
+    hasher: &'a HashAlgorithm,
+    hash_version: u8,
+    probe_scheme: ProbeScheme,
+}
+
+/// The hasher of views created with [BloomFilterRef::new].
+static DEFAULT_HASHER: HashAlgorithm = HashAlgorithm::Xxh128;
+
+impl<'a> BloomFilterRef<'a> {
+    /// Creates a view of a filter of `size_bits` bits using `hashes` hash functions over `bytes`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size_bits` is zero or `bytes` is too short for `size_bits` bits.
+    pub fn new(bytes: &'a [u8], size_bits: u64, hashes: u32) -> Self {
+        assert!(size_bits > 0, "bloom filter size must be positive!");
+        assert!(bytes.len() as u64 * 8 >= size_bits,
+                "u8 array with len {} is too short for {} bits!", bytes.len(), size_bits);
+        BloomFilterRef {
+            bytes,
+            size: size_bits,
+            hashes,
+            seed: 0,
+            hasher: &DEFAULT_HASHER,
+            hash_version: FilterBuilder::HASH_VERSION,
+            probe_scheme: ProbeScheme::Double,
+        }
+    }
+
+    /// Sets the hash seed the bytes were written with, see [crate::FilterBuilder::with_seed].
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the hasher the bytes were written with, see [crate::FilterBuilder::with_hasher].
+    pub fn with_hasher(mut self, hasher: &'a HashAlgorithm) -> Self {
+        self.hasher = hasher;
+        self
+    }
+
+    /// Sets the version of the hash scheme the bytes were written with, see
+    /// [FilterBuilder::with_hash_version].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `version` is 0 or larger than [FilterBuilder::HASH_VERSION].
+    pub fn with_hash_version(mut self, version: u8) -> Self {
+        assert!((1..=FilterBuilder::HASH_VERSION).contains(&version),
+                "unknown hash version {}!", version);
+        self.hash_version = version;
+        self
+    }
+
+    /// Sets the probe scheme the bytes were written with, see
+    /// [FilterBuilder::with_probe_scheme].
+    pub fn with_probe_scheme(mut self, scheme: ProbeScheme) -> Self {
+        self.probe_scheme = scheme;
+        self
+    }
+
+    /// The hash seed of the filter.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The size of the filter in bits.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// The borrowed bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Returns the bit at `index`. The bytes are native endian machine words, so on big endian
+    /// platforms the byte order inside every word is reversed.
+    #[inline]
+    fn get(&self, index: usize) -> bool {
+        #[cfg(target_endian = "little")]
+            let byte = index >> 3;
+        #[cfg(target_endian = "big")]
+            let byte = (index >> 3) ^ (size_of::<usize>() - 1);
+        self.bytes[byte] & (1 << (index & 7)) != 0
+    }
+
+    /// Tests whether an element is present in the filter (subject to the specified false
+    /// positive rate).
+    #[inline]
+    pub fn contains<T: AsRef<[u8]>>(&self, element: T) -> bool {
+        let probe = Probe {
+            hasher: self.hasher,
+            seed: self.seed,
+            version: self.hash_version,
+            scheme: self.probe_scheme,
+        };
+        bit_check_with(|index| self.get(index), element.as_ref(), self.size, self.hashes as u64,
+                       probe)
+    }
+
+    /// Returns the number of bits set to one in the borrowed bytes.
+    pub fn count_ones(&self) -> u64 {
+        let chunks = self.bytes.chunks_exact(size_of::<u64>());
+        let rest: u64 = chunks.remainder().iter().map(|b| b.count_ones() as u64).sum();
+        chunks.map(|w| u64::from_ne_bytes(w.try_into().unwrap()).count_ones() as u64).sum::<u64>()
+            + rest
+    }
+
+    /// Checks if two filters are compatible, i.e. have the same size, number of hashes, seed,
+    /// hasher, hash version and probe scheme.
+    /// Use [crate::BloomFilter::as_filter_ref] to compare with an owned filter.
+    pub fn compatible(&self, other: &BloomFilterRef<'_>) -> bool {
+        self.check_compatible(other).is_ok()
+    }
+
+    /// Same as [BloomFilterRef::compatible] but tells which parameter differs.
+    pub fn check_compatible(&self, other: &BloomFilterRef<'_>)
+                            -> Result<(), IncompatibleFilterError> {
+        if self.size != other.size {
+            return Err(IncompatibleFilterError::Size { left: self.size, right: other.size });
+        }
+        if self.hashes != other.hashes {
+            return Err(IncompatibleFilterError::Hashes { left: self.hashes, right: other.hashes });
+        }
+        if self.seed != other.seed {
+            return Err(IncompatibleFilterError::Seed { left: self.seed, right: other.seed });
+        }
+        if self.hasher != other.hasher {
+            return Err(IncompatibleFilterError::Hasher {
+                left: self.hasher.name().to_string(),
+                right: other.hasher.name().to_string(),
+            });
+        }
+        if self.hash_version != other.hash_version {
+            return Err(IncompatibleFilterError::HashVersion {
+                left: self.hash_version,
+                right: other.hash_version,
+            });
+        }
+        if self.probe_scheme != other.probe_scheme {
+            return Err(IncompatibleFilterError::ProbeScheme {
+                left: self.probe_scheme,
+                right: other.probe_scheme,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Hashes for BloomFilterRef<'_> {
+    ///  Returns the hash function number of the Bloom filter.
+    fn hashes(&self) -> u32 {
+        self.hashes
+    }
+}
+
+#[test]
+fn bloom_filter_ref_test() {
+    let mut bloom = FilterBuilder::new(10_000, 0.01).build_bloom_filter();
+    for i in 0..1000u32 {
+        bloom.add(i.to_le_bytes());
+    }
+    let bytes = bloom.get_u8_array().to_vec();
+    let view = BloomFilterRef::new(&bytes, bloom.config().size, bloom.hashes());
+
+    for i in 0..5000u32 {
+        assert_eq!(view.contains(i.to_le_bytes()), bloom.contains(i.to_le_bytes()));
+    }
+    assert_eq!(view.count_ones(), bloom.count_ones());
+    assert_eq!(view.compatible(&bloom.as_filter_ref()), true);
+
+    let other = FilterBuilder::from_size_and_hashes(bloom.config().size, 3).build_bloom_filter();
+    assert_eq!(view.check_compatible(&other.as_filter_ref()),
+               Err(IncompatibleFilterError::Hashes { left: bloom.hashes(), right: 3 }));
+
+    let mut seeded = FilterBuilder::new(10_000, 0.01).with_seed(7).build_bloom_filter();
+    seeded.add(b"hello");
+    let bytes = seeded.get_u8_array().to_vec();
+    let view = BloomFilterRef::new(&bytes, seeded.config().size, seeded.hashes());
+    assert_eq!(view.check_compatible(&seeded.as_filter_ref()),
+               Err(IncompatibleFilterError::Seed { left: 0, right: 7 }));
+    let view = view.with_seed(7);
+    assert_eq!(view.contains(b"hello"), true);
+    assert_eq!(view.compatible(&seeded.as_filter_ref()), true);
+}
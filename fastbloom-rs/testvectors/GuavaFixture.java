@@ -0,0 +1,73 @@
+import java.io.*;
+import java.nio.*;
+import java.nio.charset.StandardCharsets;
+
+// Writes testvectors/guava_mitz64.bin and prints the false positives listed in
+// testvectors/guava_mitz64.txt:
+//
+//   javac GuavaFixture.java && java GuavaFixture guava_mitz64.bin > guava_mitz64.txt
+//
+// The filter, its sizing and its format are ported from Guava (Murmur3_128HashFunction,
+// BloomFilterStrategies.MURMUR128_MITZ_64, BloomFilter.create and BloomFilter.writeTo) so the
+// fixture can be built without Guava. It stands for
+// BloomFilter.create(Funnels.stringFunnel(UTF_8), 1000, 0.01) with the same keys put, but the
+// bytes have not been compared with those of Guava itself.
+public class GuavaFixture {
+    static final long C1 = 0x87c37b91114253d5L, C2 = 0x4cf5ad432745937fL;
+    static long fmix64(long k) { k ^= k >>> 33; k *= 0xff51afd7ed558ccdL; k ^= k >>> 33; k *= 0xc4ceb9fe1a85ec53L; k ^= k >>> 33; return k; }
+    static long mixK1(long k1) { k1 *= C1; k1 = Long.rotateLeft(k1, 31); k1 *= C2; return k1; }
+    static long mixK2(long k2) { k2 *= C2; k2 = Long.rotateLeft(k2, 33); k2 *= C1; return k2; }
+    static long[] murmur3(byte[] data) {
+        long h1 = 0, h2 = 0; int len = data.length;
+        ByteBuffer bb = ByteBuffer.wrap(data).order(ByteOrder.LITTLE_ENDIAN);
+        int i = 0;
+        for (; i + 16 <= len; i += 16) {
+            long k1 = bb.getLong(i), k2 = bb.getLong(i + 8);
+            h1 ^= mixK1(k1); h1 = Long.rotateLeft(h1, 27); h1 += h2; h1 = h1 * 5 + 0x52dce729;
+            h2 ^= mixK2(k2); h2 = Long.rotateLeft(h2, 31); h2 += h1; h2 = h2 * 5 + 0x38495ab5;
+        }
+        int rem = len - i;
+        if (rem > 0) {
+            long k1 = 0, k2 = 0;
+            for (int j = rem - 1; j >= 8; j--) k2 ^= (long) (data[i + j] & 0xff) << ((j - 8) * 8);
+            for (int j = Math.min(rem, 8) - 1; j >= 0; j--) k1 ^= (long) (data[i + j] & 0xff) << (j * 8);
+            h1 ^= mixK1(k1); h2 ^= mixK2(k2);
+        }
+        h1 ^= len; h2 ^= len; h1 += h2; h2 += h1; h1 = fmix64(h1); h2 = fmix64(h2); h1 += h2; h2 += h1;
+        return new long[] {h1, h2};
+    }
+    long[] data; int k; long bitSize;
+    GuavaFixture(long n, double p) {
+        if (n == 0) n = 1;
+        long numBits = (long) (-n * Math.log(p) / (Math.log(2) * Math.log(2)));
+        k = Math.max(1, (int) Math.round((double) numBits / n * Math.log(2)));
+        data = new long[(int) ((numBits + 63) / 64)];
+        bitSize = data.length * 64L;
+    }
+    boolean put(byte[] key) {
+        long[] h = murmur3(key); long combined = h[0]; boolean changed = false;
+        for (int i = 0; i < k; i++) { long idx = (combined & Long.MAX_VALUE) % bitSize; changed |= (data[(int) (idx >>> 6)] & (1L << idx)) == 0; data[(int) (idx >>> 6)] |= 1L << idx; combined += h[1]; }
+        return changed;
+    }
+    boolean mightContain(byte[] key) {
+        long[] h = murmur3(key); long combined = h[0];
+        for (int i = 0; i < k; i++) { long idx = (combined & Long.MAX_VALUE) % bitSize; if ((data[(int) (idx >>> 6)] & (1L << idx)) == 0) return false; combined += h[1]; }
+        return true;
+    }
+    void writeTo(OutputStream out) throws IOException {
+        DataOutputStream d = new DataOutputStream(out);
+        d.writeByte(1); d.writeByte(k); d.writeInt(data.length);
+        for (long w : data) d.writeLong(w);
+        d.flush();
+    }
+    static byte[] utf8(String s) { return s.getBytes(StandardCharsets.UTF_8); }
+    public static void main(String[] args) throws IOException {
+        // the reference vectors of MurmurHash3_x64_128, to check the port
+        long[] h = murmur3(utf8("The quick brown fox jumps over the lazy dog"));
+        if (h[0] != 0xe34bbc7bbc071b6cL || h[1] != 0x7a433ca9c49a9347L) throw new AssertionError();
+        GuavaFixture f = new GuavaFixture(1000, 0.01);
+        for (int i = 0; i < 1000; i++) f.put(utf8("key-" + i));
+        try (FileOutputStream out = new FileOutputStream(args[0])) { f.writeTo(out); }
+        for (int i = 0; i < 10000; i++) if (f.mightContain(utf8("other-" + i))) System.out.println("other-" + i);
+    }
+}
@@ -0,0 +1,121 @@
+// Prints the BF.SCANDUMP chunks of testvectors/redisbloom_scandump.txt and the keys listed in
+// testvectors/redisbloom_exists.txt:
+//
+//   cc -O2 -o fixture RedisBloomFixture.c -lm && ./fixture dump > redisbloom_scandump.txt
+//   ./fixture exists > redisbloom_exists.txt
+//
+// The filter, its sizing and the dump are ported from RedisBloom (MurmurHash64A_Bloom,
+// bloom_init, bloom_calc_hash64, bloom_check_add64 and the dumped chain header of sb.c) so the
+// fixture can be built without Redis. It stands for `BF.RESERVE f 0.01 1000` with key-0 ..
+// key-999 added and `BF.SCANDUMP f <iter>`, but the chunks have not been compared with those
+// of a Redis server.
+#include <math.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#define BLOOM_OPT_NOROUND 1
+#define BLOOM_OPT_FORCE64 4
+
+static uint64_t murmur64a(const void *key, int len, uint64_t seed) {
+    const uint64_t m = 0xc6a4a7935bd1e995ULL;
+    const int r = 47;
+    uint64_t h = seed ^ (len * m);
+    const unsigned char *data = key;
+    const unsigned char *end = data + (len / 8) * 8;
+    while (data != end) {
+        uint64_t k;
+        memcpy(&k, data, 8);
+        data += 8;
+        k *= m; k ^= k >> r; k *= m;
+        h ^= k; h *= m;
+    }
+    switch (len & 7) {
+    case 7: h ^= (uint64_t)data[6] << 48;
+    case 6: h ^= (uint64_t)data[5] << 40;
+    case 5: h ^= (uint64_t)data[4] << 32;
+    case 4: h ^= (uint64_t)data[3] << 24;
+    case 3: h ^= (uint64_t)data[2] << 16;
+    case 2: h ^= (uint64_t)data[1] << 8;
+    case 1: h ^= (uint64_t)data[0]; h *= m;
+    }
+    h ^= h >> r; h *= m; h ^= h >> r;
+    return h;
+}
+
+struct bloom {
+    uint64_t entries, bits, bytes, size;
+    uint32_t hashes;
+    double error, bpe;
+    unsigned char *bf;
+};
+
+static void bloom_init(struct bloom *b, uint64_t entries, double error) {
+    b->error = error;
+    b->entries = entries;
+    b->bpe = -(log(error) / 0.480453013918201);
+    uint64_t bits = (uint64_t)(entries * b->bpe);
+    if (bits % 64) b->bytes = ((bits / 64) + 1) * 8; else b->bytes = bits / 8;
+    b->bits = b->bytes * 8;
+    b->hashes = (uint32_t)ceil(0.693147180559945 * b->bpe);
+    b->size = 0;
+    b->bf = calloc(b->bytes, 1);
+}
+
+static int bloom_check_add(struct bloom *b, const char *key, int add) {
+    int len = strlen(key);
+    uint64_t a = murmur64a(key, len, 0xc6a4a7935bd1e995ULL), c = murmur64a(key, len, a);
+    int found_unset = 0;
+    for (uint64_t i = 0; i < b->hashes; i++) {
+        uint64_t x = (a + i * c) % b->bits;
+        if (!(b->bf[x >> 3] & (1 << (x % 8)))) {
+            if (!add) return 0;
+            found_unset = 1;
+            b->bf[x >> 3] |= 1 << (x % 8);
+        }
+    }
+    if (add && found_unset) b->size++;
+    return add ? found_unset : 1;
+}
+
+static void put(unsigned char **p, const void *v, size_t n) { memcpy(*p, v, n); *p += n; }
+
+static void hex(const unsigned char *data, size_t len) {
+    for (size_t i = 0; i < len; i++) printf("%02x", data[i]);
+    printf("\n");
+}
+
+int main(int argc, char **argv) {
+    // the SMHasher verification value of MurmurHash64A, to check the port
+    uint8_t bytes[256], hashes[256 * 8];
+    for (int i = 0; i < 256; i++) {
+        bytes[i] = (uint8_t)i;
+        uint64_t h = murmur64a(bytes, i, 256 - i);
+        memcpy(hashes + i * 8, &h, 8);
+    }
+    if ((uint32_t)murmur64a(hashes, sizeof hashes, 0) != 0x1f0d3804) abort();
+    struct bloom b;
+    bloom_init(&b, 1000, 0.01);
+    char key[32];
+    for (int i = 0; i < 1000; i++) { sprintf(key, "key-%d", i); bloom_check_add(&b, key, 1); }
+    if (argc > 1 && strcmp(argv[1], "exists") == 0) {
+        for (int i = 0; i < 10000; i++) {
+            sprintf(key, "other-%d", i);
+            if (bloom_check_add(&b, key, 0)) printf("%s\n", key);
+        }
+        return 0;
+    }
+    // the packed dumpedChainHeader with one dumpedChainLink, little endian
+    unsigned char header[20 + 53], *p = header;
+    uint32_t nfilters = 1, options = BLOOM_OPT_NOROUND | BLOOM_OPT_FORCE64, growth = 2;
+    uint8_t n2 = 0;
+    put(&p, &b.size, 8); put(&p, &nfilters, 4); put(&p, &options, 4); put(&p, &growth, 4);
+    put(&p, &b.bytes, 8); put(&p, &b.bits, 8); put(&p, &b.size, 8); put(&p, &b.error, 8);
+    put(&p, &b.bpe, 8); put(&p, &b.hashes, 4); put(&p, &b.entries, 8); put(&p, &n2, 1);
+    printf("1 "); hex(header, sizeof header);
+    // SBChain_GetEncodedChunk: the iterator after a chunk is 1 + its end offset
+    printf("%llu ", (unsigned long long)(1 + b.bytes)); hex(b.bf, b.bytes);
+    printf("0 \n");
+    return 0;
+}
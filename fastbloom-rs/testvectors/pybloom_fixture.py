@@ -0,0 +1,125 @@
+"""Writes pybloom_live_1000.bloom and pybloom_live_1000.txt for the pybloom_live tests.
+
+A port of `BloomFilter.__init__`, `make_hashfuncs`, `add` and `tofile` of pybloom_live 4.0.0
+(pybloom_live/pybloom.py) on top of hashlib, with the little endian `bitarray` replaced by a
+bytearray holding bit i in bit i % 8 of byte i // 8. Run it with python 3 from this directory:
+
+    python3 pybloom_fixture.py
+
+The filter is `BloomFilter(capacity=1000, error_rate=0.01)` with the keys key-0 .. key-999
+added, the list holds the keys of other-0 .. other-9999 it reports present.
+
+The port has not been checked against pybloom_live itself, so the fixture only pins down the
+behaviour of the port.
+"""
+import hashlib
+import math
+from struct import calcsize, pack, unpack
+
+FILE_FMT = b'<dQQQQ'
+
+
+def make_hashfuncs(num_slices, num_bits):
+    if num_bits >= (1 << 31):
+        fmt_code, chunk_size = 'Q', 8
+    elif num_bits >= (1 << 15):
+        fmt_code, chunk_size = 'I', 4
+    else:
+        fmt_code, chunk_size = 'H', 2
+    total_hash_bits = 8 * num_slices * chunk_size
+    if total_hash_bits > 384:
+        hashfn = hashlib.sha512
+    elif total_hash_bits > 256:
+        hashfn = hashlib.sha384
+    elif total_hash_bits > 160:
+        hashfn = hashlib.sha256
+    elif total_hash_bits > 128:
+        hashfn = hashlib.sha1
+    else:
+        hashfn = hashlib.md5
+
+    fmt = fmt_code * (hashfn().digest_size // chunk_size)
+    num_salts, extra = divmod(num_slices, len(fmt))
+    if extra:
+        num_salts += 1
+    salts = tuple(hashfn(hashfn(pack('I', i)).digest()) for i in range(0, num_salts))
+
+    def _hash_maker(key):
+        if isinstance(key, str):
+            key = key.encode('utf-8')
+        else:
+            key = str(key).encode('utf-8')
+        i = 0
+        for salt in salts:
+            h = salt.copy()
+            h.update(key)
+            for uint in unpack(fmt, h.digest()):
+                yield uint % num_bits
+                i += 1
+                if i >= num_slices:
+                    return
+
+    return _hash_maker, hashfn
+
+
+class BloomFilter(object):
+    def __init__(self, capacity, error_rate=0.001):
+        num_slices = int(math.ceil(math.log(1.0 / error_rate, 2)))
+        bits_per_slice = int(math.ceil(
+            (capacity * abs(math.log(error_rate))) /
+            (num_slices * (math.log(2) ** 2))))
+        self.error_rate = error_rate
+        self.num_slices = num_slices
+        self.bits_per_slice = bits_per_slice
+        self.capacity = capacity
+        self.num_bits = num_slices * bits_per_slice
+        self.count = 0
+        self.make_hashes, self.hashfn = make_hashfuncs(self.num_slices, self.bits_per_slice)
+        self.bitarray = bytearray((self.num_bits + 7) // 8)
+
+    def _get(self, i):
+        return self.bitarray[i // 8] >> (i % 8) & 1 == 1
+
+    def __contains__(self, key):
+        offset = 0
+        for k in self.make_hashes(key):
+            if not self._get(offset + k):
+                return False
+            offset += self.bits_per_slice
+        return True
+
+    def add(self, key):
+        if self.count > self.capacity:
+            raise IndexError("BloomFilter is at capacity")
+        found_all_bits = True
+        offset = 0
+        for k in self.make_hashes(key):
+            if found_all_bits and not self._get(offset + k):
+                found_all_bits = False
+            self.bitarray[(offset + k) // 8] |= 1 << ((offset + k) % 8)
+            offset += self.bits_per_slice
+        if not found_all_bits:
+            self.count += 1
+            return False
+        return True
+
+    def tofile(self, f):
+        f.write(pack(FILE_FMT, self.error_rate, self.num_slices,
+                     self.bits_per_slice, self.capacity, self.count))
+        f.write(bytes(self.bitarray))
+
+
+# the hash functions of the port, against the digests of RFC 1321
+assert hashlib.md5(b'abc').hexdigest() == '900150983cd24fb0d6963f7d28e17f72'
+assert calcsize(FILE_FMT) == 40
+
+bloom = BloomFilter(capacity=1000, error_rate=0.01)
+assert (bloom.num_slices, bloom.bits_per_slice, bloom.hashfn) == (7, 1370, hashlib.md5)
+for i in range(1000):
+    bloom.add('key-%d' % i)
+with open('pybloom_live_1000.bloom', 'wb') as f:
+    bloom.tofile(f)
+with open('pybloom_live_1000.txt', 'w') as f:
+    for i in range(10000):
+        if 'other-%d' % i in bloom:
+            f.write('other-%d\n' % i)
@@ -0,0 +1,97 @@
+"""Writes testvectors/sbbf_int64.bin and prints the values listed in testvectors/sbbf_int64.txt:
+
+    python3 sbbf_fixture.py sbbf_int64.bin > sbbf_int64.txt
+
+The split block Bloom filter of a Parquet column chunk of INT64 values 0 .. 999, sized for them
+with a false positive probability of 0.01, and the values of 1000 .. 10999 it reports present.
+XXH64 and the filter are written from the Parquet spec (BloomFilter.md) alone, without a Parquet
+library, as an independent check of src/sbbf.rs. The bitset has not been compared with one
+written by a Parquet library.
+"""
+import math
+import struct
+import sys
+
+M64 = (1 << 64) - 1
+P1, P2, P3, P4, P5 = (0x9E3779B185EBCA87, 0xC2B2AE3D27D4EB4F, 0x165667B19E3779F9,
+                      0x85EBCA77C2B2AE63, 0x27D4EB2F165667C5)
+SALT = [0x47b6137b, 0x44974d91, 0x8824ad5b, 0xa2b7289d,
+        0x705495c7, 0x2df1424b, 0x9efc4947, 0x5c6bfb31]
+
+
+def rotl(x, r):
+    return ((x << r) | (x >> (64 - r))) & M64
+
+
+def round_(acc, lane):
+    return rotl((acc + lane * P2) & M64, 31) * P1 & M64
+
+
+def xxh64(data, seed=0):
+    n, i = len(data), 0
+    if n >= 32:
+        v = [(seed + P1 + P2) & M64, (seed + P2) & M64, seed, (seed - P1) & M64]
+        while i + 32 <= n:
+            for j in range(4):
+                v[j] = round_(v[j], struct.unpack_from("<Q", data, i + 8 * j)[0])
+            i += 32
+        h = (rotl(v[0], 1) + rotl(v[1], 7) + rotl(v[2], 12) + rotl(v[3], 18)) & M64
+        for lane in v:
+            h = ((h ^ round_(0, lane)) * P1 + P4) & M64
+    else:
+        h = (seed + P5) & M64
+    h = (h + n) & M64
+    while i + 8 <= n:
+        h ^= round_(0, struct.unpack_from("<Q", data, i)[0])
+        h = (rotl(h, 27) * P1 + P4) & M64
+        i += 8
+    if i + 4 <= n:
+        h ^= struct.unpack_from("<I", data, i)[0] * P1 & M64
+        h = (rotl(h, 23) * P2 + P3) & M64
+        i += 4
+    while i < n:
+        h ^= data[i] * P5 & M64
+        h = rotl(h, 11) * P1 & M64
+        i += 1
+    h ^= h >> 33
+    h = h * P2 & M64
+    h ^= h >> 29
+    h = h * P3 & M64
+    return h ^ (h >> 32)
+
+
+def mask(key):
+    return [1 << (((key * salt) & 0xffffffff) >> 27) for salt in SALT]
+
+
+def block_index(h, blocks):
+    return ((h >> 32) * blocks) >> 32
+
+
+def insert(filter_, value):
+    h = xxh64(value)
+    block = filter_[block_index(h, len(filter_))]
+    for i, bit in enumerate(mask(h & 0xffffffff)):
+        block[i] |= bit
+
+
+def check(filter_, value):
+    h = xxh64(value)
+    block = filter_[block_index(h, len(filter_))]
+    return all(block[i] & bit for i, bit in enumerate(mask(h & 0xffffffff)))
+
+
+assert xxh64(b"") == 0xEF46DB3751D8E999
+assert xxh64(b"a") == 0xD24EC4F1A98C6E5B
+assert xxh64(b"abc") == 0x44BC2CF5AD770999
+
+num_bits = int(-8.0 * 1000 / math.log(1.0 - 0.01 ** (1.0 / 8.0)))
+num_bytes = 1 << (max(num_bits // 8, 32) - 1).bit_length()
+filter_ = [[0] * 8 for _ in range(num_bytes // 32)]
+for v in range(1000):
+    insert(filter_, struct.pack("<q", v))
+with open(sys.argv[1], "wb") as out:
+    out.write(b"".join(struct.pack("<8I", *block) for block in filter_))
+for v in range(1000, 11000):
+    if check(filter_, struct.pack("<q", v)):
+        print(v)
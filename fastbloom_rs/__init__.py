@@ -1,7 +1,8 @@
 # -*- coding: utf-8 -*-
 
 from . import *
-from .fastbloom_rs import PyFilterBuilder, PyBloomFilter, PyCountingBloomFilter
+from .fastbloom_rs import PyFilterBuilder, PyBloomFilter, PyCountingBloomFilter, indices_for
 from .filter import BloomFilter, FilterBuilder, CountingBloomFilter
 
-__all__ = ["filter", "BloomFilter", "FilterBuilder", "PyBloomFilter", "PyCountingBloomFilter", "CountingBloomFilter"]
+__all__ = ["filter", "BloomFilter", "FilterBuilder", "PyBloomFilter", "PyCountingBloomFilter", "CountingBloomFilter",
+           "indices_for"]
@@ -121,10 +121,17 @@ class PyBloomFilter(object):
     def from_bytes(array: bytes, hashes: int) -> PyBloomFilter:
         ...
 
+    def to_pybloom_bytes(self) -> bytes:
+        ...
+
     @staticmethod
     def from_int_array(array: Sequence[int], hashes: int) -> PyBloomFilter:
         ...
 
+    @staticmethod
+    def from_pybloom_bytes(array: bytes) -> PyBloomFilter:
+        ...
+
 
 class PyCountingBloomFilter(object):
     def add(self, element: Union[str, int, bytes]):
@@ -224,3 +231,7 @@ class PyCountingBloomFilter(object):
     @staticmethod
     def from_int_array(array: Sequence[int], hashes: int, enable_repeat_insert: bool) -> PyCountingBloomFilter:
         ...
+
+
+def indices_for(key: bytes, m: int, k: int, seed: int) -> Sequence[int]:
+    ...
@@ -380,6 +380,32 @@ class BloomFilter(object):
         py_bloom = PyBloomFilter.from_int_array(array, hashes)
         return BloomFilter(py_bloom)
 
+    def to_pybloom_bytes(self) -> bytes:
+        """
+        Returns the filter in the file format of pybloom_live's BloomFilter.tofile. Only filters
+        read with from_pybloom_bytes can be written.
+
+        :return:
+        """
+        return self._py_bloom.to_pybloom_bytes()
+
+    @staticmethod
+    def from_pybloom_bytes(array: bytes) -> "BloomFilter":
+        """
+        Build a Bloom filter from a file written by pybloom_live's BloomFilter.tofile. Only
+        filters of at most 8 slices of less than 2**15 bits, which pybloom_live hashes with one
+        MD5 digest cut into 2-byte integers, can be read, e.g. those with an error rate of 0.01
+        and less than about 23 900 elements of capacity. Query str keys with
+        contains_str; pybloom_live hashes other keys as str(key), query contains_str(str(key))
+        for them. The format follows the source of pybloom_live 4.0.0 and is not tested against
+        files written by pybloom_live itself.
+
+        :param array: the content of the file
+        :return:
+        """
+        py_bloom = PyBloomFilter.from_pybloom_bytes(array)
+        return BloomFilter(py_bloom)
+
 
 class CountingBloomFilter(object):
     """
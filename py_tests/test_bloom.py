@@ -30,6 +30,9 @@ def test_bloom_builder():
     assert not bloom2.contains_bytes(b'hello world')
 
     bloom3 = BloomFilter.from_bytes(bloom.get_bytes(), bloom.hashes())
+    assert bloom3.config().size() == bloom.config().size()
+    assert bloom3.config().expected_elements() > 0
+    assert 0 < bloom3.config().false_positive_probability() < 1
 
     assert bloom3.contains_bytes(b'hello')
     assert bloom3.contains('hello')
@@ -145,3 +148,45 @@ def test_batch_check():
 
     bloom.add_bytes_batch(list(map(lambda x: bytes(x), inserts)))
     assert bloom.contains_bytes_batch(list(map(lambda x: bytes(x), checks))) == results
+
+
+def test_bloom_canonical_encoding():
+    # ints are their 8 little endian bytes and strs their UTF-8 bytes, the same encoding as
+    # BloomFilter::add_i64 and BloomFilter::add_str of the Rust crate
+    bloom = BloomFilter(100_000, 0.01)
+    bloom.add_int(-7)
+    bloom.add_str('héllo')
+    assert bloom.contains_bytes((-7).to_bytes(8, 'little', signed=True))
+    assert bloom.contains_bytes('héllo'.encode('utf-8'))
+
+    bloom2 = BloomFilter(100_000, 0.01)
+    bloom2.add_bytes((42).to_bytes(8, 'little', signed=True))
+    bloom2.add_bytes('world'.encode('utf-8'))
+    assert bloom2.contains_int(42)
+    assert bloom2.contains_str('world')
+
+
+def test_builder_invalid_parameters():
+    for expected_elements, fpp in [(0, 0.01), (100, 0.0), (100, 1.5)]:
+        try:
+            FilterBuilder(expected_elements, fpp)
+        except ValueError:
+            pass
+        else:
+            assert False, (expected_elements, fpp)
+
+
+def test_bloom_errors():
+    bloom = BloomFilter(1000, 0.01)
+    try:
+        BloomFilter.from_bytes(bloom.get_bytes(), 0)
+    except ValueError:
+        pass
+    else:
+        assert False
+    try:
+        bloom.contains_hash_indices([bloom.config().size()])
+    except IndexError:
+        pass
+    else:
+        assert False
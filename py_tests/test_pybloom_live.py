@@ -0,0 +1,23 @@
+# -*- coding: utf-8 -*-
+import os
+
+from fastbloom_rs import BloomFilter
+
+TESTVECTORS = os.path.join(os.path.dirname(__file__), '..', 'fastbloom-rs', 'testvectors')
+
+
+def test_from_pybloom_bytes():
+    # BloomFilter(capacity=1000, error_rate=0.01) of pybloom_live with key-0 .. key-999 added,
+    # see fastbloom-rs/testvectors/pybloom_fixture.py
+    with open(os.path.join(TESTVECTORS, 'pybloom_live_1000.bloom'), 'rb') as f:
+        data = f.read()
+    with open(os.path.join(TESTVECTORS, 'pybloom_live_1000.txt')) as f:
+        positives = f.read().split()
+
+    bloom = BloomFilter.from_pybloom_bytes(data)
+    assert bloom.hashes() == 7
+    for i in range(1000):
+        assert bloom.contains_str('key-%d' % i)
+    found = [key for key in ('other-%d' % i for i in range(10000)) if bloom.contains_str(key)]
+    assert found == positives
+    assert bloom.to_pybloom_bytes() == data
@@ -0,0 +1,34 @@
+# -*- coding: utf-8 -*-
+import os
+
+from fastbloom_rs import BloomFilter, indices_for
+
+VECTORS = os.path.join(os.path.dirname(__file__), '..', 'fastbloom-rs', 'testvectors', 'indices.txt')
+
+
+def known_answers():
+    with open(VECTORS) as f:
+        for line in f:
+            line = line.strip()
+            if not line or line.startswith('#') or line.startswith('hash_version'):
+                continue
+            key, m, k, seed, indices = line.split()
+            yield bytes.fromhex(key[2:]), int(m), int(k), int(seed), [int(i) for i in indices.split(',')]
+
+
+def test_known_answers():
+    answers = list(known_answers())
+    assert len(answers) == 105
+    for key, m, k, seed, indices in answers:
+        assert indices_for(key, m, k, seed) == indices
+
+
+def test_known_answers_bloom_filter():
+    # filters of the bindings have a size of whole bytes and no seed
+    for key, m, k, seed, indices in known_answers():
+        if seed != 0 or m % 8 != 0:
+            continue
+        bloom = BloomFilter.from_bytes(bytes(m // 8), k)
+        assert bloom.get_hash_indices(key) == indices
+        bloom.add_bytes(key)
+        assert bloom.contains_bytes(key)
@@ -1,6 +1,6 @@
 use pyo3::prelude::*;
 
-use crate::pybloom::{PyBloomFilter, PyFilterBuilder, PyCountingBloomFilter};
+use crate::pybloom::{indices_for, PyBloomFilter, PyFilterBuilder, PyCountingBloomFilter};
 
 pub mod pybloom;
 
@@ -9,6 +9,7 @@ fn fastbloom_rs(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyBloomFilter>().unwrap();
     m.add_class::<PyFilterBuilder>().unwrap();
     m.add_class::<PyCountingBloomFilter>().unwrap();
+    m.add_function(wrap_pyfunction!(indices_for, m)?)?;
     Ok(())
 }
 
@@ -19,4 +20,16 @@ mod tests {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn known_answers_test() {
+        use fastbloom_rs::testvectors::{known_answers, known_answers_hash_version};
+        use fastbloom_rs::FilterBuilder;
+
+        assert_eq!(known_answers_hash_version(), FilterBuilder::HASH_VERSION);
+        for answer in known_answers() {
+            let indices = crate::pybloom::indices_for(&answer.key, answer.m, answer.k, answer.seed);
+            assert_eq!(indices, answer.indices);
+        }
+    }
 }
@@ -1,7 +1,19 @@
+use pyo3::exceptions::{PyIndexError, PyValueError};
 use pyo3::prelude::*;
 use pyo3::types::PyBytes;
 
-use fastbloom_rs::{BloomFilter, CountingBloomFilter, Deletable, FilterBuilder, Hashes, Membership};
+use fastbloom_rs::{BloomFilter, CountingBloomFilter, FastBloomError, FilterBuilder, Hashes,
+                   Membership};
+use fastbloom_rs::testvectors;
+
+/// Maps the errors of the crate to the matching Python exceptions.
+fn to_py_err(e: impl Into<FastBloomError>) -> PyErr {
+    let e = e.into();
+    match e {
+        FastBloomError::IndexOutOfRange { .. } => PyIndexError::new_err(e.to_string()),
+        _ => PyValueError::new_err(e.to_string()),
+    }
+}
 
 #[pyclass]
 pub struct PyFilterBuilder {
@@ -12,11 +24,9 @@ pub struct PyFilterBuilder {
 impl PyFilterBuilder {
     #[new]
     pub fn __init__(expected_elements: u64, false_positive_probability: f64) -> PyResult<Self> {
-        Ok(
-            PyFilterBuilder {
-                filter_builder: FilterBuilder::new(expected_elements, false_positive_probability)
-            }
-        )
+        let filter_builder = FilterBuilder::try_new(expected_elements, false_positive_probability)
+            .map_err(to_py_err)?;
+        Ok(PyFilterBuilder { filter_builder })
     }
 
     pub fn build_bloom_filter(&mut self) -> PyResult<PyBloomFilter> {
@@ -59,11 +69,11 @@ pub struct PyBloomFilter {
 #[pymethods]
 impl PyBloomFilter {
     pub fn add_int(&mut self, element: i64) {
-        self.bloomfilter.add(&i64::to_le_bytes(element));
+        self.bloomfilter.add_i64(element);
     }
 
     pub fn add_int_if_not_contains(&mut self, element: i64) -> bool {
-        self.bloomfilter.add_if_not_contains(&i64::to_le_bytes(element))
+        self.bloomfilter.add_if_not_contains(i64::to_le_bytes(element))
     }
 
     pub fn add_int_batch(&mut self, array: Vec<i64>) {
@@ -73,7 +83,7 @@ impl PyBloomFilter {
     }
 
     pub fn add_str(&mut self, element: &str) {
-        self.bloomfilter.add(element.as_bytes());
+        self.bloomfilter.add_str(element);
     }
 
     pub fn add_str_if_not_contains(&mut self, element: &str) -> bool {
@@ -82,7 +92,7 @@ impl PyBloomFilter {
 
     pub fn add_str_batch(&mut self, array: Vec<&str>) {
         for x in array {
-            self.bloomfilter.add(x.as_bytes())
+            self.bloomfilter.add_str(x)
         }
     }
 
@@ -101,26 +111,26 @@ impl PyBloomFilter {
     }
 
     pub fn contains_int(&mut self, element: i64) -> bool {
-        self.bloomfilter.contains(&i64::to_le_bytes(element))
+        self.bloomfilter.contains_i64(element)
     }
 
     pub fn contains_int_batch(&mut self, elements: Vec<i64>) -> PyResult<Vec<bool>> {
         let mut res = Vec::<bool>::with_capacity(elements.len());
         for ele in elements {
-            let value = self.bloomfilter.contains(&i64::to_le_bytes(ele));
+            let value = self.bloomfilter.contains_i64(ele);
             res.push(value);
         }
         Ok(res)
     }
 
     pub fn contains_str(&mut self, element: &str) -> bool {
-        self.bloomfilter.contains(element.as_bytes())
+        self.bloomfilter.contains_str(element)
     }
 
     pub fn contains_str_batch(&mut self, elements: Vec<&str>) -> PyResult<Vec<bool>> {
         let mut res = Vec::<bool>::with_capacity(elements.len());
         for ele in elements {
-            let value = self.bloomfilter.contains(ele.as_bytes());
+            let value = self.bloomfilter.contains_str(ele);
             res.push(value);
         }
         Ok(res)
@@ -139,8 +149,8 @@ impl PyBloomFilter {
         Ok(res)
     }
 
-    pub fn contains_hash_indices(&self, indices: Vec<u64>) -> bool {
-        self.bloomfilter.contains_hash_indices(&indices)
+    pub fn contains_hash_indices(&self, indices: Vec<u64>) -> PyResult<bool> {
+        self.bloomfilter.try_contains_hash_indices(&indices).map_err(to_py_err)
     }
 
     pub fn config(&self) -> PyResult<PyFilterBuilder> {
@@ -159,6 +169,11 @@ impl PyBloomFilter {
         Ok(Vec::from(self.bloomfilter.get_u32_array()))
     }
 
+    pub fn to_pybloom_bytes<'py>(&self, py: Python<'py>) -> PyResult<&'py PyBytes> {
+        let bytes = self.bloomfilter.to_pybloom_bytes().map_err(to_py_err)?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+
     pub fn clear(&mut self) {
         self.bloomfilter.clear()
     }
@@ -190,13 +205,22 @@ impl PyBloomFilter {
 
     #[staticmethod]
     pub fn from_bytes(array: &[u8], hashes: u32) -> PyResult<Self> {
-        Ok(PyBloomFilter { bloomfilter: BloomFilter::from_u8_array(array, hashes) })
+        let bloomfilter =
+            BloomFilter::try_from_u8_array_with_size(array, (array.len() * 8) as u64, hashes)
+                .map_err(to_py_err)?;
+        Ok(PyBloomFilter { bloomfilter })
     }
 
     #[staticmethod]
     pub fn from_int_array(array: Vec<u32>, hashes: u32) -> PyResult<Self> {
         Ok(PyBloomFilter { bloomfilter: BloomFilter::from_u32_array(array.as_slice(), hashes) })
     }
+
+    #[staticmethod]
+    pub fn from_pybloom_bytes(array: &[u8]) -> PyResult<Self> {
+        let bloomfilter = BloomFilter::from_pybloom_bytes(array).map_err(to_py_err)?;
+        Ok(PyBloomFilter { bloomfilter })
+    }
 }
 
 #[pyclass]
@@ -352,4 +376,9 @@ impl PyCountingBloomFilter {
     }
 }
 
-
+/// The bit indices of `key` in a filter of `m` bits with `k` hashes and hash seed `seed`, see
+/// `fastbloom_rs::testvectors::indices_for`.
+#[pyfunction]
+pub fn indices_for(key: &[u8], m: u64, k: u32, seed: u64) -> Vec<u64> {
+    testvectors::indices_for(key, m, k, seed)
+}